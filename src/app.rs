@@ -1,15 +1,73 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{KeyCode, KeyModifiers};
+use portable_pty::ExitStatus;
 use ratatui::layout::Rect;
 
-use crate::config::LayoutConfig;
-use crate::pane::Pane;
+use crate::config::{
+    Chrome, ColorMode, Config, ConfigReload, FocusFollows, LastPaneClose, LayoutConfig, Osc52Mode, PaneConfig, PauseMode,
+    ViewportIndicator, ViewportPolicy, WeightConfig,
+};
+use crate::images::Placement;
+use crate::keys::KeyChord;
+use crate::layout;
+use crate::layout_state::{LayoutPersistence, Presets};
+use crate::pane::{Pane, RunState, ScrollOutcome};
+use crate::pause::OutputPause;
+use crate::prompt::Prompt;
+use crate::session::SessionSnapshot;
+use crate::signal::{self, Signal};
+use crate::stats::DebugStats;
+use crate::ui::{PaneAreas, TitleButton, MIN_PTY_COLS, MIN_PTY_ROWS};
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct SelectionState {
     pub pane_id: usize,
     pub anchor: (u16, u16),
     pub cursor: (u16, u16),
 }
 
+/// A weight change just made from the keyboard or wheel, shown on the pane
+/// until the keys have been left alone for `WEIGHT_PREVIEW_TIMEOUT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeightPreview {
+    pub pane_id: usize,
+    /// The weight before this run of changes.
+    pub from: u16,
+    /// The last change grew the pane.
+    pub grew: bool,
+    pub at: Instant,
+}
+
+/// A pane that has been closed, as recorded by `AppState::teardown_pane`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClosedPane {
+    pub id: usize,
+    pub name: String,
+    /// `None` if the process was still running.
+    pub exit_code: Option<u32>,
+    /// What `Action::ReopenClosed` starts again, under the pane's last name.
+    pub config: PaneConfig,
+    pub weight: u16,
+}
+
+/// Closed panes remembered in `AppState::closed_panes`.
+const CLOSED_HISTORY: usize = 20;
+
+/// How long a weight preview outlives the last change.
+pub const WEIGHT_PREVIEW_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The title-bar button under the mouse pointer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HoverTarget {
+    pub pane_id: usize,
+    pub button: TitleButton,
+}
+
 impl SelectionState {
     pub fn normalized(&self) -> ((u16, u16), (u16, u16)) {
         let (ar, ac) = self.anchor;
@@ -36,26 +94,540 @@ impl SelectionState {
     }
 }
 
+/// A change requested by user input.
+///
+/// The key and mouse handlers translate events into actions and
+/// `AppState::apply` carries them out, so behavior can be exercised without a
+/// terminal.
+#[derive(Debug, Clone)]
+pub enum Action {
+    Quit,
+    ToggleDebug,
+    Focus(usize),
+    FocusNext,
+    FocusPrev,
+    /// Spawn a pane at the end of the stack and focus it. An empty name
+    /// becomes "Shell <id>".
+    SpawnPane(Box<PaneConfig>),
+    /// Split the focused pane's slot, opening a shell in its right half.
+    SplitPane,
+    ClosePane(usize),
+    /// Close every pane except the one at this index and focus it.
+    CloseOthers(usize),
+    /// Close every pane below the one at this index and focus it.
+    CloseAfter(usize),
+    /// Start the most recently closed pane again at the end of the stack.
+    ReopenClosed,
+    ToggleCollapse(usize),
+    /// Expand the pane at this index, collapse all others and focus it.
+    Solo(usize),
+    /// Start an exited pane's command again in the same slot.
+    RestartPane(usize),
+    /// A watched file changed: rerun the pane's command, killing the
+    /// running one, unless watching is paused.
+    WatchRestart { idx: usize, changed: PathBuf },
+    /// Pause or resume file watching for the pane at this index.
+    ToggleWatch(usize),
+    /// Change the focused pane's weight by one `weight_step`.
+    GrowWeight,
+    ShrinkWeight,
+    /// Change the weight of the pane at this index without focusing it.
+    GrowWeightAt(usize),
+    ShrinkWeightAt(usize),
+    /// Move the focused slot's bottom edge by one row, in resize mode.
+    ResizeByRow { grow: bool },
+    /// Give every pane the default weight.
+    EqualizeWeights,
+    /// Collapse, expand or focus the panes of group `name`, or ask to close
+    /// them.
+    Group { name: String, op: GroupOp },
+    /// Close every pane of a group, without asking.
+    CloseGroup(String),
+    /// Remember every pane's weight and collapsed state as this preset.
+    SavePreset(u8),
+    /// Restore a saved preset onto the panes it names.
+    ApplyPreset(u8),
+    /// Switch to the next `SortMode`.
+    CycleSortMode,
+    /// Freeze or unfreeze the focused pane's view.
+    ToggleScrollLock,
+    /// Show or hide the row and column guides in the focused pane.
+    ToggleCrosshair,
+    /// Turn mouse capture off or back on.
+    ToggleMouse,
+    /// Put the focused pane's terminal back in its default modes.
+    ResetTerminal,
+    /// Scroll the focused pane's scrollback; positive moves into history.
+    Scroll { lines: i32 },
+    /// Scroll the focused pane's scrollback a page; a full-screen program
+    /// gets the PageUp / PageDown key instead.
+    ScrollPage { up: bool },
+    /// Scroll the focused pane sideways when it doesn't wrap; positive
+    /// moves right.
+    ScrollHorizontal { cols: i32 },
+    /// Move the viewport without changing focus; it stays put until the
+    /// next focus change.
+    PageViewportUp,
+    PageViewportDown,
+    /// Start the viewport at the slot holding this pane, leaving focus alone.
+    ScrollViewportTo(usize),
+    ViewportHome,
+    ViewportEnd,
+    Resize { cols: u16, rows: u16 },
+    /// Paste text into the focused pane.
+    Paste(String),
+    /// Keyboard selection starting at the top-left of the focused pane.
+    StartSelection,
+    /// Mouse selection in the pane at `pane`, in terminal coordinates.
+    StartSelectionAt { pane: usize, row: u16, col: u16 },
+    UpdateSelection { row: u16, col: u16 },
+    MoveSelection { rows: i32, cols: i32 },
+    ClearSelection,
+    /// Move the selection to the next (or previous) line matching
+    /// `error_pattern`, scrolling it into view. Past the last match it
+    /// wraps around to the first, and the other way round.
+    JumpToError { forward: bool },
+    /// Bytes for the focused pane's PTY.
+    SendInput(Vec<u8>),
+    /// Mark or unmark the pane at this index for a group action.
+    ToggleMark(usize),
+    ClearMarks,
+    /// Collapse the marked panes, or expand them if all are collapsed;
+    /// the focused pane when none are marked.
+    CollapseMarked,
+    /// Close the marked panes, or the focused one when none are.
+    CloseMarked,
+    /// Type this line, with Enter, into the marked panes or the focused one.
+    Broadcast(String),
+    /// Deliver a signal to the process of the pane at `idx`, or to the
+    /// foreground process group of its terminal.
+    SendSignal { idx: usize, signal: Signal, group: bool },
+    /// Stop or resume reading the pane's output; a stopped pane's process
+    /// blocks once its PTY buffer fills.
+    ToggleReadHold(usize),
+}
+
+/// Closes several panes at once, after asking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MassClose {
+    /// All panes but one.
+    Others,
+    /// The panes below one.
+    Below,
+    /// The marked panes.
+    Marked,
+}
+
+/// The list of panes scrolled out of view, shown over the stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Peek {
+    /// Lists the panes below the viewport rather than above it.
+    pub below: bool,
+    /// Hidden panes scrolled past at the top of the list, when they don't
+    /// all fit.
+    pub scroll: usize,
+}
+
+/// Index in `panes` of the pane `target` addresses, tried in this order:
+/// `#ID` is a pane's stable id, then a pane's exact name, then a number is
+/// its display index, counting from 1 down the stack as `{index}` does. A
+/// name more than one pane has is an error listing them rather than a guess.
+pub fn resolve_pane_target(panes: &[Pane], target: &str) -> anyhow::Result<usize> {
+    let target = target.trim();
+    if let Some(id) = target.strip_prefix('#') {
+        let id: usize = id.parse().map_err(|_| anyhow::anyhow!("{:?} is not a pane id (expected #N)", target))?;
+        return panes
+            .iter()
+            .position(|p| p.stable_id == id)
+            .ok_or_else(|| anyhow::anyhow!("no pane has id #{}", id));
+    }
+    let named: Vec<usize> = (0..panes.len()).filter(|&i| panes[i].name == target).collect();
+    match named[..] {
+        [idx] => return Ok(idx),
+        [] => {}
+        _ => {
+            let candidates: Vec<String> =
+                named.iter().map(|&i| format!("#{} (index {})", panes[i].stable_id, i + 1)).collect();
+            anyhow::bail!("{} panes are named {:?}: {}", named.len(), target, candidates.join(", "));
+        }
+    }
+    match target.parse::<usize>() {
+        Ok(index @ 1..) if index <= panes.len() => Ok(index - 1),
+        Ok(index) => anyhow::bail!("no pane at index {} (there are {})", index, panes.len()),
+        Err(_) => anyhow::bail!("no pane named {:?}", target),
+    }
+}
+
+/// What Alt+Shift+G does to every pane of a group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupOp {
+    Collapse,
+    /// Expand at the weights the panes had before collapsing.
+    Expand,
+    /// Focus the group's first pane in the stack.
+    Focus,
+    /// Close the group's panes after a confirmation.
+    Close,
+}
+
+impl GroupOp {
+    pub const ALL: [GroupOp; 4] = [GroupOp::Collapse, GroupOp::Expand, GroupOp::Focus, GroupOp::Close];
+
+    /// The verb the group picker shows, e.g. "collapse" in "collapse web".
+    pub fn label(self) -> &'static str {
+        match self {
+            GroupOp::Collapse => "collapse",
+            GroupOp::Expand => "expand",
+            GroupOp::Focus => "focus",
+            GroupOp::Close => "close",
+        }
+    }
+
+    pub fn parse(label: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|op| op.label() == label)
+    }
+}
+
+/// What a confirmed question goes on to do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Confirmed {
+    Close(MassClose),
+    /// Close every pane of the named group.
+    CloseGroup(String),
+    Signal { signal: Signal, group: bool },
+    /// Close the last pane, still running, and quit.
+    Quit,
+}
+
+/// A yes/no question shown in the footer before a mass close or a SIGKILL.
+#[derive(Debug, Clone)]
+pub struct Confirm {
+    pub message: String,
+    pub action: Confirmed,
+    /// The pane kept or signalled, by id so a reorder before the answer
+    /// can't change which panes go.
+    pub pane_id: usize,
+}
+
+/// "run #2, exited 1, src/lib.rs changed, restarting" for the run of `old`
+/// that is being replaced.
+fn divider_label(old: &Pane, reason: Option<String>) -> String {
+    let mut parts = vec![format!("run #{}", old.run)];
+    if old.spawn_error.is_some() {
+        parts.push("failed to start".to_string());
+    } else if let Some(status) = &old.exit_status {
+        parts.push(format!("exited {}", status.exit_code()));
+    }
+    parts.extend(reason);
+    parts.push("restarting".to_string());
+    parts.join(", ")
+}
+
+/// Early failures in a row after which file changes stop restarting a pane.
+const MAX_RAPID_FAILURES: u32 = 3;
+
+/// Wheel notches closer together than this count as one continuous turn.
+const WHEEL_STREAK_GAP: Duration = Duration::from_millis(100);
+
+/// Most a continuous wheel turn multiplies `WheelConfig::lines` by.
+const WHEEL_MAX_MULTIPLIER: u32 = 5;
+
+/// How the mouse wheel scrolls pane history.
+#[derive(Debug, Clone, Copy)]
+pub struct WheelConfig {
+    /// Lines per notch.
+    pub lines: u16,
+    /// Reverse the direction.
+    pub natural: bool,
+    /// Grow the step while notches keep arriving within `WHEEL_STREAK_GAP`.
+    pub acceleration: bool,
+}
+
+impl Default for WheelConfig {
+    fn default() -> Self {
+        Self {
+            lines: 3,
+            natural: false,
+            acceleration: false,
+        }
+    }
+}
+
+/// Lines that count as errors for `focus_follows_activity = "match"` when no
+/// pattern is configured.
+pub const DEFAULT_ACTIVITY_PATTERN: &str = r"(?i)\b(error|fatal|panic(ked)?)\b";
+/// Shortest time between two focus moves made by focus-follows-activity.
+const AUTO_FOCUS_INTERVAL: Duration = Duration::from_secs(1);
+/// Focus stays put while keys arrived this recently.
+const TYPING_GRACE: Duration = Duration::from_secs(2);
+
+/// Moving focus to the pane that printed last.
+pub struct FocusFollow {
+    pub mode: FocusFollows,
+    /// Lines that qualify in `Match` mode.
+    pub pattern: Option<regex::Regex>,
+    /// Pause after a manual focus change.
+    pub cooldown: Duration,
+}
+
+impl Default for FocusFollow {
+    fn default() -> Self {
+        Self {
+            mode: FocusFollows::Off,
+            pattern: None,
+            cooldown: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Keys that pick a pane in jump mode, by stack position.
+const JUMP_KEYS: &[u8] = b"123456789abcdefghijklmnopqrstuvwxyz";
+
+/// The jump-mode key for the pane at `idx`; panes past the last key get none.
+pub fn jump_key(idx: usize) -> Option<char> {
+    JUMP_KEYS.get(idx).map(|&b| b as char)
+}
+
+/// The stack position a jump-mode key picks.
+pub fn jump_index(key: char) -> Option<usize> {
+    let key = key.to_ascii_lowercase();
+    JUMP_KEYS.iter().position(|&b| b as char == key)
+}
+
+/// Order in which panes are stacked; `Off` keeps the order they were opened in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortMode {
+    #[default]
+    Off,
+    /// Most recent output first.
+    Activity,
+    Name,
+}
+
+impl SortMode {
+    pub fn next(self) -> Self {
+        match self {
+            SortMode::Off => SortMode::Activity,
+            SortMode::Activity => SortMode::Name,
+            SortMode::Name => SortMode::Off,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortMode::Off => "off",
+            SortMode::Activity => "activity",
+            SortMode::Name => "name",
+        }
+    }
+}
+
+/// Minimum time between two reorders, so busy panes don't make the stack
+/// jump around on every read.
+const SORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often auto-named panes look up their foreground program.
+const PROCESS_NAME_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Creates the pane for `Action::SpawnPane`. The event loop's implementation
+/// starts a real process; tests use a fake.
+pub trait PaneSpawner {
+    fn spawn(&mut self, pane_id: usize, config: &PaneConfig, cols: u16, rows: u16) -> anyhow::Result<Pane>;
+}
+
+/// How long a footer status message stays up.
+const STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the title says why a scroll did nothing.
+const SCROLL_NOTE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// After the window shrinks, panes keep their size this long before they
+/// shrink with it, so a squeeze that passes (a tiling window manager
+/// laying out, a window being restored) never clips what they show.
+const SQUEEZE_GRACE: Duration = Duration::from_millis(500);
+
+pub const DEFAULT_LITERAL_KEY: KeyChord = KeyChord::new(KeyModifiers::CONTROL, KeyCode::Char(' '));
+
 pub struct AppState {
     pub panes: Vec<Pane>,
     pub focused: usize,
     #[allow(dead_code)]
     pub layout_mode: LayoutConfig,
     pub should_quit: bool,
-    pub last_pane_areas: Vec<(usize, Rect)>,
+    /// Where each pane was drawn on the last frame, by stack index.
+    pub last_pane_areas: Vec<(usize, PaneAreas)>,
+    /// Ids of the panes in `last_pane_areas`, to tell whether the stack has
+    /// changed since that frame.
+    last_pane_ids: Vec<usize>,
     pub term_cols: u16,
     pub term_rows: u16,
-    pub default_shell: String,
+    /// Until then panes only grow; see `SQUEEZE_GRACE`.
+    pub squeezed_until: Option<Instant>,
+    spawner: Box<dyn PaneSpawner>,
     pub next_pane_id: usize,
     pub viewport_start: usize,
+    /// Whether rendering scrolls the viewport to keep the focused pane in
+    /// view. Paging turns it off; any focus change turns it back on.
+    pub follow_focus: bool,
     /// Name of the active shoot (git worktree), if any.
     pub active_shoot: Option<String>,
     pub selection: Option<SelectionState>,
     pub last_mouse_pos: Option<(u16, u16)>,
+    /// Tint the row and column under the pointer in the focused pane.
+    pub crosshair: bool,
+    /// Mouse capture wanted; off leaves the mouse to the outer terminal
+    /// and drops the title buttons.
+    pub mouse: bool,
+    /// Mouse capture as last set on the outer terminal.
+    mouse_captured: bool,
+    /// Collapsed panes show the line above a shell's marked prompt.
+    pub collapsed_skip_prompt: bool,
+    /// Least time between refreshes of a title's output-driven parts.
+    pub title_interval: Duration,
+    /// When ticks and unseen output last asked for a redraw; see
+    /// `title_redraw_due`.
+    title_redraw_at: Instant,
+    /// Least time between frames drawn for pane output.
+    pub frame_interval: Duration,
+    /// Last lines of output per pane in a session report.
+    pub report_lines: usize,
+    /// Where the mouse pointer is as (column, row), tracked only while
+    /// `crosshair` is on.
+    pub pointer: Option<(u16, u16)>,
+    /// Cleared whenever the pane layout changes, since the button may have moved.
+    pub hover: Option<HoverTarget>,
+    pub weight_preview: Option<WeightPreview>,
+    pub stats: DebugStats,
+    /// Resolved color capability of the outer terminal (never `Auto`).
+    pub color_mode: ColorMode,
+    /// Bold cells in the 8 basic colors are drawn bright.
+    pub bold_is_bright: bool,
+    pub chrome: Chrome,
+    /// Global title bar template.
+    pub title_format: String,
+    weights: WeightConfig,
+    /// Prefix that sends the next key to the focused pane unhandled.
+    pub literal_key: KeyChord,
+    /// The literal prefix was pressed; the next key goes to the pane.
+    pub literal_armed: bool,
+    /// Session-wide output freeze.
+    pub output_pause: OutputPause,
+    /// Plain ASCII rendering with state in words; see `Config::accessible`.
+    pub accessible: bool,
+    /// Skip purely decorative redraws; see `Config::quiet_redraws`.
+    pub quiet_redraws: bool,
+    /// The last `CLOSED_HISTORY` panes closed, newest last.
+    pub closed_panes: Vec<ClosedPane>,
+    /// Ids of the panes marked for a group action.
+    pub marked: BTreeSet<usize>,
+    /// Pane badges are shown and the next key focuses the pane it names.
+    pub jump_mode: bool,
+    /// Keys resize the focused pane a row at a time until Enter or Esc.
+    pub resize_mode: bool,
+    /// When `update_process_names` last looked.
+    process_names_at: Option<Instant>,
+    /// Drawing in the alternate screen; otherwise inline over the primary
+    /// one, in a viewport the event loop rebuilds when the window resizes.
+    pub alternate_screen: bool,
+    /// The pane whose output has kept the parser busy for a while, asked
+    /// about in the footer, by id.
+    pub heavy_output: Option<usize>,
+    /// Where the last frame laid out the pane stack, and the rows it kept
+    /// for the more above/below lines; what a one-row resize is measured
+    /// against.
+    pub stack_area: Option<(Rect, u16)>,
+    /// One-line message shown in the footer for `STATUS_TIMEOUT`.
+    pub status: Option<(String, Instant)>,
+    /// Show a restart / close / export bar in exited panes, whose keys
+    /// then work while such a pane is focused.
+    pub show_exit_bar: bool,
+    /// Sample each pane's output rate for its title; see
+    /// `Config::show_throughput`.
+    pub show_throughput: bool,
+    /// See `Config::on_last_pane_close`.
+    pub on_last_pane_close: LastPaneClose,
+    /// Keep succeeded tasks in focus cycling.
+    pub focus_finished_tasks: bool,
+    /// Panes quiet for this long are dimmed; `None` never dims.
+    pub stale_after: Option<Duration>,
+    pub sort_mode: SortMode,
+    pub wheel: WheelConfig,
+    /// When the last wheel notch arrived and how many came in a row.
+    wheel_streak: Option<(Instant, u32)>,
+    /// Line input shown in the footer; takes every key while open.
+    pub prompt: Option<Prompt>,
+    /// When `sort_panes` last reordered the stack.
+    last_sort: Option<Instant>,
+    /// Handling of OSC 52 clipboard writes from panes.
+    pub osc52: Osc52Mode,
+    /// Answer OSC 52 clipboard queries.
+    pub osc52_read: bool,
+    /// Saves layout changes between sessions; `None` when disabled.
+    pub layout_persistence: Option<LayoutPersistence>,
+    /// Template for the outer terminal's window title; `None` leaves the
+    /// title alone.
+    pub window_title_format: Option<String>,
+    /// `{session}` in the window title.
+    pub session_name: String,
+    /// Window title last sent to the outer terminal.
+    last_window_title: Option<String>,
+    /// Problems found while starting up, shown over the first frame until
+    /// dismissed with Enter.
+    pub startup_warnings: Vec<String>,
+    /// Show the key cheat sheet and offer to write an example config; set
+    /// for a first session without a config file.
+    pub welcome: bool,
+
+    pub focus_follow: FocusFollow,
+    /// Lines matching this are marked beside the pane and reachable with
+    /// `e`/`E` while selecting.
+    pub error_pattern: Option<regex::Regex>,
+    /// When focus-follows-activity last moved focus.
+    last_auto_focus: Option<Instant>,
+    /// Focus-follows-activity is paused until then after a manual focus change.
+    auto_focus_paused_until: Option<Instant>,
+    /// When the last key was pressed.
+    pub last_keypress: Option<Instant>,
+
+    /// `[[templates]]` from the config, offered by the template picker.
+    pub templates: Vec<PaneConfig>,
+    /// How to read the config again for Alt+Shift+R; unset without a
+    /// config file.
+    pub reload: Option<ConfigReload>,
+
+    /// Waiting for y/n before closing several panes.
+    pub confirm: Option<Confirm>,
+
+    /// Layout presets, saved with the layout state.
+    pub presets: Presets,
+
+    /// How panes outside the viewport are shown.
+    pub viewport_indicator: ViewportIndicator,
+    /// Where the viewport puts the focused pane when it follows focus.
+    pub viewport_policy: ViewportPolicy,
+    /// Where the last frame drew the stack map with
+    /// `viewport_indicator = "gutter"`.
+    pub gutter_area: Option<Rect>,
+    /// Hidden panes listed while the pointer rests on a "more above/below"
+    /// indicator.
+    pub peek: Option<Peek>,
+    /// Where the last frame drew the peek, and the pane on each of its
+    /// list rows, top down.
+    pub peek_area: Option<(Rect, Vec<usize>)>,
+
+    /// Inline images the last frame left room for, set by rendering.
+    pub image_placements: Vec<Placement>,
+    /// Images currently drawn on the outer terminal.
+    images_shown: Vec<Placement>,
 }
 
 impl AppState {
-    pub fn new(panes: Vec<Pane>, layout_mode: LayoutConfig, default_shell: String, active_shoot: Option<String>) -> Self {
+    pub fn new(
+        panes: Vec<Pane>,
+        layout_mode: LayoutConfig,
+        active_shoot: Option<String>,
+        spawner: Box<dyn PaneSpawner>,
+    ) -> Self {
         let next_pane_id = panes.len();
 
         Self {
@@ -64,189 +636,3196 @@ impl AppState {
             layout_mode,
             should_quit: false,
             last_pane_areas: Vec::new(),
+            last_pane_ids: Vec::new(),
             term_cols: 0,
             term_rows: 0,
-            default_shell,
+            squeezed_until: None,
+            spawner,
             next_pane_id,
             viewport_start: 0,
+            follow_focus: true,
             active_shoot,
             selection: None,
             last_mouse_pos: None,
+            crosshair: false,
+            mouse: true,
+            mouse_captured: true,
+            collapsed_skip_prompt: false,
+            title_interval: Duration::from_millis(250),
+            title_redraw_at: Instant::now(),
+            frame_interval: Duration::from_secs(1) / 30,
+            report_lines: crate::report::DEFAULT_REPORT_LINES,
+            pointer: None,
+            hover: None,
+            weight_preview: None,
+            stats: DebugStats::default(),
+            color_mode: ColorMode::Truecolor,
+            bold_is_bright: true,
+            chrome: Chrome::Full,
+            title_format: crate::title::DEFAULT_TITLE_FORMAT.to_string(),
+            weights: WeightConfig::default(),
+            literal_key: DEFAULT_LITERAL_KEY,
+            literal_armed: false,
+            output_pause: OutputPause::new(PauseMode::Backpressure, Arc::new(AtomicBool::new(false))),
+            closed_panes: Vec::new(),
+            marked: BTreeSet::new(),
+            accessible: false,
+            quiet_redraws: false,
+            jump_mode: false,
+            resize_mode: false,
+            process_names_at: None,
+            alternate_screen: true,
+            heavy_output: None,
+            stack_area: None,
+            status: None,
+            show_exit_bar: true,
+            show_throughput: false,
+            on_last_pane_close: LastPaneClose::Ignore,
+            focus_finished_tasks: false,
+            stale_after: None,
+            sort_mode: SortMode::Off,
+            wheel: WheelConfig::default(),
+            wheel_streak: None,
+            prompt: None,
+            last_sort: None,
+            osc52: Osc52Mode::System,
+            osc52_read: false,
+            layout_persistence: None,
+            window_title_format: None,
+            session_name: String::new(),
+            last_window_title: None,
+            startup_warnings: Vec::new(),
+            welcome: false,
+            focus_follow: FocusFollow::default(),
+            error_pattern: None,
+            last_auto_focus: None,
+            auto_focus_paused_until: None,
+            last_keypress: None,
+            templates: Vec::new(),
+            reload: None,
+            confirm: None,
+            presets: Presets::new(),
+            viewport_indicator: ViewportIndicator::Lines,
+            viewport_policy: ViewportPolicy::Minimal,
+            gutter_area: None,
+            peek: None,
+            peek_area: None,
+            image_placements: Vec::new(),
+            images_shown: Vec::new(),
         }
     }
 
-    pub fn focus(&mut self, idx: usize) {
-        if idx < self.panes.len() {
-            self.focused = idx;
+    /// Close the welcome screen for good. With `write_example`, write the
+    /// example config to `.bamboo.toml` here and open it in `$EDITOR` in a
+    /// new pane.
+    pub fn answer_welcome(&mut self, write_example: bool) {
+        self.welcome = false;
+        if let Some(marker) = crate::layout_state::welcome_marker() {
+            let written = marker
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|()| std::fs::write(&marker, ""));
+            if let Err(e) = written {
+                tracing::warn!(path = %marker.display(), error = %e, "failed to record the welcome as seen");
+            }
+        }
+        if !write_example {
+            return;
+        }
+        let path = std::path::Path::new(".bamboo.toml");
+        if let Err(e) = crate::wizard::write_example_config(path) {
+            tracing::warn!(error = %e, "failed to write example config");
+            self.set_status(format!("couldn't write .bamboo.toml: {}", e));
+            return;
         }
+        let editor = std::env::var("EDITOR")
+            .ok()
+            .filter(|e| !e.trim().is_empty())
+            .unwrap_or_else(|| if cfg!(windows) { "notepad" } else { "vi" }.to_string());
+        self.apply(Action::SpawnPane(Box::new(PaneConfig {
+            name: ".bamboo.toml".to_string(),
+            command: Some(format!("{} .bamboo.toml", editor)),
+            ..PaneConfig::default()
+        })));
+        self.set_status("wrote .bamboo.toml; it is used from the next start");
     }
 
-    pub fn focus_next(&mut self) {
-        if !self.panes.is_empty() {
-            self.focused = (self.focused + 1) % self.panes.len();
+    /// The outer terminal's window title, if it differs from the one last
+    /// returned here.
+    pub fn window_title_update(&mut self) -> Option<String> {
+        let format = self.window_title_format.as_deref()?;
+        let pane = self.panes.get(self.focused).map_or("", |p| p.name.as_str());
+        let title = crate::title::expand_window(
+            format,
+            &crate::title::WindowTitleContext {
+                session: &self.session_name,
+                pane,
+                n_panes: self.panes.len(),
+            },
+        );
+        if self.last_window_title.as_ref() == Some(&title) {
+            return None;
         }
+        self.last_window_title = Some(title.clone());
+        Some(title)
     }
 
-    pub fn focus_prev(&mut self) {
-        if !self.panes.is_empty() {
-            self.focused = (self.focused + self.panes.len() - 1) % self.panes.len();
+    /// Whether mouse capture should now be turned on or off on the outer
+    /// terminal, once per change of `mouse`.
+    pub fn mouse_capture_update(&mut self) -> Option<bool> {
+        if self.mouse == self.mouse_captured {
+            return None;
         }
+        self.mouse_captured = self.mouse;
+        Some(self.mouse)
+    }
+
+    /// Tell the app how startup left mouse capture.
+    pub fn set_mouse(&mut self, capture: bool) {
+        self.mouse = capture;
+        self.mouse_captured = capture;
+    }
+
+    /// Images on the outer terminal that the last frame no longer has in
+    /// the same place. Only a full repaint gets rid of them.
+    pub fn images_stale(&self) -> bool {
+        !self.images_shown.is_empty() && self.images_shown != self.image_placements
+    }
+
+    /// The screen was cleared, taking any images with it.
+    pub fn forget_images(&mut self) {
+        self.images_shown.clear();
     }
 
-    pub fn toggle_collapse_focused(&mut self) {
-        if let Some(pane) = self.panes.get_mut(self.focused) {
-            pane.collapsed = !pane.collapsed;
+    /// Images to draw after the last frame, with the sequences that draw
+    /// them; empty when the outer terminal already shows them.
+    pub fn image_updates(&mut self) -> Vec<(&Placement, &[u8])> {
+        if self.images_shown == self.image_placements {
+            return Vec::new();
         }
+        self.images_shown = self.image_placements.clone();
+        let panes = &self.panes;
+        self.images_shown
+            .iter()
+            .filter_map(|placement| {
+                let pane = panes.iter().find(|p| p.id == placement.pane_id)?;
+                Some((placement, pane.images.sequence(placement.image_id)?))
+            })
+            .collect()
     }
 
-    pub fn grow_focused_weight(&mut self, delta: u16) {
-        if let Some(pane) = self.panes.get_mut(self.focused) {
-            if !pane.collapsed {
-                pane.weight = pane.weight.saturating_add(delta).min(50);
+    pub fn apply(&mut self, action: Action) {
+        if matches!(
+            action,
+            Action::Focus(_)
+                | Action::FocusNext
+                | Action::FocusPrev
+                | Action::SpawnPane(_)
+                | Action::SplitPane
+                | Action::ClosePane(_)
+                | Action::CloseOthers(_)
+                | Action::CloseAfter(_)
+                | Action::ReopenClosed
+                | Action::Solo(_)
+                | Action::RestartPane(_)
+        ) {
+            self.follow_focus = true;
+        }
+        if matches!(action, Action::Focus(_) | Action::FocusNext | Action::FocusPrev)
+            && self.focus_follow.mode != FocusFollows::Off
+        {
+            self.auto_focus_paused_until = Some(Instant::now() + self.focus_follow.cooldown);
+        }
+        match action {
+            Action::Quit => self.should_quit = true,
+            Action::ToggleDebug => self.stats.visible = !self.stats.visible,
+            Action::Focus(idx) => self.focus(idx),
+            Action::FocusNext => self.focus_next(),
+            Action::FocusPrev => self.focus_prev(),
+            Action::SpawnPane(config) => self.spawn_pane(*config),
+            Action::ReopenClosed => self.reopen_closed(),
+            Action::SplitPane => self.split_focused(),
+            Action::ClosePane(idx) => {
+                if self.panes.len() == 1 && idx == 0 {
+                    self.close_last_pane();
+                } else if let Some(pane) = self.close_pane(idx) {
+                    self.teardown_pane(pane);
+                }
+            }
+            Action::CloseOthers(idx) => {
+                self.close_others(idx);
+            }
+            Action::CloseAfter(idx) => {
+                self.close_after(idx);
+            }
+            Action::ToggleCollapse(idx) => self.toggle_collapse_at(idx),
+            Action::Solo(idx) => self.solo(idx),
+            Action::RestartPane(idx) => self.restart_pane(idx),
+            Action::WatchRestart { idx, changed } => self.watch_restart(idx, &changed),
+            Action::ToggleWatch(idx) => {
+                if let Some(pane) = self.panes.get_mut(idx).filter(|p| !p.config.watch.is_empty()) {
+                    pane.watch_paused = !pane.watch_paused;
+                    if !pane.watch_paused {
+                        pane.rapid_failures = 0;
+                    }
+                    tracing::info!(pane = pane.id, paused = pane.watch_paused, "toggled file watching");
+                }
+            }
+            // Marks stay through weight changes, which take repeated presses.
+            Action::GrowWeight => {
+                for idx in self.marked_or_focused() {
+                    self.grow_weight_at(idx);
+                }
+            }
+            Action::ShrinkWeight => {
+                for idx in self.marked_or_focused() {
+                    self.shrink_weight_at(idx);
+                }
+            }
+            Action::GrowWeightAt(idx) => self.grow_weight_at(idx),
+            Action::ShrinkWeightAt(idx) => self.shrink_weight_at(idx),
+            Action::ResizeByRow { grow } => self.resize_by_row(grow),
+            Action::EqualizeWeights => {
+                let weight = self.weights.default_weight;
+                for pane in &mut self.panes {
+                    pane.weight = weight;
+                }
+                self.set_status(format!("every pane has weight {}", weight));
+            }
+            Action::SavePreset(slot) => {
+                self.presets.insert(slot, crate::layout_state::pane_layouts(&self.panes));
+                self.set_status(format!("preset {} saved", slot));
+                self.save_layout(true);
+            }
+            Action::ApplyPreset(slot) => match self.presets.get(&slot) {
+                Some(preset) => {
+                    crate::layout_state::apply_layouts(preset, &mut self.panes);
+                    self.set_weights(self.weights);
+                    self.set_status(format!("preset {} applied", slot));
+                }
+                None => self.set_status(format!("preset {} is empty; Alt+Shift+{} saves it", slot, slot)),
+            },
+            Action::CycleSortMode => {
+                self.sort_mode = self.sort_mode.next();
+                self.last_sort = None;
+                self.sort_panes();
+            }
+            Action::Scroll { lines } => {
+                if let Some(pane) = self.focused_pane_mut() {
+                    if pane.scroll(lines) == ScrollOutcome::NoHistory {
+                        pane.flash_for("no scrollback", SCROLL_NOTE_TIMEOUT);
+                    }
+                }
+            }
+            Action::ScrollPage { up } => {
+                let Some(pane) = self.focused_pane_mut() else {
+                    return;
+                };
+                let page = pane.rows.max(1) as i32;
+                match pane.scroll(if up { page } else { -page }) {
+                    ScrollOutcome::Scrolled => {}
+                    ScrollOutcome::NoHistory => pane.flash_for("no scrollback", SCROLL_NOTE_TIMEOUT),
+                    ScrollOutcome::AppControlled => {
+                        let (key, bytes) = if up { ("PageUp", b"\x1b[5~") } else { ("PageDown", b"\x1b[6~") };
+                        pane.flash_for(format!("app controls scrolling (sending {})", key), SCROLL_NOTE_TIMEOUT);
+                        if let Err(e) = pane.write_input(bytes) {
+                            tracing::warn!(pane = pane.id, error = %e, "failed to forward page key");
+                        }
+                    }
+                }
+            }
+            Action::ToggleScrollLock => {
+                if let Some(pane) = self.focused_pane_mut() {
+                    pane.toggle_scroll_lock();
+                    tracing::info!(pane = pane.id, locked = pane.scroll_lock.is_some(), "toggled scroll lock");
+                }
+            }
+            Action::ResetTerminal => {
+                if let Some(pane) = self.focused_pane_mut() {
+                    pane.reset_terminal();
+                    pane.flash("terminal reset");
+                    tracing::info!(pane = pane.id, "reset terminal");
+                }
+            }
+            Action::ToggleMouse => {
+                self.mouse = !self.mouse;
+                self.hover = None;
+                self.pointer = None;
+                self.last_mouse_pos = None;
+                self.set_status(if self.mouse {
+                    "mouse capture on"
+                } else {
+                    "mouse capture off (Alt+Shift+M turns it back on)"
+                });
+            }
+            Action::ToggleCrosshair => {
+                self.crosshair = !self.crosshair;
+                self.pointer = None;
+                self.set_status(if self.crosshair { "crosshair on" } else { "crosshair off" });
+            }
+            Action::ScrollHorizontal { cols } => {
+                if let Some(pane) = self.focused_pane_mut() {
+                    pane.scroll_horizontal(cols);
+                }
+            }
+            Action::PageViewportUp => self.page_viewport_up(),
+            Action::PageViewportDown => self.page_viewport_down(),
+            Action::ScrollViewportTo(idx) => {
+                self.follow_focus = false;
+                self.viewport_start = idx.min(self.panes.len().saturating_sub(1));
+            }
+            Action::ViewportHome => {
+                self.follow_focus = false;
+                self.viewport_start = 0;
+            }
+            Action::ViewportEnd => {
+                // Rendering pulls this back so the last page is full.
+                self.follow_focus = false;
+                self.viewport_start = self.panes.len().saturating_sub(1);
+            }
+            // A minimized window may report no size at all; nothing is
+            // drawn then, and the last real size stands for new panes.
+            Action::Resize { cols, rows } if cols == 0 || rows == 0 => {
+                tracing::debug!(cols, rows, "terminal resized to nothing; ignored");
+            }
+            Action::Resize { cols, rows } => {
+                tracing::debug!(cols, rows, "terminal resized");
+                if cols < self.term_cols || rows < self.term_rows {
+                    self.squeezed_until = Some(Instant::now() + SQUEEZE_GRACE);
+                }
+                self.term_cols = cols;
+                self.term_rows = rows;
+                // The resize clears the screen.
+                self.forget_images();
+            }
+            Action::StartSelection => self.start_selection(),
+            Action::StartSelectionAt { pane, row, col } => self.start_selection_at(pane, row, col),
+            Action::UpdateSelection { row, col } => self.update_selection_at(row, col),
+            Action::MoveSelection { rows, cols } => self.move_selection_cursor(rows, cols),
+            Action::ClearSelection => self.clear_selection(),
+            Action::JumpToError { forward } => self.jump_to_error(forward),
+            Action::Paste(text) => {
+                if let Some(prompt) = &mut self.prompt {
+                    prompt.insert_str(&text);
+                } else if let Some(pane) = self.focused_pane() {
+                    if let Err(e) = pane.paste(&text) {
+                        tracing::debug!(pane = pane.id, error = %e, "paste dropped");
+                    }
+                }
+            }
+            Action::SendInput(bytes) => {
+                if let Some(pane) = self.focused_pane() {
+                    if let Err(e) = pane.write_input(&bytes) {
+                        tracing::trace!(pane = pane.id, error = %e, "input dropped");
+                    }
+                }
+            }
+            Action::ToggleMark(idx) => {
+                if let Some(pane) = self.panes.get(idx) {
+                    if !self.marked.remove(&pane.id) {
+                        self.marked.insert(pane.id);
+                    }
+                }
+            }
+            Action::ClearMarks => self.marked.clear(),
+            Action::CollapseMarked => {
+                let targets = self.marked_or_focused();
+                let collapse = !targets.iter().all(|&idx| self.panes[idx].collapsed);
+                for idx in targets {
+                    if self.panes[idx].collapsed != collapse {
+                        self.toggle_collapse_at(idx);
+                    }
+                }
+                self.marked.clear();
+            }
+            Action::Group { name, op } => self.apply_to_group(&name, op),
+            Action::CloseGroup(name) => {
+                let ids: Vec<usize> = self.group_members(&name).iter().map(|&idx| self.panes[idx].id).collect();
+                self.close_by_id(ids);
+            }
+            Action::CloseMarked => {
+                let ids: Vec<usize> = self.marked_or_focused().iter().map(|&idx| self.panes[idx].id).collect();
+                self.close_by_id(ids);
+                self.marked.clear();
+            }
+            Action::Broadcast(line) => {
+                let bytes = format!("{}\r", line).into_bytes();
+                for idx in self.marked_or_focused() {
+                    let pane = &self.panes[idx];
+                    if let Err(e) = pane.write_input(&bytes) {
+                        tracing::trace!(pane = pane.id, error = %e, "broadcast input dropped");
+                    }
+                }
+                self.marked.clear();
+            }
+            Action::SendSignal { idx, signal, group } => self.send_signal(idx, signal, group),
+            Action::ToggleReadHold(idx) => {
+                if let Some(pane) = self.panes.get(idx) {
+                    let held = !pane.reads_held();
+                    pane.hold_reads(held);
+                    let message = format!("{} reading {}", if held { "paused" } else { "resumed" }, pane.name);
+                    self.set_status(message);
+                }
             }
         }
     }
 
-    pub fn shrink_focused_weight(&mut self, delta: u16) {
-        if let Some(pane) = self.panes.get_mut(self.focused) {
-            if !pane.collapsed {
-                pane.weight = pane.weight.saturating_sub(delta).max(1);
+    /// Deliver `signal` for `SendSignal`, reporting the outcome in the footer.
+    fn send_signal(&mut self, idx: usize, signal: Signal, group: bool) {
+        let Some(pane) = self.panes.get_mut(idx) else {
+            return;
+        };
+        let name = pane.name.clone();
+        if pane.exit_status.is_some() || pane.spawn_error.is_some() {
+            self.set_status(format!("{}: no such process", name));
+            return;
+        }
+        let target = if group { pane.foreground_group() } else { pane.pid };
+        let Some(target) = target else {
+            self.set_status(format!("{}: no process {} to signal", name, if group { "group" } else { "id" }));
+            return;
+        };
+        let mut result = signal::send(target, signal, group);
+        // Without signals, SIGKILL can still terminate the process.
+        if signal == Signal::Kill && !group {
+            if let (Err(e), Some(killer)) = (&result, &mut pane.killer) {
+                if e.kind() == std::io::ErrorKind::Unsupported {
+                    result = killer.kill();
+                }
             }
         }
+        let what = if group { "group" } else { "pid" };
+        let message = match result {
+            Ok(()) => format!("sent {} to {} ({} {})", signal, name, what, target),
+            Err(e) if signal::is_gone(&e) => format!("{}: no such process ({} {})", name, what, target),
+            Err(e) if e.kind() == std::io::ErrorKind::Unsupported => {
+                format!("{}: only SIGKILL is supported on this platform", name)
+            }
+            Err(e) => format!("{}: failed to send {}: {}", name, signal, e),
+        };
+        tracing::info!(pane = pane.id, %signal, group, target, "{}", message);
+        self.set_status(message);
     }
 
-    pub fn take_next_pane_id(&mut self) -> usize {
-        let id = self.next_pane_id;
-        self.next_pane_id += 1;
-        id
+    /// Close the panes with these ids and stop their processes.
+    fn close_by_id(&mut self, ids: Vec<usize>) {
+        for id in ids {
+            let Some(idx) = self.panes.iter().position(|p| p.id == id) else {
+                continue;
+            };
+            if let Some(pane) = self.close_pane(idx) {
+                self.teardown_pane(pane);
+            }
+        }
     }
 
-    pub fn add_pane(&mut self, pane: Pane) {
-        self.panes.push(pane);
-        self.focused = self.panes.len() - 1;
+    /// Indices of the panes in group `name`, in stack order.
+    pub fn group_members(&self, name: &str) -> Vec<usize> {
+        (0..self.panes.len()).filter(|&idx| self.panes[idx].config.group.as_deref() == Some(name)).collect()
     }
 
-    pub fn close_pane(&mut self, idx: usize) -> bool {
-        if self.panes.len() <= 1 || idx >= self.panes.len() {
-            return false;
-        }
-        self.panes.remove(idx);
-        if self.focused > idx {
-            self.focused -= 1;
-        } else if self.focused >= self.panes.len() {
-            self.focused = self.panes.len() - 1;
-        }
-        if self.viewport_start > 0 && self.viewport_start >= self.panes.len() {
-            self.viewport_start = self.panes.len().saturating_sub(1);
+    /// Every group some pane is in, in the order they first appear.
+    pub fn group_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for group in self.panes.iter().filter_map(|p| p.config.group.as_ref()) {
+            if !names.contains(group) {
+                names.push(group.clone());
+            }
         }
-        true
+        names
     }
 
-    pub fn remove_focused_pane(&mut self) -> bool {
-        self.close_pane(self.focused)
+    fn apply_to_group(&mut self, name: &str, op: GroupOp) {
+        let members = self.group_members(name);
+        let Some(&first) = members.first() else {
+            self.set_status(format!("no pane is in group {:?}", name));
+            return;
+        };
+        match op {
+            GroupOp::Collapse | GroupOp::Expand => {
+                let collapse = op == GroupOp::Collapse;
+                for idx in members {
+                    if self.panes[idx].collapsed != collapse {
+                        self.toggle_collapse_at(idx);
+                    }
+                }
+            }
+            GroupOp::Focus => self.apply(Action::Focus(first)),
+            GroupOp::Close => {
+                let running = members.iter().filter(|&&idx| !self.panes[idx].closed).count();
+                let plural = |n: usize, word: &str, suffix: &str| format!("{} {}{}", n, word, if n == 1 { "" } else { suffix });
+                self.confirm = Some(Confirm {
+                    message: format!(
+                        "Close group {}: {}, terminating {}? (y/n)",
+                        name,
+                        plural(members.len(), "pane", "s"),
+                        plural(running, "process", "es")
+                    ),
+                    action: Confirmed::CloseGroup(name.to_string()),
+                    pane_id: self.panes[first].id,
+                });
+            }
+        }
     }
 
-    pub fn toggle_collapse_at(&mut self, idx: usize) {
-        if let Some(pane) = self.panes.get_mut(idx) {
-            pane.collapsed = !pane.collapsed;
+    /// Stack indices of the marked panes in stack order, or the focused
+    /// pane's when none are marked.
+    pub fn marked_or_focused(&self) -> Vec<usize> {
+        if self.marked.is_empty() {
+            return if self.focused < self.panes.len() { vec![self.focused] } else { Vec::new() };
         }
+        (0..self.panes.len()).filter(|&idx| self.marked.contains(&self.panes[idx].id)).collect()
     }
 
-    pub fn page_viewport_up(&mut self) {
-        let page = self.visible_pane_count().max(1);
-        self.viewport_start = self.viewport_start.saturating_sub(page);
+    fn spawn_pane(&mut self, config: PaneConfig) {
+        let pane = self.new_pane(config);
+        self.add_pane(pane);
     }
 
-    pub fn page_viewport_down(&mut self) {
-        let page = self.visible_pane_count().max(1);
-        let max = self.panes.len().saturating_sub(1);
-        self.viewport_start = (self.viewport_start + page).min(max);
+    /// Take the newest entry off `closed_panes` and run its config again in
+    /// a new pane at the end of the stack, with the weight it had.
+    fn reopen_closed(&mut self) {
+        let Some(closed) = self.closed_panes.pop() else {
+            self.set_status("no closed pane to reopen");
+            return;
+        };
+        tracing::info!(closed = closed.id, name = %closed.name, "reopening closed pane");
+        let mut pane = self.new_pane(closed.config);
+        pane.weight = closed.weight;
+        self.add_pane(pane);
     }
 
-    fn visible_pane_count(&self) -> usize {
-        self.last_pane_areas.len()
-    }
+    /// Start `config` in a pane sized for one more in the stack, or show
+    /// why it couldn't start.
+    fn new_pane(&mut self, mut config: PaneConfig) -> Pane {
+        let pane_id = self.take_next_pane_id();
+        let unnamed = config.name.is_empty();
+        if unnamed {
+            config.name = format!("Shell {}", pane_id);
+        }
 
-    pub fn focused_pane(&self) -> Option<&Pane> {
-        self.panes.get(self.focused)
+        let cols = self.term_cols.saturating_sub(2).max(MIN_PTY_COLS);
+        let n_panes = self.panes.len() + 1;
+        let rows = (self.term_rows / n_panes as u16).saturating_sub(2).max(MIN_PTY_ROWS);
+
+        let mut pane = self.spawner.spawn(pane_id, &config, cols, rows).unwrap_or_else(|e| {
+            tracing::error!(pane = pane_id, name = %config.name, error = %e, "spawn failed");
+            Pane::spawn_failed(pane_id, &config, &e, cols, rows)
+        });
+        pane.weight = config.weight.unwrap_or(self.weights.default_weight);
+        pane.auto_name = config.auto_name.unwrap_or(unnamed);
+        pane
     }
 
-    pub fn focused_pane_mut(&mut self) -> Option<&mut Pane> {
-        self.panes.get_mut(self.focused)
+    /// Open a shell beside the focused pane, sharing its slot. A slot holds
+    /// at most two panes.
+    fn split_focused(&mut self) {
+        if self.panes.is_empty() {
+            return;
+        }
+        let leader = layout::slot_start(&self.panes, self.focused);
+        if layout::slot_end(&self.panes, leader) - leader > 1 {
+            self.set_status("this pane is already split");
+            return;
+        }
+        let pane_id = self.take_next_pane_id();
+        let config = PaneConfig {
+            name: format!("Shell {}", pane_id),
+            ..PaneConfig::default()
+        };
+        let left = &self.panes[leader];
+        let cols = (left.cols / 2).max(MIN_PTY_COLS);
+        let rows = left.rows;
+        let mut pane = self.spawner.spawn(pane_id, &config, cols, rows).unwrap_or_else(|e| {
+            tracing::error!(pane = pane_id, error = %e, "spawn failed");
+            Pane::spawn_failed(pane_id, &config, &e, cols, rows)
+        });
+        pane.weight = self.weights.default_weight;
+        pane.auto_name = true;
+        pane.split_right = true;
+        // A collapsed slot opens so the new shell can be seen.
+        self.panes[leader].collapsed = false;
+        self.panes.insert(leader + 1, pane);
+        self.focused = leader + 1;
     }
 
-    pub fn start_selection(&mut self) {
-        if let Some(pane) = self.panes.get(self.focused) {
-            self.selection = Some(SelectionState {
-                pane_id: pane.id,
-                anchor: (0, 0),
-                cursor: (0, 0),
-            });
+    /// Start the panes `lazy_start` held back once they are focused or were
+    /// on screen in the last frame. Returns whether any started.
+    pub fn start_pending_panes(&mut self) -> bool {
+        let due: Vec<usize> = (0..self.panes.len())
+            .filter(|&idx| {
+                let pane = &self.panes[idx];
+                pane.pending_start && (idx == self.focused || self.last_pane_ids.contains(&pane.id))
+            })
+            .collect();
+        for &idx in &due {
+            self.start_pending(idx);
         }
+        !due.is_empty()
     }
 
-    pub fn start_selection_at(&mut self, pane_idx: usize, row: u16, col: u16) {
-        if let Some(pane) = self.panes.get(pane_idx) {
-            self.selection = Some(SelectionState {
-                pane_id: pane.id,
-                anchor: (row, col),
-                cursor: (row, col),
-            });
-            self.focused = pane_idx;
+    /// Replace the placeholder at `idx` with its pane, started.
+    fn start_pending(&mut self, idx: usize) {
+        let Some(old) = self.panes.get(idx).filter(|p| p.pending_start) else {
+            return;
+        };
+        let config = old.config.clone();
+        let (cols, rows) = (old.cols, old.rows);
+        let pane_id = self.take_next_pane_id();
+        let mut pane = self.spawner.spawn(pane_id, &config, cols, rows).unwrap_or_else(|e| {
+            tracing::error!(pane = pane_id, name = %config.name, error = %e, "spawn failed");
+            Pane::spawn_failed(pane_id, &config, &e, cols, rows)
+        });
+        tracing::info!(pane = pane_id, name = %config.name, "lazy pane started");
+        let old = &self.panes[idx];
+        pane.stable_id = old.stable_id;
+        pane.weight = old.weight;
+        pane.collapsed = old.collapsed;
+        pane.split_right = old.split_right;
+        pane.from_config = old.from_config;
+        pane.auto_name = old.auto_name;
+        if self.marked.remove(&old.id) {
+            self.marked.insert(pane.id);
         }
+        self.panes[idx] = pane;
     }
 
-    pub fn update_selection_at(&mut self, row: u16, col: u16) {
-        if let Some(sel) = &mut self.selection {
-            sel.cursor = (row, col);
+    fn restart_pane(&mut self, idx: usize) {
+        let Some(old) = self.panes.get(idx) else {
+            return;
+        };
+        if !old.closed {
+            return;
         }
+        self.respawn(idx, None);
     }
 
-    pub fn move_selection_cursor(&mut self, dr: i32, dc: i32) {
-        let bounds = if let Some(sel) = &self.selection {
-            let pane_id = sel.pane_id;
-            self.panes
-                .iter()
-                .find(|p| p.id == pane_id)
-                .map(|p| (p.rows as i32, p.cols as i32))
-                .unwrap_or((0, 0))
-        } else {
+    fn watch_restart(&mut self, idx: usize, changed: &Path) {
+        let Some(pane) = self.panes.get_mut(idx).filter(|p| !p.watch_paused) else {
             return;
         };
-        if let Some(sel) = &mut self.selection {
-            let r = (sel.cursor.0 as i32 + dr).clamp(0, bounds.0 - 1) as u16;
-            let c = (sel.cursor.1 as i32 + dc).clamp(0, bounds.1 - 1) as u16;
-            sel.cursor = (r, c);
+        // Don't keep restarting a command that can't get going; Alt+p
+        // resumes watching.
+        if pane.rapid_failures >= MAX_RAPID_FAILURES && pane.early_failure().is_some() {
+            pane.watch_paused = true;
+            let message = format!("{}: giving up after {} rapid failures", pane.name, pane.rapid_failures);
+            tracing::warn!(pane = pane.id, "{}", message);
+            self.set_status(message);
+            return;
         }
+        self.respawn(idx, Some(format!("{} changed", changed.display())));
     }
 
-    pub fn selection_text(&self) -> Option<String> {
-        let sel = self.selection.as_ref()?;
-        let pane = self.panes.iter().find(|p| p.id == sel.pane_id)?;
-        let term = pane.term.lock();
-        let ((sr, sc), (er, ec)) = sel.normalized();
-        let mut text = String::new();
-        for row in sr..=er {
-            let start_col = if row == sr { sc } else { 0 };
-            let end_col = if row == er { ec } else { pane.cols.saturating_sub(1) };
-            let mut row_text = String::new();
-            for col in start_col..=end_col {
-                let ch = crate::terminal::cell_char(&term, row as usize, col as usize);
-                if ch == "\0" {
-                    row_text.push(' ');
-                } else {
-                    row_text.push_str(&ch);
-                }
+    /// Replace the pane at `idx` with a fresh run of its config, ending the
+    /// old process if it's still going. The old output stays above a
+    /// divider saying how the last run ended and, if given, why.
+    fn respawn(&mut self, idx: usize, reason: Option<String>) {
+        let old = &self.panes[idx];
+        let config = old.config.clone();
+        let (cols, rows) = (old.cols, old.rows);
+        let pane_id = self.take_next_pane_id();
+
+        let mut pane = match self.spawner.spawn(pane_id, &config, cols, rows) {
+            Ok(pane) => {
+                tracing::info!(old = self.panes[idx].id, pane = pane_id, name = %config.name, "pane restarted");
+                pane
             }
-            if !text.is_empty() {
-                text.push('\n');
+            Err(e) => {
+                tracing::error!(pane = pane_id, name = %config.name, error = %e, "restart failed");
+                Pane::spawn_failed(pane_id, &config, &e, cols, rows)
             }
-            text.push_str(row_text.trim_end());
+        };
+        let old = &mut self.panes[idx];
+        pane.weight = old.weight;
+        pane.collapsed = old.collapsed;
+        pane.split_right = old.split_right;
+        pane.watch_paused = old.watch_paused;
+        pane.from_config = old.from_config;
+        pane.auto_name = old.auto_name;
+        // Tasks and panes showing an error are open.
+        if pane.is_task() || pane.spawn_error.is_some() {
+            pane.collapsed = false;
         }
-        Some(text)
+        pane.stable_id = old.stable_id;
+        pane.run = old.run + 1;
+        if old.early_failure().is_some() {
+            pane.rapid_failures = old.rapid_failures;
+        }
+        pane.take_screen(old);
+        if self.marked.remove(&old.id) {
+            self.marked.insert(pane.id);
+        }
+        pane.print_divider(&divider_label(old, reason));
+        pane.print_spawn_error();
+        if self.selection.as_ref().is_some_and(|s| s.pane_id == old.id) {
+            self.selection = None;
+        }
+        self.panes[idx] = pane;
     }
 
-    pub fn clear_selection(&mut self) {
-        self.selection = None;
+    /// Reorder the stack for `sort_mode`, at most once per `SORT_INTERVAL`.
+    /// Focus stays on the same pane. Returns whether anything moved.
+    pub fn sort_panes(&mut self) -> bool {
+        if self.sort_mode == SortMode::Off || self.last_sort.is_some_and(|t| t.elapsed() < SORT_INTERVAL) {
+            return false;
+        }
+        // Split slots move as one, placed by their busiest pane or their
+        // first pane's name.
+        let mut slots = Vec::new();
+        let mut i = 0;
+        while i < self.panes.len() {
+            let end = layout::slot_end(&self.panes, i);
+            slots.push(i..end);
+            i = end;
+        }
+        match self.sort_mode {
+            SortMode::Off => {}
+            SortMode::Activity => slots.sort_by_key(|slot| {
+                std::cmp::Reverse(self.panes[slot.clone()].iter().map(|p| p.last_output_at).max())
+            }),
+            SortMode::Name => slots.sort_by_cached_key(|slot| self.panes[slot.start].name.to_lowercase()),
+        }
+        let order: Vec<usize> = slots.into_iter().flatten().collect();
+        if order.iter().enumerate().all(|(pos, &i)| pos == i) {
+            return false;
+        }
+
+        let focused_id = self.focused_pane().map(|p| p.id);
+        let mut taken: Vec<Option<Pane>> = std::mem::take(&mut self.panes).into_iter().map(Some).collect();
+        self.panes = order.iter().filter_map(|&i| taken[i].take()).collect();
+        if let Some(idx) = focused_id.and_then(|id| self.panes.iter().position(|p| p.id == id)) {
+            self.focused = idx;
+        }
+        self.last_sort = Some(Instant::now());
+        true
+    }
+
+    fn solo(&mut self, idx: usize) {
+        if idx >= self.panes.len() {
+            return;
+        }
+        let start = layout::slot_start(&self.panes, idx);
+        let end = layout::slot_end(&self.panes, start);
+        for (i, pane) in self.panes.iter_mut().enumerate() {
+            pane.collapsed = !(start..end).contains(&i);
+        }
+        self.focused = idx;
+    }
+
+    fn focus(&mut self, idx: usize) {
+        if idx < self.panes.len() {
+            self.focused = idx;
+        }
+    }
+
+    fn focus_next(&mut self) {
+        self.cycle_focus(1);
+    }
+
+    fn focus_prev(&mut self) {
+        self.cycle_focus(self.panes.len().saturating_sub(1));
+    }
+
+    /// Move focus `step` places around the stack, passing over tasks that
+    /// are done unless `focus_finished_tasks` is set.
+    fn cycle_focus(&mut self, step: usize) {
+        let n = self.panes.len();
+        let mut idx = self.focused;
+        for _ in 0..n {
+            idx = (idx + step) % n;
+            if self.focus_finished_tasks || !self.panes[idx].task_succeeded() {
+                self.focused = idx;
+                return;
+            }
+        }
+    }
+
+    /// Record that a pane's process exited. The pane finishes once its
+    /// output has ended too; see `settle_pane`.
+    pub fn pane_exited(&mut self, pane_id: usize, status: ExitStatus) {
+        let Some(idx) = self.panes.iter().position(|p| p.id == pane_id) else {
+            return;
+        };
+        let pane = &mut self.panes[idx];
+        pane.exit_status = Some(status);
+        pane.exited_at = Some(Instant::now());
+        pane.mark_dirty();
+        if let Some(code) = pane.early_failure() {
+            pane.rapid_failures += 1;
+            tracing::warn!(pane = pane_id, name = %pane.name, code, runs = pane.rapid_failures, "failed right after starting");
+        }
+        self.settle_pane(idx, Instant::now());
+    }
+
+    /// Record that a pane's reader reached end of file.
+    pub fn pane_eof(&mut self, pane_id: usize) {
+        let Some(idx) = self.panes.iter().position(|p| p.id == pane_id) else {
+            return;
+        };
+        self.panes[idx].mark_eof();
+        self.settle_pane(idx, Instant::now());
+    }
+
+    /// Finish panes that have waited long enough for the other half of
+    /// their ending; returns whether any did. Called on every tick.
+    pub fn settle_panes(&mut self) -> bool {
+        let now = Instant::now();
+        let mut settled = false;
+        for idx in 0..self.panes.len() {
+            settled |= self.settle_pane(idx, now);
+        }
+        settled
+    }
+
+    /// Mark panes stale or fresh against `stale_after`; a pane is redrawn
+    /// only when it crosses the threshold. Exited panes keep their exit
+    /// styling instead. Called on every tick.
+    pub fn update_stale_panes(&mut self) {
+        let Some(after) = self.stale_after else {
+            return;
+        };
+        let now = Instant::now();
+        for pane in &mut self.panes {
+            let stale = !pane.closed && pane.idle_for(now) >= after;
+            if stale != pane.stale {
+                pane.stale = stale;
+                pane.mark_dirty();
+            }
+        }
+    }
+
+    /// Look up what each auto-named pane is running, at most once per
+    /// `PROCESS_NAME_INTERVAL`. Called on every tick.
+    pub fn update_process_names(&mut self) {
+        let now = Instant::now();
+        if self.process_names_at.is_some_and(|at| now.duration_since(at) < PROCESS_NAME_INTERVAL) {
+            return;
+        }
+        self.process_names_at = Some(now);
+        for pane in self.panes.iter_mut().filter(|p| p.auto_name) {
+            let name = if pane.closed { None } else { pane.foreground_name() };
+            if name != pane.process_name {
+                pane.process_name = name;
+                pane.mark_dirty();
+            }
+        }
+    }
+
+    /// Take a throughput sample from each pane's read counters when one is
+    /// due, redrawing the panes whose meter moved. Called on every tick.
+    pub fn sample_throughput(&mut self) {
+        if !self.show_throughput {
+            return;
+        }
+        let now = Instant::now();
+        for pane in &mut self.panes {
+            let running = pane.run_state() == RunState::Running;
+            let (bytes, lines) = (pane.bytes_read(), pane.lines_read());
+            if pane.throughput.sample(bytes, lines, running, now) {
+                pane.mark_dirty();
+            }
+        }
+    }
+
+    /// Close each pane's parse-time interval, and ask about the first pane
+    /// to turn heavy. The question goes away with the load.
+    pub fn sample_parse_load(&mut self) {
+        let now = Instant::now();
+        for pane in &mut self.panes {
+            if pane.parse_load.sample(now) {
+                let rate = pane.parse_load.rate().as_millis();
+                tracing::warn!(pane = pane.id, name = %pane.name, rate_ms = rate, "output outpaces the parser");
+                if self.heavy_output.is_none() {
+                    self.heavy_output = Some(pane.id);
+                }
+            }
+        }
+        let still_heavy = |id| self.panes.iter().any(|p| p.id == id && p.parse_load.is_heavy() && !p.parse_load.ignored);
+        if self.heavy_output.is_some_and(|id| !still_heavy(id)) {
+            self.heavy_output = None;
+        }
+    }
+
+    /// A task collapses and gives up focus when it finishes successfully,
+    /// and stays open to show the error otherwise. Returns whether the pane
+    /// finished just now.
+    fn settle_pane(&mut self, idx: usize, now: Instant) -> bool {
+        let pane = &mut self.panes[idx];
+        if !pane.settle(now) {
+            return false;
+        }
+        let exit_code = pane.exit_status.as_ref().map(|s| s.exit_code());
+        tracing::info!(pane = pane.id, name = %pane.name, ?exit_code, "pane finished");
+        // The only pane ending well goes the way closing it would; a failure
+        // stays on screen to be read.
+        let last_ok = self.panes.len() == 1 && exit_code == Some(0);
+        match self.on_last_pane_close {
+            LastPaneClose::Quit if last_ok => {
+                self.should_quit = true;
+                return true;
+            }
+            LastPaneClose::RespawnShell if last_ok => {
+                self.respawn_shell();
+                return true;
+            }
+            _ => {}
+        }
+        let pane = &mut self.panes[idx];
+        if !pane.is_task() {
+            return true;
+        }
+        pane.collapsed = pane.task_succeeded();
+        if pane.collapsed && idx == self.focused && !self.focus_finished_tasks {
+            self.focus_next();
+        }
+        true
+    }
+
+    /// The window shrank moments ago, so panes shouldn't shrink yet.
+    pub fn squeezed(&self) -> bool {
+        self.squeezed_until.is_some_and(|until| Instant::now() < until)
+    }
+
+    /// Machine-readable state of every pane, for tooling.
+    pub fn session_snapshot(&self) -> SessionSnapshot {
+        SessionSnapshot::capture(&self.panes, self.focused)
+    }
+
+    /// Focus the pane that just printed `output`, if focus-follows-activity
+    /// wants it: the output qualifies, no focus move happened in the last
+    /// second, nothing was typed just now and no manual focus change paused
+    /// it.
+    pub fn follow_activity(&mut self, pane_id: usize, output: &[u8]) {
+        let Some(idx) = self.panes.iter().position(|p| p.id == pane_id) else {
+            return;
+        };
+        if idx == self.focused || self.prompt.is_some() || self.selection.is_some() || self.jump_mode {
+            return;
+        }
+        let now = Instant::now();
+        let recent = |at: Option<Instant>, within: Duration| at.is_some_and(|at| now.duration_since(at) < within);
+        if recent(self.last_auto_focus, AUTO_FOCUS_INTERVAL)
+            || recent(self.last_keypress, TYPING_GRACE)
+            || self.auto_focus_paused_until.is_some_and(|until| now < until)
+        {
+            return;
+        }
+        let qualifies = match self.focus_follow.mode {
+            FocusFollows::Off => false,
+            FocusFollows::Any => !output.is_empty(),
+            FocusFollows::Match => self.focus_follow.pattern.as_ref().is_some_and(|re| {
+                crate::command_capture::clean(&String::from_utf8_lossy(output), true)
+                    .lines()
+                    .any(|line| re.is_match(line))
+            }),
+        };
+        if !qualifies {
+            return;
+        }
+        tracing::debug!(pane = pane_id, "focus follows activity");
+        // Not a change by hand, so it doesn't pause following.
+        let paused_until = self.auto_focus_paused_until;
+        self.apply(Action::Focus(idx));
+        self.auto_focus_paused_until = paused_until;
+        self.last_auto_focus = Some(now);
+    }
+
+    /// How long focus-follows-activity stays paused after a manual focus
+    /// change.
+    pub fn auto_focus_paused_for(&self) -> Option<Duration> {
+        let until = self.auto_focus_paused_until?;
+        until.checked_duration_since(Instant::now()).filter(|d| !d.is_zero())
+    }
+
+    /// Show `message` in the footer for a few seconds.
+    pub fn set_status(&mut self, message: impl Into<String>) {
+        self.status = Some((message.into(), Instant::now()));
+    }
+
+    /// The footer message, while it is still fresh.
+    pub fn status_message(&self) -> Option<&str> {
+        self.status
+            .as_ref()
+            .filter(|(_, at)| at.elapsed() < STATUS_TIMEOUT)
+            .map(|(message, _)| message.as_str())
+    }
+
+    pub fn weights(&self) -> WeightConfig {
+        self.weights
+    }
+
+    /// Adopt new weight limits, pulling existing panes into the new range.
+    pub fn set_weights(&mut self, weights: WeightConfig) {
+        self.weights = weights;
+        for pane in &mut self.panes {
+            pane.weight = weights.clamp(pane.weight);
+        }
+    }
+
+    fn grow_weight_at(&mut self, idx: usize) {
+        let weights = self.weights;
+        self.change_weight_at(idx, true, |w| weights.clamp(w.saturating_add(weights.weight_step)));
+    }
+
+    fn shrink_weight_at(&mut self, idx: usize) {
+        let weights = self.weights;
+        self.change_weight_at(idx, false, |w| weights.clamp(w.saturating_sub(weights.weight_step)));
+    }
+
+    /// Grow or shrink the focused slot by one row of the last frame's layout:
+    /// the nearest weight that moves its bottom edge, or one weight unit
+    /// when no weight in range does.
+    fn resize_by_row(&mut self, grow: bool) {
+        let idx = layout::slot_start(&self.panes, self.focused);
+        let Some(pane) = self.panes.get(idx) else {
+            return;
+        };
+        if pane.collapsed {
+            self.set_status("expand the pane to resize it");
+            return;
+        }
+        let weights = self.weights;
+        let weight = pane.weight;
+        let step = |w: u16| weights.clamp(if grow { w.saturating_add(1) } else { w.saturating_sub(1) });
+        let mut target = step(weight);
+        if let Some((area, indicator)) = self.stack_area {
+            let height = |w: u16| {
+                let mut all: Vec<u16> = self.panes.iter().map(|p| p.weight).collect();
+                all[idx] = w;
+                layout::dry_run_layout(&self.panes, &all, self.chrome, indicator, self.viewport_start, area)
+                    .iter()
+                    .find(|&&(i, _)| i == idx)
+                    .map(|(_, r)| r.height)
+            };
+            let now = height(weight);
+            let mut w = target;
+            loop {
+                if height(w) != now {
+                    target = w;
+                    break;
+                }
+                if step(w) == w {
+                    break;
+                }
+                w = step(w);
+            }
+        }
+        self.change_weight_at(idx, grow, |_| target);
+    }
+
+    /// Change the weight of the slot holding the pane at `idx`, if expanded,
+    /// and keep the preview going for it.
+    fn change_weight_at(&mut self, idx: usize, grew: bool, change: impl Fn(u16) -> u16) {
+        let idx = layout::slot_start(&self.panes, idx);
+        let Some(pane) = self.panes.get_mut(idx).filter(|p| !p.collapsed) else {
+            return;
+        };
+        let now = Instant::now();
+        let from = match self.weight_preview {
+            Some(p) if p.pane_id == pane.id && now.duration_since(p.at) < WEIGHT_PREVIEW_TIMEOUT => p.from,
+            _ => pane.weight,
+        };
+        pane.weight = change(pane.weight);
+        self.weight_preview = Some(WeightPreview { pane_id: pane.id, from, grew, at: now });
+    }
+
+    /// The weight preview, while it is still fresh.
+    pub fn active_weight_preview(&self) -> Option<WeightPreview> {
+        self.weight_preview.filter(|p| p.at.elapsed() < WEIGHT_PREVIEW_TIMEOUT)
+    }
+
+    /// Write the layout state file if the layout changed. Unforced saves are
+    /// rate-limited, so this is cheap to call on every tick.
+    pub fn save_layout(&mut self, force: bool) {
+        if let Some(persistence) = &mut self.layout_persistence {
+            persistence.save_if_changed(&self.panes, &self.presets, force);
+        }
+    }
+
+    fn take_next_pane_id(&mut self) -> usize {
+        let id = self.next_pane_id;
+        self.next_pane_id += 1;
+        id
+    }
+
+    fn add_pane(&mut self, pane: Pane) {
+        self.panes.push(pane);
+        self.focused = self.panes.len() - 1;
+    }
+
+    /// `[x]` on the only pane, which `close_pane` keeps: do what
+    /// `on_last_pane_close` says instead.
+    fn close_last_pane(&mut self) {
+        match self.on_last_pane_close {
+            LastPaneClose::Ignore => {
+                self.set_status("the last pane stays open; set on_last_pane_close to quit or respawn-shell to change that")
+            }
+            LastPaneClose::Quit if !self.panes[0].closed => {
+                self.confirm = Some(Confirm {
+                    message: format!("Close {} and quit, terminating its process? (y/n)", self.panes[0].name),
+                    action: Confirmed::Quit,
+                    pane_id: self.panes[0].id,
+                });
+            }
+            LastPaneClose::Quit => self.should_quit = true,
+            LastPaneClose::RespawnShell => self.respawn_shell(),
+        }
+    }
+
+    /// Replace the only pane with a fresh default shell.
+    fn respawn_shell(&mut self) {
+        let pane = self.new_pane(PaneConfig::default());
+        self.add_pane(pane);
+        if let Some(old) = self.close_pane(0) {
+            self.teardown_pane(old);
+        }
+    }
+
+    /// Take the pane at `idx` out of the stack, keeping focus and the
+    /// viewport on the same panes. The caller tears it down.
+    fn close_pane(&mut self, idx: usize) -> Option<Pane> {
+        if self.panes.len() <= 1 || idx >= self.panes.len() {
+            return None;
+        }
+        let removed = self.panes.remove(idx);
+        if self.selection.as_ref().is_some_and(|s| s.pane_id == removed.id) {
+            self.selection = None;
+        }
+        // The right half of a split takes over the slot, at full width.
+        if !removed.split_right {
+            if let Some(next) = self.panes.get_mut(idx).filter(|p| p.split_right) {
+                next.split_right = false;
+                next.weight = removed.weight;
+                next.collapsed = removed.collapsed;
+            }
+        }
+        if self.focused > idx {
+            self.focused -= 1;
+        } else if self.focused >= self.panes.len() {
+            self.focused = self.panes.len() - 1;
+        }
+        // Keep the same panes in view when one above the viewport goes away.
+        if idx < self.viewport_start {
+            self.viewport_start -= 1;
+        }
+        if self.viewport_start > 0 && self.viewport_start >= self.panes.len() {
+            self.viewport_start = self.panes.len().saturating_sub(1);
+        }
+        Some(removed)
+    }
+
+    /// Every closed pane ends here: its process is ended, its PTY closed and
+    /// its event forwarding stopped, and it's recorded in `closed_panes`.
+    pub fn teardown_pane(&mut self, mut pane: Pane) {
+        let exit_code = pane.exit_status.as_ref().map(|status| status.exit_code());
+        tracing::info!(
+            pane = pane.id,
+            name = %pane.name,
+            exit_code,
+            bytes = pane.bytes_read(),
+            "pane closed"
+        );
+        pane.shut_down();
+        self.marked.remove(&pane.id);
+        if self.closed_panes.len() == CLOSED_HISTORY {
+            self.closed_panes.remove(0);
+        }
+        let config = PaneConfig { name: pane.name.clone(), ..pane.config.clone() };
+        self.closed_panes.push(ClosedPane {
+            id: pane.id,
+            name: pane.name.clone(),
+            exit_code,
+            config,
+            weight: pane.weight,
+        });
+    }
+
+    /// Tear down every pane, at the end of the session.
+    pub fn close_all(&mut self) {
+        for pane in std::mem::take(&mut self.panes) {
+            self.teardown_pane(pane);
+        }
+    }
+
+    /// Close every pane except the one at `keep`, terminating their
+    /// processes, and focus it. Returns how many panes were closed.
+    pub fn close_others(&mut self, keep: usize) -> usize {
+        if keep >= self.panes.len() {
+            return 0;
+        }
+        let mut kept = self.panes.remove(keep);
+        kept.split_right = false;
+        let removed = std::mem::replace(&mut self.panes, vec![kept]);
+        self.viewport_start = 0;
+        self.finish_mass_close(removed, 0)
+    }
+
+    /// Close every pane below the one at `idx`, terminating their
+    /// processes, and focus it; a pane beside it stays. Returns how many
+    /// panes were closed.
+    pub fn close_after(&mut self, idx: usize) -> usize {
+        if idx >= self.panes.len() {
+            return 0;
+        }
+        let removed = self.panes.split_off(layout::slot_end(&self.panes, idx));
+        self.viewport_start = self.viewport_start.min(idx);
+        self.finish_mass_close(removed, idx)
+    }
+
+    fn finish_mass_close(&mut self, removed: Vec<Pane>, focus: usize) -> usize {
+        let ids: Vec<usize> = removed.iter().map(|p| p.id).collect();
+        if self.selection.as_ref().is_some_and(|s| ids.contains(&s.pane_id)) {
+            self.selection = None;
+        }
+        self.focused = focus;
+        tracing::info!(closed = ids.len(), "closed panes");
+        let closed = removed.len();
+        for pane in removed {
+            self.teardown_pane(pane);
+        }
+        closed
+    }
+
+    /// Ask in the footer before `close` around the focused pane; nothing is
+    /// asked when there is nothing to close.
+    pub fn confirm_close(&mut self, close: MassClose) {
+        let focused = self.focused;
+        let closing: Vec<&Pane> = match close {
+            MassClose::Others => self.panes.iter().enumerate().filter(|&(i, _)| i != focused).map(|(_, p)| p).collect(),
+            MassClose::Below => self.panes.iter().skip(layout::slot_end(&self.panes, focused)).collect(),
+            MassClose::Marked => self.marked_or_focused().into_iter().map(|idx| &self.panes[idx]).collect(),
+        };
+        if closing.is_empty() {
+            self.set_status(match close {
+                MassClose::Others => "no other panes to close",
+                MassClose::Below => "no panes below to close",
+                MassClose::Marked => "no marked panes to close",
+            });
+            return;
+        }
+        let running = closing.iter().filter(|p| !p.closed).count();
+        let plural = |n: usize, word: &str, suffix: &str| format!("{} {}{}", n, word, if n == 1 { "" } else { suffix });
+        let message = format!(
+            "Close {}, terminating {}? (y/n)",
+            plural(closing.len(), "pane", "s"),
+            plural(running, "process", "es")
+        );
+        self.confirm = Some(Confirm {
+            message,
+            action: Confirmed::Close(close),
+            pane_id: self.panes[focused].id,
+        });
+    }
+
+    /// Signal the focused pane's process, asking first for SIGKILL.
+    pub fn confirm_signal(&mut self, signal: Signal, group: bool) {
+        let Some(pane) = self.panes.get(self.focused) else {
+            return;
+        };
+        if signal != Signal::Kill {
+            self.apply(Action::SendSignal { idx: self.focused, signal, group });
+            return;
+        }
+        let target = if group { "the foreground group of" } else { "the process of" };
+        self.confirm = Some(Confirm {
+            message: format!("Send {} to {} {}? (y/n)", signal, target, pane.name),
+            action: Confirmed::Signal { signal, group },
+            pane_id: pane.id,
+        });
+    }
+
+    /// Carry out the close that `confirm` asked about.
+    pub fn accept_confirm(&mut self) {
+        let Some(confirm) = self.confirm.take() else {
+            return;
+        };
+        let Some(idx) = self.panes.iter().position(|p| p.id == confirm.pane_id) else {
+            return;
+        };
+        self.apply(match confirm.action {
+            Confirmed::Close(MassClose::Others) => Action::CloseOthers(idx),
+            Confirmed::Close(MassClose::Below) => Action::CloseAfter(idx),
+            Confirmed::Close(MassClose::Marked) => Action::CloseMarked,
+            Confirmed::CloseGroup(name) => Action::CloseGroup(name),
+            Confirmed::Signal { signal, group } => Action::SendSignal { idx, signal, group },
+            Confirmed::Quit => Action::Quit,
+        });
+    }
+
+    /// Collapse or expand the slot holding the pane at `idx`.
+    fn toggle_collapse_at(&mut self, idx: usize) {
+        if idx >= self.panes.len() {
+            return;
+        }
+        let start = layout::slot_start(&self.panes, idx);
+        let end = layout::slot_end(&self.panes, start);
+        let collapsed = !self.panes[start].collapsed;
+        for pane in &mut self.panes[start..end] {
+            pane.collapsed = collapsed;
+        }
+    }
+
+    fn page_viewport_up(&mut self) {
+        self.follow_focus = false;
+        let page = self.visible_pane_count().max(1);
+        self.viewport_start = self.viewport_start.saturating_sub(page);
+    }
+
+    fn page_viewport_down(&mut self) {
+        self.follow_focus = false;
+        let page = self.visible_pane_count().max(1);
+        let max = self.panes.len().saturating_sub(1);
+        self.viewport_start = (self.viewport_start + page).min(max);
+    }
+
+    /// Lines to scroll for a wheel notch, as for `Action::Scroll`: positive
+    /// moves into history. With `page`, a pane height instead of the
+    /// configured step.
+    pub fn wheel_lines(&mut self, up: bool, page: bool) -> i32 {
+        let now = Instant::now();
+        let streak = match self.wheel_streak {
+            Some((at, n)) if self.wheel.acceleration && now.duration_since(at) < WHEEL_STREAK_GAP => {
+                (n + 1).min(WHEEL_MAX_MULTIPLIER)
+            }
+            _ => 1,
+        };
+        self.wheel_streak = Some((now, streak));
+        let lines = if page {
+            self.focused_pane().map_or(1, |p| p.rows.max(1) as i32)
+        } else {
+            self.wheel.lines as i32 * streak as i32
+        };
+        if up != self.wheel.natural {
+            lines
+        } else {
+            -lines
+        }
+    }
+
+    /// Record where the frame just drawn put each pane.
+    pub fn set_layout(&mut self, layout: Vec<(usize, Rect)>) {
+        let ids: Vec<usize> = layout.iter().map(|&(idx, _)| self.panes[idx].id).collect();
+        let layout: Vec<(usize, PaneAreas)> =
+            layout.into_iter().map(|(idx, area)| (idx, PaneAreas::new(area, self.chrome))).collect();
+        // Closing a pane can leave every rectangle as it was, filled by the
+        // panes that moved up.
+        if layout != self.last_pane_areas || ids != self.last_pane_ids {
+            self.hover = None;
+        }
+        self.last_pane_ids = ids;
+        self.last_pane_areas = layout;
+    }
+
+    /// Whether output from the pane shows as it arrives: the pane was on
+    /// the last frame or in the peek, and no scroll lock holds its view.
+    /// Otherwise it only moves the pane's title, status or indicator.
+    pub fn shows_output(&self, pane_id: usize) -> bool {
+        let on_screen = self.last_pane_ids.contains(&pane_id) || self.peek.is_some();
+        on_screen && self.panes.iter().any(|p| p.id == pane_id && p.scroll_lock.is_none())
+    }
+
+    /// Whether a frame for what changes on its own or through unseen output
+    /// is due: run times, idle and stale marks, hidden panes' titles and
+    /// indicators. Those redraw together at most once per `title_interval`.
+    pub fn title_redraw_due(&mut self) -> bool {
+        let now = Instant::now();
+        if now.duration_since(self.title_redraw_at) < self.title_interval {
+            return false;
+        }
+        self.title_redraw_at = now;
+        true
+    }
+
+    /// Whether `last_pane_areas` still describes the stack: no pane it
+    /// lists has been closed or moved since it was drawn. Clicks are only
+    /// mapped to panes while this holds.
+    pub fn layout_is_current(&self) -> bool {
+        self.last_pane_areas.len() == self.last_pane_ids.len()
+            && self
+                .last_pane_areas
+                .iter()
+                .zip(&self.last_pane_ids)
+                .all(|(&(idx, _), &id)| self.panes.get(idx).is_some_and(|p| p.id == id))
+    }
+
+    /// Where the pane with this id was drawn on the last frame, with its
+    /// index now. Holds while the layout is stale too, since the screen
+    /// still shows that frame.
+    pub fn drawn_pane(&self, pane_id: usize) -> Option<(usize, PaneAreas)> {
+        let (_, &(_, areas)) = self.last_pane_ids.iter().zip(&self.last_pane_areas).find(|(&id, _)| id == pane_id)?;
+        let idx = self.panes.iter().position(|p| p.id == pane_id)?;
+        Some((idx, areas))
+    }
+
+    fn visible_pane_count(&self) -> usize {
+        self.last_pane_areas.len()
+    }
+
+    pub fn focused_pane(&self) -> Option<&Pane> {
+        self.panes.get(self.focused)
+    }
+
+    pub fn focused_pane_mut(&mut self) -> Option<&mut Pane> {
+        self.panes.get_mut(self.focused)
+    }
+
+    fn start_selection(&mut self) {
+        if let Some(pane) = self.panes.get(self.focused) {
+            self.selection = Some(SelectionState {
+                pane_id: pane.id,
+                anchor: (0, 0),
+                cursor: (0, 0),
+            });
+        }
+    }
+
+    fn start_selection_at(&mut self, pane_idx: usize, row: u16, col: u16) {
+        if let Some(pane) = self.panes.get(pane_idx) {
+            self.selection = Some(SelectionState {
+                pane_id: pane.id,
+                anchor: (row, col),
+                cursor: (row, col),
+            });
+            self.focused = pane_idx;
+        }
+    }
+
+    fn update_selection_at(&mut self, row: u16, col: u16) {
+        if let Some(sel) = &mut self.selection {
+            sel.cursor = (row, col);
+        }
+    }
+
+    fn move_selection_cursor(&mut self, dr: i32, dc: i32) {
+        let Some(sel) = &self.selection else {
+            return;
+        };
+        let pane_id = sel.pane_id;
+        let Some(bounds) = self
+            .panes
+            .iter()
+            .find(|p| p.id == pane_id)
+            .map(|p| (p.rows.max(1) as i32, crate::terminal::screen_cols(&p.term).max(1) as i32))
+        else {
+            return;
+        };
+        if let Some(sel) = &mut self.selection {
+            let r = (sel.cursor.0 as i32 + dr).clamp(0, bounds.0 - 1) as u16;
+            let c = (sel.cursor.1 as i32 + dc).clamp(0, bounds.1 - 1) as u16;
+            sel.cursor = (r, c);
+        }
+    }
+
+    fn jump_to_error(&mut self, forward: bool) {
+        let Some(pattern) = self.error_pattern.clone() else {
+            self.set_status("no error_pattern in the config");
+            return;
+        };
+        let Some(sel) = &mut self.selection else {
+            return;
+        };
+        let Some(pane) = self.panes.iter_mut().find(|p| p.id == sel.pane_id) else {
+            return;
+        };
+        let current = sel.cursor.0 as i32 - pane.scroll_offset as i32;
+        let lines = pane.error_lines(&pattern, Duration::ZERO);
+        let (Some(&first), Some(&last)) = (lines.first(), lines.last()) else {
+            pane.flash_for("no error lines", SCROLL_NOTE_TIMEOUT);
+            return;
+        };
+        let target = if forward {
+            lines.iter().copied().find(|&line| line > current)
+        } else {
+            lines.iter().rev().copied().find(|&line| line < current)
+        };
+        let line = target.unwrap_or_else(|| {
+            let note = if forward { "wrapped to the first error" } else { "wrapped to the last error" };
+            pane.flash_for(note, SCROLL_NOTE_TIMEOUT);
+            if forward { first } else { last }
+        });
+        let row = pane.reveal_line(line);
+        sel.anchor = (row, 0);
+        sel.cursor = (row, 0);
+    }
+
+    pub fn selection_text(&self) -> Option<String> {
+        let sel = self.selection.as_ref()?;
+        let pane = self.panes.iter().find(|p| p.id == sel.pane_id)?;
+        let term = &pane.term;
+        let ((sr, sc), (er, ec)) = sel.normalized();
+        let mut text = String::new();
+        for row in sr..=er {
+            let start_col = if row == sr { sc } else { 0 };
+            let cols = crate::terminal::screen_cols(term) as u16;
+            let end_col = if row == er { ec } else { cols.saturating_sub(1) };
+            let mut row_text = String::new();
+            for col in start_col..=end_col {
+                let ch = crate::terminal::cell_char(term, row as usize, col as usize);
+                if ch == "\0" {
+                    row_text.push(' ');
+                } else {
+                    row_text.push_str(&ch);
+                }
+            }
+            // A soft-wrapped row runs on into the next without a break.
+            if row < er && end_col + 1 >= cols && crate::terminal::row_wrapped(term, row as usize) {
+                text.push_str(&row_text);
+            } else {
+                text.push_str(row_text.trim_end());
+                if row < er {
+                    text.push('\n');
+                }
+            }
+        }
+        Some(text)
+    }
+
+    fn clear_selection(&mut self) {
+        self.selection = None;
+    }
+
+    /// Read the config again the way startup did and `reconcile` the panes
+    /// with it, reporting the outcome in the footer.
+    pub fn reload_config(&mut self) {
+        let Some(reload) = &self.reload else {
+            self.set_status("no config file to reload");
+            return;
+        };
+        let path = reload.path.clone();
+        match reload.load() {
+            Ok(config) => {
+                let outcome = reconcile(self, &config);
+                tracing::info!(path = %path.display(), ?outcome, "config reloaded");
+                self.set_status(format!("reloaded {}: {}", path.display(), outcome));
+            }
+            Err(e) => {
+                tracing::warn!(path = %path.display(), error = %e, "config reload failed");
+                self.set_status(format!("config not reloaded: {:#}", e));
+            }
+        }
+    }
+}
+
+/// What `reconcile` changed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Reconciled {
+    pub added: usize,
+    pub closed: usize,
+    pub restarted: usize,
+}
+
+impl std::fmt::Display for Reconciled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if *self == Reconciled::default() {
+            return write!(f, "no pane changes");
+        }
+        write!(f, "{} added, {} closed, {} restarted", self.added, self.closed, self.restarted)
+    }
+}
+
+/// Whether panes for `old` and `new` differ in more than the fields a
+/// reload can apply to the running pane.
+fn needs_restart(old: &PaneConfig, new: &PaneConfig) -> bool {
+    let in_place = |config: &PaneConfig| PaneConfig {
+        name: String::new(),
+        weight: None,
+        focus: false,
+        min_height: None,
+        title_format: None,
+        ..config.clone()
+    };
+    in_place(old) != in_place(new)
+}
+
+/// Bring `state`'s panes in line with a reloaded `config` without undoing
+/// what was changed during the session.
+///
+/// The config owns which `[[panes]]` exist and everything in their entries
+/// (name, command, weight), and the default weights. The session owns
+/// scroll position, collapsed state, focus, the viewport, stack order and
+/// marks, and a weight changed since startup stays unless
+/// `config_overrides_runtime` is set. Panes opened at runtime are left
+/// alone.
+///
+/// Entries match a config pane by name, then a renamed one by an otherwise
+/// equal entry. A matched pane whose entry changed beyond its name, weight,
+/// `min_height`, `title_format` or `focus` restarts. New entries open after
+/// the pane of the entry before them; panes whose entry is gone close.
+pub fn reconcile(state: &mut AppState, config: &Config) -> Reconciled {
+    let mut outcome = Reconciled::default();
+    let mut unclaimed: Vec<usize> = state.panes.iter().filter(|p| p.from_config).map(|p| p.id).collect();
+    let mut matched: Vec<Option<usize>> = vec![None; config.panes.len()];
+    let config_of = |state: &AppState, id: usize| state.panes.iter().find(|p| p.id == id).map(|p| p.config.clone());
+    for (slot, entry) in matched.iter_mut().zip(&config.panes) {
+        if let Some(pos) = unclaimed.iter().position(|&id| config_of(state, id).is_some_and(|c| c.name == entry.name)) {
+            *slot = Some(unclaimed.remove(pos));
+        }
+    }
+    for (slot, entry) in matched.iter_mut().zip(&config.panes).filter(|(slot, _)| slot.is_none()) {
+        let same = |id: usize| config_of(state, id).is_some_and(|c| !needs_restart(&c, entry));
+        if let Some(pos) = unclaimed.iter().position(|&id| same(id)) {
+            *slot = Some(unclaimed.remove(pos));
+        }
+    }
+
+    // Matched panes take their new entries; weights first, so a restart
+    // carries the right one over.
+    let old_default = state.weights.default_weight;
+    let mut restarts = Vec::new();
+    for (entry, id) in config.panes.iter().zip(&matched) {
+        let Some(pane) = id.and_then(|id| state.panes.iter_mut().find(|p| p.id == id)) else {
+            continue;
+        };
+        let configured = pane.config.weight.unwrap_or(old_default);
+        if config.config_overrides_runtime || pane.weight == configured {
+            pane.weight = entry.weight.unwrap_or(config.weights.default_weight);
+        }
+        if needs_restart(&pane.config, entry) {
+            restarts.push(pane.id);
+        } else {
+            pane.name = entry.name.clone();
+            pane.title_format = entry.title_format.clone();
+        }
+        pane.config = entry.clone();
+    }
+
+    // New entries, after the slot of the entry before them. Focus and the
+    // viewport stay on the same panes.
+    let focused_id = state.focused_pane().map(|p| p.id);
+    let viewport_id = state.panes.get(state.viewport_start).map(|p| p.id);
+    let mut previous: Option<usize> = None;
+    for (entry, id) in config.panes.iter().zip(&matched) {
+        if let Some(id) = id {
+            previous = Some(*id);
+            continue;
+        }
+        let at = previous
+            .and_then(|id| state.panes.iter().position(|p| p.id == id))
+            .map_or(0, |idx| layout::slot_end(&state.panes, idx));
+        let mut pane = state.new_pane(entry.clone());
+        pane.from_config = true;
+        previous = Some(pane.id);
+        state.panes.insert(at, pane);
+        outcome.added += 1;
+    }
+    if let Some(idx) = focused_id.and_then(|id| state.panes.iter().position(|p| p.id == id)) {
+        state.focused = idx;
+    }
+    if let Some(idx) = viewport_id.and_then(|id| state.panes.iter().position(|p| p.id == id)) {
+        state.viewport_start = idx;
+    }
+
+    for id in unclaimed {
+        let Some(idx) = state.panes.iter().position(|p| p.id == id) else {
+            continue;
+        };
+        if let Some(pane) = state.close_pane(idx) {
+            state.teardown_pane(pane);
+            outcome.closed += 1;
+        }
+    }
+    for id in restarts {
+        if let Some(idx) = state.panes.iter().position(|p| p.id == id) {
+            state.respawn(idx, Some("config changed".to_string()));
+            outcome.restarted += 1;
+        }
+    }
+
+    state.set_weights(config.weights);
+    state.templates = config.templates.clone();
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    use alacritty_terminal::term::TermMode;
+    use portable_pty::{native_pty_system, PtySize};
+
+    use crate::terminal;
+
+    /// A pane on a bare PTY pair with no child process.
+    fn test_pane(id: usize, name: &str) -> Pane {
+        let pair = native_pty_system()
+            .openpty(PtySize {
+                rows: 5,
+                cols: 20,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .expect("openpty");
+        let writer = pair.master.take_writer().expect("pty writer");
+        Pane::new(
+            id,
+            name.to_string(),
+            pair.master,
+            crate::pty::launch_writer_thread(id, writer),
+            100,
+            Arc::default(),
+            20,
+            5,
+        )
+    }
+
+    /// Records spawn requests instead of starting processes.
+    #[derive(Default)]
+    struct FakeSpawner {
+        spawned: Rc<RefCell<Vec<(usize, String)>>>,
+        fail: bool,
+    }
+
+    impl PaneSpawner for FakeSpawner {
+        fn spawn(&mut self, pane_id: usize, config: &PaneConfig, _cols: u16, _rows: u16) -> anyhow::Result<Pane> {
+            if self.fail {
+                anyhow::bail!("spawn disabled");
+            }
+            self.spawned.borrow_mut().push((pane_id, config.name.clone()));
+            let mut pane = test_pane(pane_id, &config.name);
+            pane.config = config.clone();
+            Ok(pane)
+        }
+    }
+
+    fn app_with(n: usize) -> AppState {
+        let panes = (0..n).map(|i| test_pane(i, &format!("p{}", i))).collect();
+        AppState::new(panes, LayoutConfig::Scroll, None, Box::new(FakeSpawner::default()))
+    }
+
+    fn names(app: &AppState) -> Vec<&str> {
+        app.panes.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    #[test]
+    fn focus_wraps_in_both_directions() {
+        let mut app = app_with(3);
+        app.apply(Action::FocusPrev);
+        assert_eq!(app.focused, 2);
+        app.apply(Action::FocusNext);
+        assert_eq!(app.focused, 0);
+    }
+
+    #[test]
+    fn pane_targets_resolve_by_id_then_name_then_index() {
+        let mut app = app_with(3);
+        app.panes[2].name = "2".to_string();
+        app.apply(Action::ClosePane(0));
+        app.apply(Action::SpawnPane(Box::new(PaneConfig { name: "web".to_string(), ..Default::default() })));
+        assert_eq!(names(&app), ["p1", "2", "web"]);
+        let ids: Vec<usize> = app.panes.iter().map(|p| p.stable_id).collect();
+        assert_eq!(ids, [1, 2, 3]);
+
+        let resolve = |app: &AppState, target: &str| resolve_pane_target(&app.panes, target).map_err(|e| e.to_string());
+        assert_eq!(resolve(&app, "#3"), Ok(2));
+        assert_eq!(resolve(&app, " web "), Ok(2));
+        assert_eq!(resolve(&app, "1"), Ok(0));
+        assert_eq!(resolve(&app, "2"), Ok(1), "a name wins over an index");
+        assert_eq!(resolve(&app, "3"), Ok(2));
+        assert_eq!(resolve(&app, "#0"), Err("no pane has id #0".to_string()));
+        assert_eq!(resolve(&app, "#x"), Err("\"#x\" is not a pane id (expected #N)".to_string()));
+        assert_eq!(resolve(&app, "0"), Err("no pane at index 0 (there are 3)".to_string()));
+        assert_eq!(resolve(&app, "4"), Err("no pane at index 4 (there are 3)".to_string()));
+        assert_eq!(resolve(&app, "db"), Err("no pane named \"db\"".to_string()));
+
+        // Restarting keeps the stable id though the run gets a new one.
+        app.panes[2].mark_closed();
+        app.apply(Action::RestartPane(2));
+        assert_ne!(app.panes[2].id, 3);
+        assert_eq!(resolve(&app, "#3"), Ok(2));
+
+        app.panes[0].name = "web".to_string();
+        assert_eq!(
+            resolve(&app, "web"),
+            Err("2 panes are named \"web\": #1 (index 1), #3 (index 3)".to_string())
+        );
+        assert_eq!(resolve(&app, "#1"), Ok(0));
+    }
+
+    #[test]
+    fn minimizing_and_restoring_the_window_keeps_every_screen() {
+        use ratatui::backend::TestBackend;
+        use ratatui::Terminal;
+
+        let mut app = app_with(2);
+        let mut terminal = Terminal::new(TestBackend::new(40, 24)).unwrap();
+        let mut resize = |app: &mut AppState, cols: u16, rows: u16| {
+            terminal.backend_mut().resize(cols, rows);
+            app.apply(Action::Resize { cols, rows });
+            terminal.draw(|frame| crate::ui::render(frame, app)).unwrap();
+            terminal.backend().buffer().clone()
+        };
+        resize(&mut app, 40, 24);
+        for (i, pane) in app.panes.iter_mut().enumerate() {
+            for line in 0..12 {
+                pane.process_output(format!("pane {} line {} {}\r\n", i, line, "x".repeat(20)).as_bytes());
+            }
+        }
+        let sizes = |app: &AppState| app.panes.iter().map(|p| (p.cols, p.rows)).collect::<Vec<_>>();
+        let before = resize(&mut app, 40, 24);
+        let before_sizes = sizes(&app);
+
+        // Minimized, then squeezed on the way back.
+        resize(&mut app, 40, 0);
+        assert_eq!(app.term_rows, 24, "a window with no size is ignored");
+        resize(&mut app, 30, 12);
+        assert_eq!(sizes(&app), before_sizes, "a fresh squeeze doesn't shrink panes");
+        assert_eq!(resize(&mut app, 40, 24), before);
+        assert_eq!(sizes(&app), before_sizes);
+
+        // A squeeze that lasts does reach the panes.
+        resize(&mut app, 30, 12);
+        app.squeezed_until = None;
+        resize(&mut app, 30, 12);
+        assert!(app.panes.iter().zip(&before_sizes).all(|(p, &(cols, rows))| p.cols < cols && p.rows < rows));
+    }
+
+    #[test]
+    fn crosshair_tints_the_row_and_column_under_the_pointer_or_cursor() {
+        use ratatui::backend::TestBackend;
+        use ratatui::style::Color;
+        use ratatui::Terminal;
+
+        let mut app = app_with(1);
+        app.color_mode = ColorMode::Truecolor;
+        let mut terminal = Terminal::new(TestBackend::new(30, 10)).unwrap();
+        app.apply(Action::Resize { cols: 30, rows: 10 });
+        let mut tinted = |app: &mut AppState| {
+            terminal.draw(|frame| crate::ui::render(frame, app)).unwrap();
+            let buffer = terminal.backend().buffer();
+            let inner = app.last_pane_areas[0].1.inner;
+            let mut cells = Vec::new();
+            for y in inner.top()..inner.bottom() {
+                for x in inner.left()..inner.right() {
+                    if buffer[(x, y)].bg == Color::Rgb(0x30, 0x32, 0x3a) {
+                        cells.push((x - inner.x, y - inner.y));
+                    }
+                }
+            }
+            (inner, cells)
+        };
+        let cross = |inner: Rect, (col, row): (u16, u16)| {
+            let mut cells: Vec<(u16, u16)> =
+                (0..inner.height).map(|y| (col, y)).chain((0..inner.width).map(|x| (x, row))).collect();
+            cells.sort_by_key(|&(x, y)| (y, x));
+            cells.dedup();
+            cells
+        };
+
+        let (inner, cells) = tinted(&mut app);
+        assert!(cells.is_empty(), "off by default");
+
+        app.apply(Action::ToggleCrosshair);
+        assert_eq!(tinted(&mut app).1, [], "nothing until the pointer moves");
+        app.pointer = Some((inner.x + 4, inner.y + 2));
+        assert_eq!(tinted(&mut app).1, cross(inner, (4, 2)));
+        app.pointer = Some((0, 0));
+        assert_eq!(tinted(&mut app).1, [], "outside the content");
+
+        // While selecting, the crosshair follows the selection cursor, and
+        // the selected cell keeps its own highlight.
+        app.pointer = Some((inner.x + 4, inner.y + 2));
+        app.apply(Action::StartSelection);
+        app.apply(Action::MoveSelection { rows: 1, cols: 7 });
+        let sel = app.selection.clone().unwrap();
+        let mut expected = cross(inner, (7, 1));
+        expected.retain(|&(x, y)| !sel.contains(y, x));
+        assert!(expected.contains(&(8, 1)) && expected.contains(&(7, 3)));
+        assert_eq!(tinted(&mut app).1, expected);
+        app.apply(Action::ClearSelection);
+
+        app.apply(Action::ToggleCrosshair);
+        app.pointer = Some((inner.x + 4, inner.y + 2));
+        assert_eq!(tinted(&mut app).1, [], "toggled off");
+    }
+
+    #[test]
+    fn output_driven_titles_change_at_most_once_per_title_interval() {
+        use ratatui::backend::TestBackend;
+        use ratatui::Terminal;
+
+        let mut app = app_with(1);
+        app.title_interval = Duration::from_millis(300);
+        app.panes[0].title_format = Some("{name} in {cwd}".to_string());
+        app.panes[0].cwd = "/one".to_string();
+        let mut terminal = Terminal::new(TestBackend::new(40, 8)).unwrap();
+        app.apply(Action::Resize { cols: 40, rows: 8 });
+        let mut title = |app: &mut AppState| {
+            terminal.draw(|frame| crate::ui::render(frame, app)).unwrap();
+            let buffer = terminal.backend().buffer();
+            (0..buffer.area.width).map(|x| buffer[(x, 0)].symbol().to_string()).collect::<String>()
+        };
+
+        assert!(title(&mut app).contains("p0 in /one"));
+        let repaints = app.panes[0].title_repaints;
+        app.panes[0].cwd = "/two".to_string();
+        assert!(title(&mut app).contains("p0 in /one"), "kept within the interval");
+        assert_eq!(app.panes[0].title_repaints, repaints);
+
+        std::thread::sleep(Duration::from_millis(350));
+        assert!(title(&mut app).contains("p0 in /two"), "updated once it has passed");
+        assert_eq!(app.panes[0].title_repaints, repaints + 1);
+
+        // A change the title key tracks shows at once.
+        app.panes[0].name = "renamed".to_string();
+        assert!(title(&mut app).contains("renamed in /two"));
+    }
+
+    #[test]
+    fn jump_keys_round_trip() {
+        assert_eq!(jump_key(0), Some('1'));
+        assert_eq!(jump_key(9), Some('a'));
+        assert_eq!(jump_key(35), None);
+        assert_eq!(jump_index('A'), Some(9));
+        assert_eq!(jump_index('0'), None);
+        for idx in 0..35 {
+            assert_eq!(jump_index(jump_key(idx).unwrap()), Some(idx));
+        }
+    }
+
+    #[test]
+    fn selection_copies_soft_wrapped_rows_as_one_line() {
+        let mut app = app_with(1);
+        // 20 columns: the URL wraps after "example.com/", the second line does not.
+        app.panes[0].process_output(b"https://example.com/a-long-path\r\nnext");
+        app.apply(Action::StartSelectionAt { pane: 0, row: 0, col: 0 });
+        app.apply(Action::UpdateSelection { row: 2, col: 3 });
+        assert_eq!(app.selection_text().as_deref(), Some("https://example.com/a-long-path\nnext"));
+    }
+
+    #[test]
+    fn unwrapped_pane_scrolls_sideways_and_copies_whole_lines() {
+        let mut app = app_with(1);
+        let pane = &mut app.panes[0];
+        pane.set_wrap(false);
+        let line = "0123456789".repeat(5);
+        pane.process_output(format!("{}\r\nend", line).as_bytes());
+        assert_eq!(pane.clipped_sides(), (false, true));
+
+        app.apply(Action::ScrollHorizontal { cols: 100 });
+        // 50 columns of content in a 20-column pane.
+        assert_eq!(app.panes[0].h_scroll, 30);
+        assert_eq!(app.panes[0].clipped_sides(), (true, false));
+
+        app.apply(Action::StartSelectionAt { pane: 0, row: 0, col: 0 });
+        app.apply(Action::UpdateSelection { row: 1, col: 2 });
+        assert_eq!(app.selection_text(), Some(format!("{}\nend", line)));
+    }
+
+    #[test]
+    fn layout_goes_stale_when_a_drawn_pane_closes() {
+        let mut app = app_with(3);
+        app.set_layout(vec![(0, Rect::new(0, 0, 20, 5)), (1, Rect::new(0, 5, 20, 5))]);
+        assert!(app.layout_is_current());
+        // Panes past the drawn ones don't affect it.
+        app.apply(Action::ClosePane(2));
+        assert!(app.layout_is_current());
+        // Closing a drawn pane shifts the next one into its slot, so a click
+        // at the old rectangle would hit a different pane.
+        app.apply(Action::ClosePane(0));
+        assert!(!app.layout_is_current());
+        app.set_layout(vec![(0, Rect::new(0, 0, 20, 5))]);
+        assert!(app.layout_is_current());
+    }
+
+    #[test]
+    fn wheel_step_follows_config() {
+        let mut app = app_with(1);
+        assert_eq!(app.wheel_lines(true, false), 3);
+        assert_eq!(app.wheel_lines(true, false), 3);
+        assert_eq!(app.wheel_lines(false, true), -5);
+
+        app.wheel = WheelConfig {
+            lines: 2,
+            natural: true,
+            acceleration: true,
+        };
+        app.wheel_streak = None;
+        let steps: Vec<i32> = (0..7).map(|_| app.wheel_lines(true, false)).collect();
+        assert_eq!(steps, [-2, -4, -6, -8, -10, -10, -10]);
+        app.wheel_streak = Some((Instant::now() - WHEEL_STREAK_GAP, 4));
+        assert_eq!(app.wheel_lines(false, false), 2);
+    }
+
+    /// The pane's viewport as text, one string per row.
+    fn view(pane: &Pane) -> Vec<String> {
+        let cell = |r, c| crate::terminal::cell_char(&pane.term, r, c);
+        let row = |r| (0..pane.cols as usize).map(|c| cell(r, c)).collect::<String>();
+        (0..pane.rows as usize).map(|r| row(r).trim_end().to_string()).collect()
+    }
+
+    #[test]
+    fn a_scroll_lock_holds_the_view_while_output_scrolls_a_full_history() {
+        let mut app = app_with(1);
+        let pane = &mut app.panes[0];
+        for i in 0..150 {
+            pane.process_output(format!("old {}\r\n", i).as_bytes());
+        }
+        pane.process_output(b"last");
+        let locked = view(pane);
+        pane.toggle_scroll_lock();
+        pane.process_output(b"\r\n");
+        pane.process_output(&b"new\r\n".repeat(40));
+        assert_eq!(view(pane), locked);
+
+        // A scroll while locked moves the view, which then holds there.
+        pane.scroll_up(2);
+        let scrolled = view(pane);
+        assert_eq!(scrolled[2..], locked[..3]);
+        pane.process_output(&b"newer\r\n".repeat(10));
+        assert_eq!(view(pane), scrolled);
+        pane.resize(24, 5);
+        let resized = view(pane);
+        pane.process_output(&b"newer\r\n".repeat(10));
+        assert_eq!(view(pane), resized);
+
+        // Once its lines leave the history, it shows the oldest left.
+        pane.process_output(&b"flood\r\n".repeat(120));
+        assert_eq!(pane.term.grid().display_offset(), crate::terminal::history_lines(&pane.term));
+        pane.toggle_scroll_lock();
+        assert_eq!(view(pane)[3], "flood");
+    }
+
+    #[test]
+    fn scroll_lock_counts_output_until_released() {
+        let mut app = app_with(1);
+        app.apply(Action::ToggleScrollLock);
+        app.panes[0].process_output(b"one\r\ntwo\r\nthree");
+        assert_eq!(app.panes[0].scroll_lock.as_ref().map(|l| l.pending_lines), Some(2));
+        app.apply(Action::ToggleScrollLock);
+        assert!(app.panes[0].scroll_lock.is_none());
+    }
+
+    #[test]
+    fn reset_terminal_undoes_garbled_modes_and_keeps_scrollback() {
+        let mut app = app_with(1);
+        let pane = &mut app.panes[0];
+        for i in 0..40 {
+            pane.process_output(format!("line {}\r\n", i).as_bytes());
+        }
+        let history = terminal::history_lines(&pane.term);
+        // Line drawing charset, hidden cursor, then an OSC that never ends.
+        pane.process_output(b"\x1b(0\x1b[?25l\x1b]0;garbage");
+        app.apply(Action::ResetTerminal);
+        let pane = &mut app.panes[0];
+        pane.process_output(b"q");
+        let (row, col) = terminal::cursor_position(&pane.term);
+        assert_eq!(terminal::cell_char(&pane.term, row, col - 1), "q");
+        assert!(pane.term.mode().contains(TermMode::SHOW_CURSOR));
+        assert_eq!(terminal::history_lines(&pane.term), history);
+        assert_eq!(pane.flash_message(), Some("terminal reset"));
+    }
+
+    #[test]
+    fn reset_terminal_keeps_a_full_screen_program_on_its_screen() {
+        let mut app = app_with(1);
+        let pane = &mut app.panes[0];
+        pane.process_output(b"shell\r\n\x1b[?1049h\x1b[2;3rtop\x1b[3;5H");
+        app.apply(Action::ResetTerminal);
+        let pane = &app.panes[0];
+        assert!(pane.term.mode().contains(TermMode::ALT_SCREEN));
+        assert_eq!(terminal::cell_char(&pane.term, 0, 0), "t", "the program's screen is still shown");
+        assert_eq!(terminal::cursor_position(&pane.term), (2, 4), "the cursor stays put");
+    }
+
+    #[test]
+    fn sustained_binary_output_suggests_freezing_or_resetting() {
+        let mut app = app_with(1);
+        let pane = &mut app.panes[0];
+        pane.process_output(&[b'x'; 64 << 10]);
+        assert_eq!(pane.flash_message(), None);
+        let noise: Vec<u8> = (0..64 << 10).map(|i: u32| (i.wrapping_mul(2_654_435_761) >> 24) as u8).collect();
+        pane.process_output(&noise);
+        assert!(pane.flash_message().is_some_and(|m| m.contains("Alt+Shift+L")));
+    }
+
+    #[test]
+    fn full_screen_panes_settle_after_a_resize_until_output_or_timeout() {
+        let mut pane = test_pane(0, "top");
+        pane.resize(30, 8);
+        assert!(!pane.settling(), "only full-screen programs repaint");
+        pane.process_output(b"\x1b[?1049h");
+        pane.resize(30, 8);
+        assert!(!pane.settling(), "the size didn't change");
+        pane.resize(32, 8);
+        assert!(pane.settling());
+        pane.process_output(b"\x1b[H\x1b[2J");
+        assert!(!pane.settling(), "it repainted");
+
+        pane.resize(34, 9);
+        assert!(pane.settling());
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(!pane.settling(), "it waited long enough");
+    }
+
+    #[test]
+    fn prompt_row_follows_osc_133_marks() {
+        let mut app = app_with(1);
+        let pane = &mut app.panes[0];
+        pane.process_output(b"out\r\n\x1b]133;A\x07$ \x1b]133;B\x07");
+        assert_eq!(pane.prompt_row, Some(1));
+        pane.process_output(b"ls\r\n\x1b]133;C\x07a\r\nb\r\n");
+        assert_eq!(pane.prompt_row, None);
+        // The prompt scrolls up with the screen.
+        pane.process_output(b"\x1b]133;A\x07\x1b[1m~\x1b[0m\r\n$ ");
+        assert_eq!(pane.prompt_row, Some(3));
+        assert_eq!(crate::terminal::cursor_position(&pane.term).0, 4);
+        pane.process_output(b"\r\n\r\n");
+        assert_eq!(pane.prompt_row, Some(1));
+    }
+
+    #[test]
+    fn prompt_row_follows_split_marks_a_full_history_and_clears() {
+        let mut app = app_with(1);
+        let pane = &mut app.panes[0];
+        pane.process_output(&b"x\r\n".repeat(200));
+        pane.process_output(b"\x1b]13");
+        pane.process_output(b"3;A\x07$ ");
+        assert_eq!(pane.prompt_row, Some(4));
+        // The history is full, so scrolling no longer grows it.
+        pane.process_output(b"\r\n\r\n");
+        assert_eq!(pane.prompt_row, Some(2));
+        pane.process_output(b"\x1b[H\x1b[2J");
+        assert_eq!(pane.prompt_row, None);
+
+        // A row scrolled out of the history is reused at the bottom, which
+        // is no longer the prompt.
+        pane.process_output(b"\x1b]133;A\x07$ ");
+        assert_eq!(pane.prompt_row, Some(0));
+        pane.process_output(&b"x\r\n".repeat(101));
+        assert_eq!(pane.prompt_row, None);
+    }
+
+    /// An app whose panes came from config entries running `cmd-<name>`.
+    fn config_app(names: &[&str]) -> AppState {
+        let mut app = app_with(names.len());
+        for (pane, name) in app.panes.iter_mut().zip(names) {
+            pane.name = name.to_string();
+            pane.config = entry(name);
+            pane.from_config = true;
+            pane.weight = app.weights.default_weight;
+        }
+        app
+    }
+
+    fn entry(name: &str) -> PaneConfig {
+        PaneConfig {
+            name: name.to_string(),
+            command: Some(format!("cmd-{}", name)),
+            ..PaneConfig::default()
+        }
+    }
+
+    fn reloaded(entries: Vec<PaneConfig>) -> Config {
+        Config {
+            panes: entries,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn reload_renames_in_place_and_keeps_runtime_state() {
+        let mut app = config_app(&["a", "b", "c"]);
+        app.panes[1].process_output(b"1\r\n2\r\n3\r\n4\r\n5\r\n6\r\n7");
+        app.apply(Action::Focus(1));
+        app.apply(Action::ScrollPage { up: true });
+        let scrolled = app.panes[1].scroll_offset;
+        assert!(scrolled > 0);
+        app.panes[2].collapsed = true;
+        app.viewport_start = 1;
+
+        let renamed = PaneConfig {
+            name: "bee".to_string(),
+            ..entry("b")
+        };
+        let outcome = reconcile(&mut app, &reloaded(vec![entry("a"), renamed, entry("c")]));
+        assert_eq!(outcome, Reconciled::default());
+        assert_eq!(names(&app), ["a", "bee", "c"]);
+        assert_eq!(app.panes.iter().map(|p| p.id).collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(app.panes[1].scroll_offset, scrolled);
+        assert!(app.panes[2].collapsed);
+        assert_eq!((app.focused, app.viewport_start), (1, 1));
+    }
+
+    #[test]
+    fn reload_adds_in_the_middle_and_follows_panes_across_the_shift() {
+        let mut app = config_app(&["a", "c"]);
+        app.apply(Action::SpawnPane(Box::default()));
+        app.apply(Action::Focus(1));
+        app.viewport_start = 1;
+
+        let outcome = reconcile(&mut app, &reloaded(vec![entry("a"), entry("b"), entry("c")]));
+        assert_eq!(outcome.added, 1);
+        assert_eq!(names(&app), ["a", "b", "c", "Shell 2"], "runtime panes stay");
+        assert!(app.panes[1].from_config);
+        assert_eq!(app.focused_pane().map(|p| p.name.as_str()), Some("c"));
+        assert_eq!(app.viewport_start, 2);
+    }
+
+    #[test]
+    fn reload_closes_a_removed_focused_pane_and_restarts_changed_ones() {
+        let mut app = config_app(&["a", "b", "c"]);
+        app.apply(Action::Focus(1));
+        let changed = PaneConfig {
+            command: Some("cmd-c --verbose".to_string()),
+            ..entry("c")
+        };
+
+        let outcome = reconcile(&mut app, &reloaded(vec![entry("a"), changed]));
+        assert_eq!(outcome, Reconciled { added: 0, closed: 1, restarted: 1 });
+        assert_eq!(names(&app), ["a", "c"]);
+        assert_eq!(app.focused, 1, "the pane below takes focus");
+        assert_eq!(app.panes[1].config.command.as_deref(), Some("cmd-c --verbose"));
+        assert_eq!(app.panes[1].run, 2);
+    }
+
+    #[test]
+    fn reload_weights_yield_to_runtime_changes_unless_the_config_overrides() {
+        let mut app = config_app(&["a", "b"]);
+        app.apply(Action::GrowWeightAt(1));
+        let tweaked = app.panes[1].weight;
+        let weighted = |name| PaneConfig {
+            weight: Some(20),
+            ..entry(name)
+        };
+
+        reconcile(&mut app, &reloaded(vec![weighted("a"), weighted("b")]));
+        assert_eq!((app.panes[0].weight, app.panes[1].weight), (20, tweaked));
+
+        let mut config = reloaded(vec![weighted("a"), weighted("b")]);
+        config.config_overrides_runtime = true;
+        reconcile(&mut app, &config);
+        assert_eq!((app.panes[0].weight, app.panes[1].weight), (20, 20));
+    }
+
+    #[test]
+    fn mouse_capture_changes_reach_the_terminal_once() {
+        let mut app = app_with(1);
+        app.set_mouse(false);
+        assert_eq!(app.mouse_capture_update(), None, "startup already left it off");
+        app.apply(Action::ToggleMouse);
+        assert_eq!(app.mouse_capture_update(), Some(true));
+        assert_eq!(app.mouse_capture_update(), None);
+        app.apply(Action::ToggleMouse);
+        app.apply(Action::ToggleMouse);
+        assert_eq!(app.mouse_capture_update(), None, "toggled back before the next frame");
+        app.apply(Action::ToggleMouse);
+        assert_eq!(app.mouse_capture_update(), Some(false));
+    }
+
+    #[test]
+    fn scrolling_up_moves_into_the_history_and_down_comes_back() {
+        let mut app = app_with(1);
+        let lines: String = (0..10).map(|i| format!("line {}\r\n", i)).collect();
+        app.panes[0].process_output(lines.as_bytes());
+        let top = |app: &AppState| -> String {
+            (0..20).map(|c| terminal::cell_char(&app.panes[0].term, 0, c)).collect::<String>().trim_end().to_string()
+        };
+        assert_eq!(top(&app), "line 6");
+
+        app.panes[0].scroll_up(3);
+        assert_eq!((app.panes[0].scroll_offset, top(&app)), (3, "line 3".to_string()));
+        app.panes[0].scroll_down(2);
+        assert_eq!((app.panes[0].scroll_offset, top(&app)), (1, "line 5".to_string()));
+        app.panes[0].scroll_down(5);
+        assert_eq!((app.panes[0].scroll_offset, top(&app)), (0, "line 6".to_string()));
+    }
+
+    #[test]
+    fn paging_says_why_it_did_nothing() {
+        let mut app = app_with(1);
+        app.apply(Action::ScrollPage { up: true });
+        assert_eq!(app.panes[0].flash_message(), Some("no scrollback"));
+
+        app.panes[0].process_output(b"1\r\n2\r\n3\r\n4\r\n5\r\n6\r\n7");
+        app.apply(Action::ScrollPage { up: true });
+        assert!(app.panes[0].scroll_offset > 0);
+
+        app.panes[0].process_output(b"\x1b[?1049h");
+        app.apply(Action::ScrollPage { up: false });
+        assert_eq!(app.panes[0].flash_message(), Some("app controls scrolling (sending PageDown)"));
+    }
+
+    #[test]
+    fn focus_out_of_range_is_ignored() {
+        let mut app = app_with(2);
+        app.apply(Action::Focus(1));
+        app.apply(Action::Focus(5));
+        assert_eq!(app.focused, 1);
+    }
+
+    #[test]
+    fn closing_pane_before_focus_keeps_same_pane_focused() {
+        let mut app = app_with(4);
+        app.apply(Action::Focus(2));
+        app.apply(Action::ClosePane(0));
+        assert_eq!(names(&app), ["p1", "p2", "p3"]);
+        assert_eq!(app.panes[app.focused].name, "p2");
+    }
+
+    #[test]
+    fn closing_focused_pane_focuses_the_next_one() {
+        let mut app = app_with(3);
+        app.apply(Action::Focus(1));
+        app.apply(Action::ClosePane(1));
+        assert_eq!(app.panes[app.focused].name, "p2");
+    }
+
+    #[test]
+    fn closing_last_focused_pane_focuses_the_new_last() {
+        let mut app = app_with(3);
+        app.apply(Action::Focus(2));
+        app.apply(Action::ClosePane(2));
+        assert_eq!(app.focused, 1);
+    }
+
+    #[test]
+    fn last_remaining_pane_cannot_be_closed() {
+        let mut app = app_with(1);
+        app.apply(Action::ClosePane(0));
+        assert_eq!(app.panes.len(), 1);
+        assert!(app.close_pane(3).is_none());
+    }
+
+    #[test]
+    fn every_close_path_tears_down_each_pane_once() {
+        let mut app = app_with(6);
+        app.apply(Action::ClosePane(1));
+        app.apply(Action::CloseAfter(3));
+        app.apply(Action::CloseOthers(0));
+        app.apply(Action::ClosePane(0));
+        assert_eq!(names(&app), ["p0"]);
+        app.close_all();
+        assert!(app.panes.is_empty());
+        let closed: Vec<&str> = app.closed_panes.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(closed, ["p1", "p5", "p2", "p3", "p4", "p0"]);
+    }
+
+    #[test]
+    fn reopening_starts_the_last_closed_pane_again_at_the_end() {
+        let mut app = app_with(3);
+        app.panes[1].config = entry("p1");
+        app.panes[1].name = "renamed".into();
+        app.panes[1].weight = 3;
+        app.apply(Action::ClosePane(1));
+        app.apply(Action::Focus(0));
+
+        app.apply(Action::ReopenClosed);
+        assert_eq!(names(&app), ["p0", "p2", "renamed"]);
+        assert_eq!(app.focused, 2);
+        let reopened = &app.panes[2];
+        assert_eq!(reopened.config.command.as_deref(), Some("cmd-p1"));
+        assert_eq!(reopened.weight, 3);
+        assert_ne!(reopened.id, 1);
+        assert!(app.closed_panes.is_empty());
+
+        app.apply(Action::ReopenClosed);
+        assert_eq!(app.panes.len(), 3);
+        assert_eq!(app.status_message(), Some("no closed pane to reopen"));
+    }
+
+    #[test]
+    fn unnamed_panes_show_what_is_running_after_their_name() {
+        let mut app = app_with(0);
+        app.apply(Action::SpawnPane(Box::default()));
+        app.apply(Action::SpawnPane(Box::new(PaneConfig { name: "web".to_string(), ..Default::default() })));
+        let auto = PaneConfig { name: "db".to_string(), auto_name: Some(true), ..Default::default() };
+        app.apply(Action::SpawnPane(Box::new(auto)));
+        let auto_named: Vec<bool> = app.panes.iter().map(|p| p.auto_name).collect();
+        assert_eq!(auto_named, [true, false, true]);
+
+        let shell = &mut app.panes[0];
+        assert_eq!(shell.display_name(), shell.name);
+        shell.process_name = Some("vim".to_string());
+        assert_eq!(shell.display_name(), format!("{} — vim", shell.name));
+        shell.program_title = Some("notes.md".to_string());
+        assert_eq!(shell.display_name(), format!("{} — notes.md", shell.name));
+        assert!(shell.name.starts_with("Shell "), "the name itself stays put");
+
+        app.panes[1].process_name = Some("vim".to_string());
+        assert_eq!(app.panes[1].display_name(), "web");
+    }
+
+    #[test]
+    fn pane_groups_collapse_expand_focus_and_close_together() {
+        let mut app = app_with(5);
+        for idx in [1, 3, 4] {
+            app.panes[idx].config.group = Some("frontend".to_string());
+        }
+        app.panes[3].weight = 7;
+        assert_eq!(app.group_names(), ["frontend"]);
+
+        let group = |op| Action::Group { name: "frontend".to_string(), op };
+        app.apply(group(GroupOp::Collapse));
+        let collapsed: Vec<bool> = app.panes.iter().map(|p| p.collapsed).collect();
+        assert_eq!(collapsed, [false, true, false, true, true]);
+        app.apply(group(GroupOp::Expand));
+        assert!(app.panes.iter().all(|p| !p.collapsed));
+        assert_eq!(app.panes[3].weight, 7);
+
+        app.apply(group(GroupOp::Focus));
+        assert_eq!(app.focused, 1);
+
+        app.apply(Action::Group { name: "backend".to_string(), op: GroupOp::Close });
+        assert!(app.confirm.is_none());
+        app.apply(group(GroupOp::Close));
+        assert_eq!(app.panes.len(), 5, "closing a group asks first");
+        app.accept_confirm();
+        assert_eq!(names(&app), ["p0", "p2"]);
+        assert!(app.group_names().is_empty());
+    }
+
+    #[test]
+    fn marks_follow_panes_and_group_actions_fall_back_to_focus() {
+        let mut app = app_with(5);
+        app.apply(Action::ToggleMark(1));
+        app.apply(Action::ToggleMark(3));
+        app.apply(Action::ToggleMark(4));
+        app.apply(Action::ToggleMark(4));
+        // Closing and reordering move marked panes around; the marks go with them.
+        app.apply(Action::ClosePane(0));
+        app.panes.swap(0, 2);
+        assert_eq!(names(&app), ["p3", "p2", "p1", "p4"]);
+        assert_eq!(app.marked_or_focused(), [0, 2]);
+
+        app.apply(Action::CollapseMarked);
+        let collapsed: Vec<bool> = app.panes.iter().map(|p| p.collapsed).collect();
+        assert_eq!(collapsed, [true, false, true, false]);
+        assert!(app.marked.is_empty(), "a group action clears the marks");
+
+        app.apply(Action::ToggleMark(0));
+        app.apply(Action::ToggleMark(2));
+        app.apply(Action::CollapseMarked);
+        assert!(app.panes.iter().all(|p| !p.collapsed), "all collapsed: expand them");
+
+        app.apply(Action::ToggleMark(1));
+        app.apply(Action::ToggleMark(3));
+        app.confirm_close(MassClose::Marked);
+        app.accept_confirm();
+        assert_eq!(names(&app), ["p3", "p1"]);
+        assert!(app.marked.is_empty());
+
+        // Nothing marked: the focused pane.
+        app.focused = 1;
+        app.apply(Action::CloseMarked);
+        assert_eq!(names(&app), ["p3"]);
+    }
+
+    #[test]
+    fn panes_go_stale_after_quiet_time_and_freshen_on_output() {
+        let mut app = app_with(3);
+        app.update_stale_panes();
+        assert!(app.panes.iter().all(|p| !p.stale), "off without stale_after");
+
+        app.stale_after = Some(Duration::from_secs(60));
+        let long_ago = Instant::now() - Duration::from_secs(120);
+        for pane in &mut app.panes {
+            pane.last_output_at = Some(long_ago);
+        }
+        app.panes[1].last_output_at = Some(Instant::now());
+        app.panes[2].closed = true;
+        let generation = app.panes[0].generation();
+        app.update_stale_panes();
+        let stale: Vec<bool> = app.panes.iter().map(|p| p.stale).collect();
+        assert_eq!(stale, [true, false, false]);
+        assert_ne!(app.panes[0].generation(), generation, "crossing the threshold redraws");
+
+        let generation = app.panes[0].generation();
+        app.update_stale_panes();
+        assert_eq!(app.panes[0].generation(), generation, "staying stale doesn't");
+
+        app.panes[0].process_output(b"back\r\n");
+        assert!(!app.panes[0].stale);
+    }
+
+    #[test]
+    fn closing_pane_above_viewport_keeps_viewport_on_same_panes() {
+        let mut app = app_with(5);
+        app.viewport_start = 3;
+        app.apply(Action::ClosePane(0));
+        assert_eq!(app.panes[app.viewport_start].name, "p3");
+    }
+
+    #[test]
+    fn closing_pane_clamps_viewport_to_stack() {
+        let mut app = app_with(3);
+        app.viewport_start = 2;
+        app.apply(Action::ClosePane(2));
+        assert_eq!(app.viewport_start, 1);
+    }
+
+    #[test]
+    fn viewport_pages_by_visible_count_and_stays_in_bounds() {
+        let mut app = app_with(5);
+        app.set_layout(vec![(0, Rect::default()), (1, Rect::default())]);
+        app.apply(Action::PageViewportDown);
+        assert_eq!(app.viewport_start, 2);
+        app.apply(Action::PageViewportDown);
+        app.apply(Action::PageViewportDown);
+        assert_eq!(app.viewport_start, 4);
+        app.apply(Action::PageViewportUp);
+        assert_eq!(app.viewport_start, 2);
+        app.apply(Action::PageViewportUp);
+        app.apply(Action::PageViewportUp);
+        assert_eq!(app.viewport_start, 0);
+    }
+
+    #[test]
+    fn paging_stops_following_focus_until_focus_changes() {
+        let mut app = app_with(5);
+        app.apply(Action::PageViewportDown);
+        assert!(!app.follow_focus);
+        assert_eq!(app.focused, 0);
+        app.apply(Action::ViewportEnd);
+        assert_eq!(app.viewport_start, 4);
+        app.apply(Action::ViewportHome);
+        assert_eq!(app.viewport_start, 0);
+        assert!(!app.follow_focus);
+        app.apply(Action::FocusNext);
+        assert!(app.follow_focus);
+    }
+
+    #[test]
+    fn viewport_pages_by_one_when_nothing_is_laid_out() {
+        let mut app = app_with(3);
+        app.apply(Action::PageViewportDown);
+        assert_eq!(app.viewport_start, 1);
+    }
+
+    #[test]
+    fn weight_is_clamped() {
+        let mut app = app_with(1);
+        for _ in 0..30 {
+            app.apply(Action::GrowWeight);
+        }
+        assert_eq!(app.panes[0].weight, 50);
+        for _ in 0..30 {
+            app.apply(Action::ShrinkWeight);
+        }
+        assert_eq!(app.panes[0].weight, 1);
+    }
+
+    #[test]
+    fn weight_preview_keeps_its_start_through_a_run_of_clamped_changes() {
+        let mut app = app_with(2);
+        for _ in 0..30 {
+            app.apply(Action::GrowWeight);
+        }
+        let preview = app.active_weight_preview().expect("preview shown");
+        assert_eq!((preview.pane_id, preview.from, preview.grew), (app.panes[0].id, 10, true));
+        assert_eq!(app.panes[0].weight, 50);
+
+        for _ in 0..30 {
+            app.apply(Action::ShrinkWeight);
+        }
+        let preview = app.active_weight_preview().expect("preview shown");
+        assert_eq!((preview.from, preview.grew), (10, false));
+        assert_eq!(app.panes[0].weight, 1);
+
+        // Another pane starts a run of its own; a collapsed one none at all.
+        app.apply(Action::GrowWeightAt(1));
+        let preview = app.active_weight_preview().expect("preview shown");
+        assert_eq!((preview.pane_id, preview.from), (app.panes[1].id, 10));
+        app.apply(Action::ToggleCollapse(0));
+        app.apply(Action::GrowWeightAt(0));
+        assert_eq!(app.active_weight_preview().map(|p| p.pane_id), Some(app.panes[1].id));
+
+        app.weight_preview.as_mut().unwrap().at -= WEIGHT_PREVIEW_TIMEOUT;
+        assert!(app.active_weight_preview().is_none());
+    }
+
+    #[test]
+    fn resize_mode_steps_one_row_then_equalizes() {
+        let mut app = app_with(3);
+        let area = Rect::new(0, 0, 80, 12);
+        app.stack_area = Some((area, 0));
+        let height = |app: &AppState| {
+            let weights: Vec<u16> = app.panes.iter().map(|p| p.weight).collect();
+            layout::dry_run_layout(&app.panes, &weights, app.chrome, 0, 0, area)
+                .iter()
+                .find(|&&(i, _)| i == 0)
+                .map(|(_, r)| r.height)
+                .unwrap()
+        };
+        let before = height(&app);
+        app.apply(Action::ResizeByRow { grow: true });
+        assert_eq!(height(&app), before + 1);
+        app.apply(Action::ResizeByRow { grow: false });
+        assert_eq!(height(&app), before);
+
+        app.apply(Action::ResizeByRow { grow: true });
+        app.apply(Action::EqualizeWeights);
+        assert!(app.panes.iter().all(|p| p.weight == app.weights.default_weight));
+    }
+
+    #[test]
+    fn weight_changes_use_configured_step_and_max() {
+        let mut app = app_with(1);
+        app.set_weights(WeightConfig {
+            default_weight: 5,
+            weight_step: 7,
+            weight_max: 20,
+        });
+        app.apply(Action::GrowWeight);
+        assert_eq!(app.panes[0].weight, 17);
+        app.apply(Action::GrowWeight);
+        assert_eq!(app.panes[0].weight, 20);
+    }
+
+    #[test]
+    fn lowering_weight_max_clamps_existing_panes() {
+        let mut app = app_with(2);
+        app.panes[1].weight = 3;
+        app.set_weights(WeightConfig {
+            weight_max: 4,
+            default_weight: 4,
+            ..WeightConfig::default()
+        });
+        assert_eq!(app.panes[0].weight, 4);
+        assert_eq!(app.panes[1].weight, 3);
+    }
+
+    #[test]
+    fn spawned_pane_starts_at_default_weight() {
+        let mut app = app_with(1);
+        app.set_weights(WeightConfig {
+            default_weight: 7,
+            ..WeightConfig::default()
+        });
+        app.apply(Action::SpawnPane(Box::default()));
+        assert_eq!(app.panes[1].weight, 7);
+    }
+
+    #[test]
+    fn weight_change_at_index_leaves_focus_alone() {
+        let mut app = app_with(3);
+        app.apply(Action::GrowWeightAt(2));
+        app.apply(Action::ShrinkWeightAt(1));
+        app.apply(Action::GrowWeightAt(9));
+        let weights: Vec<u16> = app.panes.iter().map(|p| p.weight).collect();
+        assert_eq!(weights, [10, 8, 12]);
+        assert_eq!(app.focused, 0);
+    }
+
+    #[test]
+    fn collapsed_pane_ignores_weight_changes() {
+        let mut app = app_with(2);
+        app.apply(Action::ToggleCollapse(0));
+        app.apply(Action::GrowWeight);
+        assert!(app.panes[0].collapsed);
+        assert_eq!(app.panes[0].weight, 10);
+        app.apply(Action::ToggleCollapse(0));
+        app.apply(Action::GrowWeight);
+        assert_eq!(app.panes[0].weight, 12);
+    }
+
+    #[test]
+    fn toggle_collapse_targets_only_the_given_pane() {
+        let mut app = app_with(2);
+        app.apply(Action::ToggleCollapse(1));
+        app.apply(Action::ToggleCollapse(7));
+        assert!(!app.panes[0].collapsed);
+        assert!(app.panes[1].collapsed);
+        assert_eq!(app.focused, 0);
+    }
+
+    #[test]
+    fn solo_expands_one_pane_and_collapses_the_rest() {
+        let mut app = app_with(3);
+        app.apply(Action::ToggleCollapse(1));
+        app.apply(Action::Solo(1));
+        let collapsed: Vec<bool> = app.panes.iter().map(|p| p.collapsed).collect();
+        assert_eq!(collapsed, [true, false, true]);
+        assert_eq!(app.focused, 1);
+    }
+
+    #[test]
+    fn restart_replaces_only_exited_panes_in_place() {
+        let spawner = FakeSpawner::default();
+        let spawned = spawner.spawned.clone();
+        let panes = (0..2).map(|i| test_pane(i, &format!("p{}", i))).collect();
+        let mut app = AppState::new(panes, LayoutConfig::Scroll, None, Box::new(spawner));
+        app.panes[1].config.name = "p1".to_string();
+
+        app.apply(Action::RestartPane(1));
+        assert!(spawned.borrow().is_empty(), "running pane must not restart");
+
+        app.panes[1].weight = 6;
+        app.panes[1].process_output(b"old");
+        app.panes[1].mark_closed();
+        app.apply(Action::RestartPane(1));
+        assert_eq!(*spawned.borrow(), [(2, "p1".to_string())]);
+        assert_eq!(names(&app), ["p0", "p1"]);
+        assert_eq!(app.panes[1].id, 2);
+        assert_eq!(app.panes[1].weight, 6);
+        assert!(!app.panes[1].closed);
+
+        // The last run's output is kept above a divider.
+        let row = |r: usize| -> String {
+            (0..20).map(|c| crate::terminal::cell_char(&app.panes[1].term, r, c)).collect()
+        };
+        assert_eq!(row(0).trim_end(), "old");
+        assert_eq!(row(1), " run #1, restarting ");
+        assert_eq!(app.panes[1].run, 2);
+    }
+
+    #[test]
+    fn restart_reuses_the_old_terminal_and_resets_its_modes() {
+        use alacritty_terminal::grid::Dimensions;
+        use alacritty_terminal::index::{Column, Line};
+
+        let mut app = app_with(1);
+        app.panes[0].config.name = "p0".to_string();
+        let history: String = (0..50).map(|i| format!("line {}\r\n", i)).collect();
+        app.panes[0].process_output(history.as_bytes());
+        // A program that dies with mouse reporting and bracketed paste on,
+        // in a pane whose config, reloaded, turns wrapping back on.
+        app.panes[0].process_output(b"\x1b[?1000h\x1b[?2004h");
+        app.panes[0].set_wrap(false);
+        let first_line = |pane: &Pane| {
+            let grid = pane.term.grid();
+            (grid.history_size(), &grid[Line(-(grid.history_size() as i32))][Column(0)] as *const _)
+        };
+        let (history_size, cells) = first_line(&app.panes[0]);
+        assert!(history_size > 40);
+        app.panes[0].mark_closed();
+
+        app.apply(Action::RestartPane(0));
+        let pane = &app.panes[0];
+        assert_eq!(pane.id, 1);
+        assert_eq!(first_line(pane).1, cells, "the same rows, not a copy");
+        assert!(first_line(pane).0 >= history_size);
+        assert!(!pane.term.mode().intersects(TermMode::MOUSE_REPORT_CLICK | TermMode::BRACKETED_PASTE));
+        assert!(pane.wraps());
+        assert_eq!(pane.term.grid().columns(), 20, "narrowed back in place to the pane's width");
+    }
+
+    #[test]
+    fn watch_stops_restarting_after_rapid_failures() {
+        let spawner = FakeSpawner::default();
+        let spawned = spawner.spawned.clone();
+        let mut app = AppState::new(vec![test_pane(0, "p0")], LayoutConfig::Scroll, None, Box::new(spawner));
+        app.panes[0].config.name = "p0".to_string();
+        app.panes[0].config.watch = vec!["*.rs".to_string()];
+        let changed = std::path::PathBuf::from("main.rs");
+        for run in 1..=3 {
+            app.pane_exited(app.panes[0].id, ExitStatus::with_exit_code(127));
+            assert_eq!(app.panes[0].early_failure(), Some(127));
+            assert_eq!(app.panes[0].rapid_failures, run);
+            app.apply(Action::WatchRestart { idx: 0, changed: changed.clone() });
+        }
+        assert_eq!(spawned.borrow().len(), 2);
+        assert!(app.panes[0].watch_paused);
+        assert_eq!(app.status_message(), Some("p0: giving up after 3 rapid failures"));
+
+        // Resuming the watch starts the count over.
+        app.apply(Action::ToggleWatch(0));
+        app.apply(Action::WatchRestart { idx: 0, changed });
+        assert_eq!(spawned.borrow().len(), 3);
+        assert_eq!(app.panes[0].rapid_failures, 0);
+    }
+
+    #[test]
+    fn toggling_the_watch_pauses_and_resumes_restarts() {
+        let spawner = FakeSpawner::default();
+        let spawned = spawner.spawned.clone();
+        let panes = vec![test_pane(0, "p0"), test_pane(1, "p1")];
+        let mut app = AppState::new(panes, LayoutConfig::Scroll, None, Box::new(spawner));
+        app.panes[0].config.watch = vec!["*.rs".to_string()];
+        let changed = std::path::PathBuf::from("main.rs");
+
+        app.apply(Action::ToggleWatch(0));
+        assert!(app.panes[0].watch_paused);
+        app.apply(Action::WatchRestart { idx: 0, changed: changed.clone() });
+        assert_eq!(spawned.borrow().len(), 0);
+
+        app.apply(Action::ToggleWatch(0));
+        assert!(!app.panes[0].watch_paused);
+        app.apply(Action::WatchRestart { idx: 0, changed });
+        assert_eq!(spawned.borrow().len(), 1);
+        assert!(view(&app.panes[0]).iter().any(|row| row.contains("main.rs")), "{:?}", view(&app.panes[0]));
+
+        // A pane that watches nothing has no watch to pause.
+        app.apply(Action::ToggleWatch(1));
+        assert!(!app.panes[1].watch_paused);
+    }
+
+    #[test]
+    fn spawn_appends_and_focuses_with_default_name() {
+        let spawner = FakeSpawner::default();
+        let spawned = spawner.spawned.clone();
+        let mut app = AppState::new(vec![test_pane(0, "p0")], LayoutConfig::Scroll, None, Box::new(spawner));
+        app.apply(Action::SpawnPane(Box::default()));
+        assert_eq!(*spawned.borrow(), [(1, "Shell 1".to_string())]);
+        assert_eq!(names(&app), ["p0", "Shell 1"]);
+        assert_eq!(app.focused, 1);
+    }
+
+    #[test]
+    fn lazy_panes_start_once_drawn_or_focused_and_keep_their_place() {
+        let spawner = FakeSpawner::default();
+        let spawned = spawner.spawned.clone();
+        let panes = ["a", "b", "c"].iter().enumerate().map(|(i, name)| Pane::not_started(i, &entry(name), 80, 24));
+        let panes = panes.collect();
+        let mut app = AppState::new(panes, LayoutConfig::Scroll, None, Box::new(spawner));
+        app.panes[2].weight = 7;
+        assert!(!app.panes[0].accepts_input());
+
+        app.set_layout(vec![(0, Rect::new(0, 0, 80, 12)), (2, Rect::new(0, 12, 80, 12))]);
+        assert!(app.start_pending_panes());
+        assert_eq!(*spawned.borrow(), [(3, "a".to_string()), (4, "c".to_string())]);
+        assert!(app.panes[1].pending_start, "off screen and unfocused");
+        assert_eq!((app.panes[2].stable_id, app.panes[2].weight), (2, 7));
+        assert!(!app.start_pending_panes(), "each starts once");
+
+        app.apply(Action::FocusNext);
+        assert!(app.start_pending_panes());
+        assert_eq!(names(&app), ["a", "b", "c"]);
+        assert!(app.panes.iter().all(|p| !p.pending_start));
+    }
+
+    #[test]
+    fn failed_spawn_adds_an_error_pane() {
+        let spawner = FakeSpawner {
+            fail: true,
+            ..FakeSpawner::default()
+        };
+        let mut app = AppState::new(vec![test_pane(0, "p0")], LayoutConfig::Scroll, None, Box::new(spawner));
+        app.apply(Action::SpawnPane(Box::default()));
+        assert_eq!(app.panes.len(), 2);
+        let pane = &app.panes[1];
+        assert_eq!(pane.spawn_error.as_deref(), Some("spawn disabled"));
+        assert!(pane.failed() && !pane.accepts_input());
+        assert!(pane.write_input(b"x").is_err());
+    }
+
+    #[test]
+    fn selection_cursor_is_clamped_to_pane() {
+        let mut app = app_with(1);
+        app.apply(Action::StartSelection);
+        app.apply(Action::MoveSelection { rows: -1, cols: -1 });
+        assert_eq!(app.selection.as_ref().unwrap().cursor, (0, 0));
+        app.apply(Action::MoveSelection { rows: 100, cols: 100 });
+        assert_eq!(app.selection.as_ref().unwrap().cursor, (4, 19));
+    }
+
+    #[test]
+    fn error_jumps_scroll_matches_into_view_and_wrap_around() {
+        let mut app = app_with(1);
+        app.error_pattern = Some(regex::Regex::new("FAIL").unwrap());
+        // Twelve lines on a five-row screen: "FAIL a" ends up in the
+        // history, "FAIL b" on the screen's third row.
+        let text: Vec<String> = (0..12)
+            .map(|i| match i {
+                2 => "FAIL a".to_string(),
+                9 => "FAIL b".to_string(),
+                _ => format!("ok {i}"),
+            })
+            .collect();
+        app.panes[0].process_output(text.join("\r\n").as_bytes());
+        app.apply(Action::StartSelection);
+        let at_cursor = |app: &AppState| {
+            let (row, _) = app.selection.as_ref().unwrap().cursor;
+            crate::terminal::cell_char(&app.panes[0].term, row as usize, 5)
+        };
+
+        app.apply(Action::JumpToError { forward: true });
+        assert_eq!(app.selection.as_ref().unwrap().cursor, (2, 0));
+        assert_eq!((app.panes[0].scroll_offset, at_cursor(&app).as_str()), (0, "b"));
+
+        app.apply(Action::JumpToError { forward: true });
+        assert_eq!(app.selection.as_ref().unwrap().cursor, (0, 0));
+        assert_eq!((app.panes[0].scroll_offset, at_cursor(&app).as_str()), (5, "a"));
+        assert_eq!(app.panes[0].flash_message(), Some("wrapped to the first error"));
+
+        app.apply(Action::JumpToError { forward: false });
+        assert_eq!(app.selection.as_ref().unwrap().cursor, (2, 0));
+        assert_eq!((app.panes[0].scroll_offset, at_cursor(&app).as_str()), (0, "b"));
+        assert_eq!(app.panes[0].flash_message(), Some("wrapped to the last error"));
+
+        app.apply(Action::JumpToError { forward: false });
+        assert_eq!((app.panes[0].scroll_offset, at_cursor(&app).as_str()), (5, "a"));
+    }
+
+    #[test]
+    fn closing_pane_drops_its_selection() {
+        let mut app = app_with(3);
+        app.apply(Action::Focus(1));
+        app.apply(Action::StartSelection);
+        app.apply(Action::ClosePane(0));
+        assert!(app.selection.is_some());
+        app.apply(Action::ClosePane(0));
+        assert!(app.selection.is_none());
+    }
+
+    #[test]
+    fn activity_sort_keeps_focus_on_the_same_pane() {
+        let mut app = app_with(3);
+        app.apply(Action::Focus(1));
+        app.panes[2].process_output(b"x");
+        app.apply(Action::CycleSortMode);
+        assert_eq!(names(&app), ["p2", "p0", "p1"]);
+        assert_eq!(app.panes[app.focused].name, "p1");
+
+        // Damped: newer output doesn't reshuffle within the interval.
+        app.panes[1].process_output(b"x");
+        assert!(!app.sort_panes());
+        assert_eq!(names(&app), ["p2", "p0", "p1"]);
+    }
+
+    #[test]
+    fn succeeded_task_collapses_and_leaves_focus_cycling() {
+        let mut app = app_with(3);
+        for pane in &mut app.panes[..2] {
+            pane.config.kind = crate::config::PaneKind::Task;
+        }
+        app.pane_exited(1, ExitStatus::with_exit_code(2));
+        assert!(!app.panes[1].collapsed);
+
+        app.pane_exited(0, ExitStatus::with_exit_code(0));
+        assert!(!app.panes[0].collapsed, "output may still follow the exit");
+        app.pane_eof(0);
+        assert!(app.panes[0].collapsed);
+        assert_eq!(app.focused, 1);
+        app.apply(Action::FocusNext);
+        app.apply(Action::FocusNext);
+        assert_eq!(app.focused, 1);
+    }
+
+    #[test]
+    fn closing_the_last_pane_follows_on_last_pane_close() {
+        let mut app = app_with(1);
+        app.apply(Action::ClosePane(0));
+        assert_eq!(names(&app), ["p0"]);
+        assert!(app.status_message().is_some_and(|m| m.contains("on_last_pane_close")));
+
+        app.on_last_pane_close = LastPaneClose::RespawnShell;
+        app.apply(Action::ClosePane(0));
+        assert_eq!(app.panes.len(), 1);
+        assert_ne!(app.panes[0].id, 0);
+        assert!(app.panes[0].name.starts_with("Shell "));
+        assert_eq!(app.closed_panes.last().map(|c| c.id), Some(0));
+        assert_eq!(app.focused, 0);
+
+        app.on_last_pane_close = LastPaneClose::Quit;
+        app.apply(Action::ClosePane(0));
+        assert!(!app.should_quit, "a running process is asked about first");
+        assert!(app.confirm.as_ref().is_some_and(|c| c.message.contains("and quit")));
+        app.accept_confirm();
+        assert!(app.should_quit);
+
+        let mut app = app_with(1);
+        app.on_last_pane_close = LastPaneClose::Quit;
+        app.panes[0].mark_closed();
+        app.apply(Action::ClosePane(0));
+        assert!(app.confirm.is_none());
+        assert!(app.should_quit);
+    }
+
+    #[test]
+    fn last_pane_exiting_well_is_closed_by_the_policy() {
+        let finish = |app: &mut AppState, id: usize, code: u32| {
+            app.pane_exited(id, ExitStatus::with_exit_code(code));
+            app.pane_eof(id);
+        };
+        let mut app = app_with(1);
+        app.on_last_pane_close = LastPaneClose::Quit;
+        finish(&mut app, 0, 1);
+        assert!(!app.should_quit, "a failure stays to be read");
+
+        let mut app = app_with(1);
+        app.on_last_pane_close = LastPaneClose::Quit;
+        finish(&mut app, 0, 0);
+        assert!(app.should_quit);
+
+        let mut app = app_with(2);
+        app.on_last_pane_close = LastPaneClose::Quit;
+        finish(&mut app, 1, 0);
+        assert!(!app.should_quit, "other panes are still open");
+
+        let mut app = app_with(1);
+        app.on_last_pane_close = LastPaneClose::RespawnShell;
+        finish(&mut app, 0, 0);
+        assert_eq!(app.panes.len(), 1);
+        assert_ne!(app.panes[0].id, 0);
+        assert_eq!(app.panes[0].run_state(), RunState::Running);
+
+        let mut app = app_with(1);
+        finish(&mut app, 0, 0);
+        assert_eq!(names(&app), ["p0"]);
+        assert!(!app.should_quit);
+    }
+
+    #[test]
+    fn pane_finishes_once_exit_and_end_of_output_are_both_in() {
+        let mut app = app_with(2);
+        app.pane_exited(0, ExitStatus::with_exit_code(0));
+        assert_eq!(app.panes[0].run_state(), RunState::Draining);
+        app.pane_eof(0);
+        assert_eq!(app.panes[0].run_state(), RunState::Finished);
+
+        // Without an exit status, the end of output alone finishes the pane
+        // after a while.
+        app.pane_eof(1);
+        assert!(!app.panes[1].settle(Instant::now()));
+        assert!(app.panes[1].settle(Instant::now() + Duration::from_secs(2)));
+        assert_eq!(app.panes[1].run_state(), RunState::Finished);
+    }
+
+    #[test]
+    fn session_snapshot_reports_stack_order_and_state() {
+        let mut app = app_with(2);
+        app.apply(Action::Focus(1));
+        app.panes[0].mark_closed();
+        let snapshot = app.session_snapshot();
+        assert_eq!(snapshot.focused, Some(1));
+        let states: Vec<_> = snapshot.panes.iter().map(|p| (p.id, p.state.clone())).collect();
+        assert_eq!(
+            states,
+            [
+                (0, crate::session::PaneState::Exited { code: None }),
+                (1, crate::session::PaneState::Running)
+            ]
+        );
+    }
+
+    #[test]
+    fn window_title_is_sent_only_when_it_changes() {
+        let mut app = app_with(2);
+        assert_eq!(app.window_title_update(), None, "disabled without a format");
+        app.window_title_format = Some("{session}: {pane} ({n_panes})".to_string());
+        app.session_name = "work".to_string();
+        assert_eq!(app.window_title_update().as_deref(), Some("work: p0 (2)"));
+        assert_eq!(app.window_title_update(), None);
+        app.apply(Action::FocusNext);
+        assert_eq!(app.window_title_update().as_deref(), Some("work: p1 (2)"));
+    }
+
+    #[test]
+    fn focus_follows_matching_output_until_focus_is_moved_by_hand() {
+        let mut app = app_with(3);
+        app.focus_follow.mode = FocusFollows::Match;
+        app.focus_follow.pattern = Some(regex::Regex::new(DEFAULT_ACTIVITY_PATTERN).unwrap());
+
+        app.follow_activity(1, b"compiling\r\nok\r\n");
+        assert_eq!(app.focused, 0, "output without an error line");
+        app.follow_focus = false;
+        app.follow_activity(1, b"\x1b[31mError\x1b[0m: not found\r\n");
+        assert_eq!(app.focused, 1);
+        assert!(app.follow_focus, "the viewport follows as for any focus change");
+        assert!(app.auto_focus_paused_for().is_none());
+        app.follow_activity(2, b"panicked at main.rs\r\n");
+        assert_eq!(app.focused, 1, "at most one move per second");
+
+        app.last_auto_focus = None;
+        app.apply(Action::Focus(0));
+        assert!(app.auto_focus_paused_for().is_some());
+        app.follow_activity(2, b"fatal: bad revision\r\n");
+        assert_eq!(app.focused, 0, "paused after a manual focus change");
+    }
+
+    #[test]
+    fn focus_does_not_follow_activity_while_typing() {
+        let mut app = app_with(2);
+        app.focus_follow.mode = FocusFollows::Any;
+        app.last_keypress = Some(Instant::now());
+        app.follow_activity(1, b"x");
+        assert_eq!(app.focused, 0);
+        app.last_keypress = None;
+        app.follow_activity(1, b"x");
+        assert_eq!(app.focused, 1);
+    }
+
+    #[test]
+    fn closing_others_keeps_one_pane_focused_at_the_top() {
+        let mut app = app_with(5);
+        app.viewport_start = 2;
+        app.apply(Action::Focus(3));
+        app.confirm_close(MassClose::Others);
+        assert_eq!(
+            app.confirm.as_ref().map(|c| c.message.as_str()),
+            Some("Close 4 panes, terminating 4 processes? (y/n)")
+        );
+        // A reorder before the answer doesn't change which pane is kept.
+        app.panes.swap(0, 3);
+        app.accept_confirm();
+        assert_eq!(names(&app), ["p3"]);
+        assert_eq!((app.focused, app.viewport_start), (0, 0));
+    }
+
+    #[test]
+    fn sigkill_asks_first_and_dead_panes_report_no_such_process() {
+        let mut app = app_with(2);
+        app.apply(Action::Focus(1));
+        app.confirm_signal(Signal::Kill, false);
+        assert_eq!(
+            app.confirm.as_ref().map(|c| c.message.as_str()),
+            Some("Send SIGKILL to the process of p1? (y/n)")
+        );
+        app.panes.swap(0, 1);
+        app.accept_confirm();
+        assert_eq!(app.status_message(), Some("p1: no process id to signal"));
+
+        app.panes[0].exit_status = Some(ExitStatus::with_exit_code(0));
+        app.apply(Action::Focus(0));
+        app.confirm_signal(Signal::Term, false);
+        assert!(app.confirm.is_none(), "only SIGKILL asks");
+        assert_eq!(app.status_message(), Some("p1: no such process"));
+    }
+
+    #[test]
+    fn closing_below_keeps_the_panes_above() {
+        let mut app = app_with(5);
+        app.viewport_start = 3;
+        app.apply(Action::StartSelectionAt { pane: 4, row: 0, col: 0 });
+        assert_eq!(app.close_after(1), 3);
+        assert_eq!(names(&app), ["p0", "p1"]);
+        assert_eq!((app.focused, app.viewport_start), (1, 1));
+        assert!(app.selection.is_none());
+
+        app.confirm_close(MassClose::Below);
+        assert!(app.confirm.is_none(), "nothing below the last pane");
+    }
+
+    #[test]
+    fn presets_restore_weights_and_collapsed_state_by_name() {
+        let mut app = app_with(3);
+        app.panes[0].weight = 40;
+        app.panes[2].collapsed = true;
+        app.apply(Action::SavePreset(2));
+
+        app.panes[0].weight = 10;
+        app.panes[2].collapsed = false;
+        app.apply(Action::ClosePane(1));
+        app.apply(Action::ApplyPreset(2));
+        assert_eq!(app.status_message(), Some("preset 2 applied"));
+        assert_eq!(app.panes[0].weight, 40);
+        assert!(app.panes[1].collapsed);
+
+        app.apply(Action::ApplyPreset(3));
+        assert!(app.status_message().is_some_and(|m| m.contains("empty")));
+    }
+
+    #[test]
+    fn split_slot_collapses_together_and_closing_a_half_restores_full_width() {
+        let mut app = app_with(2);
+        app.apply(Action::Focus(0));
+        app.apply(Action::SplitPane);
+        assert_eq!(names(&app), ["p0", "Shell 2", "p1"]);
+        assert_eq!(app.focused, 1);
+        assert!(app.panes[1].split_right);
+        app.apply(Action::SplitPane);
+        assert_eq!(app.panes.len(), 3, "a slot holds two panes");
+
+        app.apply(Action::ToggleCollapse(1));
+        assert!(app.panes[0].collapsed && app.panes[1].collapsed);
+        assert!(!app.panes[2].collapsed);
+
+        app.apply(Action::ClosePane(0));
+        assert_eq!(names(&app), ["Shell 2", "p1"]);
+        assert!(!app.panes[0].split_right);
+        assert!(app.panes[0].collapsed, "the remaining half keeps the slot's state");
     }
 }
@@ -0,0 +1,117 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+
+/// Kept open for the life of the process: on X11 the owner of a selection
+/// has to stay around to serve it, so dropping the handle after each copy
+/// would lose the text unless a clipboard manager picks it up.
+static CLIPBOARD: Mutex<Option<arboard::Clipboard>> = Mutex::new(None);
+
+/// Command-line tools tried when arboard can't reach a clipboard, e.g. a
+/// Wayland session without the data-control protocol.
+#[cfg(windows)]
+const READ_COMMANDS: &[(&str, &[&str])] = &[("powershell", &["-NoProfile", "-Command", "Get-Clipboard -Raw"])];
+#[cfg(not(windows))]
+const READ_COMMANDS: &[(&str, &[&str])] = &[
+    ("wl-paste", &["--no-newline"]),
+    ("xclip", &["-selection", "clipboard", "-o"]),
+    ("xsel", &["--clipboard", "--output"]),
+    ("pbpaste", &[]),
+];
+
+#[cfg(windows)]
+const WRITE_COMMANDS: &[(&str, &[&str])] = &[("clip", &[])];
+#[cfg(not(windows))]
+const WRITE_COMMANDS: &[(&str, &[&str])] = &[
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+    ("pbcopy", &[]),
+];
+
+fn with_clipboard<T>(f: impl FnOnce(&mut arboard::Clipboard) -> Result<T, arboard::Error>) -> Result<T> {
+    let mut guard = CLIPBOARD.lock().unwrap_or_else(|e| e.into_inner());
+    if guard.is_none() {
+        *guard = Some(arboard::Clipboard::new()?);
+    }
+    let clipboard = guard.as_mut().expect("clipboard initialized above");
+    Ok(f(clipboard)?)
+}
+
+/// Text on the system clipboard.
+pub fn read() -> Result<String> {
+    let err = match with_clipboard(|c| c.get_text()) {
+        Ok(text) => return Ok(text),
+        Err(e) => e,
+    };
+    tracing::debug!(error = %err, "arboard read failed; trying clipboard tools");
+    for (program, args) in READ_COMMANDS {
+        let Ok(output) = Command::new(program).args(*args).stderr(Stdio::null()).output() else {
+            continue;
+        };
+        if output.status.success() {
+            return String::from_utf8(output.stdout)
+                .with_context(|| format!("{} returned non-UTF-8 text", program));
+        }
+    }
+    Err(err.context("No clipboard available"))
+}
+
+/// Put `text` on the system clipboard.
+pub fn write(text: &str) -> Result<()> {
+    let err = match with_clipboard(|c| c.set_text(text)) {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+    tracing::debug!(error = %err, "arboard write failed; trying clipboard tools");
+    for (program, args) in WRITE_COMMANDS {
+        let Ok(mut child) = Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(text.as_bytes());
+        }
+        if child.wait().is_ok_and(|s| s.success()) {
+            return Ok(());
+        }
+    }
+    Err(err.context("No clipboard available"))
+}
+
+/// Bytes to send a pane for pasting `text`: line endings become `\r`, as
+/// typed Enter would, and with `bracketed` the text is wrapped in
+/// bracketed-paste markers. An end marker inside the text is dropped so the
+/// paste can't break out early.
+pub fn encode_paste(text: &str, bracketed: bool) -> Vec<u8> {
+    let mut text = text.replace("\r\n", "\r").replace('\n', "\r");
+    if bracketed {
+        text = format!("\x1b[200~{}\x1b[201~", text.replace("\x1b[201~", ""));
+    }
+    text.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paste_normalizes_line_endings() {
+        assert_eq!(encode_paste("a\r\nb\nc\r", false), b"a\rb\rc\r");
+    }
+
+    #[test]
+    fn bracketed_paste_is_wrapped_and_cannot_be_escaped() {
+        assert_eq!(
+            encode_paste("x\x1b[201~rm -rf\n", true),
+            b"\x1b[200~xrm -rf\r\x1b[201~"
+        );
+    }
+}
@@ -0,0 +1,245 @@
+/// Output kept for one command; older output is dropped first.
+const MAX_CAPTURE: usize = 1 << 20;
+
+/// What starts an OSC 133 mark; its letter follows.
+const MARK: &[u8] = b"\x1b]133;";
+
+/// Finds OSC 133 marks in output, remembering how much of one a read ended
+/// with so a mark split between reads is still found.
+#[derive(Default)]
+pub struct MarkScanner {
+    /// Bytes of `MARK` at the end of what was scanned so far.
+    matched: usize,
+}
+
+impl MarkScanner {
+    /// Marks that end in `bytes`, as the offset just past each one's letter
+    /// and the letter.
+    pub fn scan(&mut self, bytes: &[u8]) -> Vec<(usize, u8)> {
+        let mut marks = Vec::new();
+        for (i, &b) in bytes.iter().enumerate() {
+            if self.matched == MARK.len() {
+                if b.is_ascii() {
+                    marks.push((i + 1, b));
+                }
+                self.matched = 0;
+            } else if b == MARK[self.matched] {
+                self.matched += 1;
+            } else {
+                self.matched = usize::from(b == MARK[0]);
+            }
+        }
+        marks
+    }
+}
+
+/// Collects what a shell's commands print, for shells that emit OSC 133
+/// prompt marks: `C` when a command starts, `D` when it ends and `A` at
+/// each prompt.
+pub struct CommandCapture {
+    /// Raw output since the current command started, if one is running.
+    running: Option<String>,
+    /// Cleaned-up output of the last command that finished.
+    last: Option<String>,
+    /// Keep only the final state of lines redrawn in place; see `clean`.
+    pub collapse_updates: bool,
+    marks: MarkScanner,
+}
+
+impl Default for CommandCapture {
+    fn default() -> Self {
+        Self { running: None, last: None, collapse_updates: true, marks: MarkScanner::default() }
+    }
+}
+
+impl CommandCapture {
+    /// Feed output text; it must not end partway through an escape sequence,
+    /// as `StreamAssembler` guarantees.
+    pub fn push(&mut self, text: &str) {
+        let mut from = 0;
+        for (end, letter) in self.marks.scan(text.as_bytes()) {
+            self.record(&text[from..end.saturating_sub(MARK.len() + 1).max(from)]);
+            match letter {
+                b'C' => self.running = Some(String::new()),
+                // A prompt without a `D` also ends the command.
+                b'D' | b'A' => {
+                    if let Some(raw) = self.running.take() {
+                        self.last = Some(clean(&raw, self.collapse_updates));
+                    }
+                }
+                _ => {}
+            }
+            let after = &text[end..];
+            let params = after.find(['\x07', '\x1b']).unwrap_or(after.len());
+            let terminator = match &after[params..] {
+                rest if rest.starts_with('\x07') => 1,
+                rest if rest.starts_with("\x1b\\") => 2,
+                _ => 0,
+            };
+            from = end + params + terminator;
+        }
+        self.record(&text[from..]);
+    }
+
+    fn record(&mut self, text: &str) {
+        if let Some(raw) = &mut self.running {
+            raw.push_str(text);
+            if raw.len() > MAX_CAPTURE {
+                let mut cut = raw.len() - MAX_CAPTURE;
+                while !raw.is_char_boundary(cut) {
+                    cut += 1;
+                }
+                raw.drain(..cut);
+            }
+        }
+    }
+
+    /// Output of the most recent finished command.
+    pub fn last(&self) -> Option<&str> {
+        self.last.as_deref()
+    }
+}
+
+/// Text as it would read on screen: escape sequences removed, `\r` and
+/// backspace moving back along the line to be written over, and blank lines
+/// at either end dropped.
+///
+/// With `collapse_updates`, lines a progress bar redraws in place keep only
+/// their last version, including lines revisited with cursor up / down and
+/// erase-line as `docker pull` does for each layer. Without it every redraw
+/// is kept as a line of its own.
+pub fn clean(raw: &str, collapse_updates: bool) -> String {
+    let mut lines: Vec<Vec<char>> = vec![Vec::new()];
+    let (mut row, mut col): (usize, usize) = (0, 0);
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match skip_escape(&mut chars) {
+                Some((params, action)) if collapse_updates => {
+                    let n = params.parse::<usize>().unwrap_or(1).max(1);
+                    let line = &mut lines[row];
+                    match action {
+                        'A' => row = row.saturating_sub(n),
+                        'B' => row = next_line(&mut lines, row + n - 1),
+                        'K' if params == "2" => line.clear(),
+                        'K' if params == "1" => line.iter_mut().take(col + 1).for_each(|c| *c = ' '),
+                        'K' => line.truncate(col),
+                        _ => {}
+                    }
+                }
+                _ => {}
+            },
+            '\n' => (row, col) = (next_line(&mut lines, row), 0),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\r' if !collapse_updates && !lines[row].is_empty() => (row, col) = (next_line(&mut lines, row), 0),
+            '\r' => col = 0,
+            '\x08' => col = col.saturating_sub(1),
+            c if c.is_control() && c != '\t' => {}
+            c => {
+                let line = &mut lines[row];
+                if col < line.len() {
+                    line[col] = c;
+                } else {
+                    line.resize(col, ' ');
+                    line.push(c);
+                }
+                col += 1;
+            }
+        }
+    }
+    let lines: Vec<String> = lines.iter().map(|l| l.iter().collect::<String>().trim_end().to_string()).collect();
+    let start = lines.iter().position(|l| !l.is_empty()).unwrap_or(lines.len());
+    let end = lines.iter().rposition(|l| !l.is_empty()).map_or(start, |i| i + 1);
+    lines[start..end].join("\n")
+}
+
+/// The row below `row`, adding it if the text doesn't reach that far yet.
+fn next_line(lines: &mut Vec<Vec<char>>, row: usize) -> usize {
+    if row + 1 >= lines.len() {
+        lines.resize(row + 2, Vec::new());
+    }
+    row + 1
+}
+
+/// Consume the rest of an escape sequence whose ESC was just read,
+/// returning a control sequence's parameters and final character.
+fn skip_escape(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<(String, char)> {
+    match chars.next() {
+        Some('[') => {
+            let mut params = String::new();
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    return Some((params, c));
+                }
+                params.push(c);
+            }
+            None
+        }
+        // OSC, DCS, APC, PM, SOS: up to BEL or ST.
+        Some(']' | 'P' | '_' | '^' | 'X') => {
+            while let Some(c) = chars.next() {
+                if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                    break;
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_output_between_marks() {
+        let mut capture = CommandCapture::default();
+        capture.push("\x1b]133;A\x07$ cargo test\r\n\x1b]133;C\x07\r\n\x1b[32mok\x1b[0m 1\r\n");
+        capture.push("3%\r50%\r100%\r\nFAILED  \r\n\r\n\x1b]133;D;1\x1b\\\x1b]133;A\x07$ ");
+        assert_eq!(capture.last(), Some("ok 1\n100%\nFAILED"));
+        assert!(capture.running.is_none());
+    }
+
+    /// `pip install` through rich: each redraw erases the line and starts over.
+    const PIP: &str = "Collecting numpy\r\n  Downloading numpy-2.1.0.tar.gz (18.9 MB)\r\n\x1b[?25l     \x1b[90m━━━━━━━━━━\x1b[0m \x1b[32m0.0/18.9 MB\x1b[0m \x1b[31m?\x1b[0m eta \x1b[36m-:--:--\x1b[0m\r\x1b[2K     \x1b[91m━━━\x1b[0m\x1b[90m━━━━━━━\x1b[0m \x1b[32m5.2/18.9 MB\x1b[0m \x1b[31m26.0 MB/s\x1b[0m eta \x1b[36m0:00:01\x1b[0m\r\x1b[2K     \x1b[32m━━━━━━━━━━\x1b[0m \x1b[32m18.9/18.9 MB\x1b[0m \x1b[31m31.4 MB/s\x1b[0m eta \x1b[36m0:00:00\x1b[0m\r\n\x1b[?25hInstalling collected packages: numpy\r\n";
+
+    /// `docker pull`: one line per layer, revisited with cursor up / down.
+    const DOCKER: &str = "latest: Pulling from library/alpine\n4abcf2066143: Pulling fs layer \r\nc6a83fedfae6: Pulling fs layer \r\n\x1b[2A\x1b[2K\r4abcf2066143: Downloading [==>        ]  102kB/3.4MB\r\x1b[2B\x1b[1A\x1b[2K\rc6a83fedfae6: Downloading [=====>     ]  1.1MB/2MB\r\x1b[1B\x1b[2A\x1b[2K\r4abcf2066143: Pull complete \r\x1b[2B\x1b[1A\x1b[2K\rc6a83fedfae6: Pull complete \r\x1b[1BDigest: sha256:beefdbd8a1da\nStatus: Downloaded newer image for alpine:latest\n";
+
+    #[test]
+    fn pip_progress_keeps_only_the_final_bar() {
+        assert_eq!(
+            clean(PIP, true),
+            "Collecting numpy\n  Downloading numpy-2.1.0.tar.gz (18.9 MB)\n     ━━━━━━━━━━ 18.9/18.9 MB 31.4 MB/s eta 0:00:00\nInstalling collected packages: numpy"
+        );
+        assert_eq!(clean(PIP, false).matches("eta").count(), 3);
+    }
+
+    #[test]
+    fn docker_layers_keep_only_their_final_status() {
+        assert_eq!(
+            clean(DOCKER, true),
+            "latest: Pulling from library/alpine\n4abcf2066143: Pull complete\nc6a83fedfae6: Pull complete\nDigest: sha256:beefdbd8a1da\nStatus: Downloaded newer image for alpine:latest"
+        );
+        let kept = clean(DOCKER, false);
+        assert_eq!(kept.matches("4abcf2066143:").count(), 3);
+        assert!(kept.lines().all(|line| !line.is_empty()));
+    }
+
+    #[test]
+    fn marks_split_between_reads_are_found() {
+        let mut scanner = MarkScanner::default();
+        assert_eq!(scanner.scan(b"out\r\n\x1b]13"), []);
+        assert_eq!(scanner.scan(b"3;"), []);
+        assert_eq!(scanner.scan(b"A\x07$ \x1b\x1b]133;B\x07"), [(1, b'A'), (12, b'B')]);
+        assert_eq!(scanner.scan(b"\x1b]133;\xe2\x94\x80"), []);
+    }
+
+    #[test]
+    fn nothing_is_captured_without_marks() {
+        let mut capture = CommandCapture::default();
+        capture.push("$ ls\r\nfile\r\n");
+        assert_eq!(capture.last(), None);
+    }
+}
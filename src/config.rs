@@ -1,22 +1,251 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub enum LayoutConfig {
+    #[default]
     Scroll,
     Fixed,
 }
 
-impl Default for LayoutConfig {
+/// How many colors the outer terminal can display.
+///
+/// `Auto` is resolved at startup from `NO_COLOR`, `COLORTERM` and `TERM`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    #[default]
+    Auto,
+    Truecolor,
+    #[serde(rename = "256")]
+    Ansi256,
+    #[serde(rename = "16")]
+    Ansi16,
+    None,
+}
+
+/// What to do when a pane's program sets the clipboard with OSC 52.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Osc52Mode {
+    /// Put the text on the system clipboard.
+    #[default]
+    System,
+    /// Forward the sequence to the outer terminal, e.g. over SSH.
+    Passthrough,
+    /// Ignore the request.
+    Deny,
+}
+
+/// What happens to pane output while the session is paused.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PauseMode {
+    /// Stop reading, so programs block on write and nothing is lost.
+    #[default]
+    Backpressure,
+    /// Keep programs running and discard what doesn't fit the spill.
+    Drop,
+}
+
+/// What closing the only pane does. Also applies when its process exits
+/// successfully on its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LastPaneClose {
+    /// Keep the pane, saying why in the footer.
+    #[default]
+    Ignore,
+    /// Quit bamboo, asking first if the process still runs.
+    Quit,
+    /// Replace the pane with a fresh default shell.
+    RespawnShell,
+}
+
+/// How panes scrolled out of view are shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ViewportIndicator {
+    /// "▲ N more above" / "▼ N more below" rows.
+    #[default]
+    Lines,
+    /// A one-column map of the whole stack on the right edge.
+    Gutter,
+    /// Nothing; keys still page and focus.
+    None,
+}
+
+/// Where the viewport puts the focused pane when focus moves out of view.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ViewportPolicy {
+    /// Scroll just far enough to show it, at the edge it came in from.
+    #[default]
+    Minimal,
+    /// As near the middle of the panes shown as the ends of the stack allow.
+    Centered,
+    /// With up to N panes shown above and below it, where they exist and fit.
+    Context(usize),
+}
+
+impl TryFrom<String> for ViewportPolicy {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "minimal" => Ok(ViewportPolicy::Minimal),
+            "centered" => Ok(ViewportPolicy::Centered),
+            _ => s
+                .strip_prefix("context:")
+                .and_then(|n| n.trim().parse().ok())
+                .map(ViewportPolicy::Context)
+                .ok_or_else(|| format!("expected \"minimal\", \"centered\" or \"context:N\", got {:?}", s)),
+        }
+    }
+}
+
+impl From<ViewportPolicy> for String {
+    fn from(policy: ViewportPolicy) -> Self {
+        match policy {
+            ViewportPolicy::Minimal => "minimal".to_string(),
+            ViewportPolicy::Centered => "centered".to_string(),
+            ViewportPolicy::Context(n) => format!("context:{}", n),
+        }
+    }
+}
+
+/// What happens to inline images (sixel, iTerm2) in pane output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageMode {
+    /// Strip them from the output.
+    #[default]
+    Off,
+    /// Draw them on the outer terminal while the focused pane shows them.
+    Passthrough,
+}
+
+/// Which output moves focus to the pane that printed it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FocusFollows {
+    #[default]
+    Off,
+    /// Any output.
+    Any,
+    /// Output with a line matching `focus_activity_pattern`.
+    Match,
+}
+
+impl ColorMode {
+    /// Resolve `Auto` from the environment; other modes are returned as-is.
+    pub fn resolve(self) -> Self {
+        if self != ColorMode::Auto {
+            return self;
+        }
+        let var = |name: &str| std::env::var(name).unwrap_or_default();
+        Self::detect(&var("NO_COLOR"), &var("COLORTERM"), &var("TERM"))
+    }
+
+    fn detect(no_color: &str, colorterm: &str, term: &str) -> Self {
+        if !no_color.is_empty() || term == "dumb" {
+            ColorMode::None
+        } else if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorMode::Truecolor
+        } else if term.contains("256color") {
+            ColorMode::Ansi256
+        } else {
+            ColorMode::Ansi16
+        }
+    }
+}
+
+/// How much border/title decoration each pane gets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Chrome {
+    /// Full box border with the title in the top edge.
+    #[default]
+    Full,
+    /// A single title/separator line above the content.
+    Compact,
+    /// Content only, with a focus marker in a one-column left gutter.
+    None,
+}
+
+/// Pane height weights: the starting value, the Ctrl+Up/Down step, and the
+/// upper bound (the lower bound is always 1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeightConfig {
+    #[serde(default = "default_weight")]
+    pub default_weight: u16,
+    #[serde(default = "default_weight_step")]
+    pub weight_step: u16,
+    #[serde(default = "default_weight_max")]
+    pub weight_max: u16,
+}
+
+fn default_weight() -> u16 {
+    10
+}
+
+fn default_weight_step() -> u16 {
+    2
+}
+
+fn default_weight_max() -> u16 {
+    50
+}
+
+impl Default for WeightConfig {
     fn default() -> Self {
-        Self::Scroll
+        Self {
+            default_weight: default_weight(),
+            weight_step: default_weight_step(),
+            weight_max: default_weight_max(),
+        }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl WeightConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.weight_step == 0 {
+            anyhow::bail!("weight_step must be at least 1");
+        }
+        if self.weight_max == 0 {
+            anyhow::bail!("weight_max must be at least 1");
+        }
+        if self.default_weight == 0 || self.default_weight > self.weight_max {
+            anyhow::bail!(
+                "default_weight must be between 1 and weight_max ({}), got {}",
+                self.weight_max,
+                self.default_weight
+            );
+        }
+        Ok(())
+    }
+
+    pub fn clamp(&self, weight: u16) -> u16 {
+        weight.clamp(1, self.weight_max)
+    }
+}
+
+/// Whether a pane's command is meant to keep running or to finish.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaneKind {
+    /// A shell or long-running service.
+    #[default]
+    Service,
+    /// A one-shot command: collapsed once it succeeds, kept open if it fails.
+    Task,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct PaneConfig {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -25,37 +254,603 @@ pub struct PaneConfig {
     pub cwd: Option<String>,
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub env: HashMap<String, String>,
+    /// Default foreground for cells the program leaves uncolored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fg: Option<String>,
+    /// Default background, also used to fill the pane behind its content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bg: Option<String>,
+    /// Replacements for the 16 ANSI colors, e.g. `{ blue = "#8fa1ff", 0 = "white" }`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub palette: HashMap<String, String>,
+    /// Mirror the lightness of the program's RGB colors, for output themed
+    /// for the opposite background.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub invert_luminance: bool,
+    /// Title bar template for this pane; overrides the global `title_format`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title_format: Option<String>,
+    /// Focus this pane at startup (alternative to the top-level `focus`).
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub focus: bool,
+    /// Shell for this pane when it has no `command`; overrides `default_shell`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<ShellCommand>,
+    /// Run this pane's shell as a login shell; overrides the global `login_shell`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub login_shell: Option<bool>,
+    /// Restart the command when files matching these globs change, e.g.
+    /// `["src/**/*.rs"]`, relative to `cwd`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watch: Vec<String>,
+    #[serde(default, skip_serializing_if = "is_service")]
+    pub kind: PaneKind,
+    /// Wrap long lines at the pane width (the default). With `false` they
+    /// stay whole and the pane scrolls sideways.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wrap: Option<bool>,
+    /// Content rows this pane keeps while expanded; other panes shrink, or
+    /// move out of view, first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_height: Option<u16>,
+    /// Starting height weight; overrides `default_weight`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<u16>,
+    /// Group this pane is collapsed, expanded, focused and closed with
+    /// (Alt+Shift+G). A pane is in one group at most.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Follow the name with the program in the foreground, e.g.
+    /// "Shell 3 — vim". On by default for panes opened without a name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_name: Option<bool>,
+    /// Wait to start until the pane is focused or scrolled into view;
+    /// overrides the global `lazy_start`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lazy_start: Option<bool>,
+    /// Start with the session even under `lazy_start`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub eager: bool,
+    /// Keys that aren't fields, so a misspelt one isn't silently dropped:
+    /// the file loads with a warning, and an `--override` setting one fails.
+    #[serde(flatten, skip_serializing)]
+    pub unknown: BTreeMap<String, toml::Value>,
+}
+
+impl PaneConfig {
+    /// Whether the pane waits to start, under the global `lazy_start`.
+    pub fn starts_lazily(&self, lazy_start: bool) -> bool {
+        !self.eager && self.lazy_start.unwrap_or(lazy_start)
+    }
+
+    /// Check what deserializing can't: shells, globs, colors and weight.
+    fn validate(&self, weights: &WeightConfig) -> Result<()> {
+        if self.shell.as_ref().is_some_and(ShellCommand::is_empty) {
+            anyhow::bail!("Empty shell for pane '{}'", self.name);
+        }
+        if self.group.as_ref().is_some_and(|g| g.trim().is_empty()) {
+            anyhow::bail!("Empty group for pane '{}'", self.name);
+        }
+        crate::watch::compile(&self.watch).with_context(|| format!("Pane '{}'", self.name))?;
+        for color in [&self.fg, &self.bg].into_iter().flatten() {
+            if crate::terminal::parse_color(color).is_none() {
+                anyhow::bail!("Invalid color {:?} for pane '{}'", color, self.name);
+            }
+        }
+        crate::terminal::PaneColors::parse(&self.palette, self.invert_luminance)
+            .with_context(|| format!("Pane '{}'", self.name))?;
+        if let Some(weight) = self.weight {
+            if weight == 0 || weight > weights.weight_max {
+                anyhow::bail!(
+                    "weight for pane '{}' must be between 1 and weight_max ({}), got {}",
+                    self.name,
+                    weights.weight_max,
+                    weight
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A shell as written in the config: one string split on whitespace
+/// (`"zsh -i"`), or the program and its arguments as an array
+/// (`["nu", "--config", "my config.nu"]`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ShellCommand {
+    Line(String),
+    Argv(Vec<String>),
+}
+
+impl ShellCommand {
+    fn is_empty(&self) -> bool {
+        match self {
+            ShellCommand::Line(line) => line.trim().is_empty(),
+            ShellCommand::Argv(argv) => argv.first().is_none_or(|p| p.trim().is_empty()),
+        }
+    }
+
+    /// The program and its arguments.
+    fn split(&self) -> (String, Vec<String>) {
+        let mut parts: Vec<String> = match self {
+            ShellCommand::Line(line) => line.split_whitespace().map(str::to_string).collect(),
+            ShellCommand::Argv(argv) => argv.clone(),
+        };
+        let program = if parts.is_empty() { String::new() } else { parts.remove(0) };
+        (program, parts)
+    }
+}
+
+impl fmt::Display for ShellCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShellCommand::Line(line) => f.write_str(line),
+            ShellCommand::Argv(argv) => f.write_str(&argv.join(" ")),
+        }
+    }
+}
+
+/// The shell a pane runs when it has no explicit `command`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShellSpec {
+    /// Shell binary.
+    pub program: String,
+    /// Configured arguments, passed ahead of `-l`.
+    pub args: Vec<String>,
+    /// Start it as a login shell so profile files are read.
+    pub login: bool,
+    /// Run pane commands as `shell -i -c command`, so the rc file's aliases
+    /// and functions work; otherwise commands run directly.
+    pub interactive: bool,
+}
+
+impl ShellSpec {
+    fn new(shell: &ShellCommand, login: bool, interactive: bool) -> Self {
+        let (program, args) = shell.split();
+        Self {
+            program,
+            args,
+            login,
+            interactive,
+        }
+    }
+
+    /// These defaults with the pane's own `shell` / `login_shell` applied.
+    pub fn for_pane(&self, pane: &PaneConfig) -> ShellSpec {
+        let login = pane.login_shell.unwrap_or(self.login);
+        match &pane.shell {
+            Some(shell) => ShellSpec::new(shell, login, self.interactive),
+            None => ShellSpec { login, ..self.clone() },
+        }
+    }
+
+    /// Executable and arguments to run. Login shells get `-l` after the
+    /// configured arguments, since bash takes its long options
+    /// (`--noprofile`) only first; Windows shells have no login mode.
+    pub fn argv(&self) -> Vec<String> {
+        let mut argv = vec![self.program.clone()];
+        argv.extend(self.args.iter().cloned());
+        if self.login && cfg!(not(windows)) {
+            argv.push("-l".to_string());
+        }
+        argv
+    }
+
+    /// Executable and arguments for a pane's `command`: split on whitespace
+    /// and run directly, or through this shell with `-i -c` when
+    /// `interactive` is set. Windows always runs it directly.
+    pub fn command_argv(&self, command: &str) -> Vec<String> {
+        if !self.interactive || cfg!(windows) {
+            return command.split_whitespace().map(str::to_string).collect();
+        }
+        let mut argv = vec![self.program.clone()];
+        argv.extend(self.args.iter().cloned());
+        argv.extend(["-i".to_string(), "-c".to_string(), command.to_string()]);
+        argv
+    }
+}
+
+/// Whether `program` names an existing file, directly or through `PATH`.
+fn program_exists(program: &str) -> bool {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return path.is_file();
+    }
+    let Some(dirs) = std::env::var_os("PATH") else {
+        return false;
+    };
+    let exts: &[&str] = if cfg!(windows) { &["", ".exe", ".cmd", ".bat"] } else { &[""] };
+    std::env::split_paths(&dirs).any(|dir| exts.iter().any(|ext| dir.join(format!("{}{}", program, ext)).is_file()))
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default = "default_shell")]
-    pub default_shell: String,
+    pub default_shell: ShellCommand,
+    /// Run shells (panes without a `command`) as login shells.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub login_shell: bool,
+    /// Run pane commands through the shell as `shell -i -c command`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub shell_interactive: bool,
     #[serde(default)]
     pub layout: LayoutConfig,
     #[serde(default)]
+    pub chrome: Chrome,
+    #[serde(default)]
     pub panes: Vec<PaneConfig>,
+    /// Panes that don't start with bamboo but can be opened from the
+    /// template picker (Alt+Shift+n).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub templates: Vec<PaneConfig>,
+    #[serde(flatten)]
+    pub weights: WeightConfig,
+    /// Title bar template, e.g. `"{index}:{name} {status}"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title_format: Option<String>,
+    /// Force a color mode when detection picks the wrong one.
+    #[serde(default, skip_serializing_if = "is_auto")]
+    pub color_mode: ColorMode,
+    /// Draw bold text in one of the 8 basic colors in its bright variant,
+    /// as xterm does, as well as in a heavy face.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub bold_is_bright: bool,
+    /// Append diagnostic logs to this file (off when unset).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<String>,
+    /// Log verbosity: error, warn, info, debug or trace (default: info).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<String>,
+    /// Name of the pane to focus at startup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus: Option<String>,
+    /// Start with every pane except the focused one collapsed.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub start_collapsed: bool,
+    /// Show a restart / close / export bar in panes whose process exited.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub show_exit_bar: bool,
+    /// Show each pane's output rate and its last seconds as a sparkline in
+    /// the title.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub show_throughput: bool,
+    /// Keep tasks that finished successfully in focus cycling.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub focus_finished_tasks: bool,
+    /// Name the focused pane in the outer terminal's window title.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub set_window_title: bool,
+    /// Window title template, e.g. `"{session} — {pane}"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_title_format: Option<String>,
+    /// Title to set on exit, for terminals that can't restore the one they
+    /// had; otherwise the saved title is restored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_title_fallback: Option<String>,
+    /// Lines scrolled per mouse wheel notch.
+    #[serde(default = "default_scroll_lines", skip_serializing_if = "is_default_scroll_lines")]
+    pub scroll_lines: u16,
+    /// Reverse the wheel direction, as on touchpads with natural scrolling.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub natural_scroll: bool,
+    /// Scroll further with each notch of a fast, continuous wheel turn.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub scroll_acceleration: bool,
+    /// Prefix that sends the next key straight to the focused pane
+    /// (default: `Ctrl+Space`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub literal_key: Option<String>,
+    /// Don't warn at startup about running inside tmux or another bamboo.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub allow_nested: bool,
+    /// Plain rendering for screen readers and braille displays: ASCII title
+    /// lines instead of borders, and state spelled out instead of colored.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub accessible: bool,
+    /// Leave out redraws that only decorate: hover highlights, the
+    /// crosshair, weight previews and titles following output.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub quiet_redraws: bool,
+    /// Key that freezes and resumes all pane output (default: `Alt+Pause`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pause_key: Option<String>,
+    /// Whether a paused session holds programs back or drops their output.
+    #[serde(default, skip_serializing_if = "is_backpressure")]
+    pub pause: PauseMode,
+    /// What `[x]` on the only pane does, and what happens when its process
+    /// exits successfully.
+    #[serde(default, skip_serializing_if = "is_ignore")]
+    pub on_last_pane_close: LastPaneClose,
+    /// Remember weights, collapsed state and order between sessions.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub persist_layout: bool,
+    /// Where OSC 52 clipboard writes from panes go.
+    #[serde(default, skip_serializing_if = "is_system")]
+    pub osc52: Osc52Mode,
+    /// Let panes read the clipboard with an OSC 52 query (`osc52 = "system"` only).
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub osc52_read: bool,
+    /// Move focus to the pane that most recently printed.
+    #[serde(default, skip_serializing_if = "is_off")]
+    pub focus_follows_activity: FocusFollows,
+    /// Regex a line must match with `focus_follows_activity = "match"`
+    /// (default: error, fatal or panic as a word, any case).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus_activity_pattern: Option<String>,
+    /// Regex for lines to mark beside each pane and jump between while
+    /// selecting; off when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_pattern: Option<String>,
+    /// Dim panes that have printed nothing for this many seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stale_after_secs: Option<u64>,
+    /// Seconds a manual focus change pauses focus-follows-activity.
+    #[serde(default = "default_focus_activity_cooldown", skip_serializing_if = "is_default_focus_activity_cooldown")]
+    pub focus_activity_cooldown: u64,
+    /// How panes above and below the viewport are shown.
+    #[serde(default, skip_serializing_if = "is_lines")]
+    pub viewport_indicator: ViewportIndicator,
+    /// Where the viewport puts the focused pane when it scrolls to show it.
+    #[serde(default, skip_serializing_if = "is_minimal")]
+    pub viewport_policy: ViewportPolicy,
+    /// Inline images in pane output; drawing them depends on the outer
+    /// terminal supporting them.
+    #[serde(default, skip_serializing_if = "is_images_off")]
+    pub images: ImageMode,
+    /// Tint the row and column under the mouse, or the copy-mode cursor, in
+    /// the focused pane; Alt+Shift+C toggles it at runtime.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub crosshair: bool,
+    /// Capture the mouse for clicks, the wheel and drag selection. Off, the
+    /// outer terminal keeps its own selection and link handling;
+    /// Alt+Shift+M toggles it at runtime.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub mouse: bool,
+    /// Start panes only once they are focused or scrolled into view, so a
+    /// long stack draws its first frame sooner.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub lazy_start: bool,
+    /// Draw in the terminal's alternate screen. Off, bamboo draws over the
+    /// primary screen and leaves its last frame in the scrollback on exit.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub alternate_screen: bool,
+    /// Keep only the final state of progress lines redrawn in place when
+    /// capturing a command's output, instead of every redraw.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub collapse_cr_updates: bool,
+    /// Lines of history each pane keeps above its screen.
+    #[serde(default = "default_scrollback", skip_serializing_if = "is_default_scrollback")]
+    pub scrollback: usize,
+    /// On reload (Alt+Shift+R), take pane weights from the config even for
+    /// panes resized since startup.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub config_overrides_runtime: bool,
+    /// Show a collapsed pane's last line from above the shell prompt it is
+    /// waiting at, for shells that mark their prompts with OSC 133.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub collapsed_skip_prompt: bool,
+    /// Most times a second a pane's title follows its output (run time,
+    /// cwd, lines behind a scroll lock), and the screen redraws for output
+    /// it doesn't show; focus, collapse and exit show at once.
+    #[serde(default = "default_title_refresh_rate", skip_serializing_if = "is_default_title_refresh_rate")]
+    pub title_refresh_rate: u32,
+    /// Most frames a second drawn for pane output; keys, clicks and resizes
+    /// draw at once.
+    #[serde(default = "default_max_fps", skip_serializing_if = "is_default_max_fps")]
+    pub max_fps: u32,
+    /// Last lines of output per pane in a session report (Alt+Shift+E).
+    #[serde(default = "default_report_lines", skip_serializing_if = "is_default_report_lines")]
+    pub report_lines: usize,
+    /// File this config was read from; `None` for defaults and wizard output.
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+    /// Problems found while loading that don't stop bamboo from starting;
+    /// shown once the UI is up.
+    #[serde(skip)]
+    pub warnings: Vec<String>,
+}
+
+fn is_auto(mode: &ColorMode) -> bool {
+    *mode == ColorMode::Auto
+}
+
+fn is_service(kind: &PaneKind) -> bool {
+    *kind == PaneKind::Service
+}
+
+fn is_off(mode: &FocusFollows) -> bool {
+    *mode == FocusFollows::Off
+}
+
+fn is_lines(indicator: &ViewportIndicator) -> bool {
+    *indicator == ViewportIndicator::Lines
+}
+
+fn is_minimal(policy: &ViewportPolicy) -> bool {
+    *policy == ViewportPolicy::Minimal
+}
+
+fn is_images_off(mode: &ImageMode) -> bool {
+    *mode == ImageMode::Off
+}
+
+fn default_focus_activity_cooldown() -> u64 {
+    10
+}
+
+fn is_default_focus_activity_cooldown(secs: &u64) -> bool {
+    *secs == default_focus_activity_cooldown()
+}
+
+fn is_backpressure(mode: &PauseMode) -> bool {
+    *mode == PauseMode::Backpressure
+}
+
+fn is_ignore(policy: &LastPaneClose) -> bool {
+    *policy == LastPaneClose::Ignore
+}
+
+fn is_system(mode: &Osc52Mode) -> bool {
+    *mode == Osc52Mode::System
+}
+
+fn default_scroll_lines() -> u16 {
+    3
+}
+
+fn is_default_scroll_lines(lines: &u16) -> bool {
+    *lines == default_scroll_lines()
+}
+
+fn default_scrollback() -> usize {
+    1000
+}
+
+fn is_default_scrollback(lines: &usize) -> bool {
+    *lines == default_scrollback()
+}
+
+fn default_title_refresh_rate() -> u32 {
+    4
 }
 
-fn default_shell() -> String {
-    std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string())
+fn is_default_title_refresh_rate(rate: &u32) -> bool {
+    *rate == default_title_refresh_rate()
+}
+
+fn default_max_fps() -> u32 {
+    30
+}
+
+fn is_default_max_fps(fps: &u32) -> bool {
+    *fps == default_max_fps()
+}
+
+fn default_report_lines() -> usize {
+    crate::report::DEFAULT_REPORT_LINES
+}
+
+fn is_default_report_lines(lines: &usize) -> bool {
+    *lines == default_report_lines()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// `$SHELL` on Unix; `%COMSPEC%` (normally cmd.exe) or PowerShell on Windows.
+pub fn default_shell() -> ShellCommand {
+    #[cfg(windows)]
+    let shell = std::env::var("COMSPEC").unwrap_or_else(|_| "powershell.exe".to_string());
+    #[cfg(not(windows))]
+    let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+    ShellCommand::Line(shell)
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             default_shell: default_shell(),
+            login_shell: false,
+            shell_interactive: false,
             layout: LayoutConfig::default(),
+            chrome: Chrome::default(),
             panes: vec![PaneConfig {
                 name: "Shell".to_string(),
-                command: None,
-                cwd: None,
-                env: HashMap::new(),
+                ..PaneConfig::default()
             }],
+            templates: Vec::new(),
+            weights: WeightConfig::default(),
+            title_format: None,
+            color_mode: ColorMode::Auto,
+            bold_is_bright: true,
+            log_file: None,
+            log_level: None,
+            focus: None,
+            start_collapsed: false,
+            show_exit_bar: true,
+            show_throughput: false,
+            focus_finished_tasks: false,
+            set_window_title: true,
+            window_title_format: None,
+            window_title_fallback: None,
+            scroll_lines: default_scroll_lines(),
+            natural_scroll: false,
+            scroll_acceleration: false,
+            literal_key: None,
+            allow_nested: false,
+            accessible: false,
+            quiet_redraws: false,
+            pause_key: None,
+            pause: PauseMode::Backpressure,
+            on_last_pane_close: LastPaneClose::Ignore,
+            persist_layout: true,
+            osc52: Osc52Mode::System,
+            osc52_read: false,
+            focus_follows_activity: FocusFollows::Off,
+            focus_activity_pattern: None,
+            focus_activity_cooldown: default_focus_activity_cooldown(),
+            stale_after_secs: None,
+            error_pattern: None,
+            viewport_indicator: ViewportIndicator::Lines,
+            viewport_policy: ViewportPolicy::Minimal,
+            images: ImageMode::Off,
+            crosshair: false,
+            mouse: true,
+            lazy_start: false,
+            alternate_screen: true,
+            collapse_cr_updates: true,
+            scrollback: default_scrollback(),
+            config_overrides_runtime: false,
+            collapsed_skip_prompt: false,
+            title_refresh_rate: default_title_refresh_rate(),
+            max_fps: default_max_fps(),
+            report_lines: default_report_lines(),
+            source: None,
+            warnings: Vec::new(),
         }
     }
 }
 
+/// How startup read the config, to read it again the same way on reload.
+#[derive(Debug, Clone)]
+pub struct ConfigReload {
+    pub path: PathBuf,
+    /// `--extra` panes, with their `--name`.
+    pub extras: Vec<(String, Option<String>)>,
+    /// `--override` specs.
+    pub overrides: Vec<String>,
+    /// Working directory given to every pane, as in a worktree session.
+    pub cwd: Option<String>,
+}
+
+impl ConfigReload {
+    pub fn load(&self) -> Result<Config> {
+        let mut config = Config::read_file(&self.path)?;
+        for (command, name) in &self.extras {
+            config.add_extra(command, name.as_deref());
+        }
+        for spec in &self.overrides {
+            config.apply_override(spec)?;
+        }
+        if let Some(cwd) = &self.cwd {
+            for pane in &mut config.panes {
+                pane.cwd = Some(cwd.clone());
+            }
+        }
+        Ok(config)
+    }
+}
+
 /// Whether a config file was found or the interactive wizard should be invoked.
 ///
 /// `NeedsWizard` is returned only when *no* config file exists at any of the
@@ -64,12 +859,17 @@ impl Default for Config {
 /// `Config::default()` rather than attempting to run the wizard.
 pub enum ConfigSource {
     /// Config was loaded from an explicit path or a discovered file.
-    File(Config),
+    File(Box<Config>),
     /// No config file was found anywhere; the wizard should run (TTY only).
     NeedsWizard,
 }
 
 impl Config {
+    /// Global shell settings; panes refine them with `ShellSpec::for_pane`.
+    pub fn shell(&self) -> ShellSpec {
+        ShellSpec::new(&self.default_shell, self.login_shell, self.shell_interactive)
+    }
+
     /// Look for a config file in priority order:
     ///
     /// 1. Explicit `--config <path>` flag
@@ -83,13 +883,15 @@ impl Config {
         // Explicit --config path always wins.
         if let Some(p) = path {
             let config_path = PathBuf::from(p);
-            return Self::read_file(&config_path).map(ConfigSource::File);
+            tracing::info!(path = %config_path.display(), "loading config from --config");
+            return Self::read_file(&config_path).map(|c| ConfigSource::File(Box::new(c)));
         }
 
         // Local .bamboo.toml takes next priority.
         let local = PathBuf::from(".bamboo.toml");
         if local.exists() {
-            return Self::read_file(&local).map(ConfigSource::File);
+            tracing::info!(path = %local.display(), "loading local config");
+            return Self::read_file(&local).map(|c| ConfigSource::File(Box::new(c)));
         }
 
         // Fall back to global config locations.  If one exists we use it
@@ -100,10 +902,12 @@ impl Config {
             dirs::config_dir().map(|d| d.join("bamboo").join("config.toml")),
         ];
         if let Some(global) = global_candidates.into_iter().flatten().find(|p| p.exists()) {
-            return Self::read_file(&global).map(ConfigSource::File);
+            tracing::info!(path = %global.display(), "loading global config");
+            return Self::read_file(&global).map(|c| ConfigSource::File(Box::new(c)));
         }
 
         // No config found anywhere → run the interactive wizard.
+        tracing::info!("no config found");
         Ok(ConfigSource::NeedsWizard)
     }
 
@@ -114,29 +918,367 @@ impl Config {
         let mut config: Config =
             toml::from_str(&contents).with_context(|| "Failed to parse config TOML")?;
 
+        config.source = Some(
+            std::fs::canonicalize(config_path).unwrap_or_else(|_| config_path.clone()),
+        );
+
         if config.panes.is_empty() {
             config.panes.push(PaneConfig {
                 name: "Shell".to_string(),
-                command: None,
-                cwd: None,
-                env: HashMap::new(),
+                ..PaneConfig::default()
             });
         }
 
+        config.weights.validate()?;
+        for pane in config.panes.iter().chain(&config.templates) {
+            pane.validate(&config.weights)?;
+        }
+        let mut template_names = std::collections::HashSet::new();
+        for template in &config.templates {
+            if template.name.trim().is_empty() {
+                anyhow::bail!("Templates need a name");
+            }
+            if !template_names.insert(template.name.as_str()) {
+                anyhow::bail!("Duplicate template name '{}'", template.name);
+            }
+        }
+        if config.title_refresh_rate == 0 {
+            anyhow::bail!("title_refresh_rate must be at least 1");
+        }
+        if config.max_fps == 0 {
+            anyhow::bail!("max_fps must be at least 1");
+        }
+        if config.scroll_lines == 0 {
+            anyhow::bail!("scroll_lines must be at least 1");
+        }
+        if config.report_lines == 0 {
+            anyhow::bail!("report_lines must be at least 1");
+        }
+        if config.stale_after_secs == Some(0) {
+            anyhow::bail!("stale_after_secs must be at least 1");
+        }
+        if let Some(pattern) = &config.focus_activity_pattern {
+            regex::Regex::new(pattern)
+                .with_context(|| format!("Invalid focus_activity_pattern {:?}", pattern))?;
+        }
+        if let Some(pattern) = &config.error_pattern {
+            regex::Regex::new(pattern).with_context(|| format!("Invalid error_pattern {:?}", pattern))?;
+        }
+        if let Some(spec) = &config.literal_key {
+            if crate::keys::KeyChord::parse(spec).is_none() {
+                anyhow::bail!("Invalid literal_key {:?} (expected e.g. \"Ctrl+Space\" or \"Alt+l\")", spec);
+            }
+        }
+        if let Some(spec) = &config.pause_key {
+            if crate::keys::KeyChord::parse(spec).is_none() {
+                anyhow::bail!("Invalid pause_key {:?} (expected e.g. \"Alt+Pause\" or \"F9\")", spec);
+            }
+        }
+
+        let focused: Vec<&str> = config
+            .panes
+            .iter()
+            .filter(|p| p.focus)
+            .map(|p| p.name.as_str())
+            .collect();
+        if focused.len() > 1 {
+            anyhow::bail!("Only one pane may set focus = true (found: {})", focused.join(", "));
+        }
+        if let Some(name) = &config.focus {
+            if !focused.is_empty() {
+                anyhow::bail!(
+                    "Both focus = {:?} and focus = true on pane '{}' are set; use one",
+                    name,
+                    focused[0]
+                );
+            }
+            if !config.panes.iter().any(|p| &p.name == name) {
+                anyhow::bail!("focus = {:?} does not name a pane", name);
+            }
+        }
+
+        config.warn_unknown_keys();
+        config.warn_missing_shells();
+
+        let templates = std::iter::once(&config.title_format)
+            .chain(config.panes.iter().map(|p| &p.title_format))
+            .flatten();
+        for template in templates {
+            for key in crate::title::unknown_placeholders(template) {
+                config.warnings.push(format!(
+                    "Unknown placeholder {{{}}} in title_format {:?}; it will be shown as-is",
+                    key, template
+                ));
+            }
+        }
+        if let Some(template) = &config.window_title_format {
+            for key in crate::title::unknown_window_placeholders(template) {
+                config.warnings.push(format!(
+                    "Unknown placeholder {{{}}} in window_title_format {:?}; it will be shown as-is",
+                    key, template
+                ));
+            }
+        }
+
         Ok(config)
     }
 
+    /// Warn about `[[panes]]` and `[[templates]]` keys that aren't fields;
+    /// they are ignored, so files written for other versions still load.
+    fn warn_unknown_keys(&mut self) {
+        let unknown: Vec<String> = self
+            .panes
+            .iter()
+            .chain(&self.templates)
+            .flat_map(|pane| {
+                pane.unknown
+                    .keys()
+                    .map(move |key| format!("Unknown key {:?} in pane '{}'; it is ignored", key, pane.name))
+            })
+            .collect();
+        self.warnings.extend(unknown);
+    }
+
+    /// Warn about shells that aren't on `PATH`; a pane using one fails to
+    /// start, but the others still run.
+    fn warn_missing_shells(&mut self) {
+        let shells = std::iter::once(&self.default_shell)
+            .chain(self.panes.iter().chain(&self.templates).filter_map(|p| p.shell.as_ref()));
+        let mut missing = Vec::new();
+        for shell in shells {
+            let (program, _) = shell.split();
+            if !program.is_empty() && !program_exists(&program) {
+                missing.push(format!("Shell {:?} was not found; panes using it won't start", program));
+            }
+        }
+        self.warnings.extend(missing);
+    }
+
+    /// Append a pane from `--extra`: a shell when `command` is empty, named
+    /// `name` or else after the command.
+    pub fn add_extra(&mut self, command: &str, name: Option<&str>) {
+        let command = command.trim();
+        let name = match name {
+            Some(name) => name.to_string(),
+            None if command.is_empty() => "Shell".to_string(),
+            None => command.to_string(),
+        };
+        self.panes.push(PaneConfig {
+            name,
+            command: Some(command.to_string()).filter(|c| !c.is_empty()),
+            ..PaneConfig::default()
+        });
+    }
+
+    /// Apply a `--override PANE.FIELD=VALUE` from the command line to the
+    /// pane named `PANE`. The value is read as TOML, or taken as a string
+    /// when it isn't valid TOML, so shell-unquoted text works.
+    pub fn apply_override(&mut self, spec: &str) -> Result<()> {
+        let invalid = || format!("Invalid override {:?} (expected PANE.FIELD=VALUE)", spec);
+        let (path, raw) = spec.split_once('=').with_context(invalid)?;
+        let (name, field) = path.rsplit_once('.').with_context(invalid)?;
+        let weights = self.weights;
+        let pane = self
+            .panes
+            .iter_mut()
+            .find(|p| p.name == name)
+            .with_context(|| format!("No pane named {:?} for override {:?}", name, spec))?;
+
+        let value = toml::from_str::<toml::Table>(&format!("value = {}", raw))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .unwrap_or_else(|| toml::Value::String(raw.to_string()));
+        let mut table = toml::Value::try_from(&*pane).context("Failed to apply override")?;
+        if let Some(table) = table.as_table_mut() {
+            table.insert(field.to_string(), value);
+        }
+        let patched: PaneConfig =
+            table.try_into().with_context(|| format!("Can't set {:?} in override {:?}", field, spec))?;
+        if patched.unknown.contains_key(field) {
+            anyhow::bail!("Unknown field {:?} in override {:?}", field, spec);
+        }
+        patched.validate(&weights)?;
+        *pane = patched;
+        Ok(())
+    }
+
+    /// Index of the pane to focus at startup, from `focus` or a pane's
+    /// `focus = true`. Call after any reordering of `panes`.
+    pub fn focus_index(&self) -> Option<usize> {
+        match &self.focus {
+            Some(name) => self.panes.iter().position(|p| &p.name == name),
+            None => self.panes.iter().position(|p| p.focus),
+        }
+    }
+
     pub fn resolve_cwd(cwd: &Option<String>) -> Option<PathBuf> {
-        cwd.as_ref().map(|s| {
-            if s.starts_with('~') {
-                if let Some(home) = dirs::home_dir() {
-                    home.join(s.strip_prefix("~/").unwrap_or(s))
-                } else {
-                    PathBuf::from(s)
-                }
+        cwd.as_deref().map(Self::expand_tilde)
+    }
+
+    /// Expand a leading `~` to the user's home directory.
+    pub fn expand_tilde(s: &str) -> PathBuf {
+        if s.starts_with('~') {
+            if let Some(home) = dirs::home_dir() {
+                home.join(s.strip_prefix("~/").unwrap_or(s))
             } else {
                 PathBuf::from(s)
             }
-        })
+        } else {
+            PathBuf::from(s)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shell_config(toml: &str) -> Config {
+        toml::from_str(toml).expect("config parses")
+    }
+
+    #[test]
+    fn shells_parse_from_a_line_or_an_argv_array() {
+        let line = shell_config(r#"default_shell = "zsh  -i --no-rcs""#);
+        assert_eq!(line.default_shell, ShellCommand::Line("zsh  -i --no-rcs".to_string()));
+        assert_eq!(line.shell().program, "zsh");
+        assert_eq!(line.shell().args, ["-i", "--no-rcs"]);
+
+        let argv = shell_config(r#"default_shell = ["nu", "--config", "my config.nu"]"#);
+        assert_eq!(argv.shell().program, "nu");
+        assert_eq!(argv.shell().args, ["--config", "my config.nu"], "array entries are not split");
+        assert_eq!(argv.default_shell.to_string(), "nu --config my config.nu");
+
+        assert!(ShellCommand::Line("  ".to_string()).is_empty());
+        assert!(ShellCommand::Argv(Vec::new()).is_empty());
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn login_goes_after_shell_args_and_interactive_only_wraps_commands() {
+        let config = shell_config(
+            r#"
+            default_shell = ["bash", "--noprofile"]
+            login_shell = true
+            shell_interactive = true
+            "#,
+        );
+        let shell = config.shell();
+        assert_eq!(shell.argv(), ["bash", "--noprofile", "-l"]);
+        assert_eq!(shell.command_argv("make test"), ["bash", "--noprofile", "-i", "-c", "make test"]);
+
+        let plain = ShellSpec { interactive: false, login: false, ..shell };
+        assert_eq!(plain.argv(), ["bash", "--noprofile"]);
+        assert_eq!(plain.command_argv("make  test"), ["make", "test"]);
+
+        let pane = PaneConfig {
+            shell: Some(ShellCommand::Line("zsh".to_string())),
+            login_shell: Some(false),
+            ..PaneConfig::default()
+        };
+        let own = config.shell().for_pane(&pane);
+        assert_eq!(own.argv(), ["zsh"]);
+        assert_eq!(own.command_argv("ls"), ["zsh", "-i", "-c", "ls"]);
+    }
+
+    fn override_config() -> Config {
+        shell_config(
+            r#"
+            [[panes]]
+            name = "api"
+            command = "cargo run"
+            "#,
+        )
+    }
+
+    #[test]
+    fn override_values_are_toml_or_else_plain_text() {
+        let mut config = override_config();
+        config.apply_override("api.weight=20").unwrap();
+        config.apply_override(r#"api.watch=["src/**", "Cargo.toml"]"#).unwrap();
+        config.apply_override("api.command=cargo run --release").unwrap();
+        let api = &config.panes[0];
+        assert_eq!(api.weight, Some(20));
+        assert_eq!(api.watch, ["src/**", "Cargo.toml"]);
+        assert_eq!(api.command.as_deref(), Some("cargo run --release"), "not valid TOML, so taken as text");
+
+        config.apply_override(r#"api.command="echo a=b""#).unwrap();
+        assert_eq!(config.panes[0].command.as_deref(), Some("echo a=b"));
+    }
+
+    #[test]
+    fn extras_can_be_overridden_by_name() {
+        let mut config = override_config();
+        config.add_extra(" pytest -x --lf ", Some("tests"));
+        config.add_extra("", None);
+        config.apply_override("tests.cwd=/tmp").unwrap();
+        let names: Vec<&str> = config.panes.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["api", "tests", "Shell"]);
+        assert_eq!(config.panes[1].command.as_deref(), Some("pytest -x --lf"));
+        assert_eq!(config.panes[1].cwd.as_deref(), Some("/tmp"));
+        assert_eq!(config.panes[2].command, None);
+    }
+
+    #[test]
+    fn overrides_reject_unknown_panes_fields_and_bad_values() {
+        let mut config = override_config();
+        let error = |config: &mut Config, spec| format!("{:#}", config.apply_override(spec).unwrap_err());
+
+        assert!(error(&mut config, "api").starts_with("Invalid override \"api\" (expected PANE.FIELD=VALUE)"));
+        assert!(error(&mut config, "web.weight=2").starts_with("No pane named \"web\""));
+        assert_eq!(error(&mut config, "api.colour=red"), "Unknown field \"colour\" in override \"api.colour=red\"");
+        assert!(error(&mut config, "api.weight=many").starts_with("Can't set \"weight\""));
+        assert!(error(&mut config, "api.weight=0").contains("must be between 1 and weight_max"));
+        assert_eq!(config.panes[0], override_config().panes[0], "failed overrides leave the pane alone");
+    }
+
+    #[test]
+    fn unknown_pane_keys_in_the_file_are_warnings() {
+        let mut config = shell_config(
+            r#"
+            [[panes]]
+            name = "a"
+            comand = "ls"
+            command = "make"
+            [[templates]]
+            name = "t"
+            restart = { on_failure = true }
+            "#,
+        );
+        assert_eq!(config.panes[0].command.as_deref(), Some("make"));
+        config.warn_unknown_keys();
+        assert_eq!(
+            config.warnings,
+            [
+                "Unknown key \"comand\" in pane 'a'; it is ignored",
+                "Unknown key \"restart\" in pane 't'; it is ignored",
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_shells_are_warnings() {
+        let mut config = shell_config(
+            r#"
+            default_shell = ["bamboo-no-such-shell", "-x"]
+            [[panes]]
+            name = "a"
+            shell = "/no/such/dir/zsh -i"
+            [[panes]]
+            name = "b"
+            "#,
+        );
+        config.warn_missing_shells();
+        assert_eq!(
+            config.warnings,
+            [
+                "Shell \"bamboo-no-such-shell\" was not found; panes using it won't start",
+                "Shell \"/no/such/dir/zsh\" was not found; panes using it won't start",
+            ]
+        );
+
+        let mut found = shell_config(&format!("default_shell = {:?}", std::env::current_exe().unwrap()));
+        found.warn_missing_shells();
+        assert!(found.warnings.is_empty());
     }
 }
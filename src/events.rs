@@ -1,32 +1,59 @@
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use alacritty_terminal::event::{Event as TermEvent, WindowSize};
+use alacritty_terminal::term::ClipboardType;
+use base64::Engine;
 use crossterm::event::{
     self, Event as CrosstermEvent, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton,
     MouseEvent, MouseEventKind,
 };
-use ratatui::Terminal;
+use ratatui::{Terminal, TerminalOptions, Viewport};
 use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Position, Rect};
 use tokio::sync::mpsc;
 
-use crate::app::AppState;
-use crate::config::PaneConfig;
+use crate::app::{
+    jump_index, resolve_pane_target, Action, AppState, GroupOp, HoverTarget, MassClose, PaneSpawner, Peek,
+};
+use crate::clipboard;
+use crate::config::{ImageMode, Osc52Mode, PaneConfig, ShellSpec, ViewportIndicator};
 use crate::pane::Pane;
+use crate::prompt::{History, Prompt, PromptKind, PromptOutcome};
 use crate::pty::{self, PtyEvent};
-use crate::ui::{self, FOOTER_HEIGHT};
+use crate::signal::Signal;
+use crate::stats;
+use crate::ui::{self, TitleBar, TitleButton, FOOTER_HEIGHT};
+
+/// Upper bound on queued PTY events handled between two frames.
+const MAX_DRAIN_PER_FRAME: usize = 64;
+
+/// How long after a key or click pane output still draws at once, so the
+/// echo of what was typed skips the `max_fps` wait.
+const ECHO_WINDOW: Duration = Duration::from_millis(50);
+
+/// Largest OSC 52 clipboard write accepted from a pane, in bytes of text.
+const OSC52_MAX_BYTES: usize = 1 << 20;
+
+/// Columns moved per Shift+←/→ or Shift+wheel step in panes that don't wrap.
+const H_SCROLL_STEP: i32 = 8;
 
 pub enum AppEvent {
     Terminal(CrosstermEvent),
     PtyOutput { pane_id: usize, event: PtyEvent },
+    /// A file matching the pane's `watch` patterns changed.
+    WatchTriggered { pane_id: usize, path: PathBuf },
     Tick,
 }
 
 pub async fn run_event_loop(
     terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
     app: &mut AppState,
-    mut unified_rx: mpsc::UnboundedReceiver<AppEvent>,
-    unified_tx: mpsc::UnboundedSender<AppEvent>,
+    unified_rx: mpsc::UnboundedReceiver<AppEvent>,
 ) -> anyhow::Result<()> {
-    let (ct_tx, mut ct_rx) = mpsc::unbounded_channel::<AppEvent>();
+    let (ct_tx, ct_rx) = mpsc::unbounded_channel::<AppEvent>();
 
     tokio::task::spawn_blocking(move || {
         loop {
@@ -48,114 +75,642 @@ pub async fn run_event_loop(
         }
     });
 
+    // Output marks the screen dirty and waits out `frame_interval` from the
+    // last draw; input, and output shortly after it, sets `urgent` so typing
+    // never waits for the cap.
+    let mut queues = EventQueues::new(ct_rx, unified_rx);
+    let mut redraw = true;
+    let mut urgent = true;
+    let mut last_draw = Instant::now();
     loop {
-        terminal.draw(|frame| {
-            ui::render(frame, app);
-        })?;
+        // Keys typed while the last frame drew reach their panes before the
+        // next one starts.
+        let changed = queues.drain_input(app);
+        urgent |= changed && queues.last_input.elapsed() < ECHO_WINDOW;
+        redraw |= changed;
+        if app.should_quit {
+            break;
+        }
+        if let Some(size) = queues.resized.take().filter(|_| !app.alternate_screen) {
+            refit_inline(terminal, size)?;
+        }
+
+        let next_frame = last_draw + app.frame_interval;
+        if redraw && (urgent || Instant::now() >= next_frame) {
+            redraw = false;
+            urgent = false;
+            let draw_start = Instant::now();
+            last_draw = draw_start;
+            terminal.draw(|frame| {
+                ui::render(frame, app);
+            })?;
+            if app.images_stale() {
+                terminal.clear()?;
+                app.forget_images();
+                terminal.draw(|frame| {
+                    ui::render(frame, app);
+                })?;
+            }
+            draw_images(terminal.backend_mut(), app)?;
+            app.stats.record_frame(draw_start.elapsed());
+            // Panes held back by `lazy_start` start once they have been seen.
+            if app.start_pending_panes() {
+                redraw = true;
+                urgent = true;
+            }
+            app.stats.sample(&app.panes);
+            if let Some(title) = app.window_title_update() {
+                crossterm::execute!(terminal.backend_mut(), crossterm::terminal::SetTitle(title))?;
+            }
+            // Quitting disables capture either way, so none is left behind.
+            match app.mouse_capture_update() {
+                Some(true) => crossterm::execute!(terminal.backend_mut(), crossterm::event::EnableMouseCapture)?,
+                Some(false) => crossterm::execute!(terminal.backend_mut(), crossterm::event::DisableMouseCapture)?,
+                None => {}
+            }
+        }
 
         let event = tokio::select! {
-            ev = ct_rx.recv() => match ev {
+            biased;
+            ev = queues.input.recv() => match ev {
                 Some(e) => e,
                 None => break,
             },
-            ev = unified_rx.recv() => match ev {
+            ev = queues.output.recv() => match ev {
                 Some(e) => e,
                 None => break,
             },
+            _ = tokio::time::sleep_until(next_frame.into()), if redraw => continue,
         };
 
-        match event {
-            AppEvent::Terminal(ct_event) => match ct_event {
-                CrosstermEvent::Key(key) => handle_key_event(key, app, &unified_tx),
-                CrosstermEvent::Mouse(mouse) => handle_mouse_event(mouse, app),
-                CrosstermEvent::Resize(cols, rows) => handle_resize(cols, rows, app),
-                _ => {}
-            },
-            AppEvent::PtyOutput { pane_id, event } => match event {
-                PtyEvent::Data(_) => {
-                    if let Some(pane) = app.panes.iter_mut().find(|p| p.id == pane_id) {
-                        if pane.scroll_offset == 0 {
-                            // live view; data already processed by parser in reader task
-                        }
-                    }
-                }
-                PtyEvent::Closed => {
-                    if let Some(pane) = app.panes.iter_mut().find(|p| p.id == pane_id) {
-                        pane.closed = true;
-                    }
-                }
-            },
-            AppEvent::Tick => {}
-        }
+        let changed = queues.handle(event, app) | queues.drain(app);
+        urgent |= changed && queues.last_input.elapsed() < ECHO_WINDOW;
+        redraw |= changed;
+
+        redraw |= app.sort_panes();
 
         if app.should_quit {
             break;
         }
     }
 
+    app.save_layout(true);
     Ok(())
 }
 
-fn handle_key_event(
-    key: KeyEvent,
-    app: &mut AppState,
-    unified_tx: &mpsc::UnboundedSender<AppEvent>,
-) {
+/// A terminal drawing inline over the whole primary screen. Output already
+/// on screen scrolls up into the outer terminal's scrollback to make room.
+///
+/// The viewport is then fixed over that screen: an inline one keeps the
+/// height it started with, and resizing it takes a cursor position report
+/// that the input thread would race for. `refit_inline` resizes the fixed
+/// one instead.
+pub fn inline_terminal() -> std::io::Result<Terminal<CrosstermBackend<std::io::Stdout>>> {
+    let (_, rows) = crossterm::terminal::size()?;
+    let options = TerminalOptions { viewport: Viewport::Inline(rows) };
+    let area = Terminal::with_options(CrosstermBackend::new(std::io::stdout()), options)?.get_frame().area();
+    let options = TerminalOptions { viewport: Viewport::Fixed(area) };
+    Terminal::with_options(CrosstermBackend::new(std::io::stdout()), options)
+}
+
+/// Fit a fixed viewport to the whole screen after the window became
+/// `cols`×`rows`. Called for resize events only, so the size is never
+/// queried between them.
+fn refit_inline<B: ratatui::backend::Backend>(terminal: &mut Terminal<B>, size: (u16, u16)) -> Result<(), B::Error> {
+    let area = Rect::new(0, 0, size.0, size.1);
+    if terminal.get_frame().area() == area {
+        return Ok(());
+    }
+    terminal.resize(area)
+}
+
+/// The event loop's two queues: terminal input (and ticks) from the poll
+/// thread, and output from the panes. Input always goes first, so a key is
+/// written to its pane ahead of any output already queued, however much.
+struct EventQueues {
+    input: mpsc::UnboundedReceiver<AppEvent>,
+    output: mpsc::UnboundedReceiver<AppEvent>,
+    /// When the last key, click or resize was handled.
+    last_input: Instant,
+    /// The window size from a resize the inline viewport hasn't followed.
+    resized: Option<(u16, u16)>,
+}
+
+impl EventQueues {
+    fn new(input: mpsc::UnboundedReceiver<AppEvent>, output: mpsc::UnboundedReceiver<AppEvent>) -> Self {
+        Self { input, output, last_input: Instant::now(), resized: None }
+    }
+
+    /// Handle one event; returns whether the screen needs redrawing.
+    fn handle(&mut self, event: AppEvent, app: &mut AppState) -> bool {
+        if is_user_input(&event) {
+            self.last_input = Instant::now();
+        }
+        if let AppEvent::Terminal(CrosstermEvent::Resize(cols, rows)) = event {
+            self.resized = Some((cols, rows));
+        }
+        handle_app_event(event, app)
+    }
+
+    /// Handle all input that is already queued.
+    fn drain_input(&mut self, app: &mut AppState) -> bool {
+        let mut changed = false;
+        while let Ok(event) = self.input.try_recv() {
+            changed |= self.handle(event, app);
+        }
+        changed
+    }
+
+    /// Parse any further output that is already queued before redrawing, so
+    /// a chatty pane costs one frame per burst rather than one per read.
+    /// Input that arrives meanwhile is handled before each read's output.
+    fn drain(&mut self, app: &mut AppState) -> bool {
+        let mut changed = false;
+        for _ in 0..MAX_DRAIN_PER_FRAME {
+            changed |= self.drain_input(app);
+            match self.output.try_recv() {
+                Ok(event) => changed |= self.handle(event, app),
+                Err(_) => break,
+            }
+        }
+        changed | self.drain_input(app)
+    }
+}
+
+/// Whether `event` is something the user did that output drawn right after
+/// should answer at once: a key, paste, resize, click, drag or wheel step.
+/// Pointer motion and focus changes aren't, or a program reporting every
+/// motion would have its output skip `max_fps` while the mouse moves.
+fn is_user_input(event: &AppEvent) -> bool {
+    match event {
+        AppEvent::Terminal(CrosstermEvent::Key(_) | CrosstermEvent::Paste(_) | CrosstermEvent::Resize(..)) => true,
+        AppEvent::Terminal(CrosstermEvent::Mouse(mouse)) => mouse.kind != MouseEventKind::Moved,
+        _ => false,
+    }
+}
+
+/// Draw inline images over the frame just rendered, at the cells reserved
+/// for them, leaving the cursor where the frame put it.
+fn draw_images(out: &mut impl std::io::Write, app: &mut AppState) -> std::io::Result<()> {
+    let updates = app.image_updates();
+    if updates.is_empty() {
+        return Ok(());
+    }
+    for (placement, sequence) in updates {
+        write!(out, "\x1b7\x1b[{};{}H", placement.y + 1, placement.x + 1)?;
+        out.write_all(sequence)?;
+        out.write_all(b"\x1b8")?;
+    }
+    out.flush()
+}
+
+/// Handle one event; returns whether the screen needs redrawing.
+fn handle_app_event(event: AppEvent, app: &mut AppState) -> bool {
+    match event {
+        AppEvent::Terminal(ct_event) => match ct_event {
+            // The pointer moves constantly; only a hover change is visible.
+            CrosstermEvent::Mouse(_) if !app.mouse => return false,
+            CrosstermEvent::Mouse(mouse) if mouse.kind == MouseEventKind::Moved => {
+                return handle_mouse_move(mouse.column, mouse.row, app);
+            }
+            CrosstermEvent::Key(key) => handle_key_event(key, app),
+            CrosstermEvent::Mouse(mouse) => handle_mouse_event(mouse, app),
+            CrosstermEvent::Resize(cols, rows) => app.apply(Action::Resize { cols, rows }),
+            _ => {}
+        },
+        AppEvent::PtyOutput { pane_id, event } => {
+            app.stats.queue_depth.fetch_sub(1, Ordering::Relaxed);
+            if app.output_pause.is_paused() {
+                app.output_pause.hold(pane_id, event);
+                return false;
+            }
+            return handle_pty_event(pane_id, event, app);
+        }
+        AppEvent::WatchTriggered { pane_id, path } => {
+            tracing::info!(pane = pane_id, path = %path.display(), "watched file changed");
+            if let Some(idx) = app.panes.iter().position(|p| p.id == pane_id) {
+                app.apply(Action::WatchRestart { idx, changed: path });
+            }
+        }
+        AppEvent::Tick => {
+            let settled = app.settle_panes();
+            app.update_stale_panes();
+            app.sample_throughput();
+            app.sample_parse_load();
+            app.update_process_names();
+            app.save_layout(false);
+            return settled | app.title_redraw_due();
+        }
+    }
+    true
+}
+
+/// Freeze or resume all pane output. Output held while paused is applied
+/// before the readers' next event is handled, so each pane sees its bytes in
+/// order.
+fn toggle_output_pause(app: &mut AppState) {
+    if !app.output_pause.is_paused() {
+        app.output_pause.pause();
+        tracing::info!(mode = ?app.output_pause.mode, "output paused");
+        return;
+    }
+    let dropped = app.output_pause.dropped();
+    for (pane_id, event) in app.output_pause.resume() {
+        handle_pty_event(pane_id, event, app);
+    }
+    tracing::info!(dropped, "output resumed");
+    if dropped > 0 {
+        app.set_status(format!("resumed; {} of output dropped", stats::format_bytes(dropped as f64)));
+    }
+}
+
+/// Returns whether the screen needs redrawing now. Output that shows only
+/// in a title or indicator waits for `AppState::title_redraw_due`.
+fn handle_pty_event(pane_id: usize, event: PtyEvent, app: &mut AppState) -> bool {
+    match event {
+        PtyEvent::Data(bytes) => {
+            tracing::trace!(pane = pane_id, bytes = bytes.len(), "pty data");
+            let Some(pane) = app.panes.iter_mut().find(|p| p.id == pane_id) else {
+                return false;
+            };
+            let start = Instant::now();
+            pane.process_output(&bytes);
+            pane.parse_load.record(start.elapsed());
+            for event in pane.take_term_events() {
+                handle_term_event(pane_id, event, app);
+            }
+            let focused = app.focused;
+            app.follow_activity(pane_id, &bytes);
+            app.focused != focused || app.shows_output(pane_id)
+        }
+        PtyEvent::Eof => {
+            if let Some(pane) = app.panes.iter().find(|p| p.id == pane_id) {
+                tracing::info!(pane = pane_id, name = %pane.name, "pane output ended");
+            }
+            app.pane_eof(pane_id);
+            true
+        }
+        PtyEvent::Exited(status) => {
+            if let Some(pane) = app.panes.iter().find(|p| p.id == pane_id) {
+                tracing::info!(pane = pane_id, name = %pane.name, code = status.exit_code(), "process exited");
+            }
+            app.pane_exited(pane_id, status);
+            true
+        }
+    }
+}
+
+/// Act on a request the pane's terminal parsed out of its output.
+fn handle_term_event(pane_id: usize, event: TermEvent, app: &mut AppState) {
+    match event {
+        TermEvent::ClipboardStore(kind, text) => match osc52_copy_target(app.osc52, &text) {
+            Some(Osc52Mode::System) => {
+                tracing::debug!(pane = pane_id, bytes = text.len(), "OSC 52 copy");
+                copy_to_clipboard(app, &text);
+            }
+            Some(_) => {
+                if let Err(e) = forward_osc52(&mut std::io::stdout().lock(), kind, &text) {
+                    tracing::warn!(error = %e, "failed to forward OSC 52 copy");
+                }
+            }
+            None => tracing::debug!(pane = pane_id, bytes = text.len(), "OSC 52 copy dropped"),
+        },
+        TermEvent::ClipboardLoad(_, format) => {
+            if !answers_osc52_queries(app.osc52, app.osc52_read) {
+                tracing::debug!(pane = pane_id, "OSC 52 query ignored");
+                return;
+            }
+            let text = match clipboard::read() {
+                Ok(text) => text,
+                Err(e) => {
+                    tracing::warn!(error = %e, "OSC 52 query failed");
+                    return;
+                }
+            };
+            if let Some(pane) = app.panes.iter().find(|p| p.id == pane_id) {
+                let _ = pane.write_input(format(&text).as_bytes());
+            }
+        }
+        // Replies to device attribute, cursor position and size queries.
+        TermEvent::PtyWrite(reply) => {
+            if let Some(pane) = app.panes.iter().find(|p| p.id == pane_id) {
+                let _ = pane.write_input(reply.as_bytes());
+            }
+        }
+        TermEvent::Title(title) => {
+            if let Some(pane) = app.panes.iter_mut().find(|p| p.id == pane_id) {
+                pane.program_title = Some(title).filter(|t| !t.trim().is_empty());
+            }
+        }
+        TermEvent::ResetTitle => {
+            if let Some(pane) = app.panes.iter_mut().find(|p| p.id == pane_id) {
+                pane.program_title = None;
+            }
+        }
+        TermEvent::TextAreaSizeRequest(format) => {
+            if let Some(pane) = app.panes.iter().find(|p| p.id == pane_id) {
+                let size = text_area_size(pane);
+                let _ = pane.write_input(format(size).as_bytes());
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The pane's size for a pixel size query; zero pixels mean unknown.
+fn text_area_size(pane: &Pane) -> WindowSize {
+    let (cell_width, cell_height) = pty::cell_pixel_size();
+    WindowSize {
+        num_lines: crate::terminal::screen_rows(&pane.term) as u16,
+        num_cols: pane.cols,
+        cell_width,
+        cell_height,
+    }
+}
+
+/// Where an OSC 52 copy of `text` goes under `mode`: `System` or
+/// `Passthrough`, or `None` when copies are denied or the text is over
+/// `OSC52_MAX_BYTES`.
+fn osc52_copy_target(mode: Osc52Mode, text: &str) -> Option<Osc52Mode> {
+    (mode != Osc52Mode::Deny && text.len() <= OSC52_MAX_BYTES).then_some(mode)
+}
+
+/// Whether a pane asking for the clipboard gets it: only with `osc52_read`
+/// on, and while copies go to the system clipboard it would be read from.
+fn answers_osc52_queries(mode: Osc52Mode, osc52_read: bool) -> bool {
+    osc52_read && mode == Osc52Mode::System
+}
+
+/// Re-emit an OSC 52 copy to the outer terminal, which owns the clipboard
+/// when bamboo runs over SSH.
+fn forward_osc52(out: &mut impl std::io::Write, kind: ClipboardType, text: &str) -> std::io::Result<()> {
+    let target = match kind {
+        ClipboardType::Clipboard => 'c',
+        ClipboardType::Selection => 'p',
+    };
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    write!(out, "\x1b]52;{};{}\x07", target, encoded)?;
+    out.flush()
+}
+
+fn handle_key_event(key: KeyEvent, app: &mut AppState) {
     if key.kind != KeyEventKind::Press {
         return;
     }
+    tracing::trace!(code = ?key.code, modifiers = ?key.modifiers, "key");
+    app.last_keypress = Some(Instant::now());
+
+    // The startup warnings overlay takes keys until it is dismissed.
+    if !app.startup_warnings.is_empty() {
+        if matches!(key.code, KeyCode::Enter | KeyCode::Esc) {
+            app.startup_warnings.clear();
+        }
+        return;
+    }
+
+    // Then the welcome screen, until answered.
+    if app.welcome {
+        match key.code {
+            KeyCode::Char('y' | 'Y') => app.answer_welcome(true),
+            KeyCode::Char('n' | 'N') | KeyCode::Esc => app.answer_welcome(false),
+            _ => {}
+        }
+        return;
+    }
+
+    // A pending confirmation takes exactly one key: y (or Enter) accepts,
+    // anything else declines.
+    if app.confirm.is_some() {
+        if matches!(key.code, KeyCode::Char('y' | 'Y') | KeyCode::Enter) {
+            app.accept_confirm();
+        } else {
+            app.confirm = None;
+        }
+        return;
+    }
+
+    if app.prompt.is_some() {
+        handle_prompt_key(&key, app);
+        return;
+    }
+
+    // Resize mode keeps every key until Enter or Esc, the literal prefix
+    // and the pause key included.
+    if app.resize_mode {
+        let action = match key.code {
+            KeyCode::Char('j') | KeyCode::Down => Some(Action::ResizeByRow { grow: true }),
+            KeyCode::Char('k') | KeyCode::Up => Some(Action::ResizeByRow { grow: false }),
+            KeyCode::Char('h') | KeyCode::Left => Some(Action::FocusPrev),
+            KeyCode::Char('l') | KeyCode::Right => Some(Action::FocusNext),
+            KeyCode::Char('=') => Some(Action::EqualizeWeights),
+            KeyCode::Enter | KeyCode::Esc => {
+                app.resize_mode = false;
+                None
+            }
+            _ => None,
+        };
+        if let Some(action) = action {
+            app.apply(action);
+        }
+        return;
+    }
+
+    // The key after the literal prefix skips every binding below.
+    if app.literal_armed {
+        app.literal_armed = false;
+        if key.code != KeyCode::Esc {
+            if let Some(bytes) = key_event_to_bytes(&key) {
+                app.apply(Action::SendInput(bytes));
+            }
+        }
+        return;
+    }
+    if app.literal_key.matches(&key) {
+        app.literal_armed = true;
+        return;
+    }
+
+    // A pane flooding the parser: Alt+Shift+P stops reading it, Alt+Shift+K
+    // focuses it and picks a signal (KILL asks first), Alt+Shift+I leaves it
+    // be. Plain keys keep going to the focused pane, which may be another.
+    if let Some(idx) = app.heavy_output.and_then(|id| app.panes.iter().position(|p| p.id == id)) {
+        if key.modifiers.contains(KeyModifiers::ALT) {
+            match key.code {
+                KeyCode::Char('P') => {
+                    app.heavy_output = None;
+                    app.apply(Action::ToggleReadHold(idx));
+                    return;
+                }
+                KeyCode::Char('K') => {
+                    app.heavy_output = None;
+                    app.apply(Action::Focus(idx));
+                    app.prompt = Some(Prompt::picker(PromptKind::Signal, Signal::choices()));
+                    return;
+                }
+                KeyCode::Char('I') => {
+                    app.panes[idx].parse_load.ignored = true;
+                    app.heavy_output = None;
+                    return;
+                }
+                _ => {}
+            }
+        }
+    }
+    if app.output_pause.key.matches(&key) {
+        toggle_output_pause(app);
+        return;
+    }
+
+    // Jump mode takes exactly one key: a pane's badge, or anything else to
+    // cancel.
+    if app.jump_mode {
+        app.jump_mode = false;
+        if let KeyCode::Char(c) = key.code {
+            if let Some(idx) = jump_index(c).filter(|&idx| idx < app.panes.len()) {
+                app.apply(Action::Focus(idx));
+            }
+        }
+        return;
+    }
 
     let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
     let alt = key.modifiers.contains(KeyModifiers::ALT);
 
-    if ctrl && key.code == KeyCode::Char('q') {
-        app.should_quit = true;
+    // Ctrl+Shift+q would otherwise reach the pane as Ctrl+q.
+    if ctrl && matches!(key.code, KeyCode::Char('q' | 'Q')) {
+        app.apply(Action::Quit);
+        return;
+    }
+
+    if key.code == KeyCode::F(12) && key.modifiers.contains(KeyModifiers::SHIFT) {
+        dump_session(app);
+        return;
+    }
+    if key.code == KeyCode::F(12) {
+        app.apply(Action::ToggleDebug);
         return;
     }
 
     // Alt+S toggles selection mode
     if alt && key.code == KeyCode::Char('s') {
         if app.selection.is_some() {
-            app.clear_selection();
+            app.apply(Action::ClearSelection);
         } else {
-            app.start_selection();
+            app.apply(Action::StartSelection);
         }
         return;
     }
 
     // In selection mode: intercept all keys for navigation/copy/cancel
     if app.selection.is_some() {
-        match key.code {
-            KeyCode::Up => app.move_selection_cursor(-1, 0),
-            KeyCode::Down => app.move_selection_cursor(1, 0),
-            KeyCode::Left => app.move_selection_cursor(0, -1),
-            KeyCode::Right => app.move_selection_cursor(0, 1),
+        let action = match key.code {
+            KeyCode::Up => Action::MoveSelection { rows: -1, cols: 0 },
+            KeyCode::Down => Action::MoveSelection { rows: 1, cols: 0 },
+            KeyCode::Left => Action::MoveSelection { rows: 0, cols: -1 },
+            KeyCode::Right => Action::MoveSelection { rows: 0, cols: 1 },
             KeyCode::Enter => {
                 if let Some(text) = app.selection_text() {
-                    copy_to_clipboard(&text);
+                    copy_to_clipboard(app, &text);
                 }
-                app.clear_selection();
+                Action::ClearSelection
             }
             KeyCode::Char('y') if !alt && !ctrl => {
                 if let Some(text) = app.selection_text() {
-                    copy_to_clipboard(&text);
+                    copy_to_clipboard(app, &text);
                 }
-                app.clear_selection();
+                Action::ClearSelection
+            }
+            KeyCode::Esc => Action::ClearSelection,
+            KeyCode::Char('e') if !alt && !ctrl => Action::JumpToError { forward: true },
+            KeyCode::Char('E') if !alt && !ctrl => Action::JumpToError { forward: false },
+            _ => return,
+        };
+        app.apply(action);
+        return;
+    }
+
+    // Esc closes the peek at hidden panes, then drops the marks, before
+    // anything else sees it.
+    if key.code == KeyCode::Esc && key.modifiers.is_empty() && app.peek.is_some() {
+        app.peek = None;
+        return;
+    }
+    if key.code == KeyCode::Esc && key.modifiers.is_empty() && !app.marked.is_empty() {
+        app.apply(Action::ClearMarks);
+        return;
+    }
+
+    // Keys offered by an exited pane's bar; its PTY takes no input anyway.
+    if app.show_exit_bar && !ctrl && !alt && app.focused_pane().is_some_and(|p| p.closed) {
+        match key.code {
+            KeyCode::Char('r') => {
+                app.apply(Action::RestartPane(app.focused));
+                return;
+            }
+            KeyCode::Char('x') => {
+                app.apply(Action::ClosePane(app.focused));
+                return;
+            }
+            KeyCode::Char('e') => {
+                export_scrollback(app, false);
+                return;
+            }
+            KeyCode::Char('E') => {
+                export_scrollback(app, true);
+                return;
             }
-            KeyCode::Esc => app.clear_selection(),
             _ => {}
         }
+    }
+
+    if key.modifiers == KeyModifiers::SHIFT && matches!(key.code, KeyCode::PageUp | KeyCode::PageDown) {
+        app.apply(Action::ScrollPage { up: key.code == KeyCode::PageUp });
         return;
     }
 
+    // Shift+←/→ scroll panes that don't wrap; elsewhere they reach the program.
+    if key.modifiers == KeyModifiers::SHIFT && app.focused_pane().is_some_and(|p| !p.wraps()) {
+        match key.code {
+            KeyCode::Left => {
+                app.apply(Action::ScrollHorizontal { cols: -H_SCROLL_STEP });
+                return;
+            }
+            KeyCode::Right => {
+                app.apply(Action::ScrollHorizontal { cols: H_SCROLL_STEP });
+                return;
+            }
+            _ => {}
+        }
+    }
+
     if ctrl {
         match key.code {
+            KeyCode::Char('v' | 'V') if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                paste_from_clipboard(app);
+                return;
+            }
             KeyCode::Up => {
-                app.grow_focused_weight(2);
+                app.apply(Action::GrowWeight);
                 return;
             }
             KeyCode::Down => {
-                app.shrink_focused_weight(2);
+                app.apply(Action::ShrinkWeight);
+                return;
+            }
+            KeyCode::PageUp => {
+                app.apply(Action::PageViewportUp);
+                return;
+            }
+            KeyCode::PageDown => {
+                app.apply(Action::PageViewportDown);
+                return;
+            }
+            KeyCode::Home => {
+                app.apply(Action::ViewportHome);
+                return;
+            }
+            KeyCode::End => {
+                app.apply(Action::ViewportEnd);
                 return;
             }
             _ => {}
@@ -163,299 +718,1546 @@ fn handle_key_event(
     }
 
     if alt {
-        match key.code {
-            KeyCode::Char('j') | KeyCode::Char('l') => {
-                app.focus_next();
+        let action = match key.code {
+            KeyCode::Char('j') | KeyCode::Char('l') => Some(Action::FocusNext),
+            KeyCode::Char('k') | KeyCode::Char('h') => Some(Action::FocusPrev),
+            KeyCode::Char('n') => Some(Action::SpawnPane(Box::default())),
+            KeyCode::Char('N') => {
+                if app.templates.is_empty() {
+                    app.set_status("no [[templates]] in the config");
+                } else {
+                    let names = app.templates.iter().map(|t| t.name.clone()).collect();
+                    app.prompt = Some(Prompt::picker(PromptKind::Template, names));
+                }
+                return;
+            }
+            KeyCode::Char('R') => {
+                app.reload_config();
+                return;
+            }
+            KeyCode::Char('K') => {
+                app.prompt = Some(Prompt::picker(PromptKind::Signal, Signal::choices()));
+                return;
+            }
+            KeyCode::Char('f') => {
+                let choices = app.panes.iter().map(|p| format!("#{} {}", p.stable_id, p.name)).collect();
+                app.prompt = Some(Prompt::picker(PromptKind::Focus, choices));
+                return;
+            }
+            KeyCode::Char('P') => Some(Action::ToggleReadHold(app.focused)),
+            KeyCode::Char('G') => {
+                let groups = app.group_names();
+                if groups.is_empty() {
+                    app.set_status("no pane has a group");
+                    return;
+                }
+                let choices = groups
+                    .iter()
+                    .flat_map(|name| GroupOp::ALL.map(|op| format!("{} {}", op.label(), name)))
+                    .collect();
+                app.prompt = Some(Prompt::picker(PromptKind::Group, choices));
+                return;
+            }
+            KeyCode::Char('E') => {
+                let kind = PromptKind::Report;
+                let mut prompt = Prompt::new(kind, History::load(kind));
+                prompt.insert_str(&report_path().display().to_string());
+                app.prompt = Some(prompt);
+                return;
+            }
+            KeyCode::Char('|') => Some(Action::SplitPane),
+            KeyCode::Char('w') if !app.marked.is_empty() => {
+                app.confirm_close(MassClose::Marked);
+                return;
+            }
+            KeyCode::Char('w') => Some(Action::ClosePane(app.focused)),
+            KeyCode::Char('W') => {
+                app.confirm_close(MassClose::Others);
                 return;
             }
-            KeyCode::Char('k') | KeyCode::Char('h') => {
-                app.focus_prev();
+            KeyCode::Char('J') => {
+                app.confirm_close(MassClose::Below);
                 return;
             }
-            KeyCode::Char('n') => {
-                spawn_new_pane(app, unified_tx);
+            KeyCode::Char('T') => Some(Action::ReopenClosed),
+            // Alt+Shift+digit arrives as the shifted symbol on most
+            // terminals and as the digit with SHIFT under the kitty protocol.
+            KeyCode::Char(c @ '1'..='4') if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                Some(Action::SavePreset(c as u8 - b'0'))
+            }
+            KeyCode::Char(c @ '1'..='4') => Some(Action::ApplyPreset(c as u8 - b'0')),
+            KeyCode::Char('!') => Some(Action::SavePreset(1)),
+            KeyCode::Char('@') => Some(Action::SavePreset(2)),
+            KeyCode::Char('#') => Some(Action::SavePreset(3)),
+            KeyCode::Char('$') => Some(Action::SavePreset(4)),
+            KeyCode::Char('c') if !app.marked.is_empty() => Some(Action::CollapseMarked),
+            KeyCode::Char('c') => Some(Action::ToggleCollapse(app.focused)),
+            KeyCode::Char('m') => Some(Action::ToggleMark(app.focused)),
+            KeyCode::Char('b') => {
+                let kind = PromptKind::Broadcast;
+                app.prompt = Some(Prompt::new(kind, History::load(kind)));
+                return;
+            }
+            KeyCode::Char('o') => Some(Action::CycleSortMode),
+            KeyCode::Char('p') => Some(Action::ToggleWatch(app.focused)),
+            KeyCode::Char('x') => Some(Action::ToggleScrollLock),
+            KeyCode::Char('C') => Some(Action::ToggleCrosshair),
+            KeyCode::Char('M') => Some(Action::ToggleMouse),
+            KeyCode::Char('L') => Some(Action::ResetTerminal),
+            KeyCode::Char('z') => Some(Action::Solo(app.focused)),
+            KeyCode::Char('g') => {
+                app.jump_mode = true;
                 return;
             }
-            KeyCode::Char('w') => {
-                app.remove_focused_pane();
+            KeyCode::Char('=') => {
+                app.resize_mode = true;
                 return;
             }
-            KeyCode::Char('c') => {
-                app.toggle_collapse_focused();
+            KeyCode::Char('r') => {
+                let kind = PromptKind::RunCommand;
+                app.prompt = Some(Prompt::new(kind, History::load(kind)));
                 return;
             }
             KeyCode::Char('v') => {
-                if let Some(text) = paste_from_clipboard() {
-                    if let Some(pane) = app.focused_pane() {
-                        pane.write_input(text.as_bytes());
-                    }
-                }
+                paste_from_clipboard(app);
                 return;
             }
-            _ => {}
+            KeyCode::Char('y') => {
+                copy_last_output(app);
+                return;
+            }
+            _ => None,
+        };
+        if let Some(action) = action {
+            app.apply(action);
+            return;
         }
     }
 
     if let Some(bytes) = key_event_to_bytes(&key) {
-        if let Some(pane) = app.focused_pane() {
-            pane.write_input(&bytes);
-        }
+        app.apply(Action::SendInput(bytes));
     }
 }
 
-fn copy_to_clipboard(text: &str) {
-    use std::io::Write;
-    use std::process::{Command, Stdio};
-    if let Ok(mut child) = Command::new("pbcopy").stdin(Stdio::piped()).spawn() {
-        if let Some(stdin) = child.stdin.as_mut() {
-            let _ = stdin.write_all(text.as_bytes());
+/// Feed a key to the open prompt and act on what it returns.
+fn handle_prompt_key(key: &KeyEvent, app: &mut AppState) {
+    let Some(prompt) = &mut app.prompt else {
+        return;
+    };
+    // Alt+v pastes into the prompt rather than the pane behind it.
+    if key.modifiers.contains(KeyModifiers::ALT) && key.code == KeyCode::Char('v') {
+        paste_from_clipboard(app);
+        return;
+    }
+    let kind = prompt.kind;
+    match prompt.handle_key(key) {
+        PromptOutcome::Pending => {}
+        PromptOutcome::Cancel => app.prompt = None,
+        PromptOutcome::Submit(text) => {
+            app.prompt = None;
+            let text = text.trim();
+            if text.is_empty() {
+                return;
+            }
+            match kind {
+                PromptKind::RunCommand => app.apply(Action::SpawnPane(Box::new(PaneConfig {
+                    name: text.to_string(),
+                    command: Some(text.to_string()),
+                    ..Default::default()
+                }))),
+                PromptKind::Template => {
+                    if let Some(template) = app.templates.iter().find(|t| t.name == text) {
+                        app.apply(Action::SpawnPane(Box::new(template.clone())));
+                    }
+                }
+                PromptKind::Broadcast => app.apply(Action::Broadcast(text.to_string())),
+                PromptKind::Signal => {
+                    if let Some((signal, group)) = Signal::parse_choice(text) {
+                        app.confirm_signal(signal, group);
+                    }
+                }
+                PromptKind::Report => export_report(app, text),
+                // A choice is "#ID name"; its id picks the pane.
+                PromptKind::Focus => {
+                    let target = text.split_whitespace().next().unwrap_or(text);
+                    match resolve_pane_target(&app.panes, target) {
+                        Ok(idx) => app.apply(Action::Focus(idx)),
+                        Err(e) => app.set_status(format!("{:#}", e)),
+                    }
+                }
+                // A choice is "<op> <group>".
+                PromptKind::Group => {
+                    let choice = text.trim().split_once(' ');
+                    match choice.and_then(|(op, name)| Some((GroupOp::parse(op)?, name))) {
+                        Some((op, name)) => app.apply(Action::Group { name: name.to_string(), op }),
+                        None => app.set_status(format!("expected e.g. \"collapse <group>\", got {:?}", text)),
+                    }
+                }
+            }
         }
-        let _ = child.wait();
     }
 }
 
-fn paste_from_clipboard() -> Option<String> {
-    use std::process::Command;
-    let output = Command::new("pbpaste").output().ok()?;
-    if output.status.success() {
-        String::from_utf8(output.stdout).ok()
-    } else {
-        None
+/// Copy a selection, reporting failure in the footer.
+fn copy_to_clipboard(app: &mut AppState, text: &str) {
+    if let Err(e) = clipboard::write(text) {
+        tracing::warn!(error = %e, "copy failed");
+        app.set_status(format!("copy failed: {:#}", e));
     }
 }
 
-fn spawn_new_pane(app: &mut AppState, unified_tx: &mpsc::UnboundedSender<AppEvent>) {
-    let pane_id = app.take_next_pane_id();
-    let name = format!("Shell {}", pane_id);
-
-    let cols = app.term_cols.saturating_sub(2).max(10);
-    let n_panes = app.panes.len() + 1;
-    let rows = (app.term_rows / n_panes as u16).saturating_sub(2).max(5);
-
-    let pane_config = PaneConfig {
-        name: name.clone(),
-        command: None,
-        cwd: None,
-        env: std::collections::HashMap::new(),
-    };
-
-    let spawned = match pty::spawn_pty(&pane_config, &app.default_shell, cols, rows) {
-        Ok(s) => s,
-        Err(_) => return,
+/// Write the focused pane's scrollback and screen to a text file in the
+/// temp directory, with its colors as ANSI codes if `ansi`, and say where
+/// in the footer.
+fn export_scrollback(app: &mut AppState, ansi: bool) {
+    let Some(pane) = app.focused_pane() else {
+        return;
     };
+    let name: String = pane
+        .name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let extension = if ansi { "ansi" } else { "txt" };
+    let path = std::env::temp_dir().join(format!("bamboo-{}-{}.{}", name, stamp, extension));
+    let written = std::fs::File::create(&path).and_then(|file| {
+        let mut out = std::io::BufWriter::new(file);
+        crate::terminal::write_capture(&pane.term, usize::MAX, ansi, &mut out)?;
+        std::io::Write::flush(&mut out)
+    });
+    match written {
+        Ok(()) => app.set_status(format!("exported to {}", path.display())),
+        Err(e) => {
+            tracing::warn!(path = %path.display(), error = %e, "export failed");
+            app.set_status(format!("export failed: {}", e));
+        }
+    }
+}
+
+/// Default file for a session report: Markdown in the temp directory.
+fn report_path() -> std::path::PathBuf {
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    std::env::temp_dir().join(format!("bamboo-report-{}.md", stamp))
+}
+
+/// Write a session report as the report prompt asked: `PATH [PATTERN]`,
+/// HTML if the path ends in `.html`, and only panes whose name matches
+/// `PATTERN` if given.
+fn export_report(app: &mut AppState, request: &str) {
+    let (path, pattern) = match request.split_once(char::is_whitespace) {
+        Some((path, pattern)) => (path, Some(pattern.trim())),
+        None => (request, None),
+    };
+    let only = match pattern.map(regex::Regex::new).transpose() {
+        Ok(only) => only,
+        Err(e) => {
+            app.set_status(format!("bad pane pattern: {}", e));
+            return;
+        }
+    };
+    let path = std::path::PathBuf::from(path);
+    let options = crate::report::ReportOptions {
+        format: crate::report::ReportFormat::from_path(&path),
+        lines: app.report_lines,
+        only,
+    };
+    let written = std::fs::File::create(&path).and_then(|file| {
+        let mut out = std::io::BufWriter::new(file);
+        let panes = crate::report::write_report(&app.panes, &options, &mut out)?;
+        std::io::Write::flush(&mut out)?;
+        Ok(panes)
+    });
+    match written {
+        Ok(panes) => {
+            let plural = if panes == 1 { "" } else { "s" };
+            app.set_status(format!("report of {} pane{} written to {}", panes, plural, path.display()))
+        }
+        Err(e) => {
+            tracing::warn!(path = %path.display(), error = %e, "report failed");
+            app.set_status(format!("report failed: {}", e));
+        }
+    }
+}
+
+/// Copy the focused pane's last command output: what the shell marked as
+/// such, or else a guess from the screen. The title says which was used.
+fn copy_last_output(app: &mut AppState) {
+    let Some(pane) = app.focused_pane() else {
+        return;
+    };
+    let (text, guessed) = match pane.commands.last() {
+        Some(text) => (text.to_string(), false),
+        None => (crate::terminal::recent_output_text(&pane.term), true),
+    };
+    if text.is_empty() {
+        app.set_status("no output to copy");
+        return;
+    }
+    if let Err(e) = clipboard::write(&text) {
+        tracing::warn!(error = %e, "copy failed");
+        app.set_status(format!("copy failed: {:#}", e));
+        return;
+    }
+    let lines = text.lines().count();
+    let plural = if lines == 1 { "" } else { "s" };
+    let message = if guessed {
+        format!("copied {} line{} (no shell marks, guessed)", lines, plural)
+    } else {
+        format!("copied {} line{}", lines, plural)
+    };
+    if let Some(pane) = app.focused_pane_mut() {
+        pane.flash(message);
+    }
+}
+
+/// Write `AppState::session_snapshot` as JSON to a file in the temp
+/// directory and say where in the footer.
+fn dump_session(app: &mut AppState) {
+    let path = std::env::temp_dir().join(format!("bamboo-session-{}.json", std::process::id()));
+    let result = serde_json::to_vec_pretty(&app.session_snapshot())
+        .map_err(anyhow::Error::from)
+        .and_then(|json| Ok(std::fs::write(&path, json)?));
+    match result {
+        Ok(()) => app.set_status(format!("session state written to {}", path.display())),
+        Err(e) => {
+            tracing::warn!(path = %path.display(), error = %e, "session dump failed");
+            app.set_status(format!("session dump failed: {:#}", e));
+        }
+    }
+}
+
+/// Paste the system clipboard into the focused pane.
+fn paste_from_clipboard(app: &mut AppState) {
+    match clipboard::read() {
+        Ok(text) => app.apply(Action::Paste(text)),
+        Err(e) => {
+            tracing::warn!(error = %e, "paste failed");
+            app.set_status(format!("paste failed: {:#}", e));
+        }
+    }
+}
 
-    let term = crate::terminal::new_term(rows, cols, 1000);
+/// Spawns panes backed by real PTYs whose output feeds the unified event
+/// channel.
+pub struct PtySpawner {
+    /// Global shell settings, refined per pane.
+    pub shell: ShellSpec,
+    /// Applied to every pane spawned.
+    pub images: ImageMode,
+    pub collapse_cr_updates: bool,
+    /// Lines of history per pane.
+    pub scrollback: usize,
+    pub unified_tx: mpsc::UnboundedSender<AppEvent>,
+    pub queue_depth: Arc<AtomicUsize>,
+    /// Stops every pane's reader while set; see `OutputPause`.
+    pub hold_reads: Arc<AtomicBool>,
+}
+
+impl PaneSpawner for PtySpawner {
+    fn spawn(&mut self, pane_id: usize, config: &PaneConfig, cols: u16, rows: u16) -> anyhow::Result<Pane> {
+        let mut pane = spawn_pane(pane_id, config, cols, rows, self)?;
+        pane.images.mode = self.images;
+        pane.commands.collapse_updates = self.collapse_cr_updates;
+        Ok(pane)
+    }
+}
+
+/// Spawn a pane's process and wire its PTY output into `spawner`'s event
+/// channel. Its `queue_depth` counts events sent but not yet handled, and
+/// the reader stops while `hold_reads` is set.
+pub fn spawn_pane(
+    pane_id: usize,
+    pane_config: &PaneConfig,
+    cols: u16,
+    rows: u16,
+    spawner: &PtySpawner,
+) -> anyhow::Result<Pane> {
+    let unified_tx = &spawner.unified_tx;
+    let queue_depth = spawner.queue_depth.clone();
+    let shell = spawner.shell.for_pane(pane_config);
+    let spawned = pty::spawn_pty(pane_config, &shell, cols, rows)?;
 
-    let (pty_tx, pty_rx) = mpsc::unbounded_channel::<PtyEvent>();
-    pty::launch_reader_task(spawned.reader, term.clone(), pty_tx);
+    let counters = Arc::new(pty::ReadCounters::default());
+
+    let killer = spawned.child.clone_killer();
+    let pid = spawned.child.process_id();
+    let (pty_tx, mut pty_rx) = mpsc::unbounded_channel::<PtyEvent>();
+    pty::launch_wait_thread(pane_id, spawned.child, pty_tx.clone());
+    pty::launch_reader_task(pane_id, spawned.reader, counters.clone(), spawner.hold_reads.clone(), pty_tx);
+
+    // Ends once both the reader and the wait thread are done.
+    let tx = unified_tx.clone();
+    let forwarder = tokio::spawn(async move {
+        while let Some(event) = pty_rx.recv().await {
+            queue_depth.fetch_add(1, Ordering::Relaxed);
+            if tx.send(AppEvent::PtyOutput { pane_id, event }).is_err() {
+                break;
+            }
+        }
+    });
 
-    let pane = Pane::new(
+    let mut pane = Pane::new(
         pane_id,
-        name,
+        pane_config.name.clone(),
         spawned.master,
-        spawned.writer,
-        term,
-        pty_rx,
+        pty::launch_writer_thread(pane_id, spawned.writer),
+        spawner.scrollback,
+        counters,
         cols,
         rows,
     );
-
-    app.add_pane(pane);
-
-    if let Some(mut pty_rx) = app.panes.last_mut().and_then(|p| p.pty_rx.take()) {
-        let tx = unified_tx.clone();
-        tokio::spawn(async move {
-            while let Some(event) = pty_rx.recv().await {
-                let is_closed = matches!(event, PtyEvent::Closed);
-                if tx.send(AppEvent::PtyOutput { pane_id, event }).is_err() {
-                    break;
-                }
-                if is_closed {
-                    break;
-                }
-            }
-        });
+    pane.default_fg = pane_config.fg.as_deref().and_then(crate::terminal::parse_color);
+    pane.default_bg = pane_config.bg.as_deref().and_then(crate::terminal::parse_color);
+    pane.colors = crate::terminal::PaneColors::parse(&pane_config.palette, pane_config.invert_luminance)
+        .unwrap_or_default();
+    pane.command = pane_config.command.clone().unwrap_or_else(|| shell.argv().join(" "));
+    pane.cwd = crate::config::Config::resolve_cwd(&pane_config.cwd)
+        .or_else(|| std::env::current_dir().ok())
+        .map(|p| p.display().to_string())
+        .unwrap_or_default();
+    pane.title_format = pane_config.title_format.clone();
+    pane.auto_name = pane_config.auto_name.unwrap_or(false);
+    pane.config = pane_config.clone();
+    pane.set_wrap(pane_config.wrap.unwrap_or(true));
+    pane.killer = Some(killer);
+    pane.forwarder = Some(forwarder);
+    pane.pid = pid;
+    if !pane_config.watch.is_empty() {
+        match crate::watch::start(pane_id, &pane_config.watch, Path::new(&pane.cwd), unified_tx.clone()) {
+            Ok(watcher) => pane.watcher = Some(watcher),
+            Err(e) => tracing::warn!(pane = pane_id, error = %e, "file watching disabled"),
+        }
     }
+    Ok(pane)
 }
 
 fn handle_mouse_event(mouse: MouseEvent, app: &mut AppState) {
     let col = mouse.column;
     let row = mouse.row;
 
+    if !app.startup_warnings.is_empty() || app.welcome {
+        return;
+    }
+    // Clicking elsewhere answers "no".
+    if app.confirm.is_some() && matches!(mouse.kind, MouseEventKind::Down(_)) {
+        app.confirm = None;
+        return;
+    }
+
+    // The stack changed since the last frame: the areas no longer say what
+    // is under the pointer, so a press could land on the wrong pane until
+    // the next frame brings them up to date. Releases, drags and the wheel
+    // still go through, so a selection in progress ends and is copied.
+    let stale = !app.layout_is_current();
+    if stale && matches!(mouse.kind, MouseEventKind::Down(_)) {
+        tracing::debug!(col, row, "press on a stale layout ignored");
+        return;
+    }
+
+    // The peek lies over the stack and takes clicks and the wheel first.
+    let on_peek = app.peek_area.as_ref().is_some_and(|(area, _)| area.contains(Position::new(col, row)));
     match mouse.kind {
+        MouseEventKind::Down(MouseButton::Left) if on_peek => {
+            if let Some(idx) = peek_pane_at(app, col, row) {
+                app.peek = None;
+                app.apply(Action::Focus(idx));
+            }
+        }
+        MouseEventKind::ScrollUp | MouseEventKind::ScrollDown if on_peek => {
+            if let Some(peek) = &mut app.peek {
+                peek.scroll = if mouse.kind == MouseEventKind::ScrollUp {
+                    peek.scroll.saturating_sub(1)
+                } else {
+                    peek.scroll + 1
+                };
+            }
+        }
         MouseEventKind::Down(MouseButton::Left) => {
-            app.last_mouse_pos = Some((row, col));
-            // Click on "above" scroll indicator → page up
-            if app.viewport_start > 0 && row == 0 {
-                app.page_viewport_up();
+            // Nothing is laid out while the window is too small.
+            if app.last_pane_areas.is_empty() {
                 return;
             }
+            app.last_mouse_pos = Some((row, col));
 
-            // Click on "below" scroll indicator → page down
-            let has_below = app
-                .last_pane_areas
-                .last()
-                .map(|(idx, _)| *idx + 1 < app.panes.len())
-                .unwrap_or(false);
-            if has_below && row == app.term_rows.saturating_sub(1 + FOOTER_HEIGHT) {
-                app.page_viewport_down();
+            // The stack map moves the viewport to the pane clicked.
+            if let Some(area) = app.gutter_area.filter(|a| a.contains(Position::new(col, row))) {
+                if let Some(idx) = ui::gutter_pane_at(area, app.panes.len(), row) {
+                    app.apply(Action::ScrollViewportTo(idx));
+                }
                 return;
             }
 
-            let areas = app.last_pane_areas.clone();
-            for &(pane_idx, area) in &areas {
-                // Click on title bar (top border row)
-                if row == area.y && col >= area.x && col < area.x + area.width {
-                    // Close button [x] in rightmost 3 chars before border
-                    if area.width >= 8 {
-                        let close_start = area.x + area.width.saturating_sub(4);
-                        let close_end = area.x + area.width.saturating_sub(2);
-                        if col >= close_start && col <= close_end {
-                            app.close_pane(pane_idx);
-                            return;
-                        }
-                    }
+            let hit = app.last_pane_areas.iter().copied().find(|(_, areas)| areas.contains(col, row));
+            let button = hit
+                .filter(|(_, areas)| areas.hit_title(col, row))
+                .and_then(|(idx, areas)| TitleBar::new(areas.outer, app.panes[idx].closed).button_at(col));
+            let on_content = hit.is_some_and(|(_, areas)| areas.hit_inner(col, row).is_some());
 
-                    // Collapse toggle [▾]/[▸] at positions x+1..x+3
-                    if col >= area.x + 1 && col <= area.x + 3 {
-                        app.focus(pane_idx);
-                        app.toggle_collapse_at(pane_idx);
-                        return;
+            // Scroll indicators take clicks on their own row and, as slack
+            // for near misses, the row beside it unless that lands on pane
+            // content or a button. Ctrl+click focuses the nearest hidden pane
+            // instead of paging.
+            let near = |indicator: u16| {
+                row == indicator || (row.abs_diff(indicator) == 1 && button.is_none() && !on_content)
+            };
+            let ctrl = mouse.modifiers.contains(KeyModifiers::CONTROL);
+            let lines = app.viewport_indicator == ViewportIndicator::Lines;
+            if lines && app.viewport_start > 0 && near(0) {
+                if ctrl {
+                    app.apply(Action::Focus(app.viewport_start - 1));
+                } else {
+                    app.apply(Action::PageViewportUp);
+                }
+                return;
+            }
+            let first_below = app.last_pane_areas.last().map(|(idx, _)| *idx + 1);
+            if let Some(first_below) = first_below.filter(|&i| lines && i < app.panes.len()) {
+                if near(app.term_rows.saturating_sub(1 + FOOTER_HEIGHT)) {
+                    if ctrl {
+                        app.apply(Action::Focus(first_below));
+                    } else {
+                        app.apply(Action::PageViewportDown);
                     }
-
-                    app.focus(pane_idx);
-                    app.clear_selection();
                     return;
                 }
+            }
 
-                // Click in pane body (inside borders)
-                if col > area.x && col < area.x + area.width.saturating_sub(1)
-                    && row > area.y && row < area.y + area.height.saturating_sub(1)
-                {
-                    app.focus(pane_idx);
-                    app.clear_selection();
+            if let Some((pane_idx, areas)) = hit {
+                // Shift+click marks the pane for a group action.
+                if mouse.modifiers.contains(KeyModifiers::SHIFT) {
+                    app.apply(Action::ToggleMark(pane_idx));
                     return;
                 }
-
-                // Click on border (not title bar)
-                if col >= area.x && col < area.x + area.width
-                    && row >= area.y && row < area.y + area.height
-                {
-                    app.focus(pane_idx);
-                    app.clear_selection();
-                    return;
+                // Click on title bar (top row)
+                if areas.hit_title(col, row) {
+                    match button {
+                        Some(TitleButton::Close) => {
+                            app.apply(Action::ClosePane(pane_idx));
+                            return;
+                        }
+                        Some(TitleButton::Toggle) => {
+                            app.apply(Action::Focus(pane_idx));
+                            app.apply(Action::ToggleCollapse(pane_idx));
+                            return;
+                        }
+                        Some(TitleButton::Solo) => {
+                            app.apply(Action::Solo(pane_idx));
+                            return;
+                        }
+                        Some(TitleButton::Restart) => {
+                            app.apply(Action::Focus(pane_idx));
+                            app.apply(Action::RestartPane(pane_idx));
+                            return;
+                        }
+                        None => {}
+                    }
                 }
+
+                // Title bar, body, or border: focus the pane
+                app.apply(Action::Focus(pane_idx));
+                app.apply(Action::ClearSelection);
+                return;
             }
-            app.clear_selection();
+            app.apply(Action::ClearSelection);
         }
         MouseEventKind::Drag(MouseButton::Left) => {
-            if app.selection.is_none() {
+            if app.selection.is_none() && !stale {
                 if let Some((start_row, start_col)) = app.last_mouse_pos {
                     // Start selection if we've moved at least one cell
                     if (start_row as i32 - row as i32).abs() > 0 || (start_col as i32 - col as i32).abs() > 0 {
                         // Find which pane we're in
-                        let areas = app.last_pane_areas.clone();
-                        for &(pane_idx, area) in &areas {
-                            if start_col > area.x && start_col < area.x + area.width.saturating_sub(1)
-                                && start_row > area.y && start_row < area.y + area.height.saturating_sub(1)
-                            {
-                                let r = start_row - (area.y + 1);
-                                let c = start_col - (area.x + 1);
-                                app.start_selection_at(pane_idx, r, c);
-                                break;
-                            }
+                        let hit = app.last_pane_areas.iter().find_map(|&(idx, areas)| {
+                            areas.hit_inner(start_col, start_row).map(|cell| (idx, areas.inner, cell))
+                        });
+                        if let Some((pane_idx, inner, (c, r))) = hit {
+                            let offset = crate::terminal::visible_row_offset(&app.panes[pane_idx].term, inner.height);
+                            let r = r + offset as u16;
+                            let c = c + app.panes[pane_idx].h_scroll;
+                            app.apply(Action::StartSelectionAt { pane: pane_idx, row: r, col: c });
                         }
                     }
                 }
             }
 
             if let Some(sel) = &app.selection {
-                if let Some(&(_idx, area)) = app.last_pane_areas.iter().find(|(idx, _)| app.panes[*idx].id == sel.pane_id) {
-                    let inner_w = area.width.saturating_sub(2);
-                    let inner_h = area.height.saturating_sub(2);
-                    if inner_w > 0 && inner_h > 0 {
-                        let r = row.saturating_sub(area.y + 1).min(inner_h - 1);
-                        let c = col.saturating_sub(area.x + 1).min(inner_w - 1);
-                        app.update_selection_at(r, c);
+                if let Some((idx, areas)) = app.drawn_pane(sel.pane_id) {
+                    if let Some((c, r)) = areas.clamp_inner(col, row) {
+                        let offset = crate::terminal::visible_row_offset(&app.panes[idx].term, areas.inner.height);
+                        let r = r + offset as u16;
+                        let c = c + app.panes[idx].h_scroll;
+                        app.apply(Action::UpdateSelection { row: r, col: c });
                     }
                 }
             }
         }
         MouseEventKind::Up(MouseButton::Left) => {
             app.last_mouse_pos = None;
-            if let Some(sel) = &app.selection {
-                if sel.anchor != sel.cursor {
-                    if let Some(text) = app.selection_text() {
-                        if !text.is_empty() {
-                            copy_to_clipboard(&text);
-                        }
-                    }
-                }
+            let dragged = app.selection.as_ref().is_some_and(|sel| sel.anchor != sel.cursor);
+            if let Some(text) = app.selection_text().filter(|t| dragged && !t.is_empty()) {
+                copy_to_clipboard(app, &text);
             }
         }
-        MouseEventKind::ScrollUp => {
-            if let Some(pane) = app.focused_pane_mut() {
-                pane.scroll_up(3);
+        // Ctrl+wheel resizes the pane under the pointer.
+        MouseEventKind::ScrollUp | MouseEventKind::ScrollDown
+            if mouse.modifiers.contains(KeyModifiers::CONTROL) =>
+        {
+            if let Some(&(idx, _)) = app.last_pane_areas.iter().find(|(_, areas)| areas.contains(col, row)) {
+                if mouse.kind == MouseEventKind::ScrollUp {
+                    app.apply(Action::GrowWeightAt(idx));
+                } else {
+                    app.apply(Action::ShrinkWeightAt(idx));
+                }
             }
         }
-        MouseEventKind::ScrollDown => {
-            if let Some(pane) = app.focused_pane_mut() {
-                pane.scroll_down(3);
-            }
+        // Shift+wheel scrolls a pane that doesn't wrap sideways, and pages
+        // any other.
+        MouseEventKind::ScrollUp | MouseEventKind::ScrollDown
+            if mouse.modifiers.contains(KeyModifiers::SHIFT) && app.focused_pane().is_some_and(|p| !p.wraps()) =>
+        {
+            let cols = if mouse.kind == MouseEventKind::ScrollUp { -H_SCROLL_STEP } else { H_SCROLL_STEP };
+            app.apply(Action::ScrollHorizontal { cols });
+        }
+        MouseEventKind::ScrollLeft => app.apply(Action::ScrollHorizontal { cols: -H_SCROLL_STEP }),
+        MouseEventKind::ScrollRight => app.apply(Action::ScrollHorizontal { cols: H_SCROLL_STEP }),
+        MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+            let page = mouse.modifiers.contains(KeyModifiers::SHIFT);
+            let lines = app.wheel_lines(mouse.kind == MouseEventKind::ScrollUp, page);
+            app.apply(Action::Scroll { lines });
         }
         _ => {}
     }
 }
 
-fn handle_resize(cols: u16, rows: u16, app: &mut AppState) {
-    app.term_cols = cols;
-    app.term_rows = rows;
+/// Track which title-bar button the pointer is over, and open or close the
+/// hidden-pane peek. Returns whether either changed.
+fn handle_mouse_move(col: u16, row: u16, app: &mut AppState) -> bool {
+    let pointer_moved = app.crosshair && app.pointer.replace((col, row)) != Some((col, row));
+    let pointer_moved = update_peek(col, row, app) || pointer_moved;
+    let target = if app.chrome.has_title() && app.layout_is_current() {
+        app.last_pane_areas
+            .iter()
+            .find(|(_, areas)| areas.hit_title(col, row))
+            .and_then(|&(idx, areas)| {
+                let button = TitleBar::new(areas.outer, app.panes[idx].closed).button_at(col)?;
+                Some(HoverTarget { pane_id: app.panes[idx].id, button })
+            })
+    } else {
+        None
+    };
+    if target == app.hover {
+        return pointer_moved;
+    }
+    app.hover = target;
+    true
+}
+
+/// Open the peek while the pointer rests on a "more above/below" line, and
+/// keep it while the pointer is over the list; anywhere else closes it.
+/// Returns whether it opened or closed.
+fn update_peek(col: u16, row: u16, app: &mut AppState) -> bool {
+    let on_list = app.peek_area.as_ref().is_some_and(|(area, _)| area.contains(Position::new(col, row)));
+    let lines = app.viewport_indicator == ViewportIndicator::Lines;
+    let below = app.last_pane_areas.last().is_some_and(|(idx, _)| idx + 1 < app.panes.len());
+    let peek = if on_list {
+        app.peek
+    } else if !lines || !app.layout_is_current() || app.last_pane_areas.is_empty() {
+        None
+    } else if app.viewport_start > 0 && row == 0 {
+        Some(app.peek.filter(|p| !p.below).unwrap_or(Peek { below: false, scroll: 0 }))
+    } else if below && row == app.term_rows.saturating_sub(1 + FOOTER_HEIGHT) {
+        Some(app.peek.filter(|p| p.below).unwrap_or(Peek { below: true, scroll: 0 }))
+    } else {
+        None
+    };
+    if peek == app.peek {
+        return false;
+    }
+    app.peek = peek;
+    true
+}
+
+/// The hidden pane listed on `row` of the peek, if the pointer is on one.
+fn peek_pane_at(app: &AppState, col: u16, row: u16) -> Option<usize> {
+    let (area, shown) = app.peek_area.as_ref().filter(|(area, _)| area.contains(Position::new(col, row)))?;
+    let line = row.checked_sub(area.y + 1)?;
+    shown.get(line as usize).copied()
 }
 
+/// Encode a key as the VT input sequence a program in the pane expects.
+///
+/// ConPTY translates the same sequences into console input records, so one
+/// encoding serves both Unix PTYs and Windows.
 fn key_event_to_bytes(key: &KeyEvent) -> Option<Vec<u8>> {
     let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
+    let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+
+    // xterm's modifier parameter for cursor, editing and function keys.
+    let modifier = 1 + shift as u8 + 2 * alt as u8 + 4 * ctrl as u8;
+    let csi = |suffix: char| {
+        if modifier == 1 {
+            format!("\x1b[{}", suffix).into_bytes()
+        } else {
+            format!("\x1b[1;{}{}", modifier, suffix).into_bytes()
+        }
+    };
+    let ss3 = |suffix: char| {
+        if modifier == 1 {
+            format!("\x1bO{}", suffix).into_bytes()
+        } else {
+            format!("\x1b[1;{}{}", modifier, suffix).into_bytes()
+        }
+    };
+    let tilde = |number: u8| {
+        if modifier == 1 {
+            format!("\x1b[{}~", number).into_bytes()
+        } else {
+            format!("\x1b[{};{}~", number, modifier).into_bytes()
+        }
+    };
+    // Alt sends ESC ahead of the plain encoding.
+    let meta = |bytes: Vec<u8>| {
+        if alt {
+            [vec![0x1b], bytes].concat()
+        } else {
+            bytes
+        }
+    };
 
     match key.code {
         KeyCode::Char(c) => {
-            if ctrl {
-                let byte = (c as u8).wrapping_sub(b'a').wrapping_add(1);
-                if (1..=26).contains(&byte) {
-                    Some(vec![byte])
-                } else {
-                    None
+            let bytes = if ctrl {
+                // Some platforms report Ctrl+Shift+letter as an uppercase char.
+                // The digit row follows xterm: Ctrl+2..8 stand in for the
+                // punctuation keys some layouts lack.
+                match c.to_ascii_lowercase() {
+                    c @ 'a'..='z' => vec![c as u8 - b'a' + 1],
+                    ' ' | '@' | '`' | '2' => vec![0x00],
+                    '[' | '3' => vec![0x1b],
+                    '\\' | '4' => vec![0x1c],
+                    ']' | '5' => vec![0x1d],
+                    '^' | '~' | '6' => vec![0x1e],
+                    '_' | '-' | '/' | '7' => vec![0x1f],
+                    '?' | '8' => vec![0x7f],
+                    _ => return None,
                 }
             } else {
                 let mut buf = [0u8; 4];
-                let s = c.encode_utf8(&mut buf);
-                Some(s.as_bytes().to_vec())
-            }
-        }
-        KeyCode::Enter => Some(vec![b'\r']),
-        KeyCode::Backspace => Some(vec![0x7f]),
-        KeyCode::Tab => Some(vec![b'\t']),
-        KeyCode::Esc => Some(vec![0x1b]),
-        KeyCode::Up => Some(b"\x1b[A".to_vec()),
-        KeyCode::Down => Some(b"\x1b[B".to_vec()),
-        KeyCode::Right => Some(b"\x1b[C".to_vec()),
-        KeyCode::Left => Some(b"\x1b[D".to_vec()),
-        KeyCode::Home => Some(b"\x1b[H".to_vec()),
-        KeyCode::End => Some(b"\x1b[F".to_vec()),
-        KeyCode::PageUp => Some(b"\x1b[5~".to_vec()),
-        KeyCode::PageDown => Some(b"\x1b[6~".to_vec()),
-        KeyCode::Insert => Some(b"\x1b[2~".to_vec()),
-        KeyCode::Delete => Some(b"\x1b[3~".to_vec()),
-        KeyCode::F(1) => Some(b"\x1bOP".to_vec()),
-        KeyCode::F(2) => Some(b"\x1bOQ".to_vec()),
-        KeyCode::F(3) => Some(b"\x1bOR".to_vec()),
-        KeyCode::F(4) => Some(b"\x1bOS".to_vec()),
-        KeyCode::F(5) => Some(b"\x1b[15~".to_vec()),
-        KeyCode::F(6) => Some(b"\x1b[17~".to_vec()),
-        KeyCode::F(7) => Some(b"\x1b[18~".to_vec()),
-        KeyCode::F(8) => Some(b"\x1b[19~".to_vec()),
-        KeyCode::F(9) => Some(b"\x1b[20~".to_vec()),
-        KeyCode::F(10) => Some(b"\x1b[21~".to_vec()),
-        KeyCode::F(11) => Some(b"\x1b[23~".to_vec()),
-        KeyCode::F(12) => Some(b"\x1b[24~".to_vec()),
+                c.encode_utf8(&mut buf).as_bytes().to_vec()
+            };
+            Some(meta(bytes))
+        }
+        KeyCode::Enter => Some(meta(vec![b'\r'])),
+        KeyCode::Backspace => Some(meta(vec![0x7f])),
+        KeyCode::Tab => Some(meta(vec![b'\t'])),
+        KeyCode::BackTab => Some(b"\x1b[Z".to_vec()),
+        KeyCode::Esc => Some(meta(vec![0x1b])),
+        KeyCode::Up => Some(csi('A')),
+        KeyCode::Down => Some(csi('B')),
+        KeyCode::Right => Some(csi('C')),
+        KeyCode::Left => Some(csi('D')),
+        KeyCode::Home => Some(csi('H')),
+        KeyCode::End => Some(csi('F')),
+        KeyCode::PageUp => Some(tilde(5)),
+        KeyCode::PageDown => Some(tilde(6)),
+        KeyCode::Insert => Some(tilde(2)),
+        KeyCode::Delete => Some(tilde(3)),
+        KeyCode::F(1) => Some(ss3('P')),
+        KeyCode::F(2) => Some(ss3('Q')),
+        KeyCode::F(3) => Some(ss3('R')),
+        KeyCode::F(4) => Some(ss3('S')),
+        KeyCode::F(n @ 5..=12) => Some(tilde([15, 17, 18, 19, 20, 21, 23, 24][n as usize - 5])),
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Chrome;
+
+    fn bytes(code: KeyCode, modifiers: KeyModifiers) -> Option<Vec<u8>> {
+        key_event_to_bytes(&KeyEvent::new(code, modifiers))
+    }
+
+    /// Collects what a pane's writer thread writes to its PTY.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Recorder {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A pane on a real PTY whose input is recorded instead of written.
+    fn recorded_pane(id: usize, name: &str) -> (Pane, Recorder) {
+        let pair = portable_pty::native_pty_system()
+            .openpty(portable_pty::PtySize { rows: 5, cols: 20, pixel_width: 0, pixel_height: 0 })
+            .expect("openpty");
+        let written = Recorder::default();
+        let input = pty::launch_writer_thread(id, Box::new(written.clone()));
+        (Pane::new(id, name.to_string(), pair.master, input, 100, Arc::default(), 20, 5), written)
+    }
+
+    /// What the writer thread has written once it reaches `len` bytes, or
+    /// after two seconds.
+    fn written_after(written: &Recorder, len: usize) -> Vec<u8> {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while written.0.lock().unwrap().len() < len && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        written.0.lock().unwrap().clone()
+    }
+
+    struct NoSpawner;
+
+    impl PaneSpawner for NoSpawner {
+        fn spawn(&mut self, _: usize, _: &PaneConfig, _: u16, _: u16) -> anyhow::Result<Pane> {
+            anyhow::bail!("no spawning in tests")
+        }
+    }
+
+    /// An app over recorded panes with these names.
+    fn app_of(names: &[&str]) -> AppState {
+        let panes = names.iter().enumerate().map(|(i, name)| recorded_pane(i, name).0).collect();
+        AppState::new(panes, crate::config::LayoutConfig::Scroll, None, Box::new(NoSpawner))
+    }
+
+    /// Draw a `cols`×`rows` frame, as the event loop does between events.
+    fn draw(app: &mut AppState, cols: u16, rows: u16) -> ratatui::buffer::Buffer {
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(cols, rows)).unwrap();
+        app.apply(Action::Resize { cols, rows });
+        terminal.draw(|frame| ui::render(frame, app)).unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    /// Send a mouse event the way the terminal delivers it; returns whether
+    /// the screen needs redrawing.
+    fn mouse_with(app: &mut AppState, kind: MouseEventKind, at: (u16, u16), modifiers: KeyModifiers) -> bool {
+        let (column, row) = at;
+        handle_app_event(AppEvent::Terminal(CrosstermEvent::Mouse(MouseEvent { kind, column, row, modifiers })), app)
+    }
+
+    fn mouse(app: &mut AppState, kind: MouseEventKind, at: (u16, u16)) -> bool {
+        mouse_with(app, kind, at, KeyModifiers::NONE)
+    }
+
+    /// The text of `row` in a drawn frame.
+    fn row_text(buffer: &ratatui::buffer::Buffer, row: u16) -> String {
+        (0..buffer.area.width).map(|x| buffer[(x, row)].symbol()).collect()
+    }
+
+    /// An app over `n` panes named `p0`, `p1`, ...
+    fn app_of_many(n: usize) -> AppState {
+        let names: Vec<String> = (0..n).map(|i| format!("p{}", i)).collect();
+        app_of(&names.iter().map(String::as_str).collect::<Vec<_>>())
+    }
+
+    /// Where `button` sits in the title of the pane drawn at `idx`.
+    fn title_button(app: &AppState, idx: usize, button: TitleButton) -> (u16, u16) {
+        let (_, areas) = app.last_pane_areas.iter().find(|(i, _)| *i == idx).expect("pane drawn");
+        let bar = TitleBar::new(areas.outer, app.panes[idx].closed);
+        let col = (areas.outer.left()..areas.outer.right()).find(|&c| bar.button_at(c) == Some(button));
+        (col.expect("button shown"), areas.outer.y)
+    }
+
+    fn names(app: &AppState) -> Vec<&str> {
+        app.panes.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    #[test]
+    fn a_click_on_the_old_layout_waits_for_the_next_frame() {
+        let mut app = app_of(&["a", "b", "c"]);
+        draw(&mut app, 40, 24);
+        let close = title_button(&app, 0, TitleButton::Close);
+        let left = MouseEventKind::Down(MouseButton::Left);
+
+        mouse(&mut app, left, close);
+        assert_eq!(names(&app), ["b", "c"]);
+        // A double click: the second press would hit whatever moved up.
+        mouse(&mut app, left, close);
+        assert_eq!(names(&app), ["b", "c"]);
+
+        // Once drawn, a click right away closes the pane now shown there.
+        draw(&mut app, 40, 24);
+        assert_eq!(title_button(&app, 0, TitleButton::Close), close);
+        mouse(&mut app, left, close);
+        assert_eq!(names(&app), ["c"]);
+    }
+
+    #[test]
+    fn drags_and_the_wheel_go_through_a_stale_layout() {
+        let mut app = app_of(&["a", "b", "c"]);
+        app.panes[1].process_output(b"0123456789\r\nabcdefghij\r\n");
+        draw(&mut app, 40, 24);
+        let inner = app.last_pane_areas[1].1.inner;
+        mouse(&mut app, MouseEventKind::Down(MouseButton::Left), (inner.x, inner.y));
+        mouse(&mut app, MouseEventKind::Drag(MouseButton::Left), (inner.x + 3, inner.y));
+        assert_eq!(app.selection_text().as_deref(), Some("0123"));
+
+        app.apply(Action::ClosePane(0));
+        assert!(!app.layout_is_current());
+        // Still measured against where "b" is drawn, now at index 0.
+        mouse(&mut app, MouseEventKind::Drag(MouseButton::Left), (inner.x + 2, inner.y + 1));
+        assert_eq!(app.selection_text().as_deref(), Some("0123456789\nabc"));
+
+        let before = app.panes[0].scroll_offset;
+        for _ in 0..3 {
+            app.panes[0].process_output(b"more\r\nlines\r\n");
+        }
+        mouse(&mut app, MouseEventKind::ScrollUp, (inner.x, inner.y));
+        assert!(app.panes[0].scroll_offset > before);
+    }
+
+    #[test]
+    fn keys_typed_during_a_flood_reach_the_pane_first_and_in_order() {
+        let (shell, written) = recorded_pane(0, "shell");
+        let (flood, _) = recorded_pane(1, "flood");
+        let mut app =
+            AppState::new(vec![shell, flood], crate::config::LayoutConfig::Scroll, None, Box::new(NoSpawner));
+
+        let (input_tx, input_rx) = mpsc::unbounded_channel();
+        let (output_tx, output_rx) = mpsc::unbounded_channel();
+        let mut queues = EventQueues::new(input_rx, output_rx);
+
+        // `yes` in another pane: far more reads queued than one frame drains.
+        let flood = MAX_DRAIN_PER_FRAME * 20;
+        app.stats.queue_depth.fetch_add(flood, Ordering::Relaxed);
+        for _ in 0..flood {
+            let event = PtyEvent::Data(b"y\n".repeat(512));
+            output_tx.send(AppEvent::PtyOutput { pane_id: 1, event }).unwrap();
+        }
+        let typed = "echo hello";
+        for code in typed.chars().map(KeyCode::Char).chain([KeyCode::Enter]) {
+            let key = KeyEvent::new(code, KeyModifiers::NONE);
+            input_tx.send(AppEvent::Terminal(CrosstermEvent::Key(key))).unwrap();
+        }
+
+        assert!(queues.drain(&mut app));
+        assert!(app.panes[1].unseen_output > 0, "the flood pane parsed its share of the frame");
+        assert!(!queues.output.is_empty(), "one drain should leave most of the flood queued");
+        assert_eq!(written_after(&written, typed.len() + 1), b"echo hello\r");
+        assert_eq!(app.focused, 0, "the flood didn't take focus from the typing");
+    }
+
+    #[test]
+    fn a_key_takes_effect_before_output_queued_ahead_of_it() {
+        let mut app = app_of(&["shell"]);
+        let (input_tx, input_rx) = mpsc::unbounded_channel();
+        let (output_tx, output_rx) = mpsc::unbounded_channel();
+        let mut queues = EventQueues::new(input_rx, output_rx);
+
+        let reads = MAX_DRAIN_PER_FRAME * 3;
+        app.stats.queue_depth.fetch_add(reads, Ordering::Relaxed);
+        for _ in 0..reads {
+            output_tx.send(AppEvent::PtyOutput { pane_id: 0, event: PtyEvent::Data(b"line\r\n".to_vec()) }).unwrap();
+        }
+        // Alt+x locks the view on what the pane showed before any of it.
+        let key = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::ALT);
+        input_tx.send(AppEvent::Terminal(CrosstermEvent::Key(key))).unwrap();
+
+        queues.drain(&mut app);
+        assert!(!queues.output.is_empty(), "the output takes more than one frame");
+        while !queues.output.is_empty() {
+            queues.drain(&mut app);
+        }
+        assert_eq!(app.panes[0].scroll_lock.as_ref().map(|l| l.pending_lines), Some(reads));
+    }
+
+    #[test]
+    fn heavy_output_warning_leaves_plain_keys_to_the_focused_pane() {
+        let (shell, written) = recorded_pane(0, "shell");
+        let (flood, _) = recorded_pane(1, "flood");
+        let mut app =
+            AppState::new(vec![shell, flood], crate::config::LayoutConfig::Scroll, None, Box::new(NoSpawner));
+        app.heavy_output = Some(1);
+
+        let typed = "pkill -K yes";
+        for c in typed.chars() {
+            let modifiers = if c.is_ascii_uppercase() { KeyModifiers::SHIFT } else { KeyModifiers::NONE };
+            handle_key_event(KeyEvent::new(KeyCode::Char(c), modifiers), &mut app);
+        }
+        handle_key_event(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE), &mut app);
+        assert_eq!(written_after(&written, typed.len() + 1), b"pkill -K yes\x1b");
+        assert_eq!(app.heavy_output, Some(1), "the warning stays up");
+        assert!(!app.panes[1].reads_held() && app.confirm.is_none() && app.prompt.is_none());
+        assert_eq!(app.focused, 0);
+
+        handle_key_event(KeyEvent::new(KeyCode::Char('P'), KeyModifiers::ALT | KeyModifiers::SHIFT), &mut app);
+        assert!(app.panes[1].reads_held());
+        assert!(!app.panes[0].reads_held());
+        assert_eq!(app.heavy_output, None);
+    }
+
+    #[test]
+    fn only_keys_clicks_wheel_paste_and_resize_make_output_urgent() {
+        let mouse = |kind| {
+            AppEvent::Terminal(CrosstermEvent::Mouse(MouseEvent {
+                kind,
+                column: 3,
+                row: 4,
+                modifiers: KeyModifiers::NONE,
+            }))
+        };
+        let key = AppEvent::Terminal(CrosstermEvent::Key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE)));
+        assert!(is_user_input(&key));
+        assert!(is_user_input(&AppEvent::Terminal(CrosstermEvent::Paste("x".into()))));
+        assert!(is_user_input(&AppEvent::Terminal(CrosstermEvent::Resize(80, 24))));
+        assert!(is_user_input(&mouse(MouseEventKind::Down(MouseButton::Left))));
+        assert!(is_user_input(&mouse(MouseEventKind::Up(MouseButton::Left))));
+        assert!(is_user_input(&mouse(MouseEventKind::Drag(MouseButton::Left))));
+        assert!(is_user_input(&mouse(MouseEventKind::ScrollUp)));
+
+        assert!(!is_user_input(&mouse(MouseEventKind::Moved)));
+        assert!(!is_user_input(&AppEvent::Terminal(CrosstermEvent::FocusGained)));
+        assert!(!is_user_input(&AppEvent::Terminal(CrosstermEvent::FocusLost)));
+        assert!(!is_user_input(&AppEvent::Tick));
+        let output = AppEvent::PtyOutput { pane_id: 0, event: PtyEvent::Data(b"x".to_vec()) };
+        assert!(!is_user_input(&output));
+    }
+
+    #[test]
+    fn weight_preview_shows_the_shares_and_edge_the_layout_then_uses() {
+        let mut app = app_of_many(3);
+        // Each expanded pane's share of the stack, as drawn.
+        let shares = |app: &AppState| -> Vec<u32> {
+            let heights: Vec<u32> = app.last_pane_areas.iter().map(|(_, a)| a.outer.height as u32).collect();
+            let total: u32 = heights.iter().sum();
+            heights.iter().map(|h| h * 100 / total).collect()
+        };
+        let bottom = |app: &AppState| app.last_pane_areas[0].1.outer.bottom() - 1;
+        draw(&mut app, 60, 31);
+        let before = shares(&app)[0];
+
+        app.apply(Action::GrowWeight);
+        let buffer = draw(&mut app, 60, 31);
+        let label = format!(" {}% → {}% ", before, shares(&app)[0]);
+        assert!(row_text(&buffer, bottom(&app)).contains(&label), "{:?}", row_text(&buffer, bottom(&app)));
+        let edge = (0..31).find(|&row| row_text(&buffer, row).starts_with("┄┄")).expect("next edge drawn");
+
+        app.apply(Action::GrowWeight);
+        draw(&mut app, 60, 31);
+        assert_eq!(bottom(&app), edge);
+    }
+
+    #[test]
+    fn drawn_panes_size_their_pty_to_the_inside_of_their_border() {
+        let mut app = app_of_many(2);
+        for chrome in [Chrome::Full, Chrome::Compact, Chrome::None] {
+            app.chrome = chrome;
+            draw(&mut app, 40, 16);
+            for &(idx, areas) in &app.last_pane_areas {
+                let pane = &app.panes[idx];
+                assert_eq!((pane.cols, pane.rows), (areas.inner.width, areas.inner.height), "{:?}", chrome);
+            }
+        }
+
+        // A pane with no area to fit keeps the PTY size it had.
+        let size = (app.panes[0].cols, app.panes[0].rows);
+        app.panes[0].resize(0, 10);
+        app.panes[0].resize(10, 0);
+        assert_eq!((app.panes[0].cols, app.panes[0].rows), size);
+    }
+
+    #[test]
+    fn inline_images_are_drawn_once_and_cleared_when_the_pane_scrolls() {
+        let mut app = app_of(&["img"]);
+        app.panes[0].images.mode = crate::config::ImageMode::Passthrough;
+        app.panes[0].process_output(&b"x\r\n".repeat(20));
+        app.panes[0].process_output(b"\x1b]1337;File=inline=1;width=4;height=2:AAAA\x07");
+        draw(&mut app, 40, 16);
+        // Later frames at the same size, which leave the screen's images be.
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(40, 16)).unwrap();
+        let shown = app.image_placements.clone();
+        assert_eq!(shown.len(), 1);
+        assert_eq!(app.image_updates().len(), 1);
+        assert!(app.image_updates().is_empty(), "already on the outer terminal");
+
+        app.panes[0].scroll_up(1);
+        terminal.draw(|frame| ui::render(frame, &mut app)).unwrap();
+        assert!(app.image_placements.is_empty());
+        assert!(app.images_stale(), "the old image needs a full repaint");
+        app.forget_images();
+        assert!(!app.images_stale());
+
+        app.panes[0].scroll_down(1);
+        terminal.draw(|frame| ui::render(frame, &mut app)).unwrap();
+        assert_eq!(app.image_placements, shown);
+        assert_eq!(app.image_updates().len(), 1);
+    }
+
+    #[test]
+    fn output_that_only_moves_titles_redraws_with_the_ticks_at_the_title_rate() {
+        let mut app = app_of_many(12);
+        draw(&mut app, 40, 16);
+        let output = |app: &mut AppState, pane_id: usize| {
+            handle_app_event(AppEvent::PtyOutput { pane_id, event: PtyEvent::Data(b"line\r\n".to_vec()) }, app)
+        };
+        assert!(output(&mut app, 0), "a drawn pane shows its output at once");
+        assert!(!output(&mut app, 11), "a hidden pane's output only moves the indicator");
+        app.panes[1].toggle_scroll_lock();
+        assert!(!output(&mut app, 1), "a locked pane's view holds");
+
+        app.title_interval = Duration::ZERO;
+        assert!(handle_app_event(AppEvent::Tick, &mut app));
+        app.title_interval = Duration::from_secs(3600);
+        let redraws = (0..50)
+            .map(|_| output(&mut app, 11) | output(&mut app, 1) | handle_app_event(AppEvent::Tick, &mut app))
+            .filter(|&redraw| redraw)
+            .count();
+        assert_eq!(redraws, 0, "they wait for the next title refresh");
+        assert_eq!(app.panes[11].unseen_output, 6 * 51);
+    }
+
+    #[test]
+    fn quick_jump_labels_every_pane_and_takes_one_key() {
+        let mut app = app_of_many(12);
+        let press = |app: &mut AppState, code, modifiers| handle_key_event(KeyEvent::new(code, modifiers), app);
+        draw(&mut app, 40, 16);
+        let shown = app.last_pane_areas.len();
+        let bottom = app.term_rows - 1 - FOOTER_HEIGHT;
+        let hidden_key = crate::app::jump_key(shown).unwrap();
+
+        press(&mut app, KeyCode::Char('g'), KeyModifiers::ALT);
+        assert!(app.jump_mode);
+        let buffer = draw(&mut app, 40, 16);
+        let (_, first) = app.last_pane_areas[0];
+        let labelled = (first.outer.y..first.outer.bottom()).any(|row| row_text(&buffer, row).contains("  1  "));
+        assert!(labelled, "the first pane carries its key");
+        // Hidden panes are listed with theirs on the indicator.
+        let hidden = format!(" {}  p{}", hidden_key, shown);
+        assert!(row_text(&buffer, bottom).contains(&hidden), "{:?}", row_text(&buffer, bottom));
+
+        press(&mut app, KeyCode::Char(hidden_key), KeyModifiers::NONE);
+        assert!(!app.jump_mode);
+        assert_eq!(app.focused, shown);
+
+        // Esc, or a key no pane has, cancels without moving focus.
+        press(&mut app, KeyCode::Char('g'), KeyModifiers::ALT);
+        press(&mut app, KeyCode::Esc, KeyModifiers::NONE);
+        assert!(!app.jump_mode);
+        press(&mut app, KeyCode::Char('g'), KeyModifiers::ALT);
+        press(&mut app, KeyCode::Char('z'), KeyModifiers::NONE);
+        assert!(!app.jump_mode);
+        assert_eq!(app.focused, shown);
+
+        press(&mut app, KeyCode::Char('g'), KeyModifiers::ALT);
+        press(&mut app, KeyCode::Char('1'), KeyModifiers::NONE);
+        assert_eq!(app.focused, 0);
+    }
+
+    #[test]
+    fn hovering_a_title_button_highlights_it_until_the_pointer_leaves() {
+        use ratatui::style::Color;
+
+        let mut app = app_of(&["a", "b"]);
+        let moved = |app: &mut AppState, at: (u16, u16)| mouse(app, MouseEventKind::Moved, at);
+        draw(&mut app, 60, 20);
+        let close = title_button(&app, 0, TitleButton::Close);
+        let toggle = title_button(&app, 0, TitleButton::Toggle);
+        let id = app.panes[0].id;
+
+        assert!(moved(&mut app, close));
+        assert_eq!(app.hover, Some(HoverTarget { pane_id: id, button: TitleButton::Close }));
+        assert_eq!(draw(&mut app, 60, 20)[close].bg, Color::DarkGray);
+        assert!(!moved(&mut app, close), "still on the same button");
+
+        assert!(moved(&mut app, toggle));
+        assert_eq!(app.hover, Some(HoverTarget { pane_id: id, button: TitleButton::Toggle }));
+        let buffer = draw(&mut app, 60, 20);
+        assert_ne!(buffer[close].bg, Color::DarkGray);
+        assert_eq!(buffer[toggle].bg, Color::DarkGray);
+
+        // Off the buttons, onto the title text and then the content.
+        assert!(moved(&mut app, (10, toggle.1)));
+        assert_eq!(app.hover, None);
+        assert!(!moved(&mut app, (10, toggle.1 + 2)));
+
+        // Once the panes move, the last frame's buttons can't be hovered.
+        app.panes.swap(0, 1);
+        assert!(!moved(&mut app, close));
+        assert_eq!(app.hover, None);
+    }
+
+    #[test]
+    fn resize_mode_keeps_every_key_and_marks_the_edge_it_moves() {
+        use ratatui::style::Color;
+
+        let mut app = app_of_many(3);
+        let press = |app: &mut AppState, code, modifiers| handle_key_event(KeyEvent::new(code, modifiers), app);
+        press(&mut app, KeyCode::Char('='), KeyModifiers::ALT);
+        assert!(app.resize_mode);
+        let (literal, pause) = (app.literal_key, app.output_pause.key);
+        press(&mut app, literal.code, literal.modifiers);
+        press(&mut app, pause.code, pause.modifiers);
+        assert!(app.resize_mode);
+        assert!(!app.literal_armed);
+        assert!(!app.output_pause.is_paused());
+
+        // Boxed panes: the focused one's bottom border.
+        let buffer = draw(&mut app, 40, 20);
+        let (_, first) = app.last_pane_areas[0];
+        let (_, second) = app.last_pane_areas[1];
+        let yellow = |buffer: &ratatui::buffer::Buffer, row: u16| {
+            (first.outer.x..first.outer.right()).all(|x| buffer[(x, row)].fg == Color::Yellow)
+        };
+        assert!(yellow(&buffer, first.outer.bottom() - 1));
+        assert!(!yellow(&buffer, second.outer.bottom() - 1));
+
+        // Without a box: the top row of the pane below.
+        app.chrome = Chrome::None;
+        let buffer = draw(&mut app, 40, 20);
+        let (_, second) = app.last_pane_areas[1];
+        assert!(yellow(&buffer, second.outer.y));
+
+        press(&mut app, KeyCode::Esc, KeyModifiers::NONE);
+        assert!(!app.resize_mode);
+        assert!(!yellow(&draw(&mut app, 40, 20), second.outer.y));
+    }
+
+    #[test]
+    fn the_peek_opens_over_an_indicator_row_and_closes_off_it() {
+        let mut app = app_of_many(12);
+        draw(&mut app, 40, 16);
+        let shown = app.last_pane_areas.len();
+        assert!(shown < 12);
+        let bottom = app.term_rows - 1 - FOOTER_HEIGHT;
+
+        assert!(mouse(&mut app, MouseEventKind::Moved, (10, bottom)));
+        assert_eq!(app.peek, Some(Peek { below: true, scroll: 0 }));
+        let buffer = draw(&mut app, 40, 16);
+        let (area, listed) = app.peek_area.clone().expect("peek drawn");
+        assert_eq!(listed, (shown..12).collect::<Vec<_>>());
+        assert!(row_text(&buffer, area.y).contains(&format!("▼ {} more below", 12 - shown)));
+        assert!(row_text(&buffer, area.y + 1).contains(&format!("p{}", shown)));
+
+        // Moving within the list keeps it open, leaving it closes it.
+        assert!(!mouse(&mut app, MouseEventKind::Moved, (area.x + 3, area.y + 1)));
+        assert!(app.peek.is_some());
+        assert!(mouse(&mut app, MouseEventKind::Moved, (10, area.y - 1)));
+        assert_eq!(app.peek, None);
+        // Nothing is hidden above.
+        mouse(&mut app, MouseEventKind::Moved, (10, 0));
+        assert_eq!(app.peek, None);
+
+        mouse(&mut app, MouseEventKind::Moved, (10, bottom));
+        handle_key_event(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE), &mut app);
+        assert_eq!(app.peek, None);
+    }
+
+    #[test]
+    fn indicators_take_near_misses_and_ctrl_clicks_focus_the_nearest_hidden_pane() {
+        let mut app = app_of_many(12);
+        draw(&mut app, 40, 16);
+        let bottom = app.term_rows - 1 - FOOTER_HEIGHT;
+        let click = |app: &mut AppState, row: u16, modifiers: KeyModifiers| {
+            mouse_with(app, MouseEventKind::Down(MouseButton::Left), (10, row), modifiers);
+            draw(app, 40, 16);
+        };
+
+        // The last shown pane's bottom border pages down, the first one's
+        // title back up.
+        click(&mut app, bottom - 1, KeyModifiers::NONE);
+        let start = app.viewport_start;
+        assert!(start > 0);
+        click(&mut app, 1, KeyModifiers::NONE);
+        assert!(app.viewport_start < start);
+
+        let first_below = app.last_pane_areas.last().unwrap().0 + 1;
+        click(&mut app, bottom, KeyModifiers::CONTROL);
+        assert_eq!(app.focused, first_below);
+        app.apply(Action::PageViewportDown);
+        draw(&mut app, 40, 16);
+        let nearest_above = app.viewport_start - 1;
+        click(&mut app, 0, KeyModifiers::CONTROL);
+        assert_eq!(app.focused, nearest_above);
+
+        // Beside an indicator, pane content and buttons keep their clicks.
+        app.chrome = Chrome::Compact;
+        app.apply(Action::ScrollViewportTo(0));
+        draw(&mut app, 40, 16);
+        let start = app.viewport_start;
+        click(&mut app, bottom - 1, KeyModifiers::NONE);
+        assert_eq!(app.viewport_start, start);
+        app.apply(Action::PageViewportDown);
+        draw(&mut app, 40, 16);
+        let (idx, _) = app.last_pane_areas[0];
+        let (col, row) = title_button(&app, idx, TitleButton::Toggle);
+        assert_eq!(row, 1);
+        mouse(&mut app, MouseEventKind::Down(MouseButton::Left), (col, row));
+        assert!(app.panes[idx].collapsed);
+    }
+
+    #[test]
+    fn indicators_turn_yellow_for_unseen_output_and_red_for_a_failure() {
+        use ratatui::style::Color;
+
+        let mut app = app_of_many(12);
+        let bottom = 16 - 1 - FOOTER_HEIGHT;
+        let color = |app: &mut AppState| draw(app, 40, 16)[(1, bottom)].fg;
+        assert_eq!(color(&mut app), Color::Cyan);
+        app.panes[11].activity = true;
+        assert_eq!(color(&mut app), Color::Yellow);
+        app.panes[10].exit_status = Some(portable_pty::ExitStatus::with_exit_code(1));
+        assert_eq!(color(&mut app), Color::LightRed);
+    }
+
+    #[test]
+    fn clicking_a_peeked_pane_focuses_it() {
+        let mut app = app_of_many(12);
+        draw(&mut app, 40, 16);
+        let bottom = app.term_rows - 1 - FOOTER_HEIGHT;
+        mouse(&mut app, MouseEventKind::Moved, (10, bottom));
+        draw(&mut app, 40, 16);
+        let (area, listed) = app.peek_area.clone().unwrap();
+
+        assert_eq!(peek_pane_at(&app, area.x + 3, area.y + 2), Some(listed[1]));
+        assert_eq!(peek_pane_at(&app, area.x + 3, area.y), None, "the border");
+        assert_eq!(peek_pane_at(&app, area.x + 3, area.y + 1 + listed.len() as u16), None);
+        mouse(&mut app, MouseEventKind::Down(MouseButton::Left), (area.x + 3, area.y + 2));
+        assert_eq!(app.focused, listed[1]);
+        assert_eq!(app.peek, None);
+    }
+
+    #[test]
+    fn the_peek_scrolls_with_the_wheel_up_to_its_last_page() {
+        let mut app = app_of_many(40);
+        draw(&mut app, 40, 16);
+        let hidden = 40 - app.last_pane_areas.len();
+        let bottom = app.term_rows - 1 - FOOTER_HEIGHT;
+        mouse(&mut app, MouseEventKind::Moved, (10, bottom));
+        draw(&mut app, 40, 16);
+        let (area, listed) = app.peek_area.clone().unwrap();
+        assert!(listed.len() < hidden);
+        let inside = (area.x + 3, area.y + 1);
+
+        mouse(&mut app, MouseEventKind::ScrollUp, inside);
+        assert_eq!(app.peek.unwrap().scroll, 0);
+        for _ in 0..100 {
+            mouse(&mut app, MouseEventKind::ScrollDown, inside);
+        }
+        let buffer = draw(&mut app, 40, 16);
+        let last_page = hidden - listed.len();
+        assert_eq!(app.peek.unwrap().scroll, last_page, "clamped when drawn");
+        let (area, listed) = app.peek_area.clone().unwrap();
+        assert_eq!(listed.last(), Some(&39));
+        assert!(row_text(&buffer, area.bottom() - 1).contains(&format!("of {}", hidden)));
+        // One step back up shows the row before the last page.
+        mouse(&mut app, MouseEventKind::ScrollUp, inside);
+        draw(&mut app, 40, 16);
+        assert_eq!(app.peek.unwrap().scroll, last_page - 1);
+    }
+
+    #[test]
+    fn the_inline_viewport_follows_resize_events_only() {
+        let (_input_tx, input_rx) = mpsc::unbounded_channel();
+        let (_output_tx, output_rx) = mpsc::unbounded_channel();
+        let mut queues = EventQueues::new(input_rx, output_rx);
+        let mut app = app_of(&["a"]);
+        queues.handle(AppEvent::Tick, &mut app);
+        queues.handle(AppEvent::Terminal(CrosstermEvent::FocusGained), &mut app);
+        assert_eq!(queues.resized, None);
+        queues.handle(AppEvent::Terminal(CrosstermEvent::Resize(50, 12)), &mut app);
+        queues.handle(AppEvent::Terminal(CrosstermEvent::Resize(60, 20)), &mut app);
+        assert_eq!(queues.resized.take(), Some((60, 20)), "the last size wins");
+
+        let backend = ratatui::backend::TestBackend::new(40, 10);
+        let options = TerminalOptions { viewport: Viewport::Fixed(Rect::new(0, 0, 40, 10)) };
+        let mut terminal = Terminal::with_options(backend, options).unwrap();
+        terminal.draw(|frame| frame.render_widget("kept", frame.area())).unwrap();
+        refit_inline(&mut terminal, (40, 10)).unwrap();
+        assert_eq!(row_text(terminal.backend().buffer(), 0).trim_end(), "kept", "same size: not cleared");
+
+        // The window grows, then shrinks.
+        terminal.backend_mut().resize(60, 20);
+        refit_inline(&mut terminal, (60, 20)).unwrap();
+        assert_eq!(terminal.get_frame().area(), Rect::new(0, 0, 60, 20));
+        terminal.backend_mut().resize(30, 5);
+        refit_inline(&mut terminal, (30, 5)).unwrap();
+        let frame = terminal.draw(|frame| frame.render_widget("x", frame.area())).unwrap();
+        assert_eq!(frame.area, Rect::new(0, 0, 30, 5));
+    }
+
+    #[test]
+    fn osc52_copies_follow_the_mode_up_to_the_size_cap() {
+        let largest = "x".repeat(OSC52_MAX_BYTES);
+        for mode in [Osc52Mode::System, Osc52Mode::Passthrough] {
+            assert_eq!(osc52_copy_target(mode, "hello"), Some(mode));
+            assert_eq!(osc52_copy_target(mode, &largest), Some(mode));
+            assert_eq!(osc52_copy_target(mode, &format!("{}x", largest)), None, "{:?}", mode);
+        }
+        assert_eq!(osc52_copy_target(Osc52Mode::Deny, "hello"), None);
+
+        let mut out = Vec::new();
+        forward_osc52(&mut out, ClipboardType::Clipboard, "hi").unwrap();
+        forward_osc52(&mut out, ClipboardType::Selection, "").unwrap();
+        assert_eq!(out, b"\x1b]52;c;aGk=\x07\x1b]52;p;\x07");
+    }
+
+    #[test]
+    fn clipboard_queries_get_no_reply_unless_reads_are_allowed() {
+        assert!(answers_osc52_queries(Osc52Mode::System, true));
+        assert!(!answers_osc52_queries(Osc52Mode::System, false));
+        assert!(!answers_osc52_queries(Osc52Mode::Passthrough, true));
+        assert!(!answers_osc52_queries(Osc52Mode::Deny, true));
+
+        let (pane, written) = recorded_pane(0, "shell");
+        let mut app = AppState::new(vec![pane], crate::config::LayoutConfig::Scroll, None, Box::new(NoSpawner));
+        let query = || TermEvent::ClipboardLoad(ClipboardType::Clipboard, Arc::new(|text: &str| text.to_string()));
+        for (read, mode) in [(false, Osc52Mode::System), (true, Osc52Mode::Passthrough), (true, Osc52Mode::Deny)] {
+            app.osc52_read = read;
+            app.osc52 = mode;
+            handle_term_event(0, query(), &mut app);
+        }
+        // A reply written after the queries would land behind this marker.
+        app.panes[0].write_input(b"end").unwrap();
+        assert_eq!(written_after(&written, 3), b"end");
+    }
+
+    #[test]
+    fn attribute_and_size_queries_are_answered_through_the_pty() {
+        let (pane, written) = recorded_pane(0, "shell");
+        let mut app = AppState::new(vec![pane], crate::config::LayoutConfig::Scroll, None, Box::new(NoSpawner));
+        let mut expected = Vec::new();
+        let mut ask = |app: &mut AppState, query: &[u8], reply: &str| {
+            handle_pty_event(0, PtyEvent::Data(query.to_vec()), app);
+            expected.extend_from_slice(reply.as_bytes());
+            assert_eq!(written_after(&written, expected.len()), expected, "reply to {:?}", query);
+        };
+
+        ask(&mut app, b"\x1b[c", "\x1b[?6c");
+        ask(&mut app, b"\x1b[18t", "\x1b[8;5;20t");
+        let (cell_width, cell_height) = pty::cell_pixel_size();
+        ask(&mut app, b"\x1b[14t", &format!("\x1b[4;{};{}t", 5 * cell_height, 20 * cell_width));
+        app.panes[0].resize(30, 8);
+        ask(&mut app, b"\x1b[18t", "\x1b[8;8;30t");
+
+        // The secondary attributes carry alacritty's version.
+        handle_pty_event(0, PtyEvent::Data(b"\x1b[>c".to_vec()), &mut app);
+        let reply = written_after(&written, expected.len() + "\x1b[>0;1;1c".len());
+        let reply = String::from_utf8_lossy(&reply[expected.len()..]).into_owned();
+        assert!(reply.starts_with("\x1b[>0;") && reply.ends_with(";1c"), "{:?}", reply);
+    }
+
+    /// Panes without a command run the shell the spawner resolves for
+    /// each: its own `shell` and `login_shell` over the global ones.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn spawned_panes_run_their_own_shell_over_the_default() {
+        let (unified_tx, mut unified_rx) = mpsc::unbounded_channel();
+        let mut spawner = PtySpawner {
+            shell: ShellSpec {
+                program: "/bin/echo".to_string(),
+                args: vec!["global".to_string()],
+                login: true,
+                interactive: false,
+            },
+            images: ImageMode::Off,
+            collapse_cr_updates: false,
+            scrollback: 100,
+            unified_tx,
+            queue_depth: Arc::default(),
+            hold_reads: Arc::default(),
+        };
+        let own = PaneConfig {
+            name: "own".to_string(),
+            shell: Some(crate::config::ShellCommand::Line("/bin/echo own".to_string())),
+            login_shell: Some(false),
+            ..PaneConfig::default()
+        };
+        let default = PaneConfig { name: "default".to_string(), ..PaneConfig::default() };
+        let _panes = [spawner.spawn(0, &own, 80, 24).unwrap(), spawner.spawn(1, &default, 80, 24).unwrap()];
+
+        let mut output = [String::new(), String::new()];
+        let mut ended = 0;
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+        while ended < 2 {
+            match tokio::time::timeout_at(deadline, unified_rx.recv()).await {
+                Ok(Some(AppEvent::PtyOutput { pane_id, event: PtyEvent::Data(bytes) })) => {
+                    output[pane_id].push_str(&String::from_utf8_lossy(&bytes))
+                }
+                Ok(Some(AppEvent::PtyOutput { event: PtyEvent::Eof, .. })) => ended += 1,
+                Ok(Some(_)) => {}
+                Ok(None) | Err(_) => panic!("no end of output; got {:?}", output),
+            }
+        }
+        assert_eq!(output.map(|o| o.trim_end().to_string()), ["own", "global -l"]);
+    }
+
+    #[test]
+    fn ctrl_letters_map_to_control_bytes_in_either_case() {
+        for (i, c) in ('a'..='z').enumerate() {
+            let byte = Some(vec![i as u8 + 1]);
+            assert_eq!(bytes(KeyCode::Char(c), KeyModifiers::CONTROL), byte);
+            let upper = c.to_ascii_uppercase();
+            assert_eq!(bytes(KeyCode::Char(upper), KeyModifiers::CONTROL | KeyModifiers::SHIFT), byte);
+        }
+        assert_eq!(bytes(KeyCode::Char('c'), KeyModifiers::CONTROL), Some(vec![0x03]));
+        assert_eq!(bytes(KeyCode::Char('Z'), KeyModifiers::CONTROL | KeyModifiers::SHIFT), Some(vec![0x1a]));
+    }
+
+    #[test]
+    fn ctrl_punctuation_maps_to_c0_controls() {
+        let table: &[(char, u8)] = &[
+            (' ', 0x00),
+            ('@', 0x00),
+            ('`', 0x00),
+            ('2', 0x00),
+            ('[', 0x1b),
+            ('3', 0x1b),
+            ('\\', 0x1c),
+            ('4', 0x1c),
+            (']', 0x1d),
+            ('5', 0x1d),
+            ('^', 0x1e),
+            ('~', 0x1e),
+            ('6', 0x1e),
+            ('_', 0x1f),
+            ('-', 0x1f),
+            ('/', 0x1f),
+            ('7', 0x1f),
+            ('?', 0x7f),
+            ('8', 0x7f),
+        ];
+        for &(c, byte) in table {
+            assert_eq!(bytes(KeyCode::Char(c), KeyModifiers::CONTROL), Some(vec![byte]), "Ctrl+{}", c);
+        }
+        // Shifted punctuation arrives with SHIFT set; the char decides.
+        let ctrl_shift = KeyModifiers::CONTROL | KeyModifiers::SHIFT;
+        assert_eq!(bytes(KeyCode::Char('?'), ctrl_shift), Some(vec![0x7f]));
+        assert_eq!(bytes(KeyCode::Char(' '), KeyModifiers::CONTROL | KeyModifiers::ALT), Some(vec![0x1b, 0x00]));
+        assert_eq!(bytes(KeyCode::Char('1'), KeyModifiers::CONTROL), None);
+        assert_eq!(bytes(KeyCode::Char('9'), KeyModifiers::CONTROL), None);
+    }
+
+    #[test]
+    fn plain_and_special_keys() {
+        assert_eq!(bytes(KeyCode::Char('é'), KeyModifiers::NONE), Some("é".as_bytes().to_vec()));
+        assert_eq!(bytes(KeyCode::Enter, KeyModifiers::NONE), Some(vec![b'\r']));
+        assert_eq!(bytes(KeyCode::Backspace, KeyModifiers::NONE), Some(vec![0x7f]));
+        assert_eq!(bytes(KeyCode::BackTab, KeyModifiers::SHIFT), Some(b"\x1b[Z".to_vec()));
+    }
+
+    #[test]
+    fn reserved_chords_have_encodings() {
+        assert_eq!(bytes(KeyCode::Char('w'), KeyModifiers::ALT), Some(b"\x1bw".to_vec()));
+        assert_eq!(bytes(KeyCode::Char('q'), KeyModifiers::CONTROL), Some(vec![0x11]));
+        assert_eq!(bytes(KeyCode::Up, KeyModifiers::CONTROL), Some(b"\x1b[1;5A".to_vec()));
+        assert_eq!(bytes(KeyCode::PageDown, KeyModifiers::CONTROL), Some(b"\x1b[6;5~".to_vec()));
+        assert_eq!(bytes(KeyCode::F(12), KeyModifiers::NONE), Some(b"\x1b[24~".to_vec()));
+        assert_eq!(bytes(KeyCode::F(1), KeyModifiers::SHIFT), Some(b"\x1b[1;2P".to_vec()));
+    }
+}
@@ -0,0 +1,682 @@
+//! Inline images (sixel and iTerm2's OSC 1337) in pane output.
+//!
+//! The terminal parser can't draw images, so their sequences are cut out of
+//! the output before it sees them. With `images = "passthrough"` each image
+//! is kept, the area it covers is reserved in the pane with a hyperlinked
+//! placeholder, and the event loop re-emits the sequence to the outer
+//! terminal wherever that placeholder currently is on screen.
+
+use std::collections::VecDeque;
+
+use alacritty_terminal::grid::Dimensions;
+use alacritty_terminal::index::{Column, Line};
+use alacritty_terminal::Term;
+use base64::Engine;
+
+use crate::config::ImageMode;
+use crate::terminal::PaneListener;
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+const CAN: u8 = 0x18;
+const SUB: u8 = 0x1a;
+
+const ITERM_PREFIX: &[u8] = b"\x1b]1337;File=";
+
+/// Longest parameter list accepted between `ESC P` and a sixel's `q`.
+const MAX_SIXEL_PARAMS: usize = 32;
+
+/// Images larger than this are dropped rather than buffered.
+const MAX_IMAGE_BYTES: usize = 8 << 20;
+
+/// Total size of the images a pane keeps; the oldest go first, leaving
+/// their placeholder behind.
+const MAX_STORED_BYTES: usize = 32 << 20;
+
+/// Cell size assumed when the outer terminal doesn't report pixels.
+const DEFAULT_CELL_PIXELS: (u16, u16) = (10, 20);
+
+/// URI scheme of the hyperlink that marks where an image starts.
+const MARKER_SCHEME: &str = "bamboo-image:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageKind {
+    /// `ESC P ... q <sixel data> ESC \`
+    Sixel,
+    /// `ESC ] 1337 ; File = <args> : <base64> BEL`
+    Iterm,
+}
+
+/// A complete image sequence cut out of the output.
+#[derive(Debug, PartialEq)]
+pub struct Image {
+    pub kind: ImageKind,
+    pub sequence: Vec<u8>,
+}
+
+/// Pane output with images separated from everything else.
+#[derive(Debug, PartialEq)]
+pub enum Segment {
+    Output(Vec<u8>),
+    Image(Image),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Ground,
+    /// After ESC, until it's clear whether an image starts here.
+    Prefix,
+    /// Inside an image; `oversized` ones are consumed but not kept.
+    Image { kind: ImageKind, oversized: bool },
+    /// ESC seen inside an image; `\` ends it.
+    ImageEscape { kind: ImageKind, oversized: bool },
+}
+
+/// Splits images out of PTY output. Reads cut the stream anywhere, so the
+/// start of an escape sequence is held back until it's known whether it
+/// begins an image.
+pub struct ImageFilter {
+    state: State,
+    buf: Vec<u8>,
+}
+
+impl Default for ImageFilter {
+    fn default() -> Self {
+        Self { state: State::Ground, buf: Vec::new() }
+    }
+}
+
+impl ImageFilter {
+    /// Pass a chunk of output through the filter. Output segments keep every
+    /// byte that isn't part of an image, in order.
+    pub fn split(&mut self, bytes: &[u8]) -> Vec<Segment> {
+        let mut segments = Vec::new();
+        let mut out = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            match self.state {
+                State::Ground => match bytes[i..].iter().position(|&b| b == ESC) {
+                    None => {
+                        out.extend_from_slice(&bytes[i..]);
+                        i = bytes.len();
+                    }
+                    Some(p) => {
+                        out.extend_from_slice(&bytes[i..i + p]);
+                        self.buf.clear();
+                        self.buf.push(ESC);
+                        self.state = State::Prefix;
+                        i += p + 1;
+                    }
+                },
+                State::Prefix => {
+                    let b = bytes[i];
+                    i += 1;
+                    self.buf.push(b);
+                    match classify(&self.buf) {
+                        Prefix::Partial => {}
+                        Prefix::Image(kind) => self.state = State::Image { kind, oversized: false },
+                        Prefix::Other if b == ESC => {
+                            out.extend_from_slice(&self.buf[..self.buf.len() - 1]);
+                            self.buf.clear();
+                            self.buf.push(ESC);
+                        }
+                        Prefix::Other => {
+                            out.append(&mut self.buf);
+                            self.state = State::Ground;
+                        }
+                    }
+                }
+                State::Image { kind, oversized } => {
+                    let rest = &bytes[i..];
+                    let end = rest
+                        .iter()
+                        .position(|&b| b == ESC || b == CAN || b == SUB || (b == BEL && kind == ImageKind::Iterm));
+                    let data = &rest[..end.unwrap_or(rest.len())];
+                    let oversized = oversized || self.buf.len() + data.len() > MAX_IMAGE_BYTES;
+                    if oversized {
+                        self.buf = Vec::new();
+                    } else {
+                        self.buf.extend_from_slice(data);
+                    }
+                    self.state = State::Image { kind, oversized };
+                    let Some(p) = end else {
+                        i = bytes.len();
+                        continue;
+                    };
+                    i += p + 1;
+                    match rest[p] {
+                        ESC => self.state = State::ImageEscape { kind, oversized },
+                        BEL => {
+                            self.buf.push(BEL);
+                            self.finish(kind, oversized, &mut out, &mut segments);
+                        }
+                        // CAN and SUB cancel the sequence.
+                        _ => {
+                            self.buf.clear();
+                            self.state = State::Ground;
+                        }
+                    }
+                }
+                State::ImageEscape { kind, oversized } => {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                        self.buf.extend_from_slice(b"\x1b\\");
+                        self.finish(kind, oversized, &mut out, &mut segments);
+                    } else {
+                        // Any other sequence aborts the image and is
+                        // looked at on its own.
+                        self.buf.clear();
+                        self.buf.push(ESC);
+                        self.state = State::Prefix;
+                    }
+                }
+            }
+        }
+        if !out.is_empty() {
+            segments.push(Segment::Output(out));
+        }
+        segments
+    }
+
+    fn finish(&mut self, kind: ImageKind, oversized: bool, out: &mut Vec<u8>, segments: &mut Vec<Segment>) {
+        self.state = State::Ground;
+        let sequence = std::mem::take(&mut self.buf);
+        if oversized {
+            tracing::debug!("dropping oversized inline image");
+            return;
+        }
+        if !out.is_empty() {
+            segments.push(Segment::Output(std::mem::take(out)));
+        }
+        segments.push(Segment::Image(Image { kind, sequence }));
+    }
+}
+
+enum Prefix {
+    Partial,
+    Image(ImageKind),
+    Other,
+}
+
+/// What the escape sequence started in `buf` turns out to be so far.
+fn classify(buf: &[u8]) -> Prefix {
+    if ITERM_PREFIX.starts_with(buf) {
+        return if buf.len() == ITERM_PREFIX.len() { Prefix::Image(ImageKind::Iterm) } else { Prefix::Partial };
+    }
+    // Sixel DCS: only digits and semicolons before the `q`; other DCS
+    // strings such as DECRQSS (`ESC P $ q`) have intermediates.
+    if buf.len() > 1 && buf[1] == b'P' {
+        let params = &buf[2..];
+        return match params.last() {
+            None => Prefix::Partial,
+            Some(b'q') => Prefix::Image(ImageKind::Sixel),
+            Some(b) if (b.is_ascii_digit() || *b == b';') && params.len() <= MAX_SIXEL_PARAMS => Prefix::Partial,
+            Some(_) => Prefix::Other,
+        };
+    }
+    Prefix::Other
+}
+
+impl Image {
+    /// Cells the image covers in a pane of `pane` columns and rows, and the
+    /// sequence to draw it with. `None` for images that aren't drawn inline
+    /// or whose size can't be worked out.
+    fn layout(self, cell: (u16, u16), pane: (u16, u16)) -> Option<((u16, u16), Vec<u8>)> {
+        match self.kind {
+            ImageKind::Sixel => {
+                let (width, height) = sixel_pixel_size(&self.sequence)?;
+                let cols = width.div_ceil(cell.0 as u32).max(1);
+                let rows = height.div_ceil(cell.1 as u32).max(1);
+                Some(((clamp_u16(cols), clamp_u16(rows)), self.sequence))
+            }
+            ImageKind::Iterm => iterm_layout(&self.sequence, cell, pane),
+        }
+    }
+}
+
+fn clamp_u16(n: u32) -> u16 {
+    n.min(u16::MAX as u32) as u16
+}
+
+/// Pixel size of a sixel image: its raster attributes, or whatever the
+/// data paints beyond them.
+fn sixel_pixel_size(sequence: &[u8]) -> Option<(u32, u32)> {
+    let start = sequence.iter().position(|&b| b == b'q')? + 1;
+    let data = &sequence[start..];
+    let data = data.strip_suffix(b"\x1b\\").unwrap_or(data);
+    let (mut x, mut width, mut band, mut bands) = (0u32, 0u32, 0u32, 0u32);
+    let (mut raster_width, mut raster_height) = (0, 0);
+    let mut i = 0;
+    let number = |i: &mut usize| {
+        let mut n = 0u32;
+        while let Some(d) = data.get(*i).filter(|b| b.is_ascii_digit()) {
+            n = n.saturating_mul(10).saturating_add((d - b'0') as u32);
+            *i += 1;
+        }
+        n
+    };
+    while i < data.len() {
+        let b = data[i];
+        i += 1;
+        match b {
+            b'"' => {
+                // "Pan;Pad;Ph;Pv
+                let mut fields = [0u32; 4];
+                for (n, field) in fields.iter_mut().enumerate() {
+                    *field = number(&mut i);
+                    if n < 3 && data.get(i) == Some(&b';') {
+                        i += 1;
+                    } else {
+                        break;
+                    }
+                }
+                raster_width = fields[2];
+                raster_height = fields[3];
+            }
+            b'#' => {
+                while data.get(i).is_some_and(|b| b.is_ascii_digit() || *b == b';') {
+                    i += 1;
+                }
+            }
+            b'!' => {
+                let count = number(&mut i);
+                if data.get(i).is_some_and(|b| (b'?'..=b'~').contains(b)) {
+                    i += 1;
+                    x = x.saturating_add(count);
+                    bands = bands.max(band + 1);
+                }
+            }
+            b'?'..=b'~' => {
+                x += 1;
+                bands = bands.max(band + 1);
+            }
+            b'$' => x = 0,
+            b'-' => {
+                x = 0;
+                band += 1;
+            }
+            _ => {}
+        }
+        width = width.max(x);
+    }
+    let width = width.max(raster_width);
+    let height = (bands * 6).max(raster_height);
+    (width > 0 && height > 0).then_some((width, height))
+}
+
+/// A `width` or `height` argument of an iTerm2 image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Dimension {
+    Auto,
+    Cells(u32),
+    Pixels(u32),
+    Percent(u32),
+}
+
+impl Dimension {
+    fn parse(value: &str) -> Self {
+        let number = |s: &str| s.parse().ok();
+        if let Some(n) = value.strip_suffix("px").and_then(number) {
+            Dimension::Pixels(n)
+        } else if let Some(n) = value.strip_suffix('%').and_then(number) {
+            Dimension::Percent(n)
+        } else {
+            number(value).map_or(Dimension::Auto, Dimension::Cells)
+        }
+    }
+
+    /// Cells along an axis with `cell` pixels per cell and `pane` cells.
+    fn cells(self, cell: u16, pane: u16) -> Option<u32> {
+        match self {
+            Dimension::Auto => None,
+            Dimension::Cells(n) => Some(n),
+            Dimension::Pixels(n) => Some(n.div_ceil(cell as u32)),
+            Dimension::Percent(p) => Some(pane as u32 * p.min(100) / 100),
+        }
+    }
+}
+
+/// Size an iTerm2 image the way iTerm2 does, then rewrite its arguments
+/// with that size in cells so the outer terminal draws it into exactly the
+/// area reserved for it.
+fn iterm_layout(sequence: &[u8], cell: (u16, u16), pane: (u16, u16)) -> Option<((u16, u16), Vec<u8>)> {
+    let body = sequence.strip_prefix(ITERM_PREFIX)?;
+    let colon = body.iter().position(|&b| b == b':')?;
+    let args = std::str::from_utf8(&body[..colon]).ok()?;
+    let payload = body[colon + 1..].strip_suffix(b"\x07").or_else(|| body[colon + 1..].strip_suffix(b"\x1b\\"))?;
+
+    let (mut width, mut height, mut preserve_aspect, mut inline) = (Dimension::Auto, Dimension::Auto, true, false);
+    let mut kept = Vec::new();
+    for arg in args.split(';').filter(|a| !a.is_empty()) {
+        let (key, value) = arg.split_once('=').unwrap_or((arg, ""));
+        match key {
+            "width" => width = Dimension::parse(value),
+            "height" => height = Dimension::parse(value),
+            "preserveAspectRatio" => preserve_aspect = value != "0",
+            "inline" => inline = value == "1",
+            _ => kept.push(arg),
+        }
+    }
+    // Without inline=1 the file is meant to be downloaded, not shown.
+    if !inline {
+        return None;
+    }
+
+    let pixels = base64::engine::general_purpose::STANDARD
+        .decode(payload)
+        .ok()
+        .and_then(|data| encoded_pixel_size(&data));
+    let mut cols = width.cells(cell.0, pane.0);
+    let mut rows = height.cells(cell.1, pane.1);
+    if cols.is_none() || rows.is_none() {
+        let (w, h) = pixels?;
+        // Scale the natural size down to the width given, or to the pane.
+        let natural_cols = w.div_ceil(cell.0 as u32);
+        let target_cols = match (cols, rows) {
+            (Some(c), None) => c,
+            (None, Some(r)) if preserve_aspect => {
+                let scaled_w = (r * cell.1 as u32) as u64 * w as u64 / h.max(1) as u64;
+                (scaled_w as u32).div_ceil(cell.0 as u32)
+            }
+            _ => natural_cols.min(pane.0 as u32),
+        };
+        let scaled_h = (target_cols * cell.0 as u32) as u64 * h as u64 / w.max(1) as u64;
+        cols.get_or_insert(target_cols);
+        rows.get_or_insert((scaled_h as u32).div_ceil(cell.1 as u32));
+    }
+    let (cols, rows) = (clamp_u16(cols?.max(1)), clamp_u16(rows?.max(1)));
+
+    let mut rewritten = ITERM_PREFIX.to_vec();
+    kept.push("inline=1");
+    let size = format!("width={};height={}", cols, rows);
+    kept.push(&size);
+    if !preserve_aspect {
+        kept.push("preserveAspectRatio=0");
+    }
+    rewritten.extend_from_slice(kept.join(";").as_bytes());
+    rewritten.push(b':');
+    rewritten.extend_from_slice(payload);
+    rewritten.push(BEL);
+    Some(((cols, rows), rewritten))
+}
+
+/// Width and height of a PNG, GIF or JPEG from its header.
+fn encoded_pixel_size(data: &[u8]) -> Option<(u32, u32)> {
+    let be32 = |at: usize| data.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+    let be16 = |at: usize| data.get(at..at + 2).map(|b| u16::from_be_bytes([b[0], b[1]]) as u32);
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some((be32(16)?, be32(20)?));
+    }
+    if data.starts_with(b"GIF8") {
+        let le16 = |at: usize| data.get(at..at + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as u32);
+        return Some((le16(6)?, le16(8)?));
+    }
+    if data.starts_with(&[0xff, 0xd8]) {
+        let mut at = 2;
+        while *data.get(at)? == 0xff {
+            let marker = *data.get(at + 1)?;
+            let len = be16(at + 2)? as usize;
+            // Start-of-frame markers; C4, C8 and CC are something else.
+            if (0xc0..=0xcf).contains(&marker) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+                return Some((be16(at + 7)?, be16(at + 5)?));
+            }
+            at += 2 + len;
+        }
+    }
+    None
+}
+
+/// An image kept for drawing, and the cells it covers.
+struct StoredImage {
+    id: u64,
+    sequence: Vec<u8>,
+    cols: u16,
+    rows: u16,
+}
+
+/// Where an image goes on the outer terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub pane_id: usize,
+    pub image_id: u64,
+    pub x: u16,
+    pub y: u16,
+}
+
+/// A pane's inline images.
+#[derive(Default)]
+pub struct InlineImages {
+    pub mode: ImageMode,
+    filter: ImageFilter,
+    stored: VecDeque<StoredImage>,
+    next_id: u64,
+}
+
+impl InlineImages {
+    pub fn split(&mut self, bytes: &[u8]) -> Vec<Segment> {
+        self.filter.split(bytes)
+    }
+
+    /// Drop a partly read image, e.g. one garbage output started and never
+    /// ended, so what follows is shown again.
+    pub fn reset_filter(&mut self) {
+        self.filter = ImageFilter::default();
+    }
+
+    /// Keep `image` for drawing and return what to write into the pane to
+    /// reserve its area: a placeholder carrying the marker hyperlink at the
+    /// cursor, then the cursor moved below the image. `None` when images
+    /// are off or this one can't be drawn; it is dropped either way.
+    pub fn insert(&mut self, image: Image, pane: (u16, u16), cursor_col: u16) -> Option<String> {
+        if self.mode != ImageMode::Passthrough {
+            return None;
+        }
+        let cell = match crate::pty::cell_pixel_size() {
+            (0, _) | (_, 0) => DEFAULT_CELL_PIXELS,
+            size => size,
+        };
+        let kind = image.kind;
+        let Some(((cols, rows), sequence)) = image.layout(cell, pane) else {
+            tracing::debug!(?kind, "inline image not drawable, dropped");
+            return None;
+        };
+
+        self.next_id += 1;
+        let id = self.next_id;
+        self.stored.push_back(StoredImage { id, sequence, cols, rows });
+        let mut total: usize = self.stored.iter().map(|s| s.sequence.len()).sum();
+        while total > MAX_STORED_BYTES {
+            let Some(oldest) = self.stored.pop_front() else { break };
+            total -= oldest.sequence.len();
+        }
+
+        let label = format!("[image {}x{}]", cols, rows);
+        let room = cols.min(pane.0.saturating_sub(cursor_col)) as usize;
+        let label: String = label.chars().take(room).collect();
+        Some(format!(
+            "\x1b7\x1b]8;;{}{}\x1b\\\x1b[2m{}\x1b]8;;\x1b\\\x1b8{}",
+            MARKER_SCHEME,
+            id,
+            label,
+            "\r\n".repeat(rows as usize),
+        ))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stored.is_empty()
+    }
+
+    /// The sequence that draws image `id`.
+    pub fn sequence(&self, id: u64) -> Option<&[u8]> {
+        self.stored.iter().find(|s| s.id == id).map(|s| s.sequence.as_slice())
+    }
+
+    /// Images whose marker is on the first `height` rows of the live screen
+    /// from `top`, as (id, column, row within those rows), leaving out any
+    /// that don't fit entirely within them and `width` columns.
+    pub fn visible(&self, term: &Term<PaneListener>, top: usize, width: u16, height: u16) -> Vec<(u64, u16, u16)> {
+        let mut found = Vec::new();
+        let mut seen = Vec::new();
+        let rows = term.screen_lines().min(top + height as usize);
+        let cols = term.columns().min(width as usize);
+        for line in top..rows {
+            let row = &term.grid()[Line(line as i32)];
+            for col in 0..cols {
+                let Some(link) = row[Column(col)].hyperlink() else {
+                    continue;
+                };
+                let Some(id) = link.uri().strip_prefix(MARKER_SCHEME).and_then(|id| id.parse().ok()) else {
+                    continue;
+                };
+                // Only the placeholder's first cell is where the image starts.
+                if seen.contains(&id) {
+                    continue;
+                }
+                seen.push(id);
+                let (x, y) = (col as u16, (line - top) as u16);
+                let fits = self
+                    .stored
+                    .iter()
+                    .find(|s| s.id == id)
+                    .is_some_and(|s| x.saturating_add(s.cols) <= width && y.saturating_add(s.rows) <= height);
+                if fits {
+                    found.push((id, x, y));
+                }
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split_all(filter: &mut ImageFilter, chunks: &[&[u8]]) -> (Vec<u8>, Vec<Image>) {
+        let (mut output, mut images) = (Vec::new(), Vec::new());
+        for chunk in chunks {
+            for segment in filter.split(chunk) {
+                match segment {
+                    Segment::Output(bytes) => output.extend(bytes),
+                    Segment::Image(image) => images.push(image),
+                }
+            }
+        }
+        (output, images)
+    }
+
+    #[test]
+    fn images_are_cut_out_at_any_read_boundary() {
+        let stream: &[u8] = b"a\x1b[1mb\x1bPq#0;2;0;0;0~~-~~\x1b\\c\x1b]1337;File=inline=1:AAAA\x07d";
+        for at in 0..stream.len() {
+            let mut filter = ImageFilter::default();
+            let (output, images) = split_all(&mut filter, &[&stream[..at], &stream[at..]]);
+            assert_eq!(output, b"a\x1b[1mbcd", "split at {}", at);
+            assert_eq!(images.len(), 2, "split at {}", at);
+            assert_eq!(images[0].kind, ImageKind::Sixel);
+            assert_eq!(images[0].sequence, b"\x1bPq#0;2;0;0;0~~-~~\x1b\\");
+            assert_eq!(images[1].kind, ImageKind::Iterm);
+        }
+    }
+
+    #[test]
+    fn other_strings_and_aborted_images_pass_or_vanish_cleanly() {
+        let mut filter = ImageFilter::default();
+        let other: &[u8] = b"\x1bP$qm\x1b\\\x1b]0;title\x07\x1b]1337;SetMark\x07\x1b\x1b[0m";
+        assert_eq!(split_all(&mut filter, &[other]), (other.to_vec(), Vec::new()));
+
+        // CAN cancels; a new escape sequence aborts and is kept.
+        let (output, images) = split_all(&mut filter, &[b"x\x1bPq~~\x18y\x1bPq~~\x1b[0mz"]);
+        assert_eq!(output, b"xy\x1b[0mz");
+        assert!(images.is_empty());
+    }
+
+    #[test]
+    fn oversized_images_are_dropped_without_buffering() {
+        let mut filter = ImageFilter::default();
+        let chunk = vec![b'~'; 1 << 20];
+        let mut chunks: Vec<&[u8]> = vec![b"\x1bPq"];
+        chunks.extend(std::iter::repeat_n(chunk.as_slice(), 9));
+        chunks.push(b"\x1b\\ok");
+        let (output, images) = split_all(&mut filter, &chunks);
+        assert_eq!(output, b"ok");
+        assert!(images.is_empty());
+        assert!(filter.buf.capacity() < MAX_IMAGE_BYTES);
+    }
+
+    #[test]
+    fn sixel_size_comes_from_raster_attributes_or_data() {
+        assert_eq!(sixel_pixel_size(b"\x1bPq\"1;1;40;30#0~\x1b\\"), Some((40, 30)));
+        assert_eq!(sixel_pixel_size(b"\x1bP0;1q#1!25~-!5~$~~\x1b\\"), Some((25, 12)));
+        assert_eq!(sixel_pixel_size(b"\x1bPq\x1b\\"), None);
+    }
+
+    #[test]
+    fn iterm_images_are_sized_in_cells_and_rewritten_to_that_size() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        png.extend(400u32.to_be_bytes());
+        png.extend(100u32.to_be_bytes());
+        let data = base64::engine::general_purpose::STANDARD.encode(&png);
+        let sequence = |args: &str| format!("\x1b]1337;File={}:{}\x07", args, data).into_bytes();
+        let cell = (10, 20);
+
+        let ((cols, rows), rewritten) = iterm_layout(&sequence("name=eA==;inline=1"), cell, (80, 24)).unwrap();
+        assert_eq!((cols, rows), (40, 5));
+        assert_eq!(rewritten, sequence("name=eA==;inline=1;width=40;height=5"));
+
+        // Too wide for the pane: scaled down to fit.
+        assert_eq!(iterm_layout(&sequence("inline=1"), cell, (20, 24)).unwrap().0, (20, 3));
+        assert_eq!(iterm_layout(&sequence("inline=1;width=50%;height=2"), cell, (80, 24)).unwrap().0, (40, 2));
+        assert_eq!(iterm_layout(&sequence("inline=1;height=60px"), cell, (80, 24)).unwrap().0, (24, 3));
+        assert!(iterm_layout(&sequence("name=eA=="), cell, (80, 24)).is_none());
+    }
+
+    /// A 4x2-cell iTerm2 image carrying `data`.
+    fn image(data: &str) -> Image {
+        let sequence = format!("\x1b]1337;File=inline=1;width=4;height=2:{}\x07", data).into_bytes();
+        Image { kind: ImageKind::Iterm, sequence }
+    }
+
+    #[test]
+    fn images_are_placed_at_their_marker_until_it_scrolls_off() {
+        let mut images = InlineImages { mode: ImageMode::Passthrough, ..Default::default() };
+        let mut term = crate::terminal::new_term(6, 20, 0, Default::default());
+        let mut processor = crate::terminal::new_processor();
+        let mut feed =
+            |term: &mut Term<PaneListener>, bytes: &[u8]| crate::terminal::process_bytes(term, &mut processor, bytes);
+
+        feed(&mut term, b"ab");
+        let placeholder = images.insert(image("AAAA"), (20, 6), 2).expect("drawn");
+        feed(&mut term, placeholder.as_bytes());
+        feed(&mut term, b"after");
+        assert_eq!(images.visible(&term, 0, 20, 6), [(1, 2, 0)]);
+        assert_eq!(crate::terminal::cursor_position(&term), (2, 5), "below the image");
+
+        // Only where it fits whole.
+        assert_eq!(images.visible(&term, 0, 5, 6), []);
+        assert_eq!(images.visible(&term, 0, 20, 1), []);
+
+        // From a lower top row the marker is above the rows shown, as it
+        // is once output scrolls it off the screen.
+        assert_eq!(images.visible(&term, 1, 20, 5), []);
+        feed(&mut term, b"\r\n\r\n\r\n\r\n");
+        assert_eq!(images.visible(&term, 0, 20, 6), []);
+    }
+
+    #[test]
+    fn the_oldest_images_go_once_too_much_is_kept() {
+        let mut images = InlineImages { mode: ImageMode::Passthrough, ..Default::default() };
+        // Four fit, with room for the rest of each sequence.
+        let data = "A".repeat(MAX_STORED_BYTES / 4 - 100);
+        for _ in 0..5 {
+            assert!(images.insert(image(&data), (20, 6), 0).is_some());
+        }
+        assert_eq!(images.sequence(1), None);
+        assert!(images.sequence(2).is_some());
+        assert!(images.sequence(5).is_some());
+
+        images.mode = ImageMode::Off;
+        assert_eq!(images.insert(image("AAAA"), (20, 6), 0), None);
+        assert_eq!(images.sequence(6), None);
+    }
+}
@@ -0,0 +1,121 @@
+use std::fmt;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// A configurable key combination such as `Ctrl+Space` or `Alt+l`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyChord {
+    pub modifiers: KeyModifiers,
+    pub code: KeyCode,
+}
+
+impl KeyChord {
+    pub const fn new(modifiers: KeyModifiers, code: KeyCode) -> Self {
+        Self { modifiers, code }
+    }
+
+    /// Parse `Ctrl+Space`, `alt+l`, `Ctrl+]`, `F11`, `Alt+Pause` and the like. Modifier
+    /// and key names are case-insensitive.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut parts: Vec<&str> = spec.split('+').map(str::trim).collect();
+        // "Ctrl++" names the plus key.
+        if spec.ends_with("++") {
+            parts.truncate(parts.len() - 2);
+            parts.push("+");
+        }
+        let key = parts.pop()?;
+        for part in parts {
+            modifiers |= match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+        }
+
+        let lower = key.to_ascii_lowercase();
+        let code = match lower.as_str() {
+            "space" => KeyCode::Char(' '),
+            "tab" => KeyCode::Tab,
+            "enter" => KeyCode::Enter,
+            "pause" => KeyCode::Pause,
+            _ if lower.len() > 1 && lower.starts_with('f') => KeyCode::F(lower[1..].parse().ok()?),
+            _ => {
+                let mut chars = lower.chars();
+                let c = chars.next()?;
+                if chars.next().is_some() {
+                    return None;
+                }
+                KeyCode::Char(c)
+            }
+        };
+        Some(Self::new(modifiers, code))
+    }
+
+    /// Whether `key` is this chord. Letters match in either case, since
+    /// terminals differ in how they report Shift alongside Ctrl.
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        match (key.code, self.code) {
+            (KeyCode::Char(pressed), KeyCode::Char(c)) => {
+                pressed.to_ascii_lowercase() == c
+                    && key.modifiers - KeyModifiers::SHIFT == self.modifiers - KeyModifiers::SHIFT
+            }
+            (pressed, code) => pressed == code && key.modifiers == self.modifiers,
+        }
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (flag, name) in [
+            (KeyModifiers::CONTROL, "Ctrl+"),
+            (KeyModifiers::ALT, "Alt+"),
+            (KeyModifiers::SHIFT, "Shift+"),
+        ] {
+            if self.modifiers.contains(flag) {
+                f.write_str(name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_displays_chords() {
+        for (spec, shown) in [
+            ("Ctrl+Space", "Ctrl+Space"),
+            ("alt+L", "Alt+l"),
+            ("ctrl+]", "Ctrl+]"),
+            ("Ctrl++", "Ctrl++"),
+            ("F11", "F11"),
+            ("alt+pause", "Alt+Pause"),
+        ] {
+            let chord = KeyChord::parse(spec).unwrap_or_else(|| panic!("{} should parse", spec));
+            assert_eq!(chord.to_string(), shown);
+        }
+        for bad in ["", "Hyper+x", "Ctrl+", "Ctrl+ab", "Fx"] {
+            assert_eq!(KeyChord::parse(bad), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn letters_match_regardless_of_shift() {
+        let chord = KeyChord::parse("Ctrl+g").unwrap();
+        assert!(chord.matches(&KeyEvent::new(KeyCode::Char('g'), KeyModifiers::CONTROL)));
+        assert!(chord.matches(&KeyEvent::new(
+            KeyCode::Char('G'),
+            KeyModifiers::CONTROL | KeyModifiers::SHIFT
+        )));
+        assert!(!chord.matches(&KeyEvent::new(KeyCode::Char('g'), KeyModifiers::ALT)));
+    }
+}
@@ -0,0 +1,653 @@
+use ratatui::layout::Rect;
+
+use crate::config::{Chrome, ViewportPolicy};
+use crate::pane::Pane;
+
+/// Rows taken by a "▲ N more above" / "▼ N more below" indicator, when
+/// those are shown.
+pub const INDICATOR_HEIGHT: u16 = 1;
+
+/// The parts of a pane that decide how tall it is drawn.
+pub trait LayoutPane {
+    fn collapsed(&self) -> bool;
+    fn weight(&self) -> u16;
+    /// Content rows the pane keeps while expanded (`min_height`).
+    fn min_rows(&self) -> u16;
+    /// Shares the previous pane's slot, in its right half.
+    fn split_right(&self) -> bool;
+}
+
+impl LayoutPane for Pane {
+    fn collapsed(&self) -> bool {
+        self.collapsed
+    }
+
+    fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    fn min_rows(&self) -> u16 {
+        self.config.min_height.unwrap_or(0)
+    }
+
+    fn split_right(&self) -> bool {
+        self.split_right
+    }
+}
+
+/// First pane of the slot holding the pane at `idx`.
+pub fn slot_start<P: LayoutPane>(panes: &[P], mut idx: usize) -> usize {
+    while idx > 0 && panes.get(idx).is_some_and(P::split_right) {
+        idx -= 1;
+    }
+    idx
+}
+
+/// One past the last pane of the slot starting at `start`.
+pub fn slot_end<P: LayoutPane>(panes: &[P], start: usize) -> usize {
+    let mut end = start + 1;
+    while panes.get(end).is_some_and(P::split_right) {
+        end += 1;
+    }
+    end
+}
+
+/// Height reserved for a slot before weights are applied. The first pane's
+/// collapsed state stands for the whole slot.
+fn min_height<P: LayoutPane>(chrome: Chrome, slot: &[P]) -> u16 {
+    if slot[0].collapsed() {
+        chrome.collapsed_height()
+    } else {
+        slot.iter().map(|p| chrome.expanded_height(p.min_rows())).max().unwrap_or(0)
+    }
+}
+
+/// Slots from `start` that fit in `total_height` at their minimum heights,
+/// keeping `indicator` rows for each scroll indicator that will be shown.
+///
+/// Returns the end index and the rows left over once the panes and
+/// indicators are placed. The slot at `start` is always included, even when
+/// it has to be clipped.
+fn fit<P: LayoutPane>(panes: &[P], chrome: Chrome, indicator: u16, start: usize, total_height: u16) -> (usize, u16) {
+    let has_above = start > 0;
+    let mut remaining = total_height.saturating_sub(if has_above { indicator } else { 0 });
+    let mut end = start;
+
+    while end < panes.len() {
+        let next = slot_end(panes, end);
+        let min_h = min_height(chrome, &panes[end..next]);
+        let reserved = if next < panes.len() { indicator } else { 0 };
+
+        if remaining < min_h.saturating_add(reserved) && end > start {
+            break;
+        }
+        remaining = remaining.saturating_sub(min_h);
+        end = next;
+    }
+
+    let has_below = end < panes.len();
+    (end, remaining.saturating_sub(if has_below { indicator } else { 0 }))
+}
+
+/// One past the last pane shown when the viewport starts at `start`.
+pub fn compute_visible_end<P: LayoutPane>(
+    panes: &[P],
+    chrome: Chrome,
+    indicator: u16,
+    start: usize,
+    total_height: u16,
+) -> usize {
+    fit(panes, chrome, indicator, start, total_height).0
+}
+
+/// Viewport start that keeps `focused` in view, given the current `start`.
+///
+/// `policy` widens what must be in view to the panes around the focused
+/// one: up to N on each side for `Context(N)`, as many as fit for
+/// `Centered`. Each extra pane on either side is only asked for while the
+/// whole range still fits, so near the ends of the stack or on a short
+/// screen the range shrinks back towards the focused pane alone.
+///
+/// Scrolls forward only as far as needed when the range is below the
+/// viewport. Then, while an earlier start would still show every pane through
+/// the last one, it steps back, so the stack never leaves empty rows below its
+/// last pane — moving focus up past collapsed panes keeps the screen full. A
+/// focused pane taller than the area becomes the first shown and is clipped.
+/// The viewport always starts at a slot's first pane.
+pub fn viewport_start_for<P: LayoutPane>(
+    panes: &[P],
+    chrome: Chrome,
+    indicator: u16,
+    policy: ViewportPolicy,
+    focused: usize,
+    start: usize,
+    total_height: u16,
+) -> usize {
+    if panes.is_empty() {
+        return 0;
+    }
+    let last = panes.len() - 1;
+    let focused = focused.min(last);
+    let context = match policy {
+        ViewportPolicy::Minimal => 0,
+        ViewportPolicy::Context(n) => n.min(panes.len()),
+        ViewportPolicy::Centered => panes.len(),
+    };
+
+    // First pane's slot and last pane of the range that has to show.
+    let (mut first, mut through) = (slot_start(panes, focused), focused);
+    for k in 1..=context {
+        let wider = (slot_start(panes, focused.saturating_sub(k)), (focused + k).min(last));
+        if compute_visible_end(panes, chrome, indicator, wider.0, total_height) <= wider.1 {
+            break;
+        }
+        (first, through) = wider;
+    }
+
+    let mut start = slot_start(panes, start.min(first));
+    while compute_visible_end(panes, chrome, indicator, start, total_height) <= through {
+        start = slot_end(panes, start);
+    }
+    pack_viewport_start(panes, chrome, indicator, start, total_height)
+}
+
+/// `start` kept within the stack and stepped back while an earlier start
+/// would still show every pane through the last one, so a viewport moved
+/// without regard to focus never leaves empty rows below the last pane.
+pub fn pack_viewport_start<P: LayoutPane>(
+    panes: &[P],
+    chrome: Chrome,
+    indicator: u16,
+    start: usize,
+    total_height: u16,
+) -> usize {
+    let mut start = slot_start(panes, start.min(panes.len().saturating_sub(1)));
+    while start > 0 {
+        let prev = slot_start(panes, start - 1);
+        if compute_visible_end(panes, chrome, indicator, prev, total_height) != panes.len() {
+            break;
+        }
+        start = prev;
+    }
+    start
+}
+
+/// Areas of the panes shown from `start` within `area`, and one past the last
+/// shown pane.
+///
+/// Each slot starts at its minimum height, which a larger `min_height`
+/// raises, and rows beyond that go to expanded slots in proportion to the
+/// weight of their first pane; a slot whose minimum doesn't fit is pushed
+/// below the viewport rather than squeezed. A split slot's panes share its
+/// rows side by side. The areas never extend past `area` or into the rows
+/// used by the scroll indicators.
+pub fn compute_visible_layout<P: LayoutPane>(
+    panes: &[P],
+    chrome: Chrome,
+    indicator: u16,
+    start: usize,
+    area: Rect,
+) -> (Vec<(usize, Rect)>, usize) {
+    if start >= panes.len() {
+        return (Vec::new(), start);
+    }
+
+    let (end, spare) = fit(panes, chrome, indicator, start, area.height);
+    let mut slots = Vec::new();
+    let mut i = start;
+    while i < end {
+        let next = slot_end(panes, i);
+        slots.push(i..next);
+        i = next;
+    }
+
+    let above = if start > 0 { indicator } else { 0 };
+    let below = if end < panes.len() { indicator } else { 0 };
+    let available = area.height.saturating_sub(above + below);
+
+    let mut heights: Vec<u16> = slots.iter().map(|s| min_height(chrome, &panes[s.clone()])).collect();
+    let leaders: Vec<&P> = slots.iter().map(|s| &panes[s.start]).collect();
+
+    // Weighted shares, rounded down; rows lost to rounding go one each to the
+    // first expanded slots.
+    let total_weight: u32 = leaders
+        .iter()
+        .filter(|p| !p.collapsed())
+        .map(|p| p.weight() as u32)
+        .sum();
+    if spare > 0 && total_weight > 0 {
+        let mut distributed = 0u16;
+        for (h, pane) in heights.iter_mut().zip(&leaders) {
+            if !pane.collapsed() {
+                let share = (spare as u32 * pane.weight() as u32 / total_weight) as u16;
+                *h = h.saturating_add(share);
+                distributed += share;
+            }
+        }
+        let expanded = heights.iter_mut().zip(&leaders).filter(|(_, p)| !p.collapsed());
+        for (h, _) in expanded.take((spare - distributed) as usize) {
+            *h = h.saturating_add(1);
+        }
+    }
+
+    // Only a lone slot taller than the area can overrun it; clip it. Counting
+    // down from `available` keeps the panes clear of the bottom indicator
+    // however the heights above were reached.
+    let mut left = available;
+    for h in &mut heights {
+        *h = (*h).min(left);
+        left -= *h;
+    }
+
+    let mut y = area.y + above.min(area.height);
+    debug_assert!(y + (available - left) <= area.bottom());
+    let mut result = Vec::with_capacity(end - start);
+    for (slot, h) in slots.into_iter().zip(heights) {
+        if h == 0 {
+            continue;
+        }
+        // Split slots give the left pane the odd column.
+        let count = slot.len() as u16;
+        let mut x = area.x;
+        for (n, i) in slot.enumerate() {
+            let n = n as u16;
+            let width = area.width / count + u16::from(n < area.width % count);
+            result.push((i, Rect::new(x, y, width, h)));
+            x += width;
+        }
+        y += h;
+    }
+
+    (result, end)
+}
+
+/// A pane seen with another weight.
+struct Reweighted<'a, P> {
+    pane: &'a P,
+    weight: u16,
+}
+
+impl<P: LayoutPane> LayoutPane for Reweighted<'_, P> {
+    fn collapsed(&self) -> bool {
+        self.pane.collapsed()
+    }
+
+    fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    fn min_rows(&self) -> u16 {
+        self.pane.min_rows()
+    }
+
+    fn split_right(&self) -> bool {
+        self.pane.split_right()
+    }
+}
+
+/// The areas `compute_visible_layout` would give the panes if their weights
+/// were `weights` instead, without changing them.
+pub fn dry_run_layout<P: LayoutPane>(
+    panes: &[P],
+    weights: &[u16],
+    chrome: Chrome,
+    indicator: u16,
+    start: usize,
+    area: Rect,
+) -> Vec<(usize, Rect)> {
+    let reweighted: Vec<_> = panes.iter().zip(weights).map(|(pane, &weight)| Reweighted { pane, weight }).collect();
+    compute_visible_layout(&reweighted, chrome, indicator, start, area).0
+}
+
+/// Percentage of the rows given to expanded slots in `layout` that the slot
+/// led by `idx` gets, if it is laid out and expanded.
+pub fn expanded_share<P: LayoutPane>(panes: &[P], layout: &[(usize, Rect)], idx: usize) -> Option<u16> {
+    let leaders = layout.iter().filter(|&&(i, _)| !panes[i].split_right() && !panes[i].collapsed());
+    let total: u32 = leaders.clone().map(|(_, r)| r.height as u32).sum();
+    let (_, area) = leaders.clone().find(|&&(i, _)| i == idx)?;
+    (total > 0).then(|| (area.height as u32 * 100 / total) as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct P {
+        collapsed: bool,
+        weight: u16,
+        min_rows: u16,
+        split_right: bool,
+    }
+
+    impl LayoutPane for P {
+        fn collapsed(&self) -> bool {
+            self.collapsed
+        }
+
+        fn weight(&self) -> u16 {
+            self.weight
+        }
+
+        fn min_rows(&self) -> u16 {
+            self.min_rows
+        }
+
+        fn split_right(&self) -> bool {
+            self.split_right
+        }
+    }
+
+    fn expanded(weight: u16) -> P {
+        P { collapsed: false, weight, min_rows: 0, split_right: false }
+    }
+
+    /// An expanded pane in the right half of the previous pane's slot.
+    fn beside(min_rows: u16) -> P {
+        P { collapsed: false, weight: 1, min_rows, split_right: true }
+    }
+
+    /// An expanded pane with `min_height = rows`.
+    fn tall(weight: u16, rows: u16) -> P {
+        P { collapsed: false, weight, min_rows: rows, split_right: false }
+    }
+
+    fn collapsed() -> P {
+        P { collapsed: true, weight: 10, min_rows: 0, split_right: false }
+    }
+
+    fn heights(layout: &[(usize, Rect)]) -> Vec<u16> {
+        layout.iter().map(|(_, r)| r.height).collect()
+    }
+
+    /// Areas are contiguous, inside `area`, and clear of the indicator rows.
+    /// Halves of a split slot sit side by side in the same rows.
+    fn assert_well_formed(panes: usize, indicator: u16, start: usize, area: Rect, layout: &[(usize, Rect)], end: usize) {
+        let top = area.y + if start > 0 { indicator } else { 0 };
+        let bottom = area.bottom().saturating_sub(if end < panes { indicator } else { 0 });
+        let mut y = top;
+        let mut prev: Option<Rect> = None;
+        for (_, r) in layout {
+            assert!(r.height > 0);
+            match prev {
+                Some(p) if p.y == r.y => {
+                    assert_eq!((r.x, r.height), (p.right(), p.height), "halves must line up: {:?}", layout);
+                }
+                _ => {
+                    assert_eq!(r.y, y, "areas must be contiguous: {:?}", layout);
+                    y += r.height;
+                }
+            }
+            assert!(r.right() <= area.right());
+            prev = Some(*r);
+        }
+        assert!(y <= bottom.max(top), "layout {:?} overruns {:?}", layout, area);
+    }
+
+    #[test]
+    fn layouts() {
+        // (panes, chrome, start, height, expected heights, expected end)
+        type Case = (Vec<P>, Chrome, usize, u16, Vec<u16>, usize);
+        let cases: Vec<Case> = vec![
+            // One pane gets the whole area.
+            (vec![expanded(10)], Chrome::Full, 0, 5, vec![5], 1),
+            // A lone pane taller than the area is clipped, not overrun.
+            (vec![expanded(10)], Chrome::Full, 0, 3, vec![3], 1),
+            // Collapsed panes keep their fixed height; the rest is unused.
+            (vec![collapsed(), collapsed(), collapsed()], Chrome::Full, 0, 20, vec![3, 3, 3], 3),
+            // More collapsed panes than fit: stop early and keep the indicator row.
+            (vec![collapsed(), collapsed(), collapsed(), collapsed()], Chrome::Full, 0, 10, vec![3, 3, 3], 3),
+            (vec![collapsed(), collapsed(), collapsed(), collapsed()], Chrome::Full, 0, 9, vec![3, 3], 2),
+            // Exactly fits without an indicator.
+            (vec![expanded(10), expanded(10)], Chrome::Full, 0, 10, vec![5, 5], 2),
+            // One row short for the second pane: it moves below the indicator.
+            (vec![expanded(10), expanded(10)], Chrome::Full, 0, 9, vec![8], 1),
+            // Above indicator takes the first row.
+            (vec![expanded(10), expanded(10), expanded(10)], Chrome::Full, 1, 11, vec![5, 5], 3),
+            // Uneven split: rounding leftovers go to the first panes.
+            (vec![expanded(10), expanded(10), expanded(10)], Chrome::Full, 0, 23, vec![8, 8, 7], 3),
+            // Weighted split.
+            (vec![expanded(30), expanded(10)], Chrome::Full, 0, 18, vec![11, 7], 2),
+            // Collapsed panes take no share of the spare rows.
+            (vec![collapsed(), expanded(10)], Chrome::Compact, 0, 12, vec![2, 10], 2),
+            // Chrome::None has no border rows.
+            (vec![expanded(10), expanded(10)], Chrome::None, 0, 7, vec![4, 3], 2),
+            // Zero-height area lays out nothing.
+            (vec![expanded(10)], Chrome::Full, 0, 0, vec![], 1),
+            // Only the above indicator fits.
+            (vec![expanded(10), expanded(10)], Chrome::Full, 1, 1, vec![], 2),
+            // A minimum is met before weights share out the rest: 14 + 5
+            // leaves 1 row, which goes to the first pane.
+            (vec![tall(10, 12), expanded(10)], Chrome::Full, 0, 20, vec![15, 5], 2),
+            // The other pane shrinks to its own minimum first.
+            (vec![expanded(10), tall(10, 12)], Chrome::Full, 0, 19, vec![5, 14], 2),
+            // Minimums below the default change nothing.
+            (vec![tall(10, 1), expanded(10)], Chrome::Full, 0, 10, vec![5, 5], 2),
+            // Both minimums can't be met: the second pane moves out of view
+            // instead of being squeezed.
+            (vec![tall(10, 12), tall(10, 12), expanded(10)], Chrome::Full, 0, 25, vec![24], 1),
+            // Same when the tall pane comes later in the stack.
+            (vec![expanded(10), tall(10, 12)], Chrome::Full, 0, 15, vec![14], 1),
+            // A minimum taller than the area only clips the first pane shown.
+            (vec![tall(10, 30), expanded(10)], Chrome::Full, 0, 12, vec![11], 1),
+            // Minimums count content rows, so chrome changes the height.
+            (vec![tall(10, 8), expanded(10)], Chrome::None, 0, 12, vec![9, 3], 2),
+        ];
+
+        for (i, (panes, chrome, start, height, want, want_end)) in cases.into_iter().enumerate() {
+            let area = Rect::new(0, 2, 40, height);
+            let (layout, end) = compute_visible_layout(&panes, chrome, INDICATOR_HEIGHT, start, area);
+            assert_eq!(heights(&layout), want, "case {}", i);
+            assert_eq!(end, want_end, "case {}", i);
+            assert_well_formed(panes.len(), INDICATOR_HEIGHT, start, area, &layout, end);
+        }
+    }
+
+    #[test]
+    fn without_indicator_rows_panes_get_the_whole_area() {
+        let panes = vec![expanded(10), expanded(10), expanded(10), expanded(10)];
+        let area = Rect::new(0, 2, 40, 11);
+        let (layout, end) = compute_visible_layout(&panes, Chrome::Full, 0, 1, area);
+        assert_eq!(layout, [(1, Rect::new(0, 2, 40, 6)), (2, Rect::new(0, 8, 40, 5))]);
+        assert_eq!(end, 3);
+        assert_well_formed(panes.len(), 0, 1, area, &layout, end);
+    }
+
+    #[test]
+    fn viewport_follows_focus() {
+        let stack = || -> Vec<P> {
+            vec![expanded(10), expanded(10), collapsed(), collapsed(), expanded(10), expanded(10)]
+        };
+        // (panes, focused, current start, height, expected start)
+        type Case = (Vec<P>, usize, usize, u16, usize);
+        let cases: Vec<Case> = vec![
+            // Focus already visible: nothing moves.
+            (stack(), 1, 0, 12, 0),
+            // Focus below the viewport scrolls just far enough.
+            (stack(), 3, 0, 12, 2),
+            (stack(), 5, 0, 12, 4),
+            // Focus above the viewport: start at the focused pane...
+            (stack(), 1, 4, 12, 1),
+            // ...unless the panes below it all fit, then pack downward.
+            (stack(), 3, 4, 17, 2),
+            (stack(), 2, 5, 20, 2),
+            // Everything fits: always start at the top.
+            (stack(), 5, 3, 40, 0),
+            // A focused pane taller than the area is shown first, clipped.
+            (vec![expanded(10), expanded(10), expanded(10)], 2, 0, 3, 2),
+            (vec![expanded(10), expanded(10), expanded(10)], 1, 2, 3, 1),
+            // Out-of-range focus is treated as the last pane.
+            (stack(), 9, 0, 12, 4),
+        ];
+
+        for (i, (panes, focused, start, height, want)) in cases.into_iter().enumerate() {
+            let got =
+                viewport_start_for(&panes, Chrome::Full, INDICATOR_HEIGHT, ViewportPolicy::Minimal, focused, start, height);
+            assert_eq!(got, want, "case {}", i);
+            let focused = focused.min(panes.len() - 1);
+            assert!(got <= focused, "case {}", i);
+            assert!(focused < compute_visible_end(&panes, Chrome::Full, INDICATOR_HEIGHT, got, height), "case {}", i);
+        }
+    }
+
+    #[test]
+    fn centered_and_context_viewports_keep_neighbours_in_view() {
+        use ViewportPolicy::{Centered, Context, Minimal};
+        // Nine collapsed panes, three shown at a time between the
+        // indicator rows.
+        let panes: Vec<P> = (0..9).map(|_| collapsed()).collect();
+        // (policy, focused, current start, height, expected start)
+        let cases = [
+            // Walking down: minimal pins focus to the bottom edge...
+            (Minimal, 2, 0, 12, 0),
+            (Minimal, 3, 0, 12, 1),
+            // ...centered keeps one pane below it in view.
+            (Centered, 1, 0, 12, 0),
+            (Centered, 2, 0, 12, 1),
+            (Centered, 4, 0, 12, 3),
+            (Centered, 4, 4, 12, 3),
+            // Top of the list: nothing above to center against.
+            (Centered, 0, 5, 12, 0),
+            // Bottom of the list: the last page, focus at its bottom.
+            (Centered, 8, 0, 12, 6),
+            (Centered, 7, 0, 12, 6),
+            (Context(1), 2, 0, 12, 1),
+            (Context(1), 1, 0, 12, 0),
+            (Context(1), 4, 4, 12, 3),
+            (Context(1), 0, 3, 12, 0),
+            (Context(1), 8, 0, 12, 6),
+            // More context than fits shrinks to what does.
+            (Context(5), 4, 0, 12, 3),
+            // Room for the focused pane alone: same as minimal.
+            (Context(1), 4, 0, 6, 4),
+            (Centered, 4, 8, 6, 4),
+        ];
+        for (i, (policy, focused, start, height, want)) in cases.into_iter().enumerate() {
+            let got = viewport_start_for(&panes, Chrome::Full, INDICATOR_HEIGHT, policy, focused, start, height);
+            assert_eq!(got, want, "case {} ({:?})", i, policy);
+        }
+    }
+
+    #[test]
+    fn many_collapsed_panes_never_overrun() {
+        for count in 1..12 {
+            for height in 0..40 {
+                for start in 0..count {
+                    let panes: Vec<P> = (0..count)
+                        .map(|i| if i % 3 == 0 { expanded(7) } else { collapsed() })
+                        .collect();
+                    let area = Rect::new(0, 1, 30, height);
+                    let (layout, end) = compute_visible_layout(&panes, Chrome::Full, INDICATOR_HEIGHT, start, area);
+                    assert_well_formed(panes.len(), INDICATOR_HEIGHT, start, area, &layout, end);
+                    assert_eq!(end, compute_visible_end(&panes, Chrome::Full, INDICATOR_HEIGHT, start, height));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn split_slots_share_rows_side_by_side() {
+        let panes = vec![expanded(10), beside(0), expanded(10)];
+        let area = Rect::new(0, 0, 41, 20);
+        let (layout, end) = compute_visible_layout(&panes, Chrome::Full, INDICATOR_HEIGHT, 0, area);
+        assert_eq!(end, 3);
+        assert_eq!(
+            layout,
+            [
+                (0, Rect::new(0, 0, 21, 10)),
+                (1, Rect::new(21, 0, 20, 10)),
+                (2, Rect::new(0, 10, 41, 10)),
+            ]
+        );
+
+        // The taller minimum of the two sets the slot's; when the slot below
+        // doesn't fit it moves out of view with both halves.
+        let panes = vec![expanded(10), expanded(10), beside(12)];
+        let (layout, end) = compute_visible_layout(&panes, Chrome::Full, INDICATOR_HEIGHT, 0, Rect::new(0, 0, 40, 15));
+        assert_eq!((heights(&layout), end), (vec![14], 1));
+
+        // A viewport never starts in the middle of a slot.
+        let panes = vec![expanded(10), expanded(10), beside(0), expanded(10)];
+        assert_eq!(viewport_start_for(&panes, Chrome::Full, INDICATOR_HEIGHT, ViewportPolicy::Minimal, 2, 2, 6), 1);
+        assert_eq!(compute_visible_end(&panes, Chrome::Full, INDICATOR_HEIGHT, 1, 6), 3);
+    }
+
+    /// A `min_height` near `u16::MAX` among many collapsed panes in an area
+    /// a few rows tall: the sums saturate instead of overflowing.
+    #[test]
+    fn huge_min_heights_among_collapsed_panes_fit_a_tiny_area() {
+        let mut panes: Vec<P> = (0..14).map(|_| collapsed()).collect();
+        panes.insert(7, tall(10, u16::MAX));
+        panes.push(tall(0, u16::MAX - 1));
+        for chrome in [Chrome::Full, Chrome::Compact, Chrome::None] {
+            for height in 0..6 {
+                let area = Rect::new(0, 2, 30, height);
+                for start in 0..panes.len() {
+                    let (layout, end) = compute_visible_layout(&panes, chrome, INDICATOR_HEIGHT, start, area);
+                    assert_well_formed(panes.len(), INDICATOR_HEIGHT, start, area, &layout, end);
+                    let policy = ViewportPolicy::Centered;
+                    let got = viewport_start_for(&panes, chrome, INDICATOR_HEIGHT, policy, 7, start, height);
+                    assert!(got <= 7);
+                }
+            }
+        }
+    }
+
+    /// Random stacks, chromes, sizes and starts: the layout always stays
+    /// inside the area and clear of the indicator rows, and matches
+    /// `compute_visible_end`.
+    #[test]
+    fn random_layouts_stay_inside_the_area() {
+        // xorshift64, so failures reproduce without a rand dependency.
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let mut next = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+        for _ in 0..20_000 {
+            let count = 1 + next(20) as usize;
+            let panes: Vec<P> = (0..count)
+                .map(|_| P {
+                    collapsed: next(3) > 0,
+                    weight: next(25) as u16,
+                    min_rows: if next(4) == 0 { next(15) as u16 } else { 0 },
+                    split_right: next(4) == 0,
+                })
+                .collect();
+            let chrome = [Chrome::Full, Chrome::Compact, Chrome::None][next(3) as usize];
+            let indicator = next(2) as u16;
+            let start = next(count as u64) as usize;
+            let area = Rect::new(0, next(4) as u16, 30, next(45) as u16);
+
+            let (layout, end) = compute_visible_layout(&panes, chrome, indicator, start, area);
+            assert_well_formed(panes.len(), indicator, start, area, &layout, end);
+            assert_eq!(end, compute_visible_end(&panes, chrome, indicator, start, area.height));
+            let used: u16 = layout.iter().filter(|(_, r)| r.x == area.x).map(|(_, r)| r.height).sum();
+            assert!(used <= area.height, "{:?} in {:?}", layout, area);
+            assert!(layout.iter().all(|(i, _)| (start..end).contains(i)));
+
+            let focused = next(count as u64) as usize;
+            let policy = [ViewportPolicy::Minimal, ViewportPolicy::Centered, ViewportPolicy::Context(next(4) as usize)]
+                [next(3) as usize];
+            let got = viewport_start_for(&panes, chrome, indicator, policy, focused, start, area.height);
+            assert!(got <= focused);
+            assert!(focused < compute_visible_end(&panes, chrome, indicator, got, area.height).max(got + 1));
+        }
+    }
+
+    #[test]
+    fn dry_run_lays_out_other_weights_without_changing_them() {
+        let panes = vec![expanded(10), collapsed(), expanded(10)];
+        let area = Rect::new(0, 0, 40, 23);
+        let (now, _) = compute_visible_layout(&panes, Chrome::Full, 1, 0, area);
+        assert_eq!(heights(&now), [10, 3, 10]);
+        assert_eq!(expanded_share(&panes, &now, 0), Some(50));
+        assert_eq!(expanded_share(&panes, &now, 1), None);
+
+        let wider = dry_run_layout(&panes, &[30, 10, 10], Chrome::Full, 1, 0, area);
+        assert_eq!(heights(&wider), [13, 3, 7]);
+        assert_eq!(expanded_share(&panes, &wider, 0), Some(65));
+        assert_eq!(panes[0].weight, 10);
+    }
+}
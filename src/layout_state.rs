@@ -0,0 +1,311 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::config::PaneConfig;
+use crate::pane::Pane;
+
+/// How often the event loop writes changed layout state while running.
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Per-pane layout the user adjusted at runtime, keyed by pane name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaneLayout {
+    pub name: String,
+    pub weight: u16,
+    #[serde(default)]
+    pub collapsed: bool,
+}
+
+/// Layout presets saved with Alt+Shift+1..4, by slot number.
+pub type Presets = BTreeMap<u8, Vec<PaneLayout>>;
+
+/// Contents of the state file. Entries are stored in stack order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LayoutState {
+    #[serde(default)]
+    pub panes: Vec<PaneLayout>,
+    /// Saved presets; TOML keys are strings, so the slot is written out.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub presets: BTreeMap<String, Vec<PaneLayout>>,
+}
+
+/// Weight and collapsed state of each pane, in stack order.
+pub fn pane_layouts(panes: &[Pane]) -> Vec<PaneLayout> {
+    panes
+        .iter()
+        .map(|p| PaneLayout {
+            name: p.name.clone(),
+            weight: p.weight,
+            collapsed: p.collapsed,
+        })
+        .collect()
+}
+
+/// Copy weight and collapsed state from `entries` onto panes with matching
+/// names; entries naming no pane are skipped. Weights are clamped later
+/// against the configured maximum.
+pub fn apply_layouts(entries: &[PaneLayout], panes: &mut [Pane]) {
+    let mut used = vec![false; entries.len()];
+    for pane in panes {
+        let found = entries
+            .iter()
+            .enumerate()
+            .position(|(i, e)| !used[i] && e.name == pane.name);
+        if let Some(idx) = found {
+            used[idx] = true;
+            pane.weight = entries[idx].weight.max(1);
+            pane.collapsed = entries[idx].collapsed;
+        }
+    }
+}
+
+impl LayoutState {
+    pub fn capture(panes: &[Pane], presets: &Presets) -> Self {
+        Self {
+            panes: pane_layouts(panes),
+            presets: presets.iter().map(|(slot, p)| (slot.to_string(), p.clone())).collect(),
+        }
+    }
+
+    /// Presets with a valid slot number.
+    pub fn presets(&self) -> Presets {
+        self.presets
+            .iter()
+            .filter_map(|(slot, p)| Some((slot.parse().ok()?, p.clone())))
+            .collect()
+    }
+
+    /// Read a state file. A missing or unreadable file yields `None`; a stale
+    /// layout must never stop bamboo from starting.
+    pub fn load(path: &Path) -> Option<Self> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        match toml::from_str(&contents) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                tracing::warn!(path = %path.display(), error = %e, "ignoring unreadable layout state");
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let tmp = path.with_extension("toml.tmp");
+        std::fs::write(&tmp, toml::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        std::fs::rename(&tmp, path)
+            .with_context(|| format!("Failed to replace {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Index of the first entry named `name` not yet claimed in `used`.
+    fn claim(&self, name: &str, used: &mut [bool]) -> Option<usize> {
+        let idx = self
+            .panes
+            .iter()
+            .enumerate()
+            .position(|(i, p)| !used[i] && p.name == name)?;
+        used[idx] = true;
+        Some(idx)
+    }
+
+    /// Reorder `configs` to match the saved order.
+    ///
+    /// Only panes with a saved entry move, and only among the slots such panes
+    /// already occupy, so panes added to the config since keep their place.
+    pub fn reorder(&self, configs: &mut Vec<PaneConfig>) {
+        let mut used = vec![false; self.panes.len()];
+        let mut known: Vec<(usize, usize)> = Vec::new();
+        for (i, config) in configs.iter().enumerate() {
+            if let Some(saved) = self.claim(&config.name, &mut used) {
+                known.push((i, saved));
+            }
+        }
+        let slots: Vec<usize> = known.iter().map(|&(slot, _)| slot).collect();
+        known.sort_by_key(|&(_, saved)| saved);
+
+        let mut taken: Vec<Option<PaneConfig>> = std::mem::take(configs).into_iter().map(Some).collect();
+        let mut placed: Vec<Option<PaneConfig>> = (0..taken.len()).map(|_| None).collect();
+        for (&slot, &(from, _)) in slots.iter().zip(&known) {
+            placed[slot] = taken[from].take();
+        }
+        for (slot, rest) in placed.iter_mut().zip(taken) {
+            if slot.is_none() {
+                *slot = rest;
+            }
+        }
+        *configs = placed.into_iter().flatten().collect();
+    }
+
+    /// Copy saved weight and collapsed state onto panes with matching names.
+    /// Weights are clamped later against the configured maximum.
+    pub fn apply(&self, panes: &mut [Pane]) {
+        apply_layouts(&self.panes, panes);
+    }
+}
+
+/// State file for the config at `source` (or the current directory when the
+/// config didn't come from a file): `<state dir>/bamboo/<hash>.toml`.
+pub fn state_path(source: Option<&Path>) -> Option<PathBuf> {
+    let key = match source {
+        Some(path) => path.to_path_buf(),
+        None => std::env::current_dir().ok()?,
+    };
+    Some(state_dir()?.join(format!("{:016x}.toml", fnv1a(key.to_string_lossy().as_bytes()))))
+}
+
+/// Where bamboo keeps state between sessions: `<state dir>/bamboo`.
+pub fn state_dir() -> Option<PathBuf> {
+    Some(dirs::state_dir().or_else(dirs::data_local_dir)?.join("bamboo"))
+}
+
+/// Marker left once the welcome screen has been answered, so it is shown
+/// only once.
+pub fn welcome_marker() -> Option<PathBuf> {
+    Some(state_dir()?.join("welcomed"))
+}
+
+/// Whether a session opens on the welcome screen: only without a config
+/// file, until the welcome has been answered, and never with `--no-welcome`.
+pub fn show_welcome(no_welcome: bool, has_config: bool, marker: Option<&Path>) -> bool {
+    !no_welcome && !has_config && marker.is_some_and(|marker| !marker.exists())
+}
+
+/// FNV-1a, used instead of `DefaultHasher` so file names stay stable across
+/// Rust releases.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Writes the layout to its state file when it changes.
+pub struct LayoutPersistence {
+    path: PathBuf,
+    last_saved: LayoutState,
+    last_check: Instant,
+}
+
+impl LayoutPersistence {
+    pub fn new(path: PathBuf, panes: &[Pane], presets: &Presets) -> Self {
+        Self {
+            path,
+            last_saved: LayoutState::capture(panes, presets),
+            last_check: Instant::now(),
+        }
+    }
+
+    /// Save if the layout or presets changed. Unless `force` is set this is
+    /// rate-limited to once per `SAVE_INTERVAL`.
+    pub fn save_if_changed(&mut self, panes: &[Pane], presets: &Presets, force: bool) {
+        if !force && self.last_check.elapsed() < SAVE_INTERVAL {
+            return;
+        }
+        self.last_check = Instant::now();
+        let state = LayoutState::capture(panes, presets);
+        if state == self.last_saved {
+            return;
+        }
+        match state.save(&self.path) {
+            Ok(()) => {
+                tracing::debug!(path = %self.path.display(), "saved layout state");
+                self.last_saved = state;
+            }
+            Err(e) => tracing::warn!(error = %e, "failed to save layout state"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(names: &[&str]) -> LayoutState {
+        let panes = names.iter().map(|&name| PaneLayout { name: name.to_string(), weight: 2, collapsed: false });
+        LayoutState { panes: panes.collect(), presets: BTreeMap::new() }
+    }
+
+    fn reordered(saved: &[&str], configured: &[&str]) -> Vec<String> {
+        let mut configs: Vec<PaneConfig> = configured
+            .iter()
+            .map(|&name| PaneConfig { name: name.to_string(), ..Default::default() })
+            .collect();
+        state(saved).reorder(&mut configs);
+        configs.into_iter().map(|c| c.name).collect()
+    }
+
+    fn pane(name: &str) -> Pane {
+        let config = PaneConfig { name: name.to_string(), ..Default::default() };
+        Pane::spawn_failed(0, &config, &anyhow::anyhow!("not started"), 20, 5)
+    }
+
+    #[test]
+    fn saved_order_moves_panes_to_the_first_and_last_slots() {
+        assert_eq!(reordered(&["c", "a", "b"], &["a", "b", "c"]), ["c", "a", "b"]);
+        assert_eq!(reordered(&["b", "c", "a"], &["a", "b", "c"]), ["b", "c", "a"]);
+        assert_eq!(reordered(&["a", "b", "c"], &["a", "b", "c"]), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn reordering_leaves_unsaved_panes_in_place_and_skips_missing_ones() {
+        // `new` was added to the config since; `gone` was removed from it.
+        assert_eq!(reordered(&["c", "gone", "a"], &["a", "new", "c"]), ["c", "new", "a"]);
+        assert_eq!(reordered(&["x", "y", "z", "a"], &["a"]), ["a"]);
+        assert_eq!(reordered(&[], &["a", "b"]), ["a", "b"]);
+        assert!(reordered(&["a"], &[]).is_empty());
+        // Panes sharing a name are matched in order.
+        assert_eq!(reordered(&["b", "a", "a"], &["a", "a", "b"]), ["b", "a", "a"]);
+    }
+
+    #[test]
+    fn applying_copies_weights_and_collapse_by_name() {
+        let mut saved = state(&["b", "a", "gone"]);
+        saved.panes[0].collapsed = true;
+        saved.panes[1].weight = 0;
+        let mut panes = vec![pane("a"), pane("b"), pane("new")];
+        saved.apply(&mut panes);
+        let applied: Vec<(u16, bool)> = panes.iter().map(|p| (p.weight, p.collapsed)).collect();
+        assert_eq!(applied, [(1, false), (2, true), (pane("new").weight, false)]);
+    }
+
+    #[test]
+    fn state_files_round_trip() {
+        let path = std::env::temp_dir().join(format!("bamboo-layout-{}", std::process::id())).join("state.toml");
+        let mut saved = state(&["api", "web"]);
+        saved.panes[1].collapsed = true;
+        saved.presets.insert("2".to_string(), state(&["web"]).panes);
+        saved.presets.insert("first".to_string(), Vec::new());
+        saved.save(&path).unwrap();
+
+        let loaded = LayoutState::load(&path).unwrap();
+        assert_eq!(loaded, saved);
+        assert_eq!(loaded.presets().keys().collect::<Vec<_>>(), [&2]);
+
+        std::fs::write(&path, "panes = 3").unwrap();
+        assert_eq!(LayoutState::load(&path), None);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_eq!(LayoutState::load(&path), None);
+    }
+
+    #[test]
+    fn the_welcome_shows_once_and_only_without_a_config() {
+        let dir = std::env::temp_dir().join(format!("bamboo-welcome-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let marker = dir.join("welcomed");
+        assert!(show_welcome(false, false, Some(&marker)));
+        assert!(!show_welcome(false, true, Some(&marker)), "a config file is present");
+        assert!(!show_welcome(true, false, Some(&marker)), "--no-welcome");
+        assert!(!show_welcome(false, false, None), "nowhere to remember it was answered");
+
+        std::fs::write(&marker, "").unwrap();
+        assert!(!show_welcome(false, false, Some(&marker)), "already answered");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
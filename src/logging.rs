@@ -0,0 +1,50 @@
+use std::fs::OpenOptions;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use tracing::Level;
+
+/// Parse a `--log-level` / `log_level` value. Defaults to `info`.
+pub fn parse_level(level: Option<&str>) -> Result<Level> {
+    match level.map(|l| l.to_ascii_lowercase()).as_deref() {
+        None | Some("info") => Ok(Level::INFO),
+        Some("error") => Ok(Level::ERROR),
+        Some("warn") | Some("warning") => Ok(Level::WARN),
+        Some("debug") => Ok(Level::DEBUG),
+        Some("trace") => Ok(Level::TRACE),
+        Some(other) => anyhow::bail!(
+            "Unknown log level {:?} (expected error, warn, info, debug or trace)",
+            other
+        ),
+    }
+}
+
+/// Install a global subscriber that appends plain-text records to `path`.
+///
+/// Must be called before raw mode / the alternate screen are entered so that
+/// a permission error is printed somewhere the user can read it. Also installs
+/// a panic hook that records the panic before the default hook runs.
+pub fn init(path: &Path, level: Level) -> Result<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open log file {}", path.display()))?;
+
+    tracing_subscriber::fmt()
+        .with_writer(std::sync::Mutex::new(file))
+        .with_ansi(false)
+        .with_max_level(level)
+        .with_target(false)
+        .try_init()
+        .map_err(|e| anyhow::anyhow!("Failed to install logger: {}", e))?;
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        tracing::error!(panic = %info, "bamboo panicked");
+        default_hook(info);
+    }));
+
+    tracing::info!(version = env!("CARGO_PKG_VERSION"), "logging started");
+    Ok(())
+}
@@ -1,17 +1,41 @@
 mod app;
+mod clipboard;
+mod command_capture;
 mod config;
 mod events;
+mod images;
+mod keys;
+mod layout;
+mod layout_state;
+mod logging;
+mod nesting;
 mod pane;
+mod parse_load;
+mod pause;
+mod prompt;
 mod pty;
+mod report;
+mod session;
+mod signal;
+mod stats;
+mod stream;
 mod terminal;
+mod throughput;
+mod title;
 mod ui;
+mod watch;
 mod wizard;
 mod worktree;
 
 use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
 use crossterm::{
+    cursor::MoveTo,
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
     event::{EnableMouseCapture, DisableMouseCapture},
@@ -20,22 +44,85 @@ use ratatui::Terminal;
 use ratatui::backend::CrosstermBackend;
 use tokio::sync::mpsc;
 
-use app::AppState;
-use config::{Config, ConfigSource};
-use events::{AppEvent, run_event_loop};
+use app::{Action, AppState, FocusFollow, WheelConfig};
+use config::{Chrome, Config, ConfigReload, ConfigSource, ViewportIndicator};
+use events::{AppEvent, PtySpawner, run_event_loop, spawn_pane};
 use pane::Pane;
-use pty::{PtyEvent, launch_reader_task, spawn_pty};
 
-struct TerminalGuard;
+/// Saves the outer terminal's window title on its title stack (xterm
+/// `CSI 22 t`); most terminals that set titles support it.
+const PUSH_TITLE: &str = "\x1b[22;0t";
+/// Restores the title saved by `PUSH_TITLE`.
+const POP_TITLE: &str = "\x1b[23;0t";
+
+/// Set once the outer terminal is back to normal, so the panic hook and
+/// the guard don't both restore it.
+static TERMINAL_RESTORED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone)]
+struct TerminalGuard {
+    /// Whether bamboo sets the window title and has to put it back.
+    window_title: bool,
+    title_fallback: Option<String>,
+    /// Whether bamboo draws in the alternate screen rather than inline.
+    alternate_screen: bool,
+}
+
+impl TerminalGuard {
+    /// Take over the outer terminal: raw mode, with `alternate_screen` the
+    /// alternate screen and, with `mouse`, mouse capture. A panic leaves all
+    /// of them before its message is printed.
+    fn enter(window_title: bool, title_fallback: Option<String>, alternate_screen: bool, mouse: bool) -> Result<Self> {
+        let guard = Self { window_title, title_fallback, alternate_screen };
+        enable_raw_mode()?;
+        let mut stdout = io::stdout();
+        if window_title {
+            stdout.write_all(PUSH_TITLE.as_bytes())?;
+        }
+        if alternate_screen {
+            execute!(stdout, EnterAlternateScreen)?;
+        }
+        if mouse {
+            execute!(stdout, EnableMouseCapture)?;
+        }
+
+        let hook_guard = guard.clone();
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            hook_guard.restore();
+            default_hook(info);
+        }));
+        Ok(guard)
+    }
+
+    fn restore(&self) {
+        if TERMINAL_RESTORED.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let _ = disable_raw_mode();
+        let mut stdout = io::stdout();
+        if self.alternate_screen {
+            let _ = execute!(stdout, LeaveAlternateScreen);
+        } else {
+            // The last frame fills the screen; the shell goes on below it.
+            let rows = crossterm::terminal::size().map_or(1, |(_, rows)| rows);
+            let _ = execute!(stdout, MoveTo(0, rows.saturating_sub(1)));
+            let _ = stdout.write_all(b"\r\n");
+        }
+        let _ = execute!(stdout, DisableMouseCapture);
+        if self.window_title {
+            let _ = match &self.title_fallback {
+                Some(title) => execute!(stdout, crossterm::terminal::SetTitle(title)),
+                None => stdout.write_all(POP_TITLE.as_bytes()),
+            };
+        }
+        let _ = stdout.flush();
+    }
+}
 
 impl Drop for TerminalGuard {
     fn drop(&mut self) {
-        let _ = disable_raw_mode();
-        let _ = execute!(
-            io::stdout(),
-            LeaveAlternateScreen,
-            DisableMouseCapture
-        );
+        self.restore();
     }
 }
 
@@ -74,27 +161,89 @@ impl Drop for WorktreeGuard {
     }
 }
 
-/// Parse CLI arguments and return (config_path, worktree_name).
-///
-/// `worktree_name` (from the `--shoot` / `-s` flag):
-///   - `None`        → no `--shoot` / `-s` flag
-///   - `Some(name)`  → `--shoot` / `-s` flag present; `name` is either the
-///                     supplied value or an auto-generated one
-fn parse_args() -> (Option<String>, Option<String>) {
+/// Command-line options.
+#[derive(Default)]
+struct CliArgs {
+    config_path: Option<String>,
+    /// From the `--shoot` / `-s` flag:
+    ///   - `None`        → no `--shoot` / `-s` flag
+    ///   - `Some(name)`  → `--shoot` / `-s` flag present; `name` is either the
+    ///     supplied value or an auto-generated one
+    worktree_name: Option<String>,
+    log_file: Option<String>,
+    log_level: Option<String>,
+    /// Ignore the saved layout for this session (`--fresh`).
+    fresh: bool,
+    /// Skip the first-run welcome screen (`--no-welcome`).
+    no_welcome: bool,
+    /// Skip the notice about running inside tmux or another bamboo
+    /// (`--allow-nested`).
+    allow_nested: bool,
+    /// Panes appended to the config's, as command and optional name
+    /// (`--extra CMD [--name NAME]`).
+    extras: Vec<(String, Option<String>)>,
+    /// `PANE.FIELD=VALUE` changes to the config's panes (`--override`).
+    overrides: Vec<String>,
+    /// Pane to focus at startup, by name, display index or `#ID`
+    /// (`--focus`); wins over the config's `focus`.
+    focus: Option<String>,
+    /// Draw inline in the primary screen (`--no-alt-screen`).
+    no_alt_screen: bool,
+}
+
+fn parse_args() -> Result<CliArgs> {
     let args: Vec<String> = std::env::args().collect();
-    let mut config_path: Option<String> = None;
-    let mut worktree_name: Option<String> = None;
+    let mut cli = CliArgs::default();
     let mut i = 1;
 
     while i < args.len() {
         match args[i].as_str() {
-            "--config" => {
-                if i + 1 < args.len() {
-                    config_path = Some(args[i + 1].clone());
-                    i += 2;
-                } else {
-                    i += 1;
+            "--config" if i + 1 < args.len() => {
+                cli.config_path = Some(args[i + 1].clone());
+                i += 2;
+            }
+            "--log-file" if i + 1 < args.len() => {
+                cli.log_file = Some(args[i + 1].clone());
+                i += 2;
+            }
+            "--log-level" if i + 1 < args.len() => {
+                cli.log_level = Some(args[i + 1].clone());
+                i += 2;
+            }
+            "--fresh" => {
+                cli.fresh = true;
+                i += 1;
+            }
+            "--no-welcome" => {
+                cli.no_welcome = true;
+                i += 1;
+            }
+            "--allow-nested" => {
+                cli.allow_nested = true;
+                i += 1;
+            }
+            "--no-alt-screen" => {
+                cli.no_alt_screen = true;
+                i += 1;
+            }
+            "--extra" if i + 1 < args.len() => {
+                cli.extras.push((args[i + 1].clone(), None));
+                i += 2;
+            }
+            "--name" if i + 1 < args.len() => {
+                match cli.extras.last_mut() {
+                    Some((_, name @ None)) => *name = Some(args[i + 1].clone()),
+                    _ => anyhow::bail!("--name {:?} must follow an --extra that has no name yet", args[i + 1]),
                 }
+                i += 2;
+            }
+            "--override" if i + 1 < args.len() => {
+                cli.overrides.push(args[i + 1].clone());
+                i += 2;
+            }
+            "--focus" if i + 1 < args.len() => {
+                cli.focus = Some(args[i + 1].clone());
+                i += 2;
             }
             "--shoot" | "-s" => {
                 // If the next argument exists and doesn't look like a flag, treat it
@@ -107,7 +256,7 @@ fn parse_args() -> (Option<String>, Option<String>) {
                     i += 1;
                     worktree::random_name()
                 };
-                worktree_name = Some(name);
+                cli.worktree_name = Some(name);
             }
             _ => {
                 i += 1;
@@ -115,7 +264,7 @@ fn parse_args() -> (Option<String>, Option<String>) {
         }
     }
 
-    (config_path, worktree_name)
+    Ok(cli)
 }
 
 /// After the TUI exits, decide whether to keep or remove the worktree.
@@ -154,11 +303,19 @@ fn handle_worktree_cleanup(wt: &worktree::Worktree) {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let (config_path, worktree_flag) = parse_args();
+    let cli = parse_args()?;
+
+    // Logging from the command line starts before anything else so config
+    // loading is captured too; a log file named only in the config starts
+    // once the config has been read. Both happen before raw mode.
+    let log_level = logging::parse_level(cli.log_level.as_deref())?;
+    if let Some(path) = &cli.log_file {
+        logging::init(&Config::expand_tilde(path), log_level)?;
+    }
 
     // Create a git worktree when --shoot / -s is requested. RAII guard cleans up
     // on error paths (best-effort remove or print path for manual cleanup).
-    let active_worktree: Option<worktree::Worktree> = if let Some(name) = worktree_flag {
+    let active_worktree: Option<worktree::Worktree> = if let Some(name) = cli.worktree_name.clone() {
         let wt = worktree::Worktree::create(&name)?;
         eprintln!(
             "Created shoot '{}' at {}  (branch: {})",
@@ -172,89 +329,249 @@ async fn main() -> Result<()> {
     };
     let mut worktree_guard = WorktreeGuard(active_worktree);
 
-    let mut config = match Config::load(config_path.as_deref())? {
-        ConfigSource::File(c) => c,
+    let mut config = match Config::load(cli.config_path.as_deref())? {
+        ConfigSource::File(c) => *c,
         ConfigSource::NeedsWizard => wizard::run_wizard()?,
     };
+    for (command, name) in &cli.extras {
+        config.add_extra(command, name.as_deref());
+    }
+    for spec in &cli.overrides {
+        config.apply_override(spec)?;
+    }
+
+    if cli.log_file.is_none() {
+        if let Some(path) = &config.log_file {
+            let level = match &cli.log_level {
+                Some(_) => log_level,
+                None => logging::parse_level(config.log_level.as_deref())?,
+            };
+            logging::init(&Config::expand_tilde(path), level)?;
+        }
+    }
+    // Logged only now so a log file named in the config gets them too.
+    let mut startup_warnings = std::mem::take(&mut config.warnings);
+    for warning in &startup_warnings {
+        tracing::warn!("{}", warning);
+    }
+    if cli.no_alt_screen {
+        config.alternate_screen = false;
+    }
+    let host = nesting::Host::from_env();
+    if let Some(host) = host {
+        tracing::info!(?host, "running nested");
+        if !cli.allow_nested && !config.allow_nested {
+            startup_warnings.extend(host.notice());
+        }
+    }
 
     // When running inside a worktree, redirect every pane's working directory
     // to the worktree path so all shells/commands start there in isolation.
-    if let Some(wt) = worktree_guard.get() {
-        let wt_path = wt.path.to_string_lossy().to_string();
+    let worktree_cwd = worktree_guard.get().map(|wt| wt.path.to_string_lossy().to_string());
+    if let Some(wt_path) = &worktree_cwd {
         for pane in &mut config.panes {
             pane.cwd = Some(wt_path.clone());
         }
     }
+    let reload = config.source.clone().map(|path| ConfigReload {
+        path,
+        extras: cli.extras.clone(),
+        overrides: cli.overrides.clone(),
+        cwd: worktree_cwd,
+    });
+
+    // Restore the order saved last session before spawning, so pane ids
+    // follow the on-screen order; weights and collapsed state are applied
+    // once the panes exist.
+    let state_path = if config.persist_layout {
+        layout_state::state_path(config.source.as_deref())
+    } else {
+        None
+    };
+    let saved_layout = match &state_path {
+        Some(path) if !cli.fresh => layout_state::LayoutState::load(path),
+        _ => None,
+    };
+    if let Some(saved) = &saved_layout {
+        saved.reorder(&mut config.panes);
+    }
 
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let _guard = TerminalGuard;
-
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    // Any error from here on is printed after the guard is dropped, once the
+    // terminal is back to normal.
+    let guard = TerminalGuard::enter(
+        config.set_window_title,
+        config.window_title_fallback.clone(),
+        config.alternate_screen,
+        config.mouse,
+    )?;
+
+    let mut terminal = if config.alternate_screen {
+        Terminal::new(CrosstermBackend::new(io::stdout()))?
+    } else {
+        events::inline_terminal()?
+    };
     let size = terminal.size()?;
 
-    let initial_cols = size.width.saturating_sub(2).max(10);
+    let initial_cols = size.width.saturating_sub(2).max(ui::MIN_PTY_COLS);
     let n_panes = config.panes.len().max(1);
-    let initial_rows = (size.height / n_panes as u16).saturating_sub(2).max(5);
+    let initial_rows = (size.height / n_panes as u16).saturating_sub(2).max(ui::MIN_PTY_ROWS);
 
     let (unified_tx, unified_rx) = mpsc::unbounded_channel::<AppEvent>();
 
+    let queue_depth = Arc::new(AtomicUsize::new(0));
+    let hold_reads = Arc::new(AtomicBool::new(false));
+    let spawner = PtySpawner {
+        shell: config.shell(),
+        images: config.images,
+        collapse_cr_updates: config.collapse_cr_updates,
+        scrollback: config.scrollback,
+        unified_tx,
+        queue_depth: queue_depth.clone(),
+        hold_reads: hold_reads.clone(),
+    };
     let mut panes = Vec::new();
     for (i, pane_config) in config.panes.iter().enumerate() {
-        let spawned = spawn_pty(pane_config, &config.default_shell, initial_cols, initial_rows)?;
-
-        let term = terminal::new_term(initial_rows, initial_cols, 1000);
-
-        let (pty_tx, pty_rx) = mpsc::unbounded_channel::<PtyEvent>();
-        launch_reader_task(spawned.reader, term.clone(), pty_tx);
-
-        let pane = Pane::new(
-            i,
-            pane_config.name.clone(),
-            spawned.master,
-            spawned.writer,
-            term,
-            pty_rx,
-            initial_cols,
-            initial_rows,
-        );
+        if pane_config.starts_lazily(config.lazy_start) {
+            panes.push(Pane::not_started(i, pane_config, initial_cols, initial_rows));
+            continue;
+        }
+        // A pane that can't start shows why instead of ending the session.
+        let pane = spawn_pane(i, pane_config, initial_cols, initial_rows, &spawner).unwrap_or_else(|e| {
+            tracing::error!(pane = i, name = %pane_config.name, error = %e, "spawn failed");
+            startup_warnings.push(format!("Pane '{}' failed to start: {:#}", pane_config.name, e));
+            Pane::spawn_failed(i, pane_config, &e, initial_cols, initial_rows)
+        });
         panes.push(pane);
     }
 
     for pane in &mut panes {
-        if let Some(mut pty_rx) = pane.pty_rx.take() {
-            let tx = unified_tx.clone();
-            let pane_id = pane.id;
-            tokio::spawn(async move {
-                while let Some(event) = pty_rx.recv().await {
-                    let is_closed = matches!(event, PtyEvent::Closed);
-                    if tx.send(AppEvent::PtyOutput { pane_id, event }).is_err() {
-                        break;
-                    }
-                    if is_closed {
-                        break;
-                    }
-                }
-            });
+        pane.from_config = true;
+        pane.weight = pane.config.weight.unwrap_or(config.weights.default_weight);
+        pane.images.mode = config.images;
+        pane.commands.collapse_updates = config.collapse_cr_updates;
+    }
+    let focus = match cli.focus.as_deref().map(|target| app::resolve_pane_target(&panes, target)) {
+        Some(Ok(idx)) => idx,
+        Some(Err(e)) => {
+            startup_warnings.push(format!("--focus: {:#}", e));
+            config.focus_index().unwrap_or(0)
         }
+        None => config.focus_index().unwrap_or(0),
+    };
+    if config.start_collapsed {
+        for (i, pane) in panes.iter_mut().enumerate() {
+            pane.collapsed = i != focus;
+        }
+    }
+    if let Some(saved) = &saved_layout {
+        saved.apply(&mut panes);
+        tracing::info!(path = ?state_path, "restored saved layout");
+    }
+    // Tasks are open while they run.
+    for pane in panes.iter_mut().filter(|p| p.is_task()) {
+        pane.collapsed = false;
     }
 
     let shoot_name = worktree_guard.get().map(|wt| wt.name.clone());
-    let mut app = AppState::new(panes, config.layout, config.default_shell, shoot_name);
+    let session_name = shoot_name.clone().unwrap_or_else(|| {
+        std::env::current_dir()
+            .ok()
+            .and_then(|dir| dir.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "bamboo".to_string())
+    });
+    let mut app = AppState::new(panes, config.layout, shoot_name, Box::new(spawner));
+    app.reload = reload;
+    // The first render scrolls the viewport to bring the focused pane into view.
+    app.apply(Action::Focus(focus));
+    app.set_weights(config.weights);
     app.term_cols = size.width;
     app.term_rows = size.height;
+    app.stats.queue_depth = queue_depth;
+    app.color_mode = config.color_mode.resolve();
+    app.bold_is_bright = config.bold_is_bright;
+    app.chrome = config.chrome;
+    app.viewport_indicator = config.viewport_indicator;
+    app.viewport_policy = config.viewport_policy;
+    // Plain mode reads as one title line per pane and sentences for what's
+    // out of view.
+    app.accessible = config.accessible;
+    if config.accessible {
+        app.chrome = Chrome::Compact;
+        app.viewport_indicator = ViewportIndicator::Lines;
+    }
+    app.quiet_redraws = config.quiet_redraws;
+    app.literal_key = config
+        .literal_key
+        .as_deref()
+        .and_then(keys::KeyChord::parse)
+        .unwrap_or_else(|| nesting::Host::default_literal_key(host));
+    app.output_pause = pause::OutputPause::new(config.pause, hold_reads);
+    if let Some(chord) = config.pause_key.as_deref().and_then(keys::KeyChord::parse) {
+        app.output_pause.key = chord;
+    }
+    app.show_exit_bar = config.show_exit_bar;
+    app.show_throughput = config.show_throughput;
+    app.on_last_pane_close = config.on_last_pane_close;
+    app.crosshair = config.crosshair;
+    app.set_mouse(config.mouse);
+    app.alternate_screen = config.alternate_screen;
+    app.collapsed_skip_prompt = config.collapsed_skip_prompt;
+    app.title_interval = Duration::from_secs(1) / config.title_refresh_rate;
+    app.frame_interval = Duration::from_secs(1) / config.max_fps;
+    app.report_lines = config.report_lines;
+    app.focus_finished_tasks = config.focus_finished_tasks;
+    app.stale_after = config.stale_after_secs.map(Duration::from_secs);
+    app.wheel = WheelConfig {
+        lines: config.scroll_lines,
+        natural: config.natural_scroll,
+        acceleration: config.scroll_acceleration,
+    };
+    app.focus_follow = FocusFollow {
+        mode: config.focus_follows_activity,
+        pattern: Some(regex::Regex::new(
+            config.focus_activity_pattern.as_deref().unwrap_or(app::DEFAULT_ACTIVITY_PATTERN),
+        )?),
+        cooldown: Duration::from_secs(config.focus_activity_cooldown),
+    };
+    app.error_pattern = config.error_pattern.as_deref().map(regex::Regex::new).transpose()?;
+    app.templates = std::mem::take(&mut config.templates);
+    app.osc52 = config.osc52;
+    app.osc52_read = config.osc52_read;
+    if let Some(format) = config.title_format.clone() {
+        app.title_format = format;
+    }
+    if config.set_window_title {
+        app.window_title_format = Some(
+            config
+                .window_title_format
+                .clone()
+                .unwrap_or_else(|| title::DEFAULT_WINDOW_TITLE_FORMAT.to_string()),
+        );
+    }
+    app.session_name = session_name;
+    app.startup_warnings = startup_warnings;
+    // A session without a config file gets the cheat sheet, once.
+    let has_config = config.source.is_some() || Path::new(".bamboo.toml").exists();
+    app.welcome = layout_state::show_welcome(cli.no_welcome, has_config, layout_state::welcome_marker().as_deref());
+    if let Some(path) = state_path {
+        // Presets outlive --fresh, which only skips the saved layout.
+        app.presets = layout_state::LayoutState::load(&path).map(|s| s.presets()).unwrap_or_default();
+        app.layout_persistence = Some(layout_state::LayoutPersistence::new(path, &app.panes, &app.presets));
+    }
+    tracing::info!(color_mode = ?app.color_mode, "color mode");
+
+    tracing::info!(panes = app.panes.len(), cols = size.width, rows = size.height, "entering event loop");
+    run_event_loop(&mut terminal, &mut app, unified_rx).await?;
+    tracing::info!("event loop finished");
 
-    run_event_loop(&mut terminal, &mut app, unified_rx, unified_tx).await?;
+    // `process::exit` below skips destructors, and ConPTY children would
+    // otherwise linger.
+    app.close_all();
 
     // Restore terminal and exit immediately so Ctrl+Q alone is enough.
     // (Otherwise the runtime waits for PTY reader tasks and the process can hang until Ctrl+C.)
     drop(terminal); // flush and release backend
-    let _ = crossterm::terminal::disable_raw_mode();
-    let mut stdout = io::stdout();
-    let _ = execute!(stdout, LeaveAlternateScreen, DisableMouseCapture);
-    let _ = stdout.flush();
+    guard.restore();
 
     // Disarm the guard and run interactive cleanup after the terminal is fully
     // restored so the prompt and any output are readable.
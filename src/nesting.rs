@@ -0,0 +1,112 @@
+use crossterm::event::{KeyCode, KeyModifiers};
+
+use crate::app::DEFAULT_LITERAL_KEY;
+use crate::keys::KeyChord;
+
+/// Set to `1` for every pane's process.
+pub const BAMBOO_VAR: &str = "BAMBOO";
+/// Also set to `1` for pane processes of a bamboo that is itself nested.
+pub const NESTED_VAR: &str = "BAMBOO_NESTED";
+
+/// Literal prefix for a bamboo inside another one, whose own prefix takes
+/// `Ctrl+Space` first.
+pub const NESTED_LITERAL_KEY: KeyChord = KeyChord::new(KeyModifiers::CONTROL, KeyCode::Char(']'));
+
+/// A multiplexer bamboo is running inside.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Host {
+    Bamboo,
+    Tmux,
+}
+
+impl Host {
+    /// Detect the host from environment variables; `var` looks one up.
+    /// An outer bamboo wins over a tmux further out.
+    pub fn detect(var: impl Fn(&str) -> Option<String>) -> Option<Host> {
+        let set = |name: &str| var(name).is_some_and(|v| !v.is_empty());
+        if set(BAMBOO_VAR) {
+            Some(Host::Bamboo)
+        } else if set("TMUX") || var("TERM_PROGRAM").as_deref() == Some("tmux") {
+            Some(Host::Tmux)
+        } else {
+            None
+        }
+    }
+
+    /// From this process's environment.
+    pub fn from_env() -> Option<Host> {
+        Self::detect(|name| std::env::var(name).ok())
+    }
+
+    /// What the host is likely to take before bamboo sees it, for the
+    /// startup notice.
+    pub fn notice(self) -> Vec<String> {
+        match self {
+            Host::Bamboo => vec![
+                "Running inside another bamboo: the outer one handles Alt+j/k, Alt+n, Alt+w, Alt+c, Alt+g, \
+                 Alt+v and the other Alt bindings, Ctrl+Q and F12 first; send them here with its literal \
+                 prefix (Ctrl+Space)."
+                    .to_string(),
+                format!(
+                    "This bamboo's literal prefix is {} so the outer one's doesn't swallow it. Both \
+                     capture the mouse; clicks and the wheel reach the outer one first.",
+                    NESTED_LITERAL_KEY
+                ),
+                "Start with --allow-nested or set allow_nested = true to skip this notice.".to_string(),
+            ],
+            Host::Tmux => vec![
+                "Running inside tmux: Alt bindings arrive only after tmux's escape-time (try \
+                 `set -sg escape-time 10`), and with tmux's mouse mode on, tmux takes clicks and the \
+                 wheel."
+                    .to_string(),
+                "Start with --allow-nested or set allow_nested = true to skip this notice.".to_string(),
+            ],
+        }
+    }
+
+    /// The literal prefix to use when the config doesn't name one.
+    pub fn default_literal_key(host: Option<Host>) -> KeyChord {
+        match host {
+            Some(Host::Bamboo) => NESTED_LITERAL_KEY,
+            _ => DEFAULT_LITERAL_KEY,
+        }
+    }
+}
+
+/// Variables every pane's process gets, so programs (and a bamboo started
+/// in a pane) can tell where they run.
+pub fn child_env() -> Vec<(&'static str, &'static str)> {
+    let mut env = vec![(BAMBOO_VAR, "1")];
+    if Host::from_env().is_some() {
+        env.push((NESTED_VAR, "1"));
+    }
+    env
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(vars: &[(&str, &str)]) -> Option<Host> {
+        Host::detect(|name| vars.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string()))
+    }
+
+    #[test]
+    fn detects_the_innermost_known_host() {
+        assert_eq!(detect(&[]), None);
+        assert_eq!(detect(&[("TMUX", "/tmp/tmux-0/default,123,0")]), Some(Host::Tmux));
+        assert_eq!(detect(&[("TERM_PROGRAM", "tmux")]), Some(Host::Tmux));
+        assert_eq!(detect(&[("TERM_PROGRAM", "iTerm.app")]), None);
+        assert_eq!(detect(&[("TMUX", "")]), None);
+        assert_eq!(detect(&[("BAMBOO", "1")]), Some(Host::Bamboo));
+        assert_eq!(detect(&[("TMUX", "x"), ("BAMBOO", "1")]), Some(Host::Bamboo));
+    }
+
+    #[test]
+    fn nested_bamboo_moves_the_literal_prefix() {
+        assert_eq!(Host::default_literal_key(None), DEFAULT_LITERAL_KEY);
+        assert_eq!(Host::default_literal_key(Some(Host::Tmux)), DEFAULT_LITERAL_KEY);
+        assert_eq!(Host::default_literal_key(Some(Host::Bamboo)), NESTED_LITERAL_KEY);
+        assert!(Host::Bamboo.notice()[1].contains("Ctrl+]"));
+    }
+}
@@ -1,52 +1,676 @@
+use alacritty_terminal::event::Event;
 use alacritty_terminal::grid::Scroll;
+use alacritty_terminal::term::TermMode;
 use alacritty_terminal::Term;
-use parking_lot::Mutex;
-use portable_pty::{MasterPty, PtySize};
+use portable_pty::{ChildKiller, ExitStatus, MasterPty};
+use alacritty_terminal::vte::ansi::{Color as AnsiColor, Processor};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::time::{Duration, Instant};
 
-use crate::pty::PtyEvent;
-use crate::terminal::{TermSize, VoidListener};
+use crate::command_capture::{CommandCapture, MarkScanner};
+use crate::config::{PaneConfig, PaneKind};
+use crate::images::{InlineImages, Segment};
+use crate::pty::{PtyInput, ReadCounters};
+use crate::parse_load::ParseLoad;
+use crate::throughput::Throughput;
+use crate::stream::StreamAssembler;
+use crate::terminal::{self, PaneColors, PaneListener, TermSize};
+use crate::ui::{RenderCache, TitleCache};
+use crate::watch::PaneWatcher;
+
+/// Largest single write queued for a paste.
+const PASTE_CHUNK: usize = 4096;
+
+/// Grid width of panes with wrapping off. The process still sees the pane's
+/// real width; only lines longer than this wrap.
+const NOWRAP_COLS: u16 = 512;
+
+/// How long a full-screen program gets to repaint after a resize before
+/// its reflowed screen is shown anyway.
+const RESIZE_SETTLE: Duration = Duration::from_millis(150);
+
+/// A service that exits with an error this soon after starting most likely
+/// never got going, e.g. a mistyped command.
+const EARLY_FAILURE: Duration = Duration::from_secs(2);
+
+/// How long a pane whose reader hit end of file waits for the exit status,
+/// or the reverse, before counting as finished anyway. A backgrounded
+/// grandchild can hold the PTY open long after the process exited.
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How long a `flash` message stays in the title.
+const FLASH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Output is judged binary over windows this long...
+const BINARY_WINDOW: Duration = Duration::from_secs(1);
+/// ...once this much arrives in one...
+const BINARY_MIN_BYTES: usize = 32 << 10;
+/// ...and at least one byte in this many prints as nothing.
+const BINARY_RATIO: usize = 10;
+const BINARY_HINT: &str = "binary output: Alt+x freezes the view, Alt+Shift+L resets the terminal";
+
+/// Most error lines remembered per pane; the latest are kept.
+pub const ERROR_MARK_LIMIT: usize = 1000;
+
+/// What a request to scroll a pane's history did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollOutcome {
+    Scrolled,
+    /// Nothing has scrolled off the top yet.
+    NoHistory,
+    /// A full-screen program owns the screen and does its own scrolling.
+    AppControlled,
+}
+
+/// Where a pane's process is in ending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunState {
+    Running,
+    /// The process exited or the PTY closed, but not both yet; output is
+    /// still being read.
+    Draining,
+    Finished,
+}
+
+/// A pane's connection to its running process.
+struct PtyBackend {
+    master: Box<dyn MasterPty + Send>,
+    input: PtyInput,
+}
+
+/// Lines matching the error pattern, from the last scan of the grid.
+struct ErrorMarks {
+    generation: u64,
+    at: Instant,
+    /// Grid lines as `terminal::matching_lines` numbers them.
+    lines: Vec<i32>,
+}
+
+/// A pane frozen with scroll lock: output is still parsed, but the screen
+/// shows what it did when the lock was taken.
+#[derive(Debug)]
+pub struct ScrollLock {
+    /// Lines of output that arrived since.
+    pub pending_lines: usize,
+    /// The line at the top of the view, as its `terminal::row_key` and the
+    /// grid line it was last found on; `None` once it has scrolled out of
+    /// the history.
+    top: Option<(usize, i32)>,
+}
 
 pub struct Pane {
+    /// Names this run's PTY; a restart gets a new one.
     pub id: usize,
+    /// The `id` of the pane's first run, kept across restarts, for scripts
+    /// and `#ID` targets. Never reused.
+    pub stable_id: usize,
     pub name: String,
-    pub master: Box<dyn MasterPty + Send>,
-    pub writer: Arc<Mutex<Box<dyn std::io::Write + Send>>>,
-    pub term: Arc<Mutex<Term<VoidListener>>>,
-    pub pty_rx: Option<mpsc::UnboundedReceiver<PtyEvent>>,
+    /// `None` when the process couldn't be started.
+    backend: Option<PtyBackend>,
+    /// Why the process couldn't be started.
+    pub spawn_error: Option<String>,
+    /// Waiting under `lazy_start` to be focused or seen; see `not_started`.
+    pub pending_start: bool,
+    pub term: Term<PaneListener>,
+    /// Lines of history `term` keeps.
+    scrollback: usize,
+    /// Receives clipboard requests and similar from `term`.
+    listener: PaneListener,
+    processor: Processor,
+    /// Images cut out of the output before `processor` sees it.
+    pub images: InlineImages,
+    /// Output re-split on character and escape-sequence boundaries for
+    /// consumers that read it as text.
+    output: StreamAssembler,
+    /// Output of the last shell command, when the shell marks commands.
+    pub commands: CommandCapture,
+    /// Set while the pane is frozen.
+    pub scroll_lock: Option<ScrollLock>,
+    /// Short confirmation shown in the title for `FLASH_TIMEOUT`.
+    flash: Option<(String, Instant, Duration)>,
+    /// Start of the current `BINARY_WINDOW`, with the bytes read in it and
+    /// how many of them `terminal::binary_bytes` counted.
+    binary_window: (Instant, usize, usize),
+    /// Totals read from the PTY; bumped by the reader task.
+    pub read: Arc<ReadCounters>,
+    /// Output rate sampled from `read` for the title meter.
+    pub throughput: Throughput,
+    /// Time the event loop spends parsing this pane's output.
+    pub parse_load: ParseLoad,
     pub scroll_offset: usize,
+    /// Columns scrolled off the left edge; only moves when `wrap` is off.
+    pub h_scroll: u16,
+    /// Long lines wrap at `cols`; otherwise the grid is `NOWRAP_COLS` wide.
+    wrap: bool,
     pub cols: u16,
     pub rows: u16,
+    /// Set by a resize while a full-screen program runs; cleared by its
+    /// next output.
+    resized_at: Option<Instant>,
+    /// The pane has finished: see `settle`.
     pub closed: bool,
+    /// Set once the process has been reaped.
+    pub exit_status: Option<ExitStatus>,
+    pub exited_at: Option<Instant>,
+    /// When the reader reached end of file, once nothing holds the PTY open.
+    pub eof_at: Option<Instant>,
+    /// Output arrived since the pane was last drawn.
+    pub activity: bool,
+    /// Bytes of output since the pane was last drawn focused.
+    pub unseen_output: u64,
+    /// Process id, if the platform reports one.
+    pub pid: Option<u32>,
     pub collapsed: bool,
     pub weight: u16,
+    /// Sits in the right half of the previous pane's slot (Alt+|).
+    pub split_right: bool,
+    /// Bumped whenever the visible screen may have changed (output processed,
+    /// resize, scroll).
+    generation: u64,
+    /// Cells drawn on the last frame, reused while `generation` is unchanged.
+    pub render_cache: Option<RenderCache>,
+    /// Title text last shown, refreshed at a limited rate while output flows.
+    pub title_cache: Option<TitleCache>,
+    error_marks: Option<ErrorMarks>,
+    /// Text of the divider the last restart printed; error lines above it
+    /// belong to earlier runs.
+    divider: Option<String>,
+    /// Times the title text changed, for the debug overlay.
+    pub title_repaints: u64,
+    /// Configured default colors, used unless the program sets its own via
+    /// OSC 10 / OSC 11.
+    pub default_fg: Option<AnsiColor>,
+    pub default_bg: Option<AnsiColor>,
+    /// Configured `palette` and `invert_luminance`.
+    pub colors: PaneColors,
+    /// Screen row where the shell's prompt starts while it waits for a
+    /// command, for shells that mark their prompts with OSC 133.
+    pub prompt_row: Option<usize>,
+    /// `terminal::row_key` of the prompt's row, to follow it as output
+    /// scrolls the screen.
+    prompt_key: usize,
+    /// Finds the OSC 133 marks in output as it's read.
+    marks: MarkScanner,
+    /// Command line as shown in titles (the shell for interactive panes).
+    pub command: String,
+    /// Working directory as shown in titles.
+    pub cwd: String,
+    pub started_at: Instant,
+    /// Runs in a row, up to and including this one, that failed early.
+    pub rapid_failures: u32,
+    /// Counts runs of the command in this pane, starting at 1.
+    pub run: u32,
+    /// When the process last wrote output.
+    pub last_output_at: Option<Instant>,
+    /// Quiet for longer than the app's `stale_after`; drawn dimmed unless
+    /// focused.
+    pub stale: bool,
+    /// Per-pane title template; falls back to the global one when unset.
+    pub title_format: Option<String>,
+    /// Show what is running after the name; see `display_name`.
+    pub auto_name: bool,
+    /// The foreground program, as last looked up.
+    pub process_name: Option<String>,
+    /// Title the program set with OSC 0 or 2.
+    pub program_title: Option<String>,
+    /// Config the pane was spawned from, used to restart it.
+    pub config: PaneConfig,
+    /// Listed in the config's `[[panes]]` rather than opened at runtime; a
+    /// reload may rename, restart or close it.
+    pub from_config: bool,
+    /// Terminates the pane's process when the pane is dropped.
+    pub killer: Option<Box<dyn ChildKiller + Send + Sync>>,
+    /// Moves the pane's PTY events onto the unified channel.
+    pub forwarder: Option<tokio::task::JoinHandle<()>>,
+    /// Reruns the command on file changes; set when the config has `watch`.
+    pub watcher: Option<PaneWatcher>,
+    /// File changes are ignored until this is cleared again.
+    pub watch_paused: bool,
 }
 
 impl Pane {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         id: usize,
         name: String,
         master: Box<dyn MasterPty + Send>,
-        writer: Arc<Mutex<Box<dyn std::io::Write + Send>>>,
-        term: Arc<Mutex<Term<VoidListener>>>,
-        pty_rx: mpsc::UnboundedReceiver<PtyEvent>,
+        input: PtyInput,
+        scrollback: usize,
+        read: Arc<ReadCounters>,
         cols: u16,
         rows: u16,
     ) -> Self {
+        Self::with_backend(id, name, Some(PtyBackend { master, input }), scrollback, read, cols, rows)
+    }
+
+    /// A pane for a process that couldn't be started. It shows `error` in
+    /// its body and can be restarted once the cause is fixed.
+    pub fn spawn_failed(id: usize, config: &PaneConfig, error: &anyhow::Error, cols: u16, rows: u16) -> Self {
+        let mut pane = Self::with_backend(id, config.name.clone(), None, 100, Arc::default(), cols, rows);
+        pane.closed = true;
+        pane.spawn_error = Some(format!("{:#}", error));
+        pane.print_spawn_error();
+        pane.command = config.command.clone().unwrap_or_default();
+        pane.title_format = config.title_format.clone();
+        pane.config = config.clone();
+        pane
+    }
+
+    /// A placeholder for a pane `lazy_start` holds back, replaced by the
+    /// real one when it starts.
+    pub fn not_started(id: usize, config: &PaneConfig, cols: u16, rows: u16) -> Self {
+        let mut pane = Self::with_backend(id, config.name.clone(), None, 100, Arc::default(), cols, rows);
+        pane.pending_start = true;
+        pane.print_note("\x1b[2mnot started — starts once shown or focused\x1b[0m\r\n");
+        pane.command = config.command.clone().unwrap_or_default();
+        pane.title_format = config.title_format.clone();
+        pane.auto_name = config.auto_name.unwrap_or(false);
+        pane.config = config.clone();
+        pane
+    }
+
+    fn with_backend(
+        id: usize,
+        name: String,
+        backend: Option<PtyBackend>,
+        scrollback: usize,
+        read: Arc<ReadCounters>,
+        cols: u16,
+        rows: u16,
+    ) -> Self {
+        let listener = PaneListener::default();
         Self {
             id,
+            stable_id: id,
             name,
-            master,
-            writer,
-            term,
-            pty_rx: Some(pty_rx),
+            backend,
+            spawn_error: None,
+            pending_start: false,
+            term: terminal::new_term(rows, cols, scrollback, listener.clone()),
+            scrollback,
+            listener,
+            processor: terminal::new_processor(),
+            images: InlineImages::default(),
+            output: StreamAssembler::default(),
+            commands: CommandCapture::default(),
+            flash: None,
+            binary_window: (Instant::now(), 0, 0),
+            scroll_lock: None,
+            read,
+            throughput: Throughput::default(),
+            parse_load: ParseLoad::default(),
             scroll_offset: 0,
+            h_scroll: 0,
+            wrap: true,
             cols,
             rows,
+            resized_at: None,
             closed: false,
+            exit_status: None,
+            exited_at: None,
+            eof_at: None,
+            activity: false,
+            unseen_output: 0,
+            pid: None,
             collapsed: false,
+            split_right: false,
             weight: 10,
+            generation: 0,
+            render_cache: None,
+            title_cache: None,
+            error_marks: None,
+            divider: None,
+            title_repaints: 0,
+            default_fg: None,
+            default_bg: None,
+            colors: PaneColors::default(),
+            prompt_row: None,
+            prompt_key: 0,
+            marks: MarkScanner::default(),
+            command: String::new(),
+            cwd: String::new(),
+            started_at: Instant::now(),
+            rapid_failures: 0,
+            run: 1,
+            last_output_at: None,
+            stale: false,
+            title_format: None,
+            auto_name: false,
+            process_name: None,
+            program_title: None,
+            config: PaneConfig::default(),
+            from_config: false,
+            killer: None,
+            forwarder: None,
+            watcher: None,
+            watch_paused: false,
+        }
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.read.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Whether this pane's reader is stopped; see `ReadCounters::held`.
+    pub fn reads_held(&self) -> bool {
+        self.read.held.load(Ordering::Relaxed)
+    }
+
+    pub fn hold_reads(&self, held: bool) {
+        self.read.held.store(held, Ordering::Relaxed);
+    }
+
+    pub fn lines_read(&self) -> u64 {
+        self.read.lines.load(Ordering::Relaxed)
+    }
+
+    /// Force the next frame to redraw this pane's content.
+    pub fn mark_dirty(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    /// Feed PTY output into the terminal parser.
+    pub fn process_output(&mut self, bytes: &[u8]) {
+        self.resized_at = None;
+        let mut text = String::new();
+        for segment in self.images.split(bytes) {
+            match segment {
+                Segment::Output(output) => {
+                    self.process_marked(&output);
+                    text.push_str(&self.output.push(&output));
+                }
+                Segment::Image(image) => {
+                    let (_, col) = terminal::cursor_position(&self.term);
+                    if let Some(placeholder) = self.images.insert(image, (self.cols, self.rows), col as u16) {
+                        self.process_following_rows(placeholder.as_bytes());
+                    }
+                }
+            }
+        }
+        self.scroll_offset = self.term.grid().display_offset();
+        self.mark_dirty();
+        self.activity = true;
+        self.unseen_output += bytes.len() as u64;
+        self.last_output_at = Some(Instant::now());
+        self.stale = false;
+
+        if !text.is_empty() {
+            tracing::trace!(pane = self.id, output = ?text, "pane output");
+        }
+        for reply in terminal::capability_replies(&text) {
+            let _ = self.write_input(reply.as_bytes());
+        }
+        self.commands.push(&text);
+        if let Some(lock) = &mut self.scroll_lock {
+            lock.pending_lines += text.matches('\n').count();
+        }
+        self.note_binary(bytes);
+    }
+
+    /// Suggest freezing or resetting the pane in its title while it keeps
+    /// printing what looks like a binary file.
+    fn note_binary(&mut self, bytes: &[u8]) {
+        let (start, total, binary) = &mut self.binary_window;
+        if start.elapsed() >= BINARY_WINDOW {
+            (*start, *total, *binary) = (Instant::now(), 0, 0);
+        }
+        *total += bytes.len();
+        *binary += terminal::binary_bytes(bytes);
+        let (total, binary) = (*total, *binary);
+        if total >= BINARY_MIN_BYTES && binary * BINARY_RATIO >= total && self.flash_message().is_none() {
+            tracing::debug!(pane = self.id, bytes = total, binary, "binary output");
+            self.flash_for(BINARY_HINT, BINARY_WINDOW * 3);
+        }
+    }
+
+    /// Return the terminal to its default modes, character sets and colors
+    /// after output left it garbled, keeping the process, screen and
+    /// scrollback. A half-read escape sequence or image is dropped.
+    pub fn reset_terminal(&mut self) {
+        self.processor = terminal::new_processor();
+        self.images.reset_filter();
+        self.output = StreamAssembler::default();
+        self.marks = MarkScanner::default();
+        terminal::soft_reset(&mut self.term, &mut self.processor);
+        self.mark_dirty();
+    }
+
+    /// Feed output to the terminal, noting where the shell's prompt starts
+    /// from its OSC 133 marks: `A` at the prompt, `C` once a command runs.
+    fn process_marked(&mut self, output: &[u8]) {
+        let mut from = 0;
+        for (end, letter) in self.marks.scan(output) {
+            // Up to and including the mark's letter.
+            self.process_following_rows(&output[from..end]);
+            match letter {
+                b'A' => {
+                    let row = terminal::cursor_position(&self.term).0;
+                    self.prompt_row = Some(row);
+                    self.prompt_key = terminal::row_key(&self.term, row as i32);
+                }
+                b'C' => self.prompt_row = None,
+                _ => {}
+            }
+            from = end;
+        }
+        self.process_following_rows(&output[from..]);
+    }
+
+    /// Feed output to the terminal, moving `prompt_row` with its row as the
+    /// screen scrolls or is cleared, dropping it once the row is gone, and
+    /// keeping a scroll lock's view where it was.
+    fn process_following_rows(&mut self, output: &[u8]) {
+        let following = self.prompt_row.is_some() || self.scroll_lock.is_some();
+        let span = if following { self.follow_span() } else { usize::MAX };
+        for piece in output.chunks(span) {
+            terminal::process_bytes(&mut self.term, &mut self.processor, piece);
+            if let Some(row) = self.prompt_row {
+                let line = terminal::find_line(&self.term, self.prompt_key, row as i32);
+                self.prompt_row = line.and_then(|line| usize::try_from(line).ok());
+            }
+            self.hold_scroll_lock();
+        }
+    }
+
+    /// Most output to feed at once while rows are followed: too little to
+    /// scroll a row all the way round the grid's storage and back to where
+    /// it could be taken for the row followed, since a byte scrolls at most
+    /// one line.
+    fn follow_span(&self) -> usize {
+        let history = if self.term.mode().contains(TermMode::ALT_SCREEN) { 0 } else { self.scrollback };
+        (history + self.rows as usize).saturating_sub(1).max(1)
+    }
+
+    /// Freeze the pane's view, or unfreeze it and jump to the live screen.
+    pub fn toggle_scroll_lock(&mut self) {
+        if self.scroll_lock.take().is_some() {
+            self.term.scroll_display(Scroll::Bottom);
+            self.scroll_offset = 0;
+            self.mark_dirty();
+        } else {
+            let line = -(self.term.grid().display_offset() as i32);
+            let top = Some((terminal::row_key(&self.term, line), line));
+            self.scroll_lock = Some(ScrollLock { pending_lines: 0, top });
+        }
+    }
+
+    /// Lock a scroll-locked pane on where a scroll moved its view, dropping
+    /// the frame it kept showing.
+    fn relock_view(&mut self) {
+        if let Some(lock) = &mut self.scroll_lock {
+            let line = -(self.term.grid().display_offset() as i32);
+            lock.top = Some((terminal::row_key(&self.term, line), line));
+            self.render_cache = None;
+        }
+    }
+
+    /// Scroll a scroll-locked pane back to the line its view started at,
+    /// or as near as the history still reaches.
+    fn hold_scroll_lock(&mut self) {
+        let Some(lock) = &mut self.scroll_lock else {
+            return;
+        };
+        let found = lock.top.and_then(|(key, from)| Some((key, terminal::find_line(&self.term, key, from)?)));
+        lock.top = found;
+        let offset = match found {
+            Some((_, line)) => line.min(0).unsigned_abs() as usize,
+            None => terminal::history_lines(&self.term),
+        };
+        let delta = offset as i32 - self.term.grid().display_offset() as i32;
+        self.term.scroll_display(Scroll::Delta(delta));
+    }
+
+    /// Show `message` in the title for a few seconds.
+    pub fn flash(&mut self, message: impl Into<String>) {
+        self.flash_for(message, FLASH_TIMEOUT);
+    }
+
+    /// Show a message in the title for `timeout`.
+    pub fn flash_for(&mut self, message: impl Into<String>, timeout: Duration) {
+        self.flash = Some((message.into(), Instant::now(), timeout));
+    }
+
+    /// The title message, while it is still fresh.
+    pub fn flash_message(&self) -> Option<&str> {
+        self.flash
+            .as_ref()
+            .filter(|(_, at, timeout)| at.elapsed() < *timeout)
+            .map(|(message, _, _)| message.as_str())
+    }
+
+    pub fn is_task(&self) -> bool {
+        self.config.kind == PaneKind::Task
+    }
+
+    /// A task whose command exited successfully.
+    pub fn task_succeeded(&self) -> bool {
+        self.is_task() && self.exit_status.as_ref().is_some_and(|s| s.success())
+    }
+
+    /// A service that exited with an error within `EARLY_FAILURE` of
+    /// starting; its exit code, if so.
+    pub fn early_failure(&self) -> Option<u32> {
+        let status = self.exit_status.as_ref().filter(|s| !s.success() && !self.is_task())?;
+        (self.run_time()? < EARLY_FAILURE).then(|| status.exit_code())
+    }
+
+    /// Time since the last output, or since the start if there was none.
+    pub fn idle_for(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.last_output_at.unwrap_or(self.started_at))
+    }
+
+    /// How long the process ran, once it has exited.
+    pub fn run_time(&self) -> Option<Duration> {
+        self.exited_at.map(|at| at.duration_since(self.started_at))
+    }
+
+    /// Carry `old`'s screen and scrollback over to this pane so a rerun
+    /// continues below the previous output. The terminal is reused rather
+    /// than rebuilt, so a long history is neither copied nor allocated
+    /// twice: it is soft-reset so the new run doesn't inherit the modes the
+    /// last one left on, fitted in place to this pane's size and wrap mode,
+    /// and given this pane's scrollback limit. This pane's own blank
+    /// terminal goes to `old` and is dropped with it; the parser stays this
+    /// pane's fresh one, so a sequence the old process cut off isn't
+    /// finished by the new one.
+    pub fn take_screen(&mut self, old: &mut Pane) {
+        std::mem::swap(&mut self.term, &mut old.term);
+        std::mem::swap(&mut self.listener, &mut old.listener);
+        std::mem::swap(&mut self.images, &mut old.images);
+        if self.scrollback != old.scrollback {
+            terminal::set_scrollback(&mut self.term, self.scrollback);
+        }
+        self.images.reset_filter();
+        terminal::soft_reset(&mut self.term, &mut self.processor);
+        self.resize_term();
+    }
+
+    /// Write bamboo's own text into the pane. It bypasses the output stream,
+    /// so it neither counts as activity nor reaches consumers of the
+    /// program's output, but is part of the scrollback like anything else.
+    fn print_note(&mut self, text: &str) {
+        terminal::process_bytes(&mut self.term, &mut self.processor, text.as_bytes());
+        self.scroll_offset = self.term.grid().display_offset();
+        self.mark_dirty();
+    }
+
+    /// Show why the process couldn't be started.
+    pub fn print_spawn_error(&mut self) {
+        if let Some(message) = &self.spawn_error {
+            let text = format!("\x1b[31mFailed to start: {}\x1b[0m\r\n", message.replace('\n', "\r\n"));
+            self.print_note(&text);
+        }
+    }
+
+    /// Print a dim, full-width `─── label ───` line where a new run starts.
+    /// Whatever screen state the last run left behind is reset first.
+    pub fn print_divider(&mut self, label: &str) {
+        let width = self.cols as usize;
+        let label: String = format!(" {} ", label).chars().take(width).collect();
+        self.divider = Some(label.clone());
+        let fill = width - label.chars().count();
+        let left = fill / 2;
+        let (_, col) = terminal::cursor_position(&self.term);
+        let text = format!(
+            "\x1b[?1049l\x1b[0m{}\x1b[2m{}{}{}\x1b[0m\r\n",
+            if col > 0 { "\r\n" } else { "" },
+            "─".repeat(left),
+            label,
+            "─".repeat(fill - left),
+        );
+        self.print_note(&text);
+    }
+
+    /// Events the terminal raised while processing output.
+    pub fn take_term_events(&self) -> Vec<Event> {
+        self.listener.take()
+    }
+
+    pub fn run_state(&self) -> RunState {
+        if self.closed {
+            RunState::Finished
+        } else if self.exited_at.is_some() || self.eof_at.is_some() {
+            RunState::Draining
+        } else {
+            RunState::Running
+        }
+    }
+
+    /// The reader reached end of file.
+    pub fn mark_eof(&mut self) {
+        self.eof_at.get_or_insert_with(Instant::now);
+        self.mark_dirty();
+    }
+
+    /// Finish the pane once both the exit status and the reader's end of
+    /// file are in, or one has waited `SETTLE_TIMEOUT` for the other.
+    /// Returns whether it finished just now.
+    pub fn settle(&mut self, now: Instant) -> bool {
+        if self.closed {
+            return false;
+        }
+        let waited = |at: Option<Instant>| at.is_some_and(|at| now.duration_since(at) >= SETTLE_TIMEOUT);
+        let done = (self.exited_at.is_some() && self.eof_at.is_some())
+            || waited(self.exited_at)
+            || waited(self.eof_at);
+        if done {
+            self.mark_closed();
+        }
+        done
+    }
+
+    /// The pane is finished; no more output is expected.
+    pub fn mark_closed(&mut self) {
+        self.closed = true;
+        self.mark_dirty();
+        let text = self.output.flush();
+        if !text.is_empty() {
+            tracing::trace!(pane = self.id, output = ?text, "pane output");
         }
     }
 
@@ -57,50 +681,249 @@ impl Pane {
         if cols == self.cols && rows == self.rows {
             return;
         }
+        tracing::debug!(pane = self.id, name = %self.name, cols, rows, "resizing pane");
 
-        let _ = self.master.resize(PtySize {
-            rows,
-            cols,
-            pixel_width: 0,
-            pixel_height: 0,
-        });
-
-        let size = TermSize {
-            cols: cols as usize,
-            rows: rows as usize,
-        };
-        self.term.lock().resize(size);
+        if let Some(backend) = &self.backend {
+            let _ = backend.master.resize(crate::pty::pty_size(cols, rows));
+        }
 
         self.cols = cols;
         self.rows = rows;
+        self.resize_term();
+        if self.accepts_input() && self.term.mode().contains(TermMode::ALT_SCREEN) {
+            self.resized_at = Some(Instant::now());
+        }
+    }
+
+    /// A full-screen program hasn't repainted since the last resize yet.
+    pub fn settling(&self) -> bool {
+        self.resized_at.is_some_and(|at| at.elapsed() < RESIZE_SETTLE)
+    }
+
+    /// Size the grid for the pane's size and wrap mode.
+    fn resize_term(&mut self) {
+        let cols = if self.wrap { self.cols } else { self.cols.max(NOWRAP_COLS) };
+        self.term.resize(TermSize {
+            cols: cols as usize,
+            rows: self.rows as usize,
+        });
+        // Reflowing moved the rows a scroll lock was following.
+        self.relock_view();
+        self.mark_dirty();
+    }
+
+    pub fn wraps(&self) -> bool {
+        self.wrap
+    }
+
+    /// Turn line wrapping on or off. Lines already wrapped stay wrapped.
+    pub fn set_wrap(&mut self, wrap: bool) {
+        if wrap != self.wrap {
+            self.wrap = wrap;
+            self.h_scroll = 0;
+            self.resize_term();
+        }
+    }
+
+    /// Move the view sideways by `cols`, positive to the right, within the
+    /// widest line on screen. Does nothing while wrapping.
+    pub fn scroll_horizontal(&mut self, cols: i32) {
+        if self.wrap {
+            return;
+        }
+        let max = terminal::content_width(&self.term).saturating_sub(self.cols as usize) as i32;
+        let h_scroll = (self.h_scroll as i32 + cols).clamp(0, max.max(0)) as u16;
+        if h_scroll != self.h_scroll {
+            self.h_scroll = h_scroll;
+            self.mark_dirty();
+        }
+    }
+
+    /// Whether content is cut off at the left and right edges of the view.
+    pub fn clipped_sides(&self) -> (bool, bool) {
+        if self.wrap {
+            return (false, false);
+        }
+        let right = terminal::content_width(&self.term) > (self.h_scroll + self.cols) as usize;
+        (self.h_scroll > 0, right)
+    }
+
+    /// Scroll the history by `lines`, positive moving into it, unless there
+    /// is none to scroll through.
+    pub fn scroll(&mut self, lines: i32) -> ScrollOutcome {
+        if self.term.mode().contains(TermMode::ALT_SCREEN) {
+            return ScrollOutcome::AppControlled;
+        }
+        if terminal::history_lines(&self.term) == 0 {
+            return ScrollOutcome::NoHistory;
+        }
+        if lines > 0 {
+            self.scroll_up(lines as usize);
+        } else {
+            self.scroll_down(lines.unsigned_abs() as usize);
+        }
+        ScrollOutcome::Scrolled
+    }
+
+    /// Lines since the last restart matching `pattern`, rescanned when the
+    /// screen has changed and the last scan is older than `max_age`.
+    pub fn error_lines(&mut self, pattern: &regex::Regex, max_age: Duration) -> &[i32] {
+        let fresh = self
+            .error_marks
+            .as_ref()
+            .is_some_and(|m| m.generation == self.generation || m.at.elapsed() < max_age);
+        if !fresh {
+            self.error_marks = Some(ErrorMarks {
+                generation: self.generation,
+                at: Instant::now(),
+                lines: terminal::matching_lines(&self.term, pattern, self.divider.as_deref(), ERROR_MARK_LIMIT),
+            });
+        }
+        self.error_marks.as_ref().map_or(&[], |m| &m.lines)
+    }
+
+    /// Scroll so grid line `line` is in view, at the top if it is in the
+    /// history. Returns the viewport row it ends up on.
+    pub fn reveal_line(&mut self, line: i32) -> u16 {
+        let offset = line.min(0).unsigned_abs() as i32;
+        self.term.scroll_display(Scroll::Delta(offset - self.term.grid().display_offset() as i32));
+        self.scroll_offset = self.term.grid().display_offset();
+        self.relock_view();
+        self.mark_dirty();
+        (line + self.scroll_offset as i32).max(0) as u16
     }
 
     pub fn scroll_up(&mut self, lines: usize) {
-        let mut term = self.term.lock();
-        term.scroll_display(Scroll::Delta(-(lines as i32)));
-        self.scroll_offset = term.grid().display_offset();
+        self.term.scroll_display(Scroll::Delta(lines as i32));
+        self.scroll_offset = self.term.grid().display_offset();
+        self.relock_view();
+        self.mark_dirty();
     }
 
     pub fn scroll_down(&mut self, lines: usize) {
-        let mut term = self.term.lock();
-
-        let current_offset = term.grid().display_offset();
+        let current_offset = self.term.grid().display_offset();
         if current_offset == 0 {
             self.scroll_offset = 0;
             return;
         }
 
         if lines >= current_offset {
-            term.scroll_display(Scroll::Bottom);
+            self.term.scroll_display(Scroll::Bottom);
         } else {
-            term.scroll_display(Scroll::Delta(lines as i32));
+            self.term.scroll_display(Scroll::Delta(-(lines as i32)));
+        }
+
+        self.scroll_offset = self.term.grid().display_offset();
+        self.relock_view();
+        self.mark_dirty();
+    }
+
+    /// The process couldn't be started or exited with a non-zero status.
+    pub fn failed(&self) -> bool {
+        self.spawn_error.is_some() || self.exit_status.as_ref().is_some_and(|s| !s.success())
+    }
+
+    /// Where keystrokes go while they can still reach the process: not once
+    /// it has exited or a write to its PTY has failed.
+    fn input(&self) -> Option<&PtyInput> {
+        self.backend
+            .as_ref()
+            .map(|b| &b.input)
+            .filter(|input| !self.closed && !input.is_dead())
+    }
+
+    pub fn accepts_input(&self) -> bool {
+        self.input().is_some()
+    }
+
+    /// Process group in the foreground of the pane's terminal: the job the
+    /// shell is running, or the shell itself at its prompt.
+    #[cfg(unix)]
+    pub fn foreground_group(&self) -> Option<u32> {
+        let leader = self.backend.as_ref()?.master.process_group_leader()?;
+        u32::try_from(leader).ok()
+    }
+
+    #[cfg(not(unix))]
+    pub fn foreground_group(&self) -> Option<u32> {
+        None
+    }
+
+    /// Name of the program in the foreground of the pane's terminal, from
+    /// `/proc`: its argv[0], or `comm` when that is empty. `None` while the
+    /// pane's own process is in the foreground, as a shell at its prompt is.
+    #[cfg(target_os = "linux")]
+    pub fn foreground_name(&self) -> Option<String> {
+        let group = self.foreground_group().filter(|&group| Some(group) != self.pid)?;
+        let cmdline = std::fs::read(format!("/proc/{}/cmdline", group)).unwrap_or_default();
+        let argv0 = cmdline.split(|&b| b == 0).next().unwrap_or_default();
+        let name = match std::path::Path::new(&*String::from_utf8_lossy(argv0)).file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => std::fs::read_to_string(format!("/proc/{}/comm", group)).ok()?.trim_end().to_string(),
+        };
+        Some(name).filter(|name| !name.is_empty())
+    }
+
+    /// Without `/proc`, the name of the pane's own program.
+    #[cfg(not(target_os = "linux"))]
+    pub fn foreground_name(&self) -> Option<String> {
+        let program = self.command.split_whitespace().next()?;
+        std::path::Path::new(program).file_name().map(|name| name.to_string_lossy().into_owned())
+    }
+
+    /// What the title's `{name}` shows: with `auto_name`, the name followed
+    /// by the program's own title if it set one, else the foreground
+    /// program's name. `name` itself stays as configured, for addressing.
+    pub fn display_name(&self) -> String {
+        match self.program_title.as_ref().or(self.process_name.as_ref()).filter(|_| self.auto_name) {
+            Some(running) => format!("{} — {}", self.name, running),
+            None => self.name.clone(),
         }
+    }
+
+    /// Queue `data` for the PTY without blocking.
+    pub fn write_input(&self, data: &[u8]) -> anyhow::Result<()> {
+        let Some(input) = self.input() else {
+            anyhow::bail!("pane '{}' no longer accepts input", self.name);
+        };
+        input.send(data.to_vec())
+    }
 
-        self.scroll_offset = term.grid().display_offset();
+    /// Paste `text`, bracketed if the program asked for that. Large pastes
+    /// are queued in pieces so the writer thread never holds one huge buffer
+    /// and a slow reader can't stall anything but that thread.
+    pub fn paste(&self, text: &str) -> anyhow::Result<()> {
+        let Some(input) = self.input() else {
+            anyhow::bail!("pane '{}' no longer accepts input", self.name);
+        };
+        let bracketed = self.term.mode().contains(TermMode::BRACKETED_PASTE);
+        let bytes = crate::clipboard::encode_paste(text, bracketed);
+        for chunk in bytes.chunks(PASTE_CHUNK) {
+            input.send(chunk.to_vec())?;
+        }
+        Ok(())
     }
 
-    pub fn write_input(&self, data: &[u8]) {
-        let mut writer = self.writer.lock();
-        let _ = std::io::Write::write_all(&mut *writer, data);
+    /// End the process, close the PTY and stop forwarding its events. Safe
+    /// to call again; dropping the pane does it too.
+    pub fn shut_down(&mut self) {
+        // Unix shells get SIGHUP when the master closes anyway, but ConPTY
+        // children can outlive the console, so terminate explicitly.
+        if let Some(mut killer) = self.killer.take().filter(|_| !self.closed) {
+            if let Err(e) = killer.kill() {
+                tracing::debug!(pane = self.id, error = %e, "failed to terminate process");
+            }
+        }
+        // Dropping the input ends the writer thread.
+        self.backend = None;
+        if let Some(forwarder) = self.forwarder.take() {
+            forwarder.abort();
+        }
+    }
+}
+
+impl Drop for Pane {
+    fn drop(&mut self) {
+        self.shut_down();
     }
 }
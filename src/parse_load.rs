@@ -0,0 +1,119 @@
+use std::time::{Duration, Instant};
+
+/// Parsing time per second of wall clock above which a second counts as
+/// heavy.
+pub const HEAVY_PER_SEC: Duration = Duration::from_millis(250);
+/// Heavy seconds in a row before a pane is flagged; a full-screen redraw
+/// is over well within this.
+pub const HEAVY_AFTER: u32 = 3;
+/// Each sample covers this much time.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How much of the event loop one pane's output takes to parse, and whether
+/// it has taken too much for too long.
+#[derive(Debug)]
+pub struct ParseLoad {
+    /// Parsing time since the last sample.
+    busy: Duration,
+    sampled_at: Instant,
+    /// Parsing time per second over the last interval.
+    rate: Duration,
+    /// Heavy seconds in a row.
+    streak: u32,
+    /// The warning was dismissed; it stays quiet until the load drops.
+    pub ignored: bool,
+}
+
+impl Default for ParseLoad {
+    fn default() -> Self {
+        Self {
+            busy: Duration::ZERO,
+            sampled_at: Instant::now(),
+            rate: Duration::ZERO,
+            streak: 0,
+            ignored: false,
+        }
+    }
+}
+
+impl ParseLoad {
+    pub fn record(&mut self, parsing: Duration) {
+        self.busy += parsing;
+    }
+
+    /// Close the interval once `SAMPLE_INTERVAL` has passed. Returns whether
+    /// this sample made the pane heavy.
+    pub fn sample(&mut self, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.sampled_at);
+        if elapsed < SAMPLE_INTERVAL {
+            return false;
+        }
+        self.rate = self.busy.mul_f64(1.0 / elapsed.as_secs_f64());
+        self.busy = Duration::ZERO;
+        self.sampled_at = now;
+        if self.rate < HEAVY_PER_SEC {
+            self.streak = 0;
+            self.ignored = false;
+            return false;
+        }
+        self.streak += 1;
+        self.streak == HEAVY_AFTER
+    }
+
+    /// Parsing time per second over the last interval.
+    pub fn rate(&self) -> Duration {
+        self.rate
+    }
+
+    pub fn is_heavy(&self) -> bool {
+        self.streak >= HEAVY_AFTER
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `busy` of parsing per second for each entry, returning the
+    /// seconds at which the pane became heavy.
+    fn run(load: &mut ParseLoad, start: Instant, busy: &[u64]) -> Vec<usize> {
+        let mut flagged = Vec::new();
+        for (i, &ms) in busy.iter().enumerate() {
+            load.record(Duration::from_millis(ms));
+            if load.sample(start + SAMPLE_INTERVAL * (i as u32 + 1)) {
+                flagged.push(i);
+            }
+        }
+        flagged
+    }
+
+    #[test]
+    fn sustained_parsing_is_flagged_once() {
+        let start = Instant::now();
+        let mut load = ParseLoad { sampled_at: start, ..Default::default() };
+        assert_eq!(run(&mut load, start, &[600, 700, 900, 900, 800]), [2]);
+        assert!(load.is_heavy());
+        assert_eq!(load.rate(), Duration::from_millis(800));
+    }
+
+    #[test]
+    fn bursts_like_a_full_screen_redraw_are_not() {
+        let start = Instant::now();
+        let mut load = ParseLoad { sampled_at: start, ..Default::default() };
+        assert!(run(&mut load, start, &[400, 30, 20, 400, 400, 10, 900, 0]).is_empty());
+        assert!(!load.is_heavy());
+    }
+
+    #[test]
+    fn ignoring_lasts_until_the_load_drops() {
+        let start = Instant::now();
+        let mut load = ParseLoad { sampled_at: start, ..Default::default() };
+        run(&mut load, start, &[500, 500, 500]);
+        load.ignored = true;
+        load.record(Duration::from_millis(500));
+        load.sample(start + SAMPLE_INTERVAL * 4);
+        assert!(load.ignored);
+        load.sample(start + SAMPLE_INTERVAL * 5);
+        assert!(!load.ignored && !load.is_heavy());
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crossterm::event::{KeyCode, KeyModifiers};
+
+use crate::config::PauseMode;
+use crate::keys::KeyChord;
+use crate::pty::PtyEvent;
+
+pub const DEFAULT_PAUSE_KEY: KeyChord = KeyChord::new(KeyModifiers::ALT, KeyCode::Pause);
+
+/// Output a pane may pile up while paused with `pause = "drop"`; the rest is
+/// discarded and counted.
+pub const SPILL_LIMIT: usize = 1 << 20;
+
+/// Session-wide freeze of pane output. While paused no PTY event reaches a
+/// pane; they're held here and handed back in arrival order on resume.
+pub struct OutputPause {
+    pub mode: PauseMode,
+    pub key: KeyChord,
+    /// Shared with every reader task, which stops reading while it's set.
+    /// Only set when pausing with `PauseMode::Backpressure`.
+    pub hold_reads: Arc<AtomicBool>,
+    held: Option<Held>,
+}
+
+#[derive(Default)]
+struct Held {
+    events: VecDeque<(usize, PtyEvent)>,
+    /// Bytes held per pane, against `SPILL_LIMIT`.
+    bytes: HashMap<usize, usize>,
+    dropped: u64,
+}
+
+impl OutputPause {
+    pub fn new(mode: PauseMode, hold_reads: Arc<AtomicBool>) -> Self {
+        Self {
+            mode,
+            key: DEFAULT_PAUSE_KEY,
+            hold_reads,
+            held: None,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.held.is_some()
+    }
+
+    pub fn pause(&mut self) {
+        if self.mode == PauseMode::Backpressure {
+            self.hold_reads.store(true, Ordering::Relaxed);
+        }
+        self.held.get_or_insert_with(Held::default);
+    }
+
+    /// Unpause, returning the held events to apply before anything the
+    /// readers send from now on.
+    pub fn resume(&mut self) -> VecDeque<(usize, PtyEvent)> {
+        self.hold_reads.store(false, Ordering::Relaxed);
+        self.held.take().map(|held| held.events).unwrap_or_default()
+    }
+
+    /// Keep `event` for resume. With `PauseMode::Drop`, output past a pane's
+    /// `SPILL_LIMIT` is discarded.
+    pub fn hold(&mut self, pane_id: usize, event: PtyEvent) {
+        let Some(held) = &mut self.held else {
+            return;
+        };
+        if let PtyEvent::Data(bytes) = &event {
+            let total = held.bytes.entry(pane_id).or_default();
+            if self.mode == PauseMode::Drop && *total + bytes.len() > SPILL_LIMIT {
+                held.dropped += bytes.len() as u64;
+                return;
+            }
+            *total += bytes.len();
+        }
+        held.events.push_back((pane_id, event));
+    }
+
+    /// Bytes discarded since pausing.
+    pub fn dropped(&self) -> u64 {
+        self.held.as_ref().map_or(0, |held| held.dropped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(events: &VecDeque<(usize, PtyEvent)>) -> Vec<(usize, usize)> {
+        events
+            .iter()
+            .filter_map(|(id, e)| match e {
+                PtyEvent::Data(bytes) => Some((*id, bytes.len())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn drop_mode_keeps_order_and_counts_overflow() {
+        let hold_reads = Arc::new(AtomicBool::new(false));
+        let mut pause = OutputPause::new(PauseMode::Drop, hold_reads.clone());
+        pause.hold(0, PtyEvent::Data(vec![b'x'; 10]));
+        assert!(pause.resume().is_empty(), "nothing is held while running");
+
+        pause.pause();
+        assert!(!hold_reads.load(Ordering::Relaxed), "drop mode keeps reading");
+        pause.hold(0, PtyEvent::Data(vec![b'a'; SPILL_LIMIT - 1]));
+        pause.hold(1, PtyEvent::Data(vec![b'b'; 5]));
+        pause.hold(0, PtyEvent::Data(vec![b'c'; 2]));
+        pause.hold(0, PtyEvent::Eof);
+        assert_eq!(pause.dropped(), 2);
+
+        let held = pause.resume();
+        assert_eq!(data(&held), vec![(0, SPILL_LIMIT - 1), (1, 5)]);
+        assert!(matches!(held.back(), Some((0, PtyEvent::Eof))));
+        assert_eq!(pause.dropped(), 0);
+    }
+
+    #[test]
+    fn backpressure_mode_holds_the_readers() {
+        let hold_reads = Arc::new(AtomicBool::new(false));
+        let mut pause = OutputPause::new(PauseMode::Backpressure, hold_reads.clone());
+        pause.pause();
+        assert!(hold_reads.load(Ordering::Relaxed));
+        pause.hold(0, PtyEvent::Data(vec![b'a'; SPILL_LIMIT + 1]));
+        assert_eq!(pause.dropped(), 0);
+        assert_eq!(data(&pause.resume()), vec![(0, SPILL_LIMIT + 1)]);
+        assert!(!hold_reads.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,419 @@
+use std::path::PathBuf;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Modifier, Style};
+
+/// Entries kept per history file.
+const HISTORY_LIMIT: usize = 200;
+
+/// What a prompt asks for. Each kind keeps its own history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptKind {
+    /// A command to run in a new pane.
+    RunCommand,
+    /// A `[[templates]]` entry to open as a new pane.
+    Template,
+    /// A line to type into the marked panes.
+    Broadcast,
+    /// A signal for the focused pane's process.
+    Signal,
+    /// Where to write a session report, and which panes to put in it.
+    Report,
+    /// A pane to focus, from the pane switcher.
+    Focus,
+    /// A group and what to do to it, e.g. "collapse web".
+    Group,
+}
+
+impl PromptKind {
+    fn label(self) -> &'static str {
+        match self {
+            PromptKind::RunCommand => "Run",
+            PromptKind::Template => "Template",
+            PromptKind::Broadcast => "Broadcast",
+            PromptKind::Signal => "Signal",
+            PromptKind::Report => "Report",
+            PromptKind::Focus => "Focus",
+            PromptKind::Group => "Group",
+        }
+    }
+
+    fn history_file(self) -> &'static str {
+        match self {
+            PromptKind::RunCommand => "history-run",
+            PromptKind::Template => "history-template",
+            PromptKind::Broadcast => "history-broadcast",
+            PromptKind::Signal => "history-signal",
+            PromptKind::Report => "history-report",
+            PromptKind::Focus => "history-focus",
+            PromptKind::Group => "history-group",
+        }
+    }
+}
+
+/// Result of a key press in a prompt.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PromptOutcome {
+    /// Still editing.
+    Pending,
+    Submit(String),
+    Cancel,
+}
+
+/// Previous entries for one prompt kind, oldest first.
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Vec<String>,
+    /// File the entries are read from and saved to; `None` keeps them in
+    /// memory only.
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /// The history for `kind` from the state directory. A missing or
+    /// unreadable file starts it empty.
+    pub fn load(kind: PromptKind) -> Self {
+        let path = crate::layout_state::state_dir().map(|dir| dir.join(format!("{}.txt", kind.history_file())));
+        let entries = path
+            .as_ref()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .map(|s| s.lines().filter(|l| !l.is_empty()).map(str::to_string).collect())
+            .unwrap_or_default();
+        Self { entries, path }
+    }
+
+    /// Record `entry` as the newest, dropping an older copy of it.
+    fn push(&mut self, entry: &str) {
+        if entry.trim().is_empty() {
+            return;
+        }
+        self.entries.retain(|e| e != entry);
+        self.entries.push(entry.to_string());
+        let excess = self.entries.len().saturating_sub(HISTORY_LIMIT);
+        self.entries.drain(..excess);
+        if let Some(path) = &self.path {
+            let saved = path
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| std::fs::write(path, self.entries.join("\n") + "\n"));
+            if let Err(e) = saved {
+                tracing::warn!(path = %path.display(), error = %e, "failed to save prompt history");
+            }
+        }
+    }
+}
+
+/// A one-line text input shown in the footer, with readline-style editing
+/// and history.
+pub struct Prompt {
+    pub kind: PromptKind,
+    text: Vec<char>,
+    /// Insertion point, in characters.
+    cursor: usize,
+    history: History,
+    /// Entry shown while browsing history; `None` while editing fresh text.
+    history_pos: Option<usize>,
+    /// The fresh text, kept while browsing history.
+    draft: Vec<char>,
+    /// Names to pick from; the text filters them. Empty for free text.
+    choices: Vec<String>,
+    /// Highlighted entry of `matches()`.
+    selected: usize,
+}
+
+impl Prompt {
+    pub fn new(kind: PromptKind, history: History) -> Self {
+        Self {
+            kind,
+            text: Vec::new(),
+            cursor: 0,
+            history,
+            history_pos: None,
+            draft: Vec::new(),
+            choices: Vec::new(),
+            selected: 0,
+        }
+    }
+
+    /// A prompt that picks one of `choices`, filtered by what is typed.
+    /// Up/Down and Tab move through the matches and Enter submits the
+    /// highlighted one.
+    pub fn picker(kind: PromptKind, choices: Vec<String>) -> Self {
+        Self {
+            choices,
+            ..Self::new(kind, History::default())
+        }
+    }
+
+    /// Choices matching the text: those it is a prefix of first, then those
+    /// starting with its first letter, then the rest.
+    pub fn matches(&self) -> Vec<&str> {
+        let query = self.text();
+        let mut matches: Vec<&str> = self
+            .choices
+            .iter()
+            .map(String::as_str)
+            .filter(|c| fuzzy_match(&query, c))
+            .collect();
+        let query = query.trim().to_lowercase();
+        let first = query.chars().next().map(String::from).unwrap_or_default();
+        matches.sort_by_key(|c| {
+            let c = c.to_lowercase();
+            (!c.starts_with(&query), !c.starts_with(&first))
+        });
+        matches
+    }
+
+    pub fn text(&self) -> String {
+        self.text.iter().collect()
+    }
+
+    /// Insert `s` at the cursor, e.g. pasted text. Line breaks become spaces.
+    pub fn insert_str(&mut self, s: &str) {
+        for c in s.chars() {
+            let c = if c == '\n' || c == '\r' { ' ' } else { c };
+            if !c.is_control() {
+                self.text.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+        }
+    }
+
+    pub fn handle_key(&mut self, key: &KeyEvent) -> PromptOutcome {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if !self.choices.is_empty() {
+            match key.code {
+                KeyCode::Enter => {
+                    return match self.matches().get(self.selected) {
+                        Some(choice) => PromptOutcome::Submit(choice.to_string()),
+                        None => PromptOutcome::Pending,
+                    };
+                }
+                KeyCode::Up | KeyCode::BackTab => {
+                    let len = self.matches().len().max(1);
+                    self.selected = (self.selected + len - 1) % len;
+                    return PromptOutcome::Pending;
+                }
+                KeyCode::Down | KeyCode::Tab => {
+                    self.selected = (self.selected + 1) % self.matches().len().max(1);
+                    return PromptOutcome::Pending;
+                }
+                // Any edit below changes the matches; start from the best.
+                _ => self.selected = 0,
+            }
+        }
+        match key.code {
+            KeyCode::Enter => {
+                let text = self.text();
+                self.history.push(&text);
+                return PromptOutcome::Submit(text);
+            }
+            KeyCode::Esc => return PromptOutcome::Cancel,
+            KeyCode::Char('c') if ctrl => return PromptOutcome::Cancel,
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.text.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.len(),
+            KeyCode::Char('e') if ctrl => self.cursor = self.text.len(),
+            KeyCode::Char('w') if ctrl => self.delete_word(),
+            KeyCode::Char('k') if ctrl => self.text.truncate(self.cursor),
+            KeyCode::Char('u') if ctrl => {
+                self.text.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.text.remove(self.cursor);
+            }
+            KeyCode::Delete if self.cursor < self.text.len() => {
+                self.text.remove(self.cursor);
+            }
+            KeyCode::Up => self.browse_history(true),
+            KeyCode::Down => self.browse_history(false),
+            KeyCode::Char(c) if !ctrl && !key.modifiers.contains(KeyModifiers::ALT) => {
+                self.text.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            _ => {}
+        }
+        PromptOutcome::Pending
+    }
+
+    /// Delete back to the start of the word before the cursor.
+    fn delete_word(&mut self) {
+        let mut start = self.cursor;
+        while start > 0 && self.text[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        while start > 0 && !self.text[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        self.text.drain(start..self.cursor);
+        self.cursor = start;
+    }
+
+    /// Step to an older (`back`) or newer history entry. Stepping past the
+    /// newest returns to the text being typed.
+    fn browse_history(&mut self, back: bool) {
+        let len = self.history.entries.len();
+        let pos = match (self.history_pos, back) {
+            (None, true) if len > 0 => {
+                self.draft = self.text.clone();
+                Some(len - 1)
+            }
+            (None, _) => return,
+            (Some(pos), true) => Some(pos.saturating_sub(1)),
+            (Some(pos), false) if pos + 1 < len => Some(pos + 1),
+            (Some(_), false) => None,
+        };
+        self.history_pos = pos;
+        self.text = match pos {
+            Some(pos) => self.history.entries[pos].chars().collect(),
+            None => std::mem::take(&mut self.draft),
+        };
+        self.cursor = self.text.len();
+    }
+
+    /// Draw `Label: text` on one row, scrolled so the cursor stays in view.
+    pub fn render(&self, buf: &mut Buffer, area: Rect) {
+        let label = format!(" {}: ", self.kind.label());
+        let label_style = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+        buf.set_style(area, Style::default().fg(Color::White));
+        let (x, _) = buf.set_stringn(area.x, area.y, &label, area.width as usize, label_style);
+
+        let width = area.right().saturating_sub(x) as usize;
+        if width == 0 {
+            return;
+        }
+        let start = (self.cursor + 1).saturating_sub(width);
+        let visible: String = self.text.iter().skip(start).take(width).collect();
+        buf.set_stringn(x, area.y, &visible, width, Style::default().fg(Color::White));
+        let cursor_x = x + (self.cursor - start) as u16;
+        if cursor_x < area.right() {
+            if let Some(cell) = buf.cell_mut((cursor_x, area.y)) {
+                cell.set_style(Style::default().add_modifier(Modifier::REVERSED));
+            }
+        }
+
+        if !self.choices.is_empty() {
+            let mut x = cursor_x + 2;
+            let matches = self.matches();
+            if matches.is_empty() {
+                buf.set_stringn(x, area.y, "no match", area.right().saturating_sub(x) as usize, Style::default().fg(Color::DarkGray));
+            }
+            for (i, choice) in matches.iter().enumerate() {
+                if x >= area.right() {
+                    break;
+                }
+                let style = if i == self.selected {
+                    Style::default().fg(Color::Black).bg(Color::Yellow)
+                } else {
+                    Style::default().fg(Color::Gray)
+                };
+                let (end, _) = buf.set_stringn(x, area.y, choice, area.right().saturating_sub(x) as usize, style);
+                x = end + 2;
+            }
+        }
+    }
+}
+
+/// Whether the characters of `query` appear in order in `candidate`,
+/// ignoring case.
+fn fuzzy_match(query: &str, candidate: &str) -> bool {
+    let mut chars = candidate.chars().flat_map(char::to_lowercase);
+    query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .filter(|c| !c.is_whitespace())
+        .all(|q| chars.any(|c| c == q))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompt(history: &[&str]) -> Prompt {
+        let history = History {
+            entries: history.iter().map(|s| s.to_string()).collect(),
+            path: None,
+        };
+        Prompt::new(PromptKind::RunCommand, history)
+    }
+
+    fn press(p: &mut Prompt, code: KeyCode, modifiers: KeyModifiers) -> PromptOutcome {
+        p.handle_key(&KeyEvent::new(code, modifiers))
+    }
+
+    fn ctrl(p: &mut Prompt, c: char) {
+        press(p, KeyCode::Char(c), KeyModifiers::CONTROL);
+    }
+
+    #[test]
+    fn editing_moves_and_deletes_around_the_cursor() {
+        let mut p = prompt(&[]);
+        p.insert_str("cargo test --all");
+        ctrl(&mut p, 'w');
+        assert_eq!(p.text(), "cargo test ");
+        ctrl(&mut p, 'a');
+        press(&mut p, KeyCode::Right, KeyModifiers::NONE);
+        press(&mut p, KeyCode::Char('X'), KeyModifiers::SHIFT);
+        assert_eq!(p.text(), "cXargo test ");
+        ctrl(&mut p, 'k');
+        assert_eq!(p.text(), "cX");
+        press(&mut p, KeyCode::Left, KeyModifiers::NONE);
+        ctrl(&mut p, 'u');
+        assert_eq!(p.text(), "X");
+        ctrl(&mut p, 'e');
+        press(&mut p, KeyCode::Backspace, KeyModifiers::NONE);
+        assert_eq!(p.text(), "");
+        p.insert_str("a\nb");
+        assert_eq!(p.text(), "a b");
+    }
+
+    #[test]
+    fn history_browses_and_restores_the_draft() {
+        let mut p = prompt(&["make", "ls"]);
+        p.insert_str("dr");
+        press(&mut p, KeyCode::Up, KeyModifiers::NONE);
+        assert_eq!(p.text(), "ls");
+        press(&mut p, KeyCode::Up, KeyModifiers::NONE);
+        press(&mut p, KeyCode::Up, KeyModifiers::NONE);
+        assert_eq!(p.text(), "make");
+        press(&mut p, KeyCode::Down, KeyModifiers::NONE);
+        press(&mut p, KeyCode::Down, KeyModifiers::NONE);
+        assert_eq!(p.text(), "dr");
+
+        press(&mut p, KeyCode::Up, KeyModifiers::NONE);
+        press(&mut p, KeyCode::Up, KeyModifiers::NONE);
+        assert_eq!(press(&mut p, KeyCode::Enter, KeyModifiers::NONE), PromptOutcome::Submit("make".into()));
+        assert_eq!(p.history.entries, ["ls", "make"]);
+    }
+
+    #[test]
+    fn picker_filters_fuzzily_and_submits_the_highlighted_match() {
+        let names = ["ssh-bastion", "psql-staging", "tail-nginx"].map(String::from).to_vec();
+        let mut p = Prompt::picker(PromptKind::Template, names);
+        p.insert_str("s");
+        assert_eq!(p.matches(), ["ssh-bastion", "psql-staging"]);
+        p.insert_str("tg");
+        assert_eq!(p.matches(), ["psql-staging"]);
+        ctrl(&mut p, 'u');
+        p.insert_str("tn");
+        assert_eq!(p.matches(), ["tail-nginx", "ssh-bastion", "psql-staging"]);
+        ctrl(&mut p, 'u');
+        press(&mut p, KeyCode::Char('T'), KeyModifiers::SHIFT);
+        assert_eq!(p.matches(), ["tail-nginx", "ssh-bastion", "psql-staging"], "prefix matches first");
+        press(&mut p, KeyCode::Tab, KeyModifiers::NONE);
+        press(&mut p, KeyCode::Tab, KeyModifiers::NONE);
+        press(&mut p, KeyCode::Up, KeyModifiers::NONE);
+        press(&mut p, KeyCode::Up, KeyModifiers::NONE);
+        assert_eq!(press(&mut p, KeyCode::Enter, KeyModifiers::NONE), PromptOutcome::Submit("tail-nginx".into()));
+
+        p.insert_str("zzz");
+        assert!(p.matches().is_empty());
+        assert_eq!(press(&mut p, KeyCode::Enter, KeyModifiers::NONE), PromptOutcome::Pending);
+    }
+}
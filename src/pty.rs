@@ -1,71 +1,121 @@
-use alacritty_terminal::Term;
 use anyhow::{Context, Result};
-use parking_lot::Mutex;
-use portable_pty::{native_pty_system, CommandBuilder, MasterPty, PtySize};
-use std::io::Read;
+use portable_pty::{native_pty_system, Child, CommandBuilder, ExitStatus, MasterPty, PtySize};
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::mpsc;
 
-use crate::config::{Config, PaneConfig};
-use crate::terminal::VoidListener;
+use crate::config::{Config, PaneConfig, ShellSpec};
 
 #[derive(Debug)]
 pub enum PtyEvent {
-    Data(#[allow(dead_code)] Vec<u8>),
-    Closed,
+    Data(Vec<u8>),
+    /// The reader hit end of file or failed. The process may still be
+    /// running, and it may have exited long before.
+    Eof,
+    /// The child process has been reaped.
+    Exited(ExitStatus),
+}
+
+/// What a pane's reader task has read so far. Bumped by the reader and
+/// read by the event loop without locking.
+#[derive(Debug, Default)]
+pub struct ReadCounters {
+    pub bytes: AtomicU64,
+    /// Newlines among `bytes`.
+    pub lines: AtomicU64,
+    /// Stops this pane's reader alone while set, like the global `hold`.
+    pub held: AtomicBool,
 }
 
 pub struct SpawnedPty {
     pub master: Box<dyn MasterPty + Send>,
-    pub writer: Arc<Mutex<Box<dyn std::io::Write + Send>>>,
+    pub writer: Box<dyn Write + Send>,
     pub reader: Box<dyn Read + Send>,
-    #[allow(dead_code)]
-    pub child: Box<dyn portable_pty::Child + Send + Sync>,
+    pub child: Box<dyn Child + Send + Sync>,
+}
+
+/// Pixel size of one cell of the outer terminal, or zeros when it doesn't
+/// report pixels.
+pub fn cell_pixel_size() -> (u16, u16) {
+    crossterm::terminal::window_size().map_or((0, 0), cell_size)
+}
+
+fn cell_size(w: crossterm::terminal::WindowSize) -> (u16, u16) {
+    if w.columns > 0 && w.rows > 0 {
+        (w.width / w.columns, w.height / w.rows)
+    } else {
+        (0, 0)
+    }
+}
+
+/// PTY size for a `cols` x `rows` pane. Programs that size images or work
+/// out the cell aspect ratio read the pixel fields.
+pub fn pty_size(cols: u16, rows: u16) -> PtySize {
+    sized_by_cell(cols, rows, cell_pixel_size())
+}
+
+/// `pty_size` with cells of `cell_width` x `cell_height` pixels.
+fn sized_by_cell(cols: u16, rows: u16, (cell_width, cell_height): (u16, u16)) -> PtySize {
+    PtySize {
+        rows,
+        cols,
+        pixel_width: cols.saturating_mul(cell_width),
+        pixel_height: rows.saturating_mul(cell_height),
+    }
 }
 
+/// Start a pane's process on a new PTY. Panes without a `command` run
+/// `shell`, already resolved for this pane.
 pub fn spawn_pty(
     pane_config: &PaneConfig,
-    default_shell: &str,
+    shell: &ShellSpec,
     cols: u16,
     rows: u16,
 ) -> Result<SpawnedPty> {
+    let _span = tracing::info_span!("spawn_pty", name = %pane_config.name).entered();
     let pty_system = native_pty_system();
 
     let pair = pty_system
-        .openpty(PtySize {
-            rows,
-            cols,
-            pixel_width: 0,
-            pixel_height: 0,
-        })
+        .openpty(pty_size(cols, rows))
         .context("Failed to open PTY")?;
 
-    let command_str = pane_config.command.as_deref().unwrap_or(default_shell);
-    let mut parts = command_str.split_whitespace();
-    let executable = parts.next().unwrap_or(default_shell);
-    let mut cmd = CommandBuilder::new(executable);
-    for arg in parts {
-        cmd.arg(arg);
+    let argv: Vec<String> = match &pane_config.command {
+        Some(command) if !command.trim().is_empty() => shell.command_argv(command),
+        _ => shell.argv(),
+    };
+    if argv.is_empty() {
+        anyhow::bail!("No command or shell to run");
     }
+    let command_str = argv.join(" ");
+    let mut cmd = CommandBuilder::from_argv(argv.iter().map(Into::into).collect());
 
     let cwd = Config::resolve_cwd(&pane_config.cwd)
         .or_else(|| std::env::current_dir().ok());
     if let Some(cwd) = cwd {
+        // The child would otherwise start somewhere else without a word.
+        if !cwd.is_dir() {
+            anyhow::bail!("Working directory {} does not exist", cwd.display());
+        }
         cmd.cwd(cwd);
     }
 
+    for (k, v) in crate::nesting::child_env() {
+        cmd.env(k, v);
+    }
     for (k, v) in &pane_config.env {
         cmd.env(k, v);
     }
 
+    tracing::info!(command = command_str, cols, rows, "spawning");
     let child = pair.slave.spawn_command(cmd).context("Failed to spawn child process")?;
+    tracing::debug!(pid = ?child.process_id(), "spawned");
     drop(pair.slave);
 
-    let writer = Arc::new(Mutex::new(
-        pair.master
-            .take_writer()
-            .context("Failed to take PTY writer")?,
-    ));
+    let writer = pair
+        .master
+        .take_writer()
+        .context("Failed to take PTY writer")?;
 
     let reader = pair
         .master
@@ -80,35 +130,237 @@ pub fn spawn_pty(
     })
 }
 
+/// How often a held reader checks whether it may read again.
+const HOLD_POLL: std::time::Duration = std::time::Duration::from_millis(20);
+
+/// Read PTY output on a blocking thread and forward it as `PtyEvent::Data`.
+///
+/// Parsing happens on the event loop, so the renderer never waits on a pane
+/// that is busy digesting a large burst of output. While `hold` is set the
+/// task stops reading, so a chatty child blocks on its full PTY buffer; the
+/// pane's own `counters.held` does the same for it alone.
 pub fn launch_reader_task(
+    pane_id: usize,
     mut reader: Box<dyn Read + Send>,
-    term: Arc<Mutex<Term<VoidListener>>>,
+    counters: Arc<ReadCounters>,
+    hold: Arc<AtomicBool>,
     tx: mpsc::UnboundedSender<PtyEvent>,
 ) {
     tokio::task::spawn_blocking(move || {
-        let mut processor = crate::terminal::new_processor();
+        let _span = tracing::debug_span!("reader", pane = pane_id).entered();
+        tracing::debug!("reader task started");
         let mut buf = [0u8; 4096];
         loop {
+            if hold.load(Ordering::Relaxed) || counters.held.load(Ordering::Relaxed) {
+                std::thread::sleep(HOLD_POLL);
+                continue;
+            }
             match reader.read(&mut buf) {
                 Ok(0) => {
-                    let _ = tx.send(PtyEvent::Closed);
+                    tracing::debug!("reader reached EOF");
+                    let _ = tx.send(PtyEvent::Eof);
                     break;
                 }
                 Ok(n) => {
-                    let bytes = buf[..n].to_vec();
-                    {
-                        let mut term = term.lock();
-                        crate::terminal::process_bytes(&mut term, &mut processor, &bytes);
+                    counters.bytes.fetch_add(n as u64, Ordering::Relaxed);
+                    let lines = buf[..n].iter().filter(|&&b| b == b'\n').count();
+                    if lines > 0 {
+                        counters.lines.fetch_add(lines as u64, Ordering::Relaxed);
                     }
-                    if tx.send(PtyEvent::Data(bytes)).is_err() {
+                    if tx.send(PtyEvent::Data(buf[..n].to_vec())).is_err() {
+                        tracing::debug!("event channel closed");
                         break;
                     }
                 }
-                Err(_) => {
-                    let _ = tx.send(PtyEvent::Closed);
+                Err(e) => {
+                    tracing::warn!(error = %e, "reader failed");
+                    let _ = tx.send(PtyEvent::Eof);
                     break;
                 }
             }
         }
+        tracing::debug!("reader task finished");
     });
 }
+
+/// Sends input to a pane's PTY through a dedicated writer thread, so a PTY
+/// whose buffer is full can't stall the event loop.
+pub struct PtyInput {
+    tx: std::sync::mpsc::Sender<Vec<u8>>,
+    dead: Arc<AtomicBool>,
+}
+
+impl PtyInput {
+    /// Queue `data` for the PTY. Fails once an earlier write has failed.
+    pub fn send(&self, data: Vec<u8>) -> Result<()> {
+        if self.is_dead() {
+            anyhow::bail!("PTY input is closed");
+        }
+        self.tx
+            .send(data)
+            .map_err(|_| anyhow::anyhow!("PTY writer thread has exited"))
+    }
+
+    /// Whether a write has failed, e.g. because the process exited.
+    pub fn is_dead(&self) -> bool {
+        self.dead.load(Ordering::Relaxed)
+    }
+}
+
+/// Start the writer thread for a pane. It exits when the `PtyInput` is
+/// dropped or the first write fails.
+pub fn launch_writer_thread(pane_id: usize, mut writer: Box<dyn Write + Send>) -> PtyInput {
+    let (tx, rx) = std::sync::mpsc::channel::<Vec<u8>>();
+    let dead = Arc::new(AtomicBool::new(false));
+    let flag = dead.clone();
+    let spawned = std::thread::Builder::new()
+        .name(format!("pty-writer-{}", pane_id))
+        .spawn(move || {
+            let _span = tracing::debug_span!("writer", pane = pane_id).entered();
+            while let Ok(data) = rx.recv() {
+                if let Err(e) = writer.write_all(&data).and_then(|_| writer.flush()) {
+                    tracing::warn!(error = %e, "write failed; disabling input");
+                    flag.store(true, Ordering::Relaxed);
+                    break;
+                }
+            }
+            tracing::debug!("writer thread finished");
+        });
+    if let Err(e) = spawned {
+        tracing::error!(pane = pane_id, error = %e, "failed to start writer thread");
+        dead.store(true, Ordering::Relaxed);
+    }
+    PtyInput { tx, dead }
+}
+
+/// Wait for the child on its own thread and report its exit status.
+pub fn launch_wait_thread(
+    pane_id: usize,
+    mut child: Box<dyn Child + Send + Sync>,
+    tx: mpsc::UnboundedSender<PtyEvent>,
+) {
+    let spawned = std::thread::Builder::new()
+        .name(format!("pty-wait-{}", pane_id))
+        .spawn(move || match child.wait() {
+            Ok(status) => {
+                let _ = tx.send(PtyEvent::Exited(status));
+            }
+            Err(e) => tracing::warn!(pane = pane_id, error = %e, "waiting for child failed"),
+        });
+    if let Err(e) = spawned {
+        tracing::error!(pane = pane_id, error = %e, "failed to start wait thread");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn pixel_sizes_scale_the_cell_or_stay_unknown() {
+        let window = |columns, rows, width, height| crossterm::terminal::WindowSize { rows, columns, width, height };
+        assert_eq!(cell_size(window(100, 50, 800, 800)), (8, 16));
+        assert_eq!(cell_size(window(100, 50, 0, 0)), (0, 0), "no pixels reported");
+        assert_eq!(cell_size(window(0, 0, 800, 800)), (0, 0), "no cells to divide by");
+
+        let pixels = |size: PtySize| (size.pixel_width, size.pixel_height);
+        assert_eq!(pixels(sized_by_cell(80, 24, (8, 16))), (640, 384));
+        assert_eq!(pixels(sized_by_cell(80, 24, (0, 0))), (0, 0));
+        assert_eq!(pixels(sized_by_cell(0, 24, (8, 16))), (0, 384), "a zero-width pane");
+        assert_eq!(pixels(sized_by_cell(10_000, 5_000, (8, 16))), (u16::MAX, u16::MAX));
+    }
+
+    /// The PTY reports the size it was opened and resized to, pixels and all.
+    #[cfg(unix)]
+    #[test]
+    fn the_pty_takes_the_pane_size() {
+        let config = PaneConfig {
+            name: "sized".to_string(),
+            command: Some("sleep 5".to_string()),
+            ..PaneConfig::default()
+        };
+        let mut spawned = spawn_pty(&config, &Config::default().shell(), 30, 8).expect("spawn");
+        assert_eq!(spawned.master.get_size().expect("size"), pty_size(30, 8));
+        spawned.master.resize(pty_size(12, 3)).expect("resize");
+        assert_eq!(spawned.master.get_size().expect("size"), pty_size(12, 3));
+        let _ = spawned.child.kill();
+    }
+
+    /// Spawn a real process on the platform's PTY (ConPTY on Windows) and see
+    /// its output come back.
+    #[test]
+    fn spawned_process_output_is_readable() {
+        let command = if cfg!(windows) {
+            "cmd.exe /C echo bamboo-smoke"
+        } else {
+            "echo bamboo-smoke"
+        };
+        let config = PaneConfig {
+            name: "smoke".to_string(),
+            command: Some(command.to_string()),
+            ..PaneConfig::default()
+        };
+        let mut spawned = spawn_pty(&config, &crate::config::Config::default().shell(), 80, 24).expect("spawn");
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut reader = spawned.reader;
+        std::thread::spawn(move || {
+            let mut seen = Vec::new();
+            let mut buf = [0u8; 1024];
+            while let Ok(n) = reader.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                seen.extend_from_slice(&buf[..n]);
+                if String::from_utf8_lossy(&seen).contains("bamboo-smoke") {
+                    let _ = tx.send(());
+                    break;
+                }
+            }
+        });
+
+        let found = rx.recv_timeout(Duration::from_secs(10)).is_ok();
+        let _ = spawned.child.kill();
+        assert!(found, "no output from the spawned process");
+    }
+
+    /// A backgrounded child that outlives its parent keeps the PTY open: the
+    /// exit status comes first, and the child's output still follows it.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn exit_and_end_of_output_arrive_separately() {
+        let script = std::env::temp_dir().join(format!("bamboo-orphan-{}.sh", std::process::id()));
+        std::fs::write(&script, "trap '' HUP\n(sleep 0.5; echo late) &\necho early\n").unwrap();
+        let config = PaneConfig {
+            name: "orphan".to_string(),
+            command: Some(format!("/bin/sh {}", script.display())),
+            ..PaneConfig::default()
+        };
+        let spawned = spawn_pty(&config, &crate::config::Config::default().shell(), 80, 24).expect("spawn");
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        launch_wait_thread(0, spawned.child, tx.clone());
+        launch_reader_task(0, spawned.reader, Arc::default(), Arc::default(), tx);
+
+        let mut output = String::new();
+        let mut output_at_exit = None;
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+        loop {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some(PtyEvent::Data(bytes))) => output.push_str(&String::from_utf8_lossy(&bytes)),
+                Ok(Some(PtyEvent::Exited(status))) => {
+                    assert!(status.success());
+                    output_at_exit = Some(output.clone());
+                }
+                Ok(Some(PtyEvent::Eof)) => break,
+                Ok(None) | Err(_) => panic!("no end of output; got {:?}", output),
+            }
+        }
+        let _ = std::fs::remove_file(&script);
+        drop(spawned.master);
+
+        let output_at_exit = output_at_exit.expect("exit status before end of output");
+        assert!(!output_at_exit.contains("late"), "{:?}", output_at_exit);
+        assert!(output.contains("early") && output.contains("late"), "{:?}", output);
+    }
+}
@@ -0,0 +1,451 @@
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use regex::Regex;
+
+use crate::pane::Pane;
+use crate::session::{PaneSnapshot, PaneState};
+
+/// Lines of output each pane contributes unless the config says otherwise.
+pub const DEFAULT_REPORT_LINES: usize = 500;
+
+/// xterm's default colors for SGR 30–37 and 90–97.
+const PALETTE: [&str; 16] = [
+    "#000000", "#cd0000", "#00cd00", "#cdcd00", "#0000ee", "#cd00cd", "#00cdcd", "#e5e5e5", "#7f7f7f", "#ff0000",
+    "#00ff00", "#ffff00", "#5c5cff", "#ff00ff", "#00ffff", "#ffffff",
+];
+
+/// Page colors, also what inverse video swaps in for a default color.
+const PAGE_FG: &str = "#d4d4d4";
+const PAGE_BG: &str = "#1e1e1e";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    /// HTML for `.html` and `.htm` paths, Markdown for anything else.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("html" | "htm") => ReportFormat::Html,
+            _ => ReportFormat::Markdown,
+        }
+    }
+}
+
+/// What goes into a session report.
+pub struct ReportOptions {
+    pub format: ReportFormat,
+    /// Last lines of output kept per pane.
+    pub lines: usize,
+    /// Only panes whose name matches.
+    pub only: Option<Regex>,
+}
+
+/// Write every pane's name, command, state and the end of its output to
+/// `out`, in the order the panes were opened. Returns how many panes were
+/// written.
+pub fn write_report(panes: &[Pane], options: &ReportOptions, out: &mut impl Write) -> io::Result<usize> {
+    let mut chosen: Vec<&Pane> =
+        panes.iter().filter(|p| options.only.as_ref().is_none_or(|re| re.is_match(&p.name))).collect();
+    chosen.sort_by_key(|p| p.id);
+
+    let html = options.format == ReportFormat::Html;
+    if html {
+        writeln!(out, "<!DOCTYPE html>")?;
+        writeln!(out, "<html><head><meta charset=\"utf-8\"><title>bamboo session report</title>")?;
+        writeln!(
+            out,
+            "<style>body{{font-family:sans-serif}}pre{{background:{};color:{};padding:1em;overflow-x:auto}}\
+             dt{{font-weight:bold}}</style>",
+            PAGE_BG, PAGE_FG
+        )?;
+        writeln!(out, "</head><body>\n<h1>bamboo session report</h1>")?;
+        writeln!(out, "<p>{} of {} panes.</p>", chosen.len(), panes.len())?;
+    } else {
+        writeln!(out, "# bamboo session report\n\n{} of {} panes.", chosen.len(), panes.len())?;
+    }
+
+    let now = Instant::now();
+    for pane in &chosen {
+        let snapshot = PaneSnapshot::capture(pane);
+        let ran = crate::title::format_duration(pane.run_time().unwrap_or_else(|| now - pane.started_at));
+        let fields = [
+            ("command", snapshot.command.clone()),
+            ("status", state_text(&snapshot.state)),
+            ("ran", ran),
+            ("cwd", snapshot.cwd.clone()),
+        ];
+        let mut output = Vec::new();
+        crate::terminal::write_capture(&pane.term, options.lines, html, &mut output)?;
+        let output = String::from_utf8_lossy(&output);
+
+        if html {
+            writeln!(out, "<section>\n<h2>{}</h2>\n<dl>", escape_html(&pane.name))?;
+            for (label, value) in &fields {
+                writeln!(out, "<dt>{}</dt><dd><code>{}</code></dd>", label, escape_html(value))?;
+            }
+            writeln!(out, "</dl>\n<pre>{}</pre>\n</section>", ansi_to_html(&output))?;
+        } else {
+            writeln!(out, "\n## {}\n", escape_markdown(&pane.name))?;
+            for (label, value) in &fields {
+                writeln!(out, "- {}: {}", label, inline_code(value))?;
+            }
+            let fence = fence_for(&output);
+            writeln!(out, "\n{}text\n{}{}", fence, output, fence)?;
+        }
+    }
+
+    if html {
+        writeln!(out, "</body></html>")?;
+    }
+    Ok(chosen.len())
+}
+
+fn state_text(state: &PaneState) -> String {
+    match state {
+        PaneState::Running => "running".to_string(),
+        PaneState::Exited { code: Some(code) } => format!("exited with code {}", code),
+        PaneState::Exited { code: None } => "exited".to_string(),
+        PaneState::Failed { error } => format!("failed to start: {}", error),
+    }
+}
+
+/// The longest run of backticks in `text`.
+fn longest_backtick_run(text: &str) -> usize {
+    text.split(|c| c != '`').map(str::len).max().unwrap_or(0)
+}
+
+/// A code fence longer than any backtick run in `text`, so output that
+/// contains fences of its own can't close it.
+fn fence_for(text: &str) -> String {
+    "`".repeat(longest_backtick_run(text).max(2) + 1)
+}
+
+/// `text` as Markdown inline code, delimited past any backticks it holds.
+fn inline_code(text: &str) -> String {
+    let ticks = "`".repeat(longest_backtick_run(text) + 1);
+    let pad = if text.starts_with('`') || text.ends_with('`') { " " } else { "" };
+    format!("{}{}{}{}{}", ticks, pad, text, pad, ticks)
+}
+
+/// `text` with a backslash before each character Markdown could read as
+/// syntax in a heading: code spans, emphasis, links, HTML, and a closing
+/// run of `#`.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '#' | '*' | '_' | '[' | ']' | '<' | '>') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Colors and attributes set by SGR codes, as `ansi_to_html` tracks them.
+#[derive(Clone, Default, PartialEq)]
+struct SgrStyle {
+    fg: Option<String>,
+    bg: Option<String>,
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underline: bool,
+    inverse: bool,
+    strikeout: bool,
+}
+
+impl SgrStyle {
+    /// Apply one SGR sequence's parameters; an empty list resets.
+    fn apply(&mut self, params: &[u16]) {
+        if params.is_empty() {
+            *self = SgrStyle::default();
+        }
+        let mut params = params.iter().copied();
+        while let Some(p) = params.next() {
+            match p {
+                0 => *self = SgrStyle::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                7 => self.inverse = true,
+                9 => self.strikeout = true,
+                22 => (self.bold, self.dim) = (false, false),
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.inverse = false,
+                29 => self.strikeout = false,
+                30..=37 => self.fg = Some(PALETTE[(p - 30) as usize].to_string()),
+                90..=97 => self.fg = Some(PALETTE[(p - 90 + 8) as usize].to_string()),
+                40..=47 => self.bg = Some(PALETTE[(p - 40) as usize].to_string()),
+                100..=107 => self.bg = Some(PALETTE[(p - 100 + 8) as usize].to_string()),
+                38 => self.fg = extended_color(&mut params),
+                48 => self.bg = extended_color(&mut params),
+                39 => self.fg = None,
+                49 => self.bg = None,
+                _ => {}
+            }
+        }
+    }
+
+    /// Inline CSS for this style; empty for the default.
+    fn css(&self) -> String {
+        let (mut fg, mut bg) = (self.fg.clone(), self.bg.clone());
+        if self.inverse {
+            (fg, bg) = (Some(bg.unwrap_or_else(|| PAGE_BG.to_string())), Some(fg.unwrap_or_else(|| PAGE_FG.to_string())));
+        }
+        let mut css = Vec::new();
+        css.extend(fg.map(|c| format!("color:{}", c)));
+        css.extend(bg.map(|c| format!("background-color:{}", c)));
+        if self.bold {
+            css.push("font-weight:bold".to_string());
+        }
+        if self.dim {
+            css.push("opacity:0.6".to_string());
+        }
+        if self.italic {
+            css.push("font-style:italic".to_string());
+        }
+        let lines: Vec<&str> = [(self.underline, "underline"), (self.strikeout, "line-through")]
+            .iter()
+            .filter(|(on, _)| *on)
+            .map(|(_, line)| *line)
+            .collect();
+        if !lines.is_empty() {
+            css.push(format!("text-decoration:{}", lines.join(" ")));
+        }
+        css.join(";")
+    }
+}
+
+/// The color after a 38 or 48: `5;N` from the 256-color palette or
+/// `2;R;G;B`. Malformed ones give the default color.
+fn extended_color(params: &mut impl Iterator<Item = u16>) -> Option<String> {
+    match params.next()? {
+        5 => params.next().and_then(|n| u8::try_from(n).ok()).map(indexed_color),
+        2 => {
+            let mut channel = || params.next().and_then(|c| u8::try_from(c).ok());
+            let (r, g, b) = (channel()?, channel()?, channel()?);
+            Some(format!("#{:02x}{:02x}{:02x}", r, g, b))
+        }
+        _ => None,
+    }
+}
+
+/// A 256-color palette entry: the 16 basic colors, a 6×6×6 cube, then 24
+/// grays.
+fn indexed_color(n: u8) -> String {
+    match n {
+        0..=15 => PALETTE[n as usize].to_string(),
+        16..=231 => {
+            let n = n - 16;
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            format!("#{:02x}{:02x}{:02x}", level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        _ => {
+            let v = 8 + (n - 232) * 10;
+            format!("#{:02x}{:02x}{:02x}", v, v, v)
+        }
+    }
+}
+
+/// Escape `text` for HTML, turning its SGR sequences into `<span>`s with
+/// inline styles. Other escape sequences are dropped.
+pub fn ansi_to_html(text: &str) -> String {
+    let mut html = String::with_capacity(text.len());
+    let mut style = SgrStyle::default();
+    let mut open = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            html.push_str(&escape_html(c.encode_utf8(&mut [0; 4])));
+            continue;
+        }
+        if chars.peek() != Some(&'[') {
+            chars.next();
+            continue;
+        }
+        chars.next();
+        let mut body = String::new();
+        let mut last = None;
+        for c in chars.by_ref() {
+            if ('\x40'..='\x7e').contains(&c) {
+                last = Some(c);
+                break;
+            }
+            body.push(c);
+        }
+        if last != Some('m') {
+            continue;
+        }
+        let params: Vec<u16> = body.split([';', ':']).map(|p| p.parse().unwrap_or(0)).collect();
+        let mut next = style.clone();
+        next.apply(if body.is_empty() { &[] } else { &params });
+        if next == style {
+            continue;
+        }
+        style = next;
+        if open {
+            html.push_str("</span>");
+            open = false;
+        }
+        let css = style.css();
+        if !css.is_empty() {
+            html.push_str(&format!("<span style=\"{}\">", css));
+            open = true;
+        }
+    }
+    if open {
+        html.push_str("</span>");
+    }
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use portable_pty::{native_pty_system, ExitStatus, PtySize};
+
+    /// A 30×4 pane that ran `command` for 75 seconds, printed `output` and
+    /// exited with `code`.
+    fn finished_pane(id: usize, name: &str, command: &str, output: &str, code: u32) -> Pane {
+        let pair = native_pty_system()
+            .openpty(PtySize { rows: 4, cols: 30, pixel_width: 0, pixel_height: 0 })
+            .expect("openpty");
+        let input = crate::pty::launch_writer_thread(id, Box::new(io::sink()));
+        let mut pane = Pane::new(id, name.to_string(), pair.master, input, 100, Arc::default(), 30, 4);
+        pane.command = command.to_string();
+        pane.cwd = "/src".to_string();
+        pane.process_output(output.as_bytes());
+        pane.exit_status = Some(ExitStatus::with_exit_code(code));
+        pane.exited_at = Some(pane.started_at + Duration::from_secs(75));
+        pane
+    }
+
+    fn report(panes: &[Pane], format: ReportFormat, only: Option<&str>) -> (usize, String) {
+        let options = ReportOptions { format, lines: 2, only: only.map(|re| Regex::new(re).unwrap()) };
+        let mut out = Vec::new();
+        let written = write_report(panes, &options, &mut out).unwrap();
+        (written, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn markdown_report_lists_panes_in_opening_order() {
+        let panes = [
+            finished_pane(1, "web", "npm start", "one\r\ntwo\r\nthree", 0),
+            finished_pane(0, "test #1 `unit`", "cargo test", "```\r\n\x1b[31mFAILED\x1b[0m", 101),
+        ];
+        let (written, text) = report(&panes, ReportFormat::Markdown, None);
+        assert_eq!(written, 2);
+        assert_eq!(
+            text,
+            concat!(
+                "# bamboo session report\n\n2 of 2 panes.\n",
+                "\n## test \\#1 \\`unit\\`\n\n",
+                "- command: `cargo test`\n",
+                "- status: `exited with code 101`\n",
+                "- ran: `1m15s`\n",
+                "- cwd: `/src`\n",
+                "\n````text\n```\nFAILED\n````\n",
+                "\n## web\n\n",
+                "- command: `npm start`\n",
+                "- status: `exited with code 0`\n",
+                "- ran: `1m15s`\n",
+                "- cwd: `/src`\n",
+                "\n```text\ntwo\nthree\n```\n",
+            )
+        );
+    }
+
+    #[test]
+    fn html_report_escapes_names_and_colors_output() {
+        let panes = [finished_pane(0, "<api> & co", "make", "\x1b[31mFAILED\x1b[0m", 2)];
+        let (_, html) = report(&panes, ReportFormat::Html, None);
+        assert!(html.starts_with("<!DOCTYPE html>\n"));
+        assert!(html.contains("<p>1 of 1 panes.</p>"));
+        assert!(html.contains("<h2>&lt;api&gt; &amp; co</h2>"));
+        assert!(html.contains("<dt>status</dt><dd><code>exited with code 2</code></dd>"));
+        assert!(html.contains("<pre><span style=\"color:#cd0000\">FAILED</span>"));
+        assert!(html.ends_with("</body></html>\n"));
+    }
+
+    #[test]
+    fn the_pattern_keeps_only_matching_panes() {
+        let panes = [
+            finished_pane(0, "api", "make api", "a", 0),
+            finished_pane(1, "web", "npm start", "w", 0),
+            finished_pane(2, "api-tests", "make test", "t", 1),
+        ];
+        let (written, text) = report(&panes, ReportFormat::Markdown, Some("^api"));
+        assert_eq!(written, 2);
+        assert!(text.contains("2 of 3 panes."));
+        assert!(text.contains("## api\n") && text.contains("## api-tests\n"));
+        assert!(!text.contains("## web"));
+        assert!(text.find("## api\n") < text.find("## api-tests"));
+    }
+
+    #[test]
+    fn basic_colors_and_bold_become_spans() {
+        assert_eq!(
+            ansi_to_html("ok \x1b[1;31mFAIL\x1b[0m <done>\n"),
+            "ok <span style=\"color:#cd0000;font-weight:bold\">FAIL</span> &lt;done&gt;\n"
+        );
+        assert_eq!(ansi_to_html("\x1b[92;44mx\x1b[39my\x1b[m"), concat!(
+            "<span style=\"color:#00ff00;background-color:#0000ee\">x</span>",
+            "<span style=\"background-color:#0000ee\">y</span>"
+        ));
+        assert_eq!(ansi_to_html("\x1b[0mplain\x1b[0m"), "plain");
+    }
+
+    #[test]
+    fn extended_colors_and_inverse() {
+        assert_eq!(ansi_to_html("\x1b[38;5;196mr"), "<span style=\"color:#ff0000\">r</span>");
+        assert_eq!(ansi_to_html("\x1b[38;5;16mk"), "<span style=\"color:#000000\">k</span>");
+        assert_eq!(ansi_to_html("\x1b[48;5;244mg"), "<span style=\"background-color:#808080\">g</span>");
+        assert_eq!(ansi_to_html("\x1b[38;5;9mb"), "<span style=\"color:#ff0000\">b</span>");
+        assert_eq!(ansi_to_html("\x1b[38;2;1;2;255mt"), "<span style=\"color:#0102ff\">t</span>");
+        assert_eq!(
+            ansi_to_html("\x1b[7mi"),
+            format!("<span style=\"color:{};background-color:{}\">i</span>", PAGE_BG, PAGE_FG)
+        );
+        // A truncated extended color falls back to the default.
+        assert_eq!(ansi_to_html("\x1b[38;5mz"), "z");
+    }
+
+    #[test]
+    fn other_escapes_are_dropped() {
+        assert_eq!(ansi_to_html("a\x1b[2Kb\x1b7c"), "abc");
+    }
+
+    #[test]
+    fn fences_outlast_backticks_in_the_output() {
+        assert_eq!(fence_for("plain"), "```");
+        assert_eq!(fence_for("```rust\n```"), "````");
+        assert_eq!(inline_code("cargo test"), "`cargo test`");
+        assert_eq!(inline_code("echo `date`"), "`` echo `date` ``");
+        assert_eq!(ReportFormat::from_path(Path::new("/tmp/r.HTML")), ReportFormat::Html);
+        assert_eq!(ReportFormat::from_path(Path::new("/tmp/r.md")), ReportFormat::Markdown);
+    }
+}
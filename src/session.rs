@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+
+use crate::pane::Pane;
+
+/// Bumped whenever a field is renamed or removed, so scripts reading
+/// snapshots can tell. Adding fields doesn't bump it.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Machine-readable description of a running session, written as JSON for
+/// tooling.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionSnapshot {
+    pub version: u32,
+    /// Id of the focused pane.
+    pub focused: Option<usize>,
+    /// Panes in stack order.
+    pub panes: Vec<PaneSnapshot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaneSnapshot {
+    pub id: usize,
+    /// Kept across restarts, unlike `id`; what `#ID` targets refer to.
+    pub stable_id: usize,
+    pub name: String,
+    pub command: String,
+    pub pid: Option<u32>,
+    pub state: PaneState,
+    pub cwd: String,
+    pub rows: u16,
+    pub cols: u16,
+    pub weight: u16,
+    pub collapsed: bool,
+    /// Lines scrolled back from the live view.
+    pub scroll_offset: usize,
+    /// Bytes of output since the pane was last shown focused.
+    pub unseen_output: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum PaneState {
+    Running,
+    /// `code` is unknown until the process has been reaped.
+    Exited { code: Option<u32> },
+    /// The process couldn't be started.
+    Failed { error: String },
+}
+
+impl SessionSnapshot {
+    pub fn capture(panes: &[Pane], focused: usize) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            focused: panes.get(focused).map(|p| p.id),
+            panes: panes.iter().map(PaneSnapshot::capture).collect(),
+        }
+    }
+}
+
+impl PaneSnapshot {
+    pub fn capture(pane: &Pane) -> Self {
+        let state = if let Some(error) = &pane.spawn_error {
+            PaneState::Failed { error: error.clone() }
+        } else if pane.closed || pane.exit_status.is_some() {
+            PaneState::Exited {
+                code: pane.exit_status.as_ref().map(|s| s.exit_code()),
+            }
+        } else {
+            PaneState::Running
+        };
+        Self {
+            id: pane.id,
+            stable_id: pane.stable_id,
+            name: pane.name.clone(),
+            command: pane.command.clone(),
+            pid: pane.pid,
+            state,
+            cwd: pane.cwd.clone(),
+            rows: pane.rows,
+            cols: pane.cols,
+            weight: pane.weight,
+            collapsed: pane.collapsed,
+            scroll_offset: pane.scroll_offset,
+            unseen_output: pane.unseen_output,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pane(id: usize, state: PaneState) -> PaneSnapshot {
+        PaneSnapshot {
+            id,
+            stable_id: id,
+            name: "api".to_string(),
+            command: "cargo run".to_string(),
+            pid: Some(4242),
+            state,
+            cwd: "/src/api".to_string(),
+            rows: 20,
+            cols: 80,
+            weight: 10,
+            collapsed: false,
+            scroll_offset: 3,
+            unseen_output: 512,
+        }
+    }
+
+    /// Scripts parse this output; a failure here means the schema changed
+    /// and `SNAPSHOT_VERSION` probably needs a bump.
+    #[test]
+    fn json_schema_is_stable() {
+        let snapshot = SessionSnapshot {
+            version: SNAPSHOT_VERSION,
+            focused: Some(0),
+            panes: vec![
+                pane(0, PaneState::Running),
+                pane(1, PaneState::Exited { code: Some(1) }),
+                pane(2, PaneState::Failed { error: "no such file".to_string() }),
+            ],
+        };
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "version": 1,
+                "focused": 0,
+                "panes": [
+                    {
+                        "id": 0, "stable_id": 0, "name": "api", "command": "cargo run", "pid": 4242,
+                        "state": { "status": "running" },
+                        "cwd": "/src/api", "rows": 20, "cols": 80, "weight": 10,
+                        "collapsed": false, "scroll_offset": 3, "unseen_output": 512
+                    },
+                    {
+                        "id": 1, "stable_id": 1, "name": "api", "command": "cargo run", "pid": 4242,
+                        "state": { "status": "exited", "code": 1 },
+                        "cwd": "/src/api", "rows": 20, "cols": 80, "weight": 10,
+                        "collapsed": false, "scroll_offset": 3, "unseen_output": 512
+                    },
+                    {
+                        "id": 2, "stable_id": 2, "name": "api", "command": "cargo run", "pid": 4242,
+                        "state": { "status": "failed", "error": "no such file" },
+                        "cwd": "/src/api", "rows": 20, "cols": 80, "weight": 10,
+                        "collapsed": false, "scroll_offset": 3, "unseen_output": 512
+                    }
+                ]
+            })
+        );
+        let back: SessionSnapshot = serde_json::from_value(json).unwrap();
+        assert_eq!(back, snapshot);
+    }
+}
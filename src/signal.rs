@@ -0,0 +1,131 @@
+use std::fmt;
+use std::io;
+
+/// Signals offered by the Alt+Shift+K picker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Term,
+    Int,
+    Hup,
+    Quit,
+    Usr1,
+    Usr2,
+    Kill,
+}
+
+pub const SIGNALS: [Signal; 7] =
+    [Signal::Term, Signal::Int, Signal::Hup, Signal::Quit, Signal::Usr1, Signal::Usr2, Signal::Kill];
+
+/// Suffix of a picker entry that signals the pane's foreground process
+/// group rather than the process bamboo started.
+const GROUP_SUFFIX: &str = " (group)";
+
+impl Signal {
+    pub fn name(self) -> &'static str {
+        match self {
+            Signal::Term => "TERM",
+            Signal::Int => "INT",
+            Signal::Hup => "HUP",
+            Signal::Quit => "QUIT",
+            Signal::Usr1 => "USR1",
+            Signal::Usr2 => "USR2",
+            Signal::Kill => "KILL",
+        }
+    }
+
+    /// Picker entries: each signal for the process, then for its group.
+    pub fn choices() -> Vec<String> {
+        let single = SIGNALS.iter().map(|s| s.name().to_string());
+        let group = SIGNALS.iter().map(|s| format!("{}{}", s.name(), GROUP_SUFFIX));
+        single.chain(group).collect()
+    }
+
+    /// The signal and whether it goes to the group, from a picker entry.
+    pub fn parse_choice(choice: &str) -> Option<(Signal, bool)> {
+        let (name, group) = match choice.strip_suffix(GROUP_SUFFIX) {
+            Some(name) => (name, true),
+            None => (choice, false),
+        };
+        let name = name.trim().to_ascii_uppercase();
+        let name = name.strip_prefix("SIG").unwrap_or(&name);
+        SIGNALS.iter().find(|s| s.name() == name).map(|&s| (s, group))
+    }
+
+    #[cfg(unix)]
+    fn number(self) -> libc::c_int {
+        match self {
+            Signal::Term => libc::SIGTERM,
+            Signal::Int => libc::SIGINT,
+            Signal::Hup => libc::SIGHUP,
+            Signal::Quit => libc::SIGQUIT,
+            Signal::Usr1 => libc::SIGUSR1,
+            Signal::Usr2 => libc::SIGUSR2,
+            Signal::Kill => libc::SIGKILL,
+        }
+    }
+}
+
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SIG{}", self.name())
+    }
+}
+
+/// Deliver `signal` to process `pid`, or to process group `pid` when
+/// `group` is set.
+#[cfg(unix)]
+pub fn send(pid: u32, signal: Signal, group: bool) -> io::Result<()> {
+    let pid = libc::pid_t::try_from(pid).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    let target = if group { -pid } else { pid };
+    // SAFETY: kill(2) takes plain integers and touches no memory of ours.
+    if unsafe { libc::kill(target, signal.number()) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// The target of `send` had already gone.
+#[cfg(unix)]
+pub fn is_gone(e: &io::Error) -> bool {
+    e.raw_os_error() == Some(libc::ESRCH)
+}
+
+#[cfg(not(unix))]
+pub fn is_gone(_e: &io::Error) -> bool {
+    false
+}
+
+/// Windows has no signals; the caller falls back to terminating the
+/// process for `Signal::Kill`.
+#[cfg(not(unix))]
+pub fn send(_pid: u32, _signal: Signal, _group: bool) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picker_entries_round_trip() {
+        for choice in Signal::choices() {
+            let (signal, group) = Signal::parse_choice(&choice).unwrap();
+            assert!(choice.starts_with(signal.name()));
+            assert_eq!(group, choice.ends_with(GROUP_SUFFIX));
+        }
+        assert_eq!(Signal::parse_choice("sigusr1"), Some((Signal::Usr1, false)));
+        assert_eq!(Signal::parse_choice("STOP"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sending_to_a_reaped_process_reports_no_such_process() {
+        let mut child = std::process::Command::new("sleep").arg("10").spawn().unwrap();
+        let pid = child.id();
+        send(pid, Signal::Term, false).unwrap();
+        assert!(!child.wait().unwrap().success());
+        let err = send(pid, Signal::Term, false).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ESRCH));
+    }
+}
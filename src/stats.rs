@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::pane::Pane;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Cheap in-process counters shown by the F12 debug overlay.
+///
+/// Reader tasks and forwarders bump atomics; the event loop samples them once
+/// per second so the overlay only formats a handful of numbers per frame.
+pub struct DebugStats {
+    pub visible: bool,
+    /// PTY events sent to the event loop but not yet handled.
+    pub queue_depth: Arc<AtomicUsize>,
+    /// Duration of the most recent `terminal.draw`.
+    pub last_frame: Duration,
+    pub draws_per_sec: f64,
+    /// Bytes per second read from each pane's PTY, keyed by pane id.
+    pub pane_rates: HashMap<usize, f64>,
+    /// Title text changes per second for each pane, keyed by pane id.
+    pub title_rates: HashMap<usize, f64>,
+    /// Resident memory of the whole process; `None` where the OS doesn't
+    /// report it.
+    pub resident: Option<u64>,
+    frames_since_sample: u32,
+    sample_start: Instant,
+    pane_totals: HashMap<usize, u64>,
+    title_totals: HashMap<usize, u64>,
+}
+
+impl Default for DebugStats {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            queue_depth: Arc::new(AtomicUsize::new(0)),
+            last_frame: Duration::ZERO,
+            draws_per_sec: 0.0,
+            pane_rates: HashMap::new(),
+            title_rates: HashMap::new(),
+            resident: None,
+            frames_since_sample: 0,
+            sample_start: Instant::now(),
+            pane_totals: HashMap::new(),
+            title_totals: HashMap::new(),
+        }
+    }
+}
+
+impl DebugStats {
+    pub fn record_frame(&mut self, elapsed: Duration) {
+        self.last_frame = elapsed;
+        self.frames_since_sample += 1;
+    }
+
+    pub fn queued(&self) -> usize {
+        self.queue_depth.load(Ordering::Relaxed)
+    }
+
+    /// Recompute per-second rates once `SAMPLE_INTERVAL` has elapsed.
+    pub fn sample(&mut self, panes: &[Pane]) {
+        let elapsed = self.sample_start.elapsed();
+        if elapsed < SAMPLE_INTERVAL {
+            return;
+        }
+        let secs = elapsed.as_secs_f64();
+        self.draws_per_sec = self.frames_since_sample as f64 / secs;
+        self.frames_since_sample = 0;
+        self.sample_start = Instant::now();
+        self.resident = resident_bytes();
+
+        self.pane_rates.clear();
+        let mut totals = HashMap::with_capacity(panes.len());
+        for pane in panes {
+            let total = pane.bytes_read();
+            let previous = self.pane_totals.get(&pane.id).copied().unwrap_or(total);
+            self.pane_rates
+                .insert(pane.id, total.saturating_sub(previous) as f64 / secs);
+            totals.insert(pane.id, total);
+        }
+        self.pane_totals = totals;
+
+        self.title_rates.clear();
+        let mut totals = HashMap::with_capacity(panes.len());
+        for pane in panes {
+            let previous = self.title_totals.get(&pane.id).copied().unwrap_or(pane.title_repaints);
+            self.title_rates
+                .insert(pane.id, pane.title_repaints.saturating_sub(previous) as f64 / secs);
+            totals.insert(pane.id, pane.title_repaints);
+        }
+        self.title_totals = totals;
+    }
+}
+
+/// The process's resident set size, from `/proc/self/status` on Linux.
+fn resident_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Format a byte count as a short human-readable string.
+pub fn format_bytes(bytes: f64) -> String {
+    if bytes >= 1024.0 * 1024.0 {
+        format!("{:.1}M", bytes / (1024.0 * 1024.0))
+    } else if bytes >= 1024.0 {
+        format!("{:.1}K", bytes / 1024.0)
+    } else {
+        format!("{:.0}B", bytes)
+    }
+}
@@ -0,0 +1,183 @@
+/// Bytes of an unterminated escape sequence kept before giving up on it, so a
+/// program that never finishes an OSC can't grow the buffer without bound.
+const MAX_PENDING: usize = 4096;
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Ground,
+    /// After ESC.
+    Escape,
+    /// Inside `ESC [ ...` until the final byte.
+    Csi,
+    /// Inside an OSC/DCS/APC/PM/SOS string until BEL or ST.
+    Str,
+    /// ESC seen inside a string; `\` completes the ST.
+    StrEscape,
+}
+
+/// Reassembles PTY output into text that never ends partway through a UTF-8
+/// character or an escape sequence.
+///
+/// Reads split the stream at arbitrary byte offsets; consumers that look at
+/// output as text (logging, matching) go through this instead of decoding
+/// each read on its own. The terminal parser keeps receiving raw bytes.
+#[derive(Default)]
+pub struct StreamAssembler {
+    pending: Vec<u8>,
+}
+
+impl StreamAssembler {
+    /// Add a chunk of output and return the text that is now complete. An
+    /// incomplete tail is held back until the next call.
+    pub fn push(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+        let mut split = complete_prefix(&self.pending);
+        if self.pending.len() - split > MAX_PENDING {
+            split = self.pending.len();
+        }
+        let text = String::from_utf8_lossy(&self.pending[..split]).into_owned();
+        self.pending.drain(..split);
+        text
+    }
+
+    /// Return whatever is still held back, e.g. once the PTY has closed.
+    pub fn flush(&mut self) -> String {
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        text
+    }
+}
+
+/// Length of the longest prefix of `bytes` that ends between characters and
+/// outside any escape sequence.
+fn complete_prefix(bytes: &[u8]) -> usize {
+    let mut state = State::Ground;
+    let mut utf8_left = 0u8;
+    let mut boundary = 0;
+
+    for (i, &b) in bytes.iter().enumerate() {
+        // A byte that isn't the expected continuation means the character
+        // was truncated; lossy decoding replaces it and the byte is handled
+        // on its own below.
+        if utf8_left > 0 && b & 0xC0 == 0x80 {
+            utf8_left -= 1;
+            if utf8_left == 0 && state == State::Ground {
+                boundary = i + 1;
+            }
+            continue;
+        }
+
+        let (next, complete) = match state {
+            _ if b == 0x18 || b == 0x1a => (State::Ground, true), // CAN/SUB abort
+            State::Ground if b == 0x1b => (State::Escape, false),
+            State::Ground => (State::Ground, true),
+            State::Escape => escape(b),
+            State::Csi => match b {
+                0x1b => (State::Escape, false),
+                0x40..=0x7e => (State::Ground, true),
+                _ => (State::Csi, false),
+            },
+            State::Str => match b {
+                0x07 => (State::Ground, true),
+                0x1b => (State::StrEscape, false),
+                _ => (State::Str, false),
+            },
+            State::StrEscape if b == b'\\' => (State::Ground, true),
+            State::StrEscape => escape(b),
+        };
+        state = next;
+
+        utf8_left = match b {
+            0xc2..=0xdf => 1,
+            0xe0..=0xef => 2,
+            0xf0..=0xf4 => 3,
+            _ => 0,
+        };
+        if complete && utf8_left == 0 {
+            boundary = i + 1;
+        }
+    }
+    boundary
+}
+
+/// Transition for the byte after ESC.
+fn escape(b: u8) -> (State, bool) {
+    match b {
+        b'[' => (State::Csi, false),
+        b']' | b'P' | b'_' | b'^' | b'X' => (State::Str, false),
+        // Intermediates such as the `(` in `ESC ( B`, or another ESC.
+        0x1b | 0x20..=0x2f => (State::Escape, false),
+        _ => (State::Ground, true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &[u8] = "plain é ü 漢字 🎋\r\n\
+        \x1b[1;31mred\x1b[0m \x1b(B\x1b]0;tïtle\x07 \x1b]2;st\x1b\\ \x1bPdcs\x1b\\ end ✓"
+        .as_bytes();
+
+    fn assemble(chunks: &[&[u8]]) -> Vec<String> {
+        let mut assembler = StreamAssembler::default();
+        let mut out: Vec<String> = chunks.iter().map(|c| assembler.push(c)).collect();
+        out.push(assembler.flush());
+        out
+    }
+
+    #[test]
+    fn every_two_way_split_reassembles_exactly() {
+        for at in 0..=SAMPLE.len() {
+            let (a, b) = SAMPLE.split_at(at);
+            let out = assemble(&[a, b]);
+            assert_eq!(out.concat().as_bytes(), SAMPLE, "split at {}", at);
+            assert!(out.iter().all(|s| !s.contains('\u{fffd}')), "split at {}: {:?}", at, out);
+        }
+    }
+
+    #[test]
+    fn byte_at_a_time_never_splits_a_character_or_sequence() {
+        let chunks: Vec<&[u8]> = SAMPLE.chunks(1).collect();
+        let out = assemble(&chunks);
+        assert_eq!(out.concat().as_bytes(), SAMPLE);
+        for piece in out.iter().filter(|s| !s.is_empty()) {
+            assert!(!piece.contains('\u{fffd}'));
+            assert!(!piece.ends_with('\x1b'), "{:?}", piece);
+        }
+        // The OSC title arrives in one piece.
+        assert!(out.iter().any(|s| s == "\x1b]0;tïtle\x07"));
+    }
+
+    #[test]
+    fn incomplete_tail_is_held_until_completed() {
+        let mut assembler = StreamAssembler::default();
+        assert_eq!(assembler.push(b"ab\xe6\xbc"), "ab");
+        assert_eq!(assembler.push(b"\xa2\x1b["), "漢");
+        assert_eq!(assembler.push(b"31"), "");
+        assert_eq!(assembler.push(b"m!"), "\x1b[31m!");
+    }
+
+    #[test]
+    fn invalid_bytes_do_not_stall_output() {
+        let mut assembler = StreamAssembler::default();
+        assert_eq!(assembler.push(b"a\xffb\xe6c"), "a\u{fffd}b\u{fffd}c");
+    }
+
+    #[test]
+    fn unterminated_string_is_released_past_the_limit() {
+        let mut assembler = StreamAssembler::default();
+        assert_eq!(assembler.push(b"\x1b]0;"), "");
+        let long = vec![b'x'; MAX_PENDING];
+        assert_eq!(assembler.push(&long).len(), MAX_PENDING + 4);
+        assert_eq!(assembler.push(b"ok"), "ok");
+    }
+
+    #[test]
+    fn flush_returns_the_held_tail() {
+        let mut assembler = StreamAssembler::default();
+        assert_eq!(assembler.push(b"x\x1b]0;half"), "x");
+        assert_eq!(assembler.flush(), "\x1b]0;half");
+        assert_eq!(assembler.flush(), "");
+    }
+}
@@ -1,18 +1,37 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
 use alacritty_terminal::event::{Event, EventListener};
 use alacritty_terminal::grid::Dimensions;
+use alacritty_terminal::index::{Column, Line};
 use alacritty_terminal::term::cell::Flags as CellFlags;
-use alacritty_terminal::term::Config as TermConfig;
+use alacritty_terminal::term::{Config as TermConfig, Osc52};
 use alacritty_terminal::vte::ansi::{Color as AnsiColor, NamedColor, Processor, Rgb};
 use alacritty_terminal::Term;
-use parking_lot::Mutex;
-use std::sync::Arc;
+use regex::Regex;
+
+use crate::config::ColorMode;
 
-/// No-op event listener — we poll state rather than react to events.
-#[derive(Clone)]
-pub struct VoidListener;
+/// Queues the events a `Term` raises while parsing output, such as OSC 52
+/// clipboard requests, for the event loop to act on after each read.
+/// Screen state is still polled rather than tracked through events.
+#[derive(Clone, Default)]
+pub struct PaneListener {
+    events: Arc<Mutex<Vec<Event>>>,
+}
 
-impl EventListener for VoidListener {
-    fn send_event(&self, _event: Event) {}
+impl PaneListener {
+    /// Events raised since the last call.
+    pub fn take(&self) -> Vec<Event> {
+        std::mem::take(&mut *self.events.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl EventListener for PaneListener {
+    fn send_event(&self, event: Event) {
+        self.events.lock().unwrap_or_else(|e| e.into_inner()).push(event);
+    }
 }
 
 /// Terminal size passed to `Term::new` and `Term::resize`.
@@ -35,17 +54,25 @@ impl Dimensions for TermSize {
     }
 }
 
-/// Create a new alacritty `Term` wrapped in `Arc<Mutex<_>>`.
-pub fn new_term(rows: u16, cols: u16, scrollback: usize) -> Arc<Mutex<Term<VoidListener>>> {
+/// Create a new alacritty `Term` reporting to `listener`.
+pub fn new_term(rows: u16, cols: u16, scrollback: usize, listener: PaneListener) -> Term<PaneListener> {
     let size = TermSize {
         cols: cols as usize,
         rows: rows as usize,
     };
     let config = TermConfig {
         scrolling_history: scrollback,
+        // Whether clipboard requests are honored is decided by `osc52`.
+        osc52: Osc52::CopyPaste,
         ..TermConfig::default()
     };
-    Arc::new(Mutex::new(Term::new(config, &size, VoidListener)))
+    Term::new(config, &size, listener)
+}
+
+/// Change how many lines of history `term` keeps. Lines past a lower limit
+/// are dropped, and their memory freed, right away.
+pub fn set_scrollback(term: &mut Term<PaneListener>, lines: usize) {
+    term.grid_mut().update_history(lines);
 }
 
 /// Create a new `vte::ansi::Processor` for feeding bytes into the `Term`.
@@ -53,9 +80,47 @@ pub fn new_processor() -> Processor {
     Processor::new()
 }
 
+/// Put a terminal back in its default modes without clearing it: ASCII
+/// character sets, plain colors and the default palette, a visible cursor,
+/// autowrap, the full scroll region, and no mouse, paste or keypad modes.
+/// Unlike RIS (`ESC c`) it keeps the screen and scrollback, and a
+/// full-screen program stays on its alternate screen.
+pub const SOFT_RESET: &[u8] = b"\x1b(B\x1b)B\x0f\x1b[0m\x1b]104\x1b\\\x1b]110\x1b\\\x1b]111\x1b\\\
+    \x1b[r\x1b[4l\x1b[?6l\x1b[?7h\x1b[?25h\x1b[?1l\x1b>\
+    \x1b[?1000l\x1b[?1002l\x1b[?1003l\x1b[?1006l\x1b[?2004l";
+
+/// Put `term` back in its default modes with `SOFT_RESET`, leaving the
+/// cursor where it was rather than where resetting the scroll region sends
+/// it, the top left.
+pub fn soft_reset(term: &mut Term<PaneListener>, processor: &mut Processor) {
+    let (row, col) = cursor_position(term);
+    process_bytes(term, processor, SOFT_RESET);
+    process_bytes(term, processor, format!("\x1b[{};{}H", row + 1, col + 1).as_bytes());
+}
+
+/// Bytes of `output` that no text prints: C0 controls other than those
+/// terminals use every day, DEL, and invalid UTF-8. A sequence cut off at
+/// the end of the chunk doesn't count; the next read completes it.
+pub fn binary_bytes(output: &[u8]) -> usize {
+    let controls = output
+        .iter()
+        .filter(|&&b| (b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x07 | 0x08 | 0x1b)) || b == 0x7f)
+        .count();
+    let mut invalid = 0;
+    let mut rest = output;
+    while let Err(e) = std::str::from_utf8(rest) {
+        let Some(len) = e.error_len() else {
+            break;
+        };
+        invalid += len;
+        rest = &rest[e.valid_up_to() + len..];
+    }
+    controls + invalid
+}
+
 /// Feed bytes from PTY output into the terminal.
 pub fn process_bytes(
-    term: &mut Term<VoidListener>,
+    term: &mut Term<PaneListener>,
     processor: &mut Processor,
     bytes: &[u8],
 ) {
@@ -64,10 +129,10 @@ pub fn process_bytes(
 
 /// Get the full grapheme text in a cell at the given (row, col) position.
 /// Row 0 is the top of the visible viewport (accounting for display_offset/scrollback).
-pub fn cell_char(term: &Term<VoidListener>, row: usize, col: usize) -> String {
-    use alacritty_terminal::index::{Column, Line};
+pub fn cell_char(term: &Term<PaneListener>, row: usize, col: usize) -> String {
+    use alacritty_terminal::index::Column;
     let grid = term.grid();
-    let line = Line(row as i32);
+    let line = viewport_line(term, row);
     let column = Column(col);
     if row < term.screen_lines() && col < term.columns() {
         let cell = &grid[line][column];
@@ -83,10 +148,229 @@ pub fn cell_char(term: &Term<VoidListener>, row: usize, col: usize) -> String {
     }
 }
 
+/// Whether the row at `row` (as for `cell_char`) continues on the next row
+/// because the terminal soft-wrapped a long line.
+pub fn row_wrapped(term: &Term<PaneListener>, row: usize) -> bool {
+    use alacritty_terminal::index::Column;
+    let cols = term.columns();
+    row < term.screen_lines()
+        && cols > 0
+        && term.grid()[viewport_line(term, row)][Column(cols - 1)].flags.contains(CellFlags::WRAPLINE)
+}
+
+/// The grid line shown at viewport row `row`, which is in the history
+/// while the pane is scrolled back.
+fn viewport_line(term: &Term<PaneListener>, row: usize) -> Line {
+    Line(row as i32 - term.grid().display_offset() as i32)
+}
+
+/// The scrollback followed by the screen as text, as `write_capture`
+/// writes it: soft-wrapped rows joined into the line the program wrote,
+/// trailing blanks trimmed, and blank rows below the last output left out.
+pub fn scrollback_text(term: &Term<PaneListener>) -> String {
+    let mut text = Vec::new();
+    // Writing into a Vec can't fail.
+    let _ = write_capture(term, usize::MAX, false, &mut text);
+    String::from_utf8_lossy(&text).into_owned()
+}
+
+/// Write the last `lines` lines of the scrollback and screen to `out`, one
+/// row at a time so a long history never sits in memory as one string.
+/// Lines are counted as the program wrote them: soft-wrapped rows join,
+/// and blank rows below the last output are left out. With `ansi`, each
+/// line carries SGR codes for its colors and attributes and ends reset.
+pub fn write_capture(term: &Term<PaneListener>, lines: usize, ansi: bool, out: &mut impl Write) -> io::Result<()> {
+    use alacritty_terminal::index::Column;
+    let grid = term.grid();
+    let cols = term.columns();
+    let top = -(grid.history_size() as i32);
+    let wrapped = |line: i32| cols > 0 && grid[Line(line)][Column(cols - 1)].flags.contains(CellFlags::WRAPLINE);
+    let blank = |line: i32| (0..cols).all(|col| grid[Line(line)][Column(col)].c == ' ');
+    let Some(end) = (top..term.screen_lines() as i32).rev().find(|&line| !blank(line)).filter(|_| lines > 0) else {
+        return Ok(());
+    };
+    // Walk up to the start of the `lines`th logical line from the bottom.
+    let mut start = end;
+    let mut counted = 1;
+    while start > top {
+        if !wrapped(start - 1) {
+            if counted == lines {
+                break;
+            }
+            counted += 1;
+        }
+        start -= 1;
+    }
+
+    let mut text = String::new();
+    let mut pen = Pen::default();
+    for line in start..=end {
+        let row = &grid[Line(line)];
+        // Trailing cells that show nothing are dropped, unless the row
+        // runs on into the next.
+        let used = if wrapped(line) {
+            cols
+        } else {
+            (0..cols)
+                .rposition(|col| row[Column(col)].c != ' ' || (ansi && Pen::of(&row[Column(col)]).bg.is_some()))
+                .map_or(0, |col| col + 1)
+        };
+        for cell in (0..used).map(|col| &row[Column(col)]) {
+            if cell.flags.contains(CellFlags::WIDE_CHAR_SPACER) {
+                continue;
+            }
+            if ansi {
+                let next = Pen::of(cell);
+                if next != pen {
+                    next.write_sgr(&mut text);
+                    pen = next;
+                }
+            }
+            text.push(cell.c);
+            text.extend(cell.zerowidth().into_iter().flatten());
+        }
+        if !wrapped(line) {
+            if ansi && pen != Pen::default() {
+                text.push_str("\x1b[0m");
+                pen = Pen::default();
+            }
+            text.push('\n');
+            out.write_all(text.as_bytes())?;
+            text.clear();
+        }
+    }
+    out.write_all(text.as_bytes())
+}
+
+/// The colors and attributes `write_capture` tracks between cells; `None`
+/// is the default color.
+#[derive(Clone, Copy, PartialEq)]
+struct Pen {
+    fg: Option<AnsiColor>,
+    bg: Option<AnsiColor>,
+    flags: CellFlags,
+}
+
+impl Default for Pen {
+    fn default() -> Self {
+        Self {
+            fg: None,
+            bg: None,
+            flags: CellFlags::empty(),
+        }
+    }
+}
+
+impl Pen {
+    const FLAGS: [(CellFlags, &'static str); 6] = [
+        (CellFlags::BOLD, "1"),
+        (CellFlags::DIM, "2"),
+        (CellFlags::ITALIC, "3"),
+        (CellFlags::UNDERLINE, "4"),
+        (CellFlags::INVERSE, "7"),
+        (CellFlags::STRIKEOUT, "9"),
+    ];
+
+    fn of(cell: &alacritty_terminal::term::cell::Cell) -> Self {
+        let color = |color: AnsiColor, default: NamedColor| match color {
+            AnsiColor::Named(named) if named == default => None,
+            color => Some(color),
+        };
+        let flags = Self::FLAGS.iter().fold(CellFlags::empty(), |acc, &(flag, _)| acc | (cell.flags & flag));
+        Self {
+            fg: color(cell.fg, NamedColor::Foreground),
+            bg: color(cell.bg, NamedColor::Background),
+            flags,
+        }
+    }
+
+    /// Reset, then set everything this pen has, so no attribute of the
+    /// previous one lingers.
+    fn write_sgr(&self, out: &mut String) {
+        let mut params = vec!["0".to_string()];
+        params.extend(Self::FLAGS.iter().filter(|(flag, _)| self.flags.contains(*flag)).map(|(_, p)| p.to_string()));
+        params.extend(self.fg.and_then(|c| sgr_color(c, 30)));
+        params.extend(self.bg.and_then(|c| sgr_color(c, 40)));
+        out.push_str(&format!("\x1b[{}m", params.join(";")));
+    }
+}
+
+/// SGR parameters for `color` as a foreground (`base` 30) or background
+/// (`base` 40) color.
+fn sgr_color(color: AnsiColor, base: u8) -> Option<String> {
+    let extended = base + 8;
+    match color {
+        AnsiColor::Named(named) => {
+            let n = named as usize;
+            match n {
+                0..=7 => Some((base as usize + n).to_string()),
+                8..=15 => Some((base as usize + 60 + n - 8).to_string()),
+                _ => {
+                    let dim = NamedColor::DimBlack as usize;
+                    (dim..dim + 8).contains(&n).then(|| (base as usize + n - dim).to_string())
+                }
+            }
+        }
+        AnsiColor::Indexed(idx) => Some(format!("{};5;{}", extended, idx)),
+        AnsiColor::Spec(Rgb { r, g, b }) => Some(format!("{};2;{};{};{}", extended, r, g, b)),
+    }
+}
+
+/// Grid lines whose text matches `pattern`, oldest first: negative in the
+/// history, 0 at the top of the screen. The scan runs upwards from the
+/// bottom and stops at a line containing `stop`, or once `limit` lines
+/// matched.
+pub fn matching_lines(term: &Term<PaneListener>, pattern: &Regex, stop: Option<&str>, limit: usize) -> Vec<i32> {
+    use alacritty_terminal::index::Column;
+    let grid = term.grid();
+    let mut lines = Vec::new();
+    let mut text = String::new();
+    for line in (-(grid.history_size() as i32)..term.screen_lines() as i32).rev() {
+        let row = &grid[Line(line)];
+        text.clear();
+        text.extend((0..term.columns()).map(|col| row[Column(col)].c));
+        if stop.is_some_and(|stop| text.contains(stop)) {
+            break;
+        }
+        if pattern.is_match(&text) {
+            lines.push(line);
+            if lines.len() == limit {
+                break;
+            }
+        }
+    }
+    lines.reverse();
+    lines
+}
+
+/// A guess at the last command's output for shells that don't mark
+/// commands: the lines between the last blank line and the prompt the
+/// cursor is on, at most a screenful.
+pub fn recent_output_text(term: &Term<PaneListener>) -> String {
+    let text = scrollback_text(term);
+    let mut lines: Vec<&str> = text.lines().collect();
+    // The last line is the prompt waiting for the next command.
+    lines.pop();
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    let earliest = lines.len().saturating_sub(term.screen_lines());
+    let start = lines[earliest..]
+        .iter()
+        .rposition(|l| l.is_empty())
+        .map_or(earliest, |i| earliest + i + 1);
+    lines[start..].join("\n")
+}
+
 /// Information about a single cell for rendering.
-pub struct CellInfo {
-    /// Full grapheme: base char + any zero-width combining codepoints.
-    pub ch: String,
+///
+/// Borrows the cell's zero-width codepoints so the render loop can write
+/// single-codepoint cells without allocating.
+pub struct CellInfo<'a> {
+    /// Base character of the grapheme.
+    pub c: char,
+    /// Zero-width combining codepoints following `c`, if any.
+    pub zerowidth: Option<&'a [char]>,
     pub fg: AnsiColor,
     pub bg: AnsiColor,
     pub bold: bool,
@@ -98,22 +382,24 @@ pub struct CellInfo {
     pub strikethrough: bool,
 }
 
+impl CellInfo<'_> {
+    /// True for cells that were never written or only hold a space.
+    pub fn is_blank(&self) -> bool {
+        (self.c == ' ' || self.c == '\0') && self.zerowidth.is_none_or(|zw| zw.is_empty())
+    }
+}
+
 /// Get cell info at the given (row, col) in the visible viewport.
-pub fn cell_info(term: &Term<VoidListener>, row: usize, col: usize) -> CellInfo {
-    use alacritty_terminal::index::{Column, Line};
+pub fn cell_info(term: &Term<PaneListener>, row: usize, col: usize) -> CellInfo<'_> {
+    use alacritty_terminal::index::Column;
     let grid = term.grid();
-    let line = Line(row as i32);
+    let line = viewport_line(term, row);
     let column = Column(col);
     if row < term.screen_lines() && col < term.columns() {
         let cell = &grid[line][column];
-        let mut ch = String::from(cell.c);
-        if let Some(zw) = cell.zerowidth() {
-            for &c in zw {
-                ch.push(c);
-            }
-        }
         CellInfo {
-            ch,
+            c: cell.c,
+            zerowidth: cell.zerowidth(),
             fg: cell.fg,
             bg: cell.bg,
             bold: cell.flags.contains(CellFlags::BOLD),
@@ -129,7 +415,8 @@ pub fn cell_info(term: &Term<VoidListener>, row: usize, col: usize) -> CellInfo
         }
     } else {
         CellInfo {
-            ch: String::from(' '),
+            c: ' ',
+            zerowidth: None,
             fg: AnsiColor::Named(NamedColor::Foreground),
             bg: AnsiColor::Named(NamedColor::Background),
             bold: false,
@@ -142,9 +429,37 @@ pub fn cell_info(term: &Term<VoidListener>, row: usize, col: usize) -> CellInfo
     }
 }
 
-/// Convert an alacritty `Color` to a ratatui `Color`.
-pub fn convert_color(color: AnsiColor) -> ratatui::style::Color {
+/// xterm's default RGB values for the 16 ANSI colors, used to pick the
+/// nearest match when downgrading.
+const ANSI16_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// Channel levels of the 6×6×6 color cube in the 256-color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Convert an alacritty `Color` to a ratatui `Color`, downgrading to what the
+/// outer terminal can display under `mode`.
+pub fn convert_color(color: AnsiColor, mode: ColorMode) -> ratatui::style::Color {
     use ratatui::style::Color;
+    if mode == ColorMode::None {
+        return Color::Reset;
+    }
     match color {
         AnsiColor::Named(named) => match named {
             NamedColor::Black | NamedColor::DimBlack => Color::Black,
@@ -169,47 +484,440 @@ pub fn convert_color(color: AnsiColor) -> ratatui::style::Color {
             NamedColor::Background => Color::Reset,
             NamedColor::Cursor => Color::Reset,
         },
-        AnsiColor::Spec(Rgb { r, g, b }) => Color::Rgb(r, g, b),
-        AnsiColor::Indexed(idx) => match idx {
-            0 => Color::Black,
-            1 => Color::Red,
-            2 => Color::Green,
-            3 => Color::Yellow,
-            4 => Color::Blue,
-            5 => Color::Magenta,
-            6 => Color::Cyan,
-            7 => Color::White,
-            8 => Color::DarkGray,
-            9 => Color::LightRed,
-            10 => Color::LightGreen,
-            11 => Color::LightYellow,
-            12 => Color::LightBlue,
-            13 => Color::LightMagenta,
-            14 => Color::LightCyan,
-            15 => Color::Gray,
-            n => Color::Indexed(n),
+        AnsiColor::Spec(Rgb { r, g, b }) => match mode {
+            ColorMode::Ansi256 => Color::Indexed(nearest_256(r, g, b)),
+            ColorMode::Ansi16 => ansi16_color(nearest_16(r, g, b)),
+            _ => Color::Rgb(r, g, b),
         },
+        AnsiColor::Indexed(idx) if idx < 16 => ansi16_color(idx),
+        AnsiColor::Indexed(idx) => match mode {
+            ColorMode::Ansi16 => {
+                let (r, g, b) = indexed_rgb(idx);
+                ansi16_color(nearest_16(r, g, b))
+            }
+            _ => Color::Indexed(idx),
+        },
+    }
+}
+
+/// The bright counterpart of one of the 8 basic colors, as SGR 30–37 or a
+/// palette index below 8 give them; any other color is returned as-is.
+pub fn bright_variant(color: AnsiColor) -> AnsiColor {
+    match color {
+        AnsiColor::Named(named) if (named as usize) < 8 => AnsiColor::Named(named.to_bright()),
+        AnsiColor::Indexed(idx) if idx < 8 => AnsiColor::Indexed(idx + 8),
+        color => color,
+    }
+}
+
+/// Parse a config color: a name (`"blue"`, `"lightred"`), a palette index
+/// (`"236"`) or `"#rrggbb"`.
+pub fn parse_color(s: &str) -> Option<AnsiColor> {
+    use ratatui::style::Color;
+    let color: Color = s.parse().ok()?;
+    let idx = match color {
+        Color::Rgb(r, g, b) => return Some(AnsiColor::Spec(Rgb { r, g, b })),
+        Color::Indexed(n) => n,
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::White => 7,
+        Color::DarkGray => 8,
+        Color::LightRed => 9,
+        Color::LightGreen => 10,
+        Color::LightYellow => 11,
+        Color::LightBlue => 12,
+        Color::LightMagenta => 13,
+        Color::LightCyan => 14,
+        Color::Gray => 15,
+        Color::Reset => return None,
+    };
+    Some(AnsiColor::Indexed(idx))
+}
+
+/// A pane's own versions of the 16 ANSI colors, plus the option to flip the
+/// lightness of RGB colors, for programs with hard-coded colors meant for a
+/// different background. Applied only when drawing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PaneColors {
+    ansi: [Option<AnsiColor>; 16],
+    invert_luminance: bool,
+}
+
+impl PaneColors {
+    /// Build from a pane's `palette` table, which maps a color name or an
+    /// index below 16 to a color as `parse_color` reads it.
+    pub fn parse(palette: &HashMap<String, String>, invert_luminance: bool) -> anyhow::Result<Self> {
+        let mut ansi = [None; 16];
+        for (key, value) in palette {
+            let idx = match parse_color(key) {
+                Some(AnsiColor::Indexed(idx)) if idx < 16 => idx,
+                _ => anyhow::bail!("palette key {:?} is not one of the 16 ANSI colors", key),
+            };
+            let color = parse_color(value).ok_or_else(|| anyhow::anyhow!("invalid palette color {:?}", value))?;
+            ansi[idx as usize] = Some(color);
+        }
+        Ok(Self { ansi, invert_luminance })
+    }
+
+    /// The color to draw in place of `color`.
+    pub fn apply(&self, color: AnsiColor) -> AnsiColor {
+        let idx = match color {
+            AnsiColor::Indexed(idx) if idx < 16 => idx as usize,
+            AnsiColor::Named(named) if (named as usize) < 16 => named as usize,
+            AnsiColor::Named(named) if (NamedColor::DimBlack..=NamedColor::DimWhite).contains(&named) => {
+                named as usize - NamedColor::DimBlack as usize
+            }
+            AnsiColor::Spec(rgb) if self.invert_luminance => return AnsiColor::Spec(invert_lightness(rgb)),
+            _ => return color,
+        };
+        self.ansi[idx].unwrap_or(color)
+    }
+}
+
+/// `rgb` with its HSL lightness mirrored, keeping hue and saturation, so a
+/// dark blue becomes a light blue and white becomes black.
+fn invert_lightness(rgb: Rgb) -> Rgb {
+    let (max, min) = (rgb.r.max(rgb.g).max(rgb.b), rgb.r.min(rgb.g).min(rgb.b));
+    // Mirroring L about 1/2 keeps chroma, so every channel moves by the
+    // same amount.
+    let shift = 255 - (max as i32 + min as i32);
+    let adjust = |v: u8| (v as i32 + shift).clamp(0, 255) as u8;
+    Rgb { r: adjust(rgb.r), g: adjust(rgb.g), b: adjust(rgb.b) }
+}
+
+/// The child's default foreground or background as set via OSC 10 / OSC 11.
+pub fn dynamic_color(term: &Term<PaneListener>, named: NamedColor) -> Option<AnsiColor> {
+    term.colors()[named].map(AnsiColor::Spec)
+}
+
+/// ratatui color for one of the 16 ANSI palette entries.
+fn ansi16_color(idx: u8) -> ratatui::style::Color {
+    use ratatui::style::Color;
+    match idx {
+        0 => Color::Black,
+        1 => Color::Red,
+        2 => Color::Green,
+        3 => Color::Yellow,
+        4 => Color::Blue,
+        5 => Color::Magenta,
+        6 => Color::Cyan,
+        7 => Color::White,
+        8 => Color::DarkGray,
+        9 => Color::LightRed,
+        10 => Color::LightGreen,
+        11 => Color::LightYellow,
+        12 => Color::LightBlue,
+        13 => Color::LightMagenta,
+        14 => Color::LightCyan,
+        15 => Color::Gray,
+        n => Color::Indexed(n),
+    }
+}
+
+/// RGB value of a 256-color palette entry (16..=255).
+fn indexed_rgb(idx: u8) -> (u8, u8, u8) {
+    match idx {
+        0..=15 => ANSI16_RGB[idx as usize],
+        16..=231 => {
+            let i = idx - 16;
+            (
+                CUBE_LEVELS[(i / 36) as usize],
+                CUBE_LEVELS[((i / 6) % 6) as usize],
+                CUBE_LEVELS[(i % 6) as usize],
+            )
+        }
+        _ => {
+            let v = 8 + 10 * (idx - 232);
+            (v, v, v)
+        }
     }
 }
 
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// Nearest entry in the cube or grayscale ramp of the 256-color palette.
+/// The first 16 entries are skipped since their RGB values vary by theme.
+fn nearest_256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |v: u8| {
+        CUBE_LEVELS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, &l)| (l as i32 - v as i32).abs())
+            .map(|(i, _)| i as u8)
+            .unwrap_or(0)
+    };
+    let cube = 16 + 36 * level(r) + 6 * level(g) + level(b);
+
+    let avg = (r as u32 + g as u32 + b as u32) / 3;
+    let gray = 232 + ((avg.saturating_sub(8) + 5) / 10).min(23) as u8;
+
+    if distance((r, g, b), indexed_rgb(gray)) < distance((r, g, b), indexed_rgb(cube)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+/// Nearest of the 16 ANSI colors.
+fn nearest_16(r: u8, g: u8, b: u8) -> u8 {
+    ANSI16_RGB
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, &rgb)| distance((r, g, b), rgb))
+        .map(|(i, _)| i as u8)
+        .unwrap_or(7)
+}
+
 /// Get the number of screen lines (rows) in the terminal.
-pub fn screen_rows(term: &Term<VoidListener>) -> usize {
+pub fn screen_rows(term: &Term<PaneListener>) -> usize {
     term.screen_lines()
 }
 
+/// Columns up to the last non-blank cell of the widest row in view.
+pub fn content_width(term: &Term<PaneListener>) -> usize {
+    (0..screen_rows(term))
+        .map(|row| {
+            (0..term.columns())
+                .rev()
+                .find(|&col| !cell_info(term, row, col).is_blank())
+                .map_or(0, |col| col + 1)
+        })
+        .max()
+        .unwrap_or(0)
+}
+
 /// Get the number of columns in the terminal.
-pub fn screen_cols(term: &Term<VoidListener>) -> usize {
+pub fn screen_cols(term: &Term<PaneListener>) -> usize {
     term.columns()
 }
 
 /// Get cursor position (row, col) in the visible viewport.
-pub fn cursor_position(term: &Term<VoidListener>) -> (usize, usize) {
+pub fn cursor_position(term: &Term<PaneListener>) -> (usize, usize) {
     let point = term.grid().cursor.point;
     (point.line.0 as usize, point.column.0)
 }
 
+/// First screen row to draw when only `height` rows fit, chosen so the
+/// cursor stays visible when the PTY is taller than its pane.
+pub fn visible_row_offset(term: &Term<PaneListener>, height: u16) -> usize {
+    let hidden = screen_rows(term).saturating_sub(height as usize);
+    let (cursor_row, _) = cursor_position(term);
+    (cursor_row + 1).saturating_sub(height as usize).min(hidden)
+}
+
+/// Identifies the row now at grid line `line` (negative in the history)
+/// wherever the grid moves it: scrolling and clearing move rows with their
+/// cells rather than copying them, even once the history is full and its
+/// oldest rows are reused.
+pub fn row_key(term: &Term<PaneListener>, line: i32) -> usize {
+    &term.grid()[Line(line)][Column(0)] as *const _ as usize
+}
+
+/// Grid line of the row `row_key` gave `key`, looking up from line `from`
+/// since output moves rows up; `None` once it has left the history.
+pub fn find_line(term: &Term<PaneListener>, key: usize, from: i32) -> Option<i32> {
+    let top = -(history_lines(term) as i32);
+    (top..=from.min(screen_rows(term) as i32 - 1)).rev().find(|&line| row_key(term, line) == key)
+}
+
+/// Lines that have scrolled off the top into the history.
+pub fn history_lines(term: &Term<PaneListener>) -> usize {
+    term.grid().history_size()
+}
+
+/// Approximate heap bytes held by the scrollback history.
+pub fn scrollback_bytes(term: &Term<PaneListener>) -> usize {
+    term.grid().history_size() * term.columns() * std::mem::size_of::<alacritty_terminal::term::cell::Cell>()
+}
+
 /// Get the current scrollback display offset (0 = no scroll, positive = scrolled up).
 #[allow(dead_code)]
-pub fn display_offset(term: &Term<VoidListener>) -> usize {
+pub fn display_offset(term: &Term<PaneListener>) -> usize {
     term.grid().display_offset()
 }
+
+/// Replies to the XTGETTCAP queries (`DCS + q <hex names> ST`) in `text`,
+/// which alacritty leaves unanswered. Capabilities we don't know get the
+/// "invalid" reply so the program stops waiting instead of timing out.
+pub fn capability_replies(text: &str) -> Vec<String> {
+    let mut replies = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("\x1bP+q") {
+        rest = &rest[start + 4..];
+        let end = rest.find(['\x1b', '\x07']).unwrap_or(rest.len());
+        for name in rest[..end].split(';').filter(|n| !n.is_empty()) {
+            replies.push(match decode_hex(name).as_deref().and_then(capability) {
+                Some(value) => format!("\x1bP1+r{}={}\x1b\\", name, encode_hex(&value)),
+                None => format!("\x1bP0+r{}\x1b\\", name),
+            });
+        }
+        rest = &rest[end..];
+    }
+    replies
+}
+
+fn capability(name: &str) -> Option<String> {
+    match name {
+        "TN" | "name" => Some(std::env::var("TERM").unwrap_or_else(|_| "xterm-256color".to_string())),
+        "Co" | "colors" => Some("256".to_string()),
+        "RGB" => Some("8/8/8".to_string()),
+        _ => None,
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<String> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+fn encode_hex(text: &str) -> String {
+    text.bytes().map(|b| format!("{:02X}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pane_palette_replaces_ansi_colors_only() {
+        let palette = HashMap::from([
+            ("blue".to_string(), "#8fa1ff".to_string()),
+            ("0".to_string(), "white".to_string()),
+        ]);
+        let colors = PaneColors::parse(&palette, false).unwrap();
+        let light_blue = AnsiColor::Spec(Rgb { r: 0x8f, g: 0xa1, b: 0xff });
+        assert_eq!(colors.apply(AnsiColor::Indexed(4)), light_blue);
+        assert_eq!(colors.apply(AnsiColor::Named(NamedColor::Blue)), light_blue);
+        assert_eq!(colors.apply(AnsiColor::Named(NamedColor::DimBlue)), light_blue);
+        assert_eq!(colors.apply(AnsiColor::Named(NamedColor::Black)), AnsiColor::Indexed(7));
+        assert_eq!(colors.apply(AnsiColor::Indexed(12)), AnsiColor::Indexed(12));
+        assert_eq!(colors.apply(AnsiColor::Indexed(100)), AnsiColor::Indexed(100));
+        let fg = AnsiColor::Named(NamedColor::Foreground);
+        assert_eq!(colors.apply(fg), fg);
+
+        let bad = HashMap::from([("100".to_string(), "red".to_string())]);
+        assert!(PaneColors::parse(&bad, false).is_err());
+        let bad = HashMap::from([("red".to_string(), "nope".to_string())]);
+        assert!(PaneColors::parse(&bad, false).is_err());
+    }
+
+    #[test]
+    fn inverting_luminance_keeps_hue() {
+        let colors = PaneColors::parse(&HashMap::new(), true).unwrap();
+        let spec = |r, g, b| AnsiColor::Spec(Rgb { r, g, b });
+        assert_eq!(colors.apply(spec(255, 255, 255)), spec(0, 0, 0));
+        assert_eq!(colors.apply(spec(0, 0, 128)), spec(127, 127, 255));
+        assert_eq!(colors.apply(spec(128, 128, 128)), spec(127, 127, 127));
+        assert_eq!(colors.apply(AnsiColor::Indexed(4)), AnsiColor::Indexed(4));
+    }
+
+    #[test]
+    fn scrollback_text_includes_history() {
+        let mut term = new_term(2, 10, 100, PaneListener::default());
+        process_bytes(&mut term, &mut new_processor(), "one\r\ntwo  \r\n三\r\n".as_bytes());
+        assert_eq!(scrollback_text(&term), "one\ntwo\n三\n");
+    }
+
+    #[test]
+    fn cells_are_read_from_the_history_while_scrolled_back() {
+        let mut term = new_term(2, 4, 100, PaneListener::default());
+        process_bytes(&mut term, &mut new_processor(), b"abcdefgh\r\none\r\ntwo");
+        let row = |term: &Term<PaneListener>, r| (0..4).map(|c| cell_char(term, r, c)).collect::<String>();
+        assert_eq!((row(&term, 0), row(&term, 1)), ("one ".to_string(), "two ".to_string()));
+
+        term.scroll_display(alacritty_terminal::grid::Scroll::Delta(2));
+        assert_eq!((row(&term, 0), row(&term, 1)), ("abcd".to_string(), "efgh".to_string()));
+        assert!(row_wrapped(&term, 0) && !row_wrapped(&term, 1));
+        assert_eq!((cell_info(&term, 0, 0).c, cell_info(&term, 1, 3).c), ('a', 'h'));
+    }
+
+    #[test]
+    fn lowering_the_scrollback_limit_trims_history() {
+        let mut term = new_term(2, 10, 100, PaneListener::default());
+        let mut processor = new_processor();
+        for i in 0..50 {
+            process_bytes(&mut term, &mut processor, format!("line {}\r\n", i).as_bytes());
+        }
+        assert_eq!(history_lines(&term), 49);
+        set_scrollback(&mut term, 10);
+        assert_eq!(history_lines(&term), 10);
+        process_bytes(&mut term, &mut processor, b"more\r\nmore\r\n");
+        assert_eq!(history_lines(&term), 10, "the new limit holds for later output");
+        assert!(scrollback_text(&term).starts_with("line 41\n"));
+    }
+
+    #[test]
+    fn matching_lines_stop_at_the_divider_and_the_limit() {
+        let mut term = new_term(3, 20, 100, PaneListener::default());
+        let output = "error: old\r\n── run #1 ──\r\nok\r\nerror: a\r\nfine\r\nerror: b\r\n";
+        process_bytes(&mut term, &mut new_processor(), output.as_bytes());
+        let pattern = Regex::new("error").unwrap();
+        assert_eq!(matching_lines(&term, &pattern, None, 10), [-4, -1, 1]);
+        assert_eq!(matching_lines(&term, &pattern, Some(" run #1 "), 10), [-1, 1]);
+        assert_eq!(matching_lines(&term, &pattern, None, 1), [1]);
+    }
+
+    #[test]
+    fn scrollback_text_joins_soft_wrapped_rows() {
+        let mut term = new_term(4, 5, 100, PaneListener::default());
+        // Exactly the width: the cursor waits at the margin, nothing wraps.
+        process_bytes(&mut term, &mut new_processor(), b"abcde\r\nabcdef\r\nx");
+        assert!(!row_wrapped(&term, 0));
+        assert!(row_wrapped(&term, 1));
+        assert_eq!(scrollback_text(&term), "abcde\nabcdef\nx\n");
+    }
+
+    #[test]
+    fn binary_bytes_counts_what_text_never_holds() {
+        assert_eq!(binary_bytes(b"plain\ttext\x07\x08\r\n\x1b[1mbold"), 0);
+        assert_eq!(binary_bytes("h\u{e9}llo \u{4e09}".as_bytes()), 0);
+        assert_eq!(binary_bytes(b"\x00\x01\x7f\xff\xfe"), 5);
+        // A character split across reads isn't counted; a broken one is.
+        assert_eq!(binary_bytes(b"ab\xc3"), 0);
+        assert_eq!(binary_bytes(b"\xc3(ok\xe4\xb8"), 1);
+    }
+
+    #[test]
+    fn capture_counts_written_lines_and_keeps_colors_on_request() {
+        let mut term = new_term(4, 5, 100, PaneListener::default());
+        let output = "one\r\ntwo\r\nabcdefgh\r\n\x1b[1;31mred\x1b[0m \x1b[48;5;22m \x1b[0m\r\n";
+        process_bytes(&mut term, &mut new_processor(), output.as_bytes());
+        let capture = |lines, ansi| {
+            let mut out = Vec::new();
+            write_capture(&term, lines, ansi, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(capture(2, false), "abcdefgh\nred\n");
+        assert_eq!(capture(100, false), "one\ntwo\nabcdefgh\nred\n");
+        assert_eq!(capture(2, true), "abcdefgh\n\x1b[0;1;31mred\x1b[0m \x1b[0;48;5;22m \x1b[0m\n");
+        assert_eq!(capture(1, true), "\x1b[0;1;31mred\x1b[0m \x1b[0;48;5;22m \x1b[0m\n");
+    }
+
+    #[test]
+    fn recent_output_runs_from_the_last_blank_line() {
+        let mut term = new_term(6, 20, 100, PaneListener::default());
+        process_bytes(&mut term, &mut new_processor(), b"old\r\n\r\n$ make\r\nbuilt\r\n\r\n$ ");
+        assert_eq!(recent_output_text(&term), "$ make\nbuilt");
+    }
+
+    #[test]
+    fn answers_known_and_unknown_capabilities() {
+        // "Co" and "xx", hex-encoded, then an unrelated DCS.
+        let replies = capability_replies("ab\x1bP+q436F;7878\x1b\\cd\x1bPq#0\x1b\\");
+        assert_eq!(replies, vec!["\x1bP1+r436F=323536\x1b\\", "\x1bP0+r7878\x1b\\"]);
+    }
+}
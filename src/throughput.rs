@@ -0,0 +1,129 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::stats::format_bytes;
+
+/// Seconds of history the sparkline covers.
+pub const HISTORY: usize = 10;
+/// Each sample is the output of this much time.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const ASCII_BARS: [char; 8] = ['_', '.', ',', ':', '-', '=', '+', '#'];
+
+/// Rolling bytes and lines per second of one pane, sampled from the reader
+/// task's running totals.
+#[derive(Debug, Default)]
+pub struct Throughput {
+    /// Bytes and lines read in each of the last `HISTORY` intervals, oldest
+    /// first.
+    samples: VecDeque<(u64, u64)>,
+    /// Totals at the last sample.
+    totals: Option<(u64, u64)>,
+    sampled_at: Option<Instant>,
+}
+
+impl Throughput {
+    /// Take a sample from the reader's totals once `SAMPLE_INTERVAL` has
+    /// passed since the last one. A pane whose process is gone samples zero
+    /// whatever is still draining. Returns whether a sample was taken.
+    pub fn sample(&mut self, bytes: u64, lines: u64, running: bool, now: Instant) -> bool {
+        let Some(last) = self.totals.replace((bytes, lines)) else {
+            self.sampled_at = Some(now);
+            return false;
+        };
+        if self.sampled_at.is_some_and(|at| now.duration_since(at) < SAMPLE_INTERVAL) {
+            self.totals = Some(last);
+            return false;
+        }
+        self.sampled_at = Some(now);
+        let delta = if running { (bytes.saturating_sub(last.0), lines.saturating_sub(last.1)) } else { (0, 0) };
+        if self.samples.len() == HISTORY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(delta);
+        true
+    }
+
+    /// Bytes and lines per second over the last interval.
+    pub fn rate(&self) -> (u64, u64) {
+        self.samples.back().copied().unwrap_or_default()
+    }
+
+    /// The last `HISTORY` seconds of bytes per second as bars scaled to the
+    /// busiest one.
+    pub fn sparkline(&self, ascii: bool) -> String {
+        let bars = if ascii { &ASCII_BARS } else { &BARS };
+        let peak = self.samples.iter().map(|&(b, _)| b).max().unwrap_or(0);
+        self.samples
+            .iter()
+            .map(|&(b, _)| match b {
+                0 => bars[0],
+                _ => bars[((b * 7).div_ceil(peak) as usize).clamp(1, 7)],
+            })
+            .collect()
+    }
+
+    /// Title text, e.g. "▂▄▆ 1.2K/s 40 lines/s"; `None` before the first
+    /// sample.
+    pub fn meter(&self, accessible: bool) -> Option<String> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let (bytes, lines) = self.rate();
+        let bytes = format_bytes(bytes as f64);
+        Some(if accessible {
+            format!("[output {}/s, {} lines/s, last {}s: {}]", bytes, lines, self.samples.len(), self.sparkline(true))
+        } else {
+            format!("{} {}/s {} lines/s", self.sparkline(false), bytes, lines)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_once_a_second_and_keeps_ten() {
+        let start = Instant::now();
+        let mut t = Throughput::default();
+        assert!(!t.sample(0, 0, true, start));
+        assert_eq!(t.meter(false), None);
+        assert!(!t.sample(500, 5, true, start + Duration::from_millis(500)));
+        assert!(t.sample(2048, 20, true, start + SAMPLE_INTERVAL));
+        assert_eq!(t.rate(), (2048, 20));
+        assert_eq!(t.meter(false).unwrap(), "█ 2.0K/s 20 lines/s");
+
+        for i in 2..=15u64 {
+            t.sample(2048 + (i - 1) * 100, 20, true, start + SAMPLE_INTERVAL * i as u32);
+        }
+        assert_eq!(t.sparkline(false).chars().count(), HISTORY);
+        assert_eq!(t.rate(), (100, 0));
+    }
+
+    #[test]
+    fn bars_scale_to_the_busiest_second_and_degrade_to_ascii() {
+        let start = Instant::now();
+        let mut t = Throughput::default();
+        t.sample(0, 0, true, start);
+        for (i, total) in [0u64, 100, 400, 1000].into_iter().enumerate() {
+            t.sample(total, 0, true, start + SAMPLE_INTERVAL * (i as u32 + 1));
+        }
+        assert_eq!(t.sparkline(false), "▁▃▅█");
+        assert_eq!(t.sparkline(true), "_,-#");
+        assert_eq!(t.meter(true).unwrap(), "[output 600B/s, 0 lines/s, last 4s: _,-#]");
+    }
+
+    #[test]
+    fn exited_pane_reads_zero() {
+        let start = Instant::now();
+        let mut t = Throughput::default();
+        t.sample(0, 0, true, start);
+        t.sample(1000, 10, true, start + SAMPLE_INTERVAL);
+        t.sample(5000, 50, false, start + SAMPLE_INTERVAL * 2);
+        assert_eq!(t.rate(), (0, 0));
+        t.sample(5000, 50, false, start + SAMPLE_INTERVAL * 3);
+        assert_eq!(t.rate(), (0, 0));
+    }
+}
@@ -0,0 +1,221 @@
+use std::time::Duration;
+
+/// Template used when neither the pane nor the config sets `title_format`.
+pub const DEFAULT_TITLE_FORMAT: &str = "{name} {scroll} {weight}";
+
+/// Outer terminal window title used when the config sets none.
+pub const DEFAULT_WINDOW_TITLE_FORMAT: &str = "bamboo: {pane}";
+
+const PLACEHOLDERS: &[&str] = &[
+    "index", "id", "name", "command", "status", "duration", "cwd", "scroll", "weight",
+];
+
+const WINDOW_PLACEHOLDERS: &[&str] = &["session", "pane", "n_panes"];
+
+/// Pane state a title template can refer to.
+pub struct TitleContext<'a> {
+    /// 1-based position of the pane in the stack.
+    pub index: usize,
+    /// The pane's stable id, kept across restarts.
+    pub id: usize,
+    pub name: &'a str,
+    pub command: &'a str,
+    pub status: &'a str,
+    pub duration: Duration,
+    pub cwd: &'a str,
+    /// Lines scrolled back from the live view (0 = live).
+    pub scroll: usize,
+    pub weight: u16,
+    pub collapsed: bool,
+}
+
+impl TitleContext<'_> {
+    fn value(&self, key: &str) -> Option<String> {
+        Some(match key {
+            "index" => self.index.to_string(),
+            "id" => format!("#{}", self.id),
+            "name" => self.name.to_string(),
+            "command" => self.command.to_string(),
+            "status" => self.status.to_string(),
+            "duration" => format_duration(self.duration),
+            "cwd" => self.cwd.to_string(),
+            "scroll" if self.scroll > 0 => format!("[scroll: -{}]", self.scroll),
+            "scroll" => String::new(),
+            // Weight has no effect while collapsed, so don't advertise it.
+            "weight" if self.collapsed => String::new(),
+            "weight" => format!("(w:{})", self.weight),
+            _ => return None,
+        })
+    }
+}
+
+/// What the outer terminal's window title can refer to.
+pub struct WindowTitleContext<'a> {
+    /// The shoot name, or the directory bamboo was started in.
+    pub session: &'a str,
+    /// Name of the focused pane.
+    pub pane: &'a str,
+    pub n_panes: usize,
+}
+
+impl WindowTitleContext<'_> {
+    fn value(&self, key: &str) -> Option<String> {
+        Some(match key {
+            "session" => self.session.to_string(),
+            "pane" => self.pane.to_string(),
+            "n_panes" => self.n_panes.to_string(),
+            _ => return None,
+        })
+    }
+}
+
+/// Expand `{placeholder}`s in `template`; `{{` and `}}` stand for literal
+/// braces. Unknown placeholders are kept literally. Runs of spaces left by
+/// empty placeholders are collapsed.
+pub fn expand(template: &str, ctx: &TitleContext) -> String {
+    expand_with(template, |key| ctx.value(key))
+}
+
+/// `expand` for a `window_title_format`.
+pub fn expand_window(template: &str, ctx: &WindowTitleContext) -> String {
+    expand_with(template, |key| ctx.value(key))
+}
+
+fn expand_with(template: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(template.len() + 16);
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open].replace("}}", "}"));
+        let after = &rest[open + 1..];
+        if let Some(escaped) = after.strip_prefix('{') {
+            out.push('{');
+            rest = escaped;
+            continue;
+        }
+        match after.find('}') {
+            Some(close) => {
+                let key = &after[..close];
+                match value(key) {
+                    Some(v) => out.push_str(&v),
+                    None => {
+                        out.push('{');
+                        out.push_str(key);
+                        out.push('}');
+                    }
+                }
+                rest = &after[close + 1..];
+            }
+            None => {
+                out.push_str(&rest[open..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(&rest.replace("}}", "}"));
+
+    let mut collapsed = String::with_capacity(out.len());
+    for word in out.split(' ').filter(|w| !w.is_empty()) {
+        if !collapsed.is_empty() {
+            collapsed.push(' ');
+        }
+        collapsed.push_str(word);
+    }
+    collapsed
+}
+
+/// Placeholders in `template` that `expand` doesn't recognize.
+pub fn unknown_placeholders(template: &str) -> Vec<&str> {
+    unknown_in(template, PLACEHOLDERS)
+}
+
+/// Placeholders in `template` that `expand_window` doesn't recognize.
+pub fn unknown_window_placeholders(template: &str) -> Vec<&str> {
+    unknown_in(template, WINDOW_PLACEHOLDERS)
+}
+
+fn unknown_in<'a>(template: &'a str, known: &[&str]) -> Vec<&'a str> {
+    let mut unknown = Vec::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        let after = &rest[open + 1..];
+        if let Some(escaped) = after.strip_prefix('{') {
+            rest = escaped;
+            continue;
+        }
+        let Some(close) = after.find('}') else {
+            break;
+        };
+        let key = &after[..close];
+        if !known.contains(&key) {
+            unknown.push(key);
+        }
+        rest = &after[close + 1..];
+    }
+    unknown
+}
+
+/// Compact elapsed time: `42s`, `3m12s`, `2h05m`.
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{:02}m", secs / 3600, (secs % 3600) / 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> TitleContext<'static> {
+        TitleContext {
+            index: 2,
+            id: 7,
+            name: "api",
+            command: "cargo run",
+            status: "running",
+            duration: Duration::from_secs(192),
+            cwd: "~/src/api",
+            scroll: 0,
+            weight: 3,
+            collapsed: false,
+        }
+    }
+
+    #[test]
+    fn every_placeholder_expands() {
+        let ctx = TitleContext { scroll: 12, ..context() };
+        let all = PLACEHOLDERS.iter().map(|p| format!("{{{}}}", p)).collect::<Vec<_>>().join("|");
+        assert_eq!(expand(&all, &ctx), "2|#7|api|cargo run|running|3m12s|~/src/api|[scroll: -12]|(w:3)");
+        let window = WindowTitleContext { session: "shop", pane: "api", n_panes: 4 };
+        assert_eq!(expand_window("{session}/{pane} of {n_panes}", &window), "shop/api of 4");
+        assert!(unknown_placeholders(&all).is_empty());
+    }
+
+    #[test]
+    fn empty_values_leave_no_extra_spaces() {
+        assert_eq!(expand(DEFAULT_TITLE_FORMAT, &context()), "api (w:3)");
+        let collapsed = TitleContext { collapsed: true, ..context() };
+        assert_eq!(expand(" {scroll} {name}  {weight} ", &collapsed), "api");
+        let unnamed = TitleContext { name: "", ..context() };
+        assert_eq!(expand("{name}", &unnamed), "");
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(expand("{{name}} {{{name}}}", &context()), "{name} {api}");
+        assert_eq!(expand("a}}b{{", &context()), "a}b{");
+        assert!(unknown_placeholders("{{nope}} {name}").is_empty());
+    }
+
+    #[test]
+    fn unknown_placeholders_are_kept_and_reported() {
+        assert_eq!(expand("{name} {host} {", &context()), "api {host} {");
+        assert_eq!(expand("{}", &context()), "{}");
+        assert_eq!(unknown_placeholders("{name} {host} {} {pane} {"), ["host", "", "pane"]);
+        assert_eq!(unknown_window_placeholders("{pane} {name}"), ["name"]);
+    }
+}
@@ -1,29 +1,348 @@
-use ratatui::buffer::Buffer;
-use ratatui::layout::Rect;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use ratatui::buffer::{Buffer, Cell};
+use ratatui::layout::{Position, Rect};
 use ratatui::style::{Color, Modifier, Style};
+use ratatui::symbols::border;
 use ratatui::text::Line;
-use ratatui::widgets::{Block, Borders};
+use ratatui::widgets::{Block, Borders, Clear};
 use ratatui::Frame;
 
-use crate::app::{AppState, SelectionState};
-use crate::pane::Pane;
-use crate::terminal;
+use alacritty_terminal::vte::ansi::{Color as AnsiColor, NamedColor};
+use alacritty_terminal::Term;
+use regex::Regex;
+
+use crate::app::{jump_key, AppState, Peek, SelectionState, SortMode, WeightPreview};
+use crate::config::{Chrome, ColorMode, FocusFollows, ViewportIndicator};
+use crate::images::Placement;
+use crate::layout;
+use crate::pane::{Pane, RunState};
+use crate::stats;
+use crate::terminal::{self, CellInfo, PaneColors, PaneListener};
+use crate::title::{self, TitleContext};
 
-const COLLAPSED_HEIGHT: u16 = 3;
-const MIN_EXPANDED_HEIGHT: u16 = 5;
-const INDICATOR_HEIGHT: u16 = 1;
+/// Content rows shown by a collapsed pane.
+const COLLAPSED_CONTENT_ROWS: u16 = 1;
+/// Smallest number of content rows an expanded pane is given.
+const MIN_EXPANDED_CONTENT_ROWS: u16 = 3;
 pub const FOOTER_HEIGHT: u16 = 1;
+/// Narrowest window the pane stack is drawn in.
+const MIN_WINDOW_WIDTH: u16 = 20;
+/// Least time between rescans of a pane for error lines while it prints.
+const ERROR_SCAN_INTERVAL: Duration = Duration::from_millis(250);
+/// PTYs are never sized below this; smaller panes clip the grid instead.
+pub const MIN_PTY_COLS: u16 = 20;
+pub const MIN_PTY_ROWS: u16 = 5;
+
+impl Chrome {
+    /// Rows used by borders and title above and below the content.
+    fn vertical_overhead(self) -> u16 {
+        match self {
+            Chrome::Full => 2,
+            Chrome::Compact => 1,
+            Chrome::None => 0,
+        }
+    }
+
+    pub fn collapsed_height(self) -> u16 {
+        self.vertical_overhead() + COLLAPSED_CONTENT_ROWS
+    }
+
+    pub fn min_expanded_height(self) -> u16 {
+        self.vertical_overhead() + MIN_EXPANDED_CONTENT_ROWS
+    }
+
+    /// Height of an expanded pane showing at least `content_rows` rows.
+    pub fn expanded_height(self, content_rows: u16) -> u16 {
+        self.vertical_overhead().saturating_add(content_rows.max(MIN_EXPANDED_CONTENT_ROWS))
+    }
+
+    /// Shortest window that fits one expanded pane and the footer.
+    fn min_window_height(self) -> u16 {
+        self.min_expanded_height() + FOOTER_HEIGHT
+    }
+
+    /// Whether panes have a title row carrying the toggle/name/close buttons.
+    pub fn has_title(self) -> bool {
+        self != Chrome::None
+    }
+
+    /// The content area of a pane occupying `area`.
+    pub fn inner(self, area: Rect) -> Rect {
+        match self {
+            Chrome::Full => Rect::new(
+                area.x + 1,
+                area.y + 1,
+                area.width.saturating_sub(2),
+                area.height.saturating_sub(2),
+            ),
+            Chrome::Compact => Rect::new(area.x, area.y + 1, area.width, area.height.saturating_sub(1)),
+            Chrome::None => Rect::new(area.x + 1, area.y, area.width.saturating_sub(1), area.height),
+        }
+        .intersection(area)
+    }
+}
+
+/// Where a pane was drawn: its whole area, title row and content. Worked
+/// out once per frame and used by both rendering and mouse handling, so a
+/// click lands on the cell that was drawn there.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PaneAreas {
+    pub outer: Rect,
+    /// `None` with `chrome = "none"`.
+    pub title_row: Option<Rect>,
+    pub inner: Rect,
+}
+
+impl PaneAreas {
+    pub fn new(outer: Rect, chrome: Chrome) -> Self {
+        Self {
+            outer,
+            title_row: chrome.has_title().then(|| Rect::new(outer.x, outer.y, outer.width, outer.height.min(1))),
+            inner: chrome.inner(outer),
+        }
+    }
+
+    pub fn contains(&self, col: u16, row: u16) -> bool {
+        self.outer.contains(Position::new(col, row))
+    }
+
+    pub fn hit_title(&self, col: u16, row: u16) -> bool {
+        self.title_row.is_some_and(|title| title.contains(Position::new(col, row)))
+    }
+
+    /// The content cell at `col`, `row`, relative to the content's top-left
+    /// corner, or `None` on the border, title or outside.
+    pub fn hit_inner(&self, col: u16, row: u16) -> Option<(u16, u16)> {
+        self.inner
+            .contains(Position::new(col, row))
+            .then(|| (col - self.inner.x, row - self.inner.y))
+    }
+
+    /// Like `hit_inner`, but a point outside is pulled to the nearest
+    /// content cell, as for a drag leaving the pane. `None` when the pane
+    /// shows no content.
+    pub fn clamp_inner(&self, col: u16, row: u16) -> Option<(u16, u16)> {
+        if self.inner.is_empty() {
+            return None;
+        }
+        let col = col.clamp(self.inner.x, self.inner.right() - 1);
+        let row = row.clamp(self.inner.y, self.inner.bottom() - 1);
+        Some((col - self.inner.x, row - self.inner.y))
+    }
+}
+
+/// Title columns an optional button may not eat into.
+const MIN_TITLE_WIDTH: u16 = 8;
+
+/// Column ranges of the controls in a pane's title row.
+///
+/// Controls that don't fit are left out; rendering and mouse hit-testing both
+/// go through this so a click on a cramped title can't land on the wrong one.
+pub struct TitleBar {
+    /// Pane title text.
+    pub title: Option<Range<u16>>,
+    /// Buttons and the columns they cover, left to right from the toggle and
+    /// then right to left from the close button.
+    pub buttons: Vec<(TitleButton, Range<u16>)>,
+}
+
+/// A clickable control in a pane's title row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TitleButton {
+    /// `[▾]`/`[▸]` collapse toggle.
+    Toggle,
+    /// `[x]` close button.
+    Close,
+    /// `[◱]` expand this pane and collapse the rest.
+    Solo,
+    /// `[↻]` run the pane's command again; only shown once it has exited.
+    Restart,
+}
+
+impl TitleBar {
+    /// Lay out the title row of a pane drawn in `area`. `exited` adds the
+    /// restart button.
+    ///
+    /// Right-hand buttons are placed in priority order (close, solo, restart)
+    /// and each one after close only while the title keeps `MIN_TITLE_WIDTH`
+    /// columns, so a narrowing pane drops them from the least important.
+    pub fn new(area: Rect, exited: bool) -> Self {
+        let (x, w) = (area.x, area.width);
+        let mut buttons = Vec::new();
+        if w >= 5 {
+            buttons.push((TitleButton::Toggle, x + 1..x + 4));
+        }
+
+        // One past the last column available to the title.
+        let mut right = x + w.saturating_sub(1);
+        if w >= 8 {
+            buttons.push((TitleButton::Close, right - 3..right));
+            right -= 4;
+            let extra = [Some(TitleButton::Solo), exited.then_some(TitleButton::Restart)];
+            for button in extra.into_iter().flatten() {
+                if right < x + 9 + MIN_TITLE_WIDTH {
+                    break;
+                }
+                buttons.push((button, right - 3..right));
+                right -= 4;
+            }
+        }
+
+        Self {
+            title: (w > 10).then(|| x + 5..right),
+            buttons,
+        }
+    }
+
+    /// A title row without buttons, for when the mouse is left to the outer
+    /// terminal and they couldn't be clicked.
+    pub fn plain(area: Rect) -> Self {
+        Self {
+            title: (area.width > 2).then(|| area.x + 1..area.right() - 1),
+            buttons: Vec::new(),
+        }
+    }
+
+    /// The control covering column `col`, if any.
+    pub fn button_at(&self, col: u16) -> Option<TitleButton> {
+        self.buttons
+            .iter()
+            .find(|(_, r)| r.contains(&col))
+            .map(|&(button, _)| button)
+    }
+}
+
+/// What a pane's title shows that changes only through the user or the
+/// pane's lifecycle; a change here refreshes the title at once.
+#[derive(PartialEq)]
+struct TitleKey {
+    index: usize,
+    collapsed: bool,
+    state: RunState,
+    weight: u16,
+    scroll: usize,
+    watch_paused: bool,
+    locked: bool,
+    flash: Option<String>,
+    clipped: (bool, bool),
+    stale: bool,
+    name: String,
+}
+
+impl TitleKey {
+    fn of(pane: &Pane, index: usize) -> Self {
+        Self {
+            index,
+            collapsed: pane.collapsed,
+            state: pane.run_state(),
+            weight: pane.weight,
+            scroll: pane.scroll_offset,
+            watch_paused: pane.watch_paused,
+            locked: pane.scroll_lock.is_some(),
+            flash: pane.flash_message().map(str::to_owned),
+            clipped: pane.clipped_sides(),
+            stale: pane.stale,
+            name: pane.display_name(),
+        }
+    }
+}
+
+/// The title text last expanded for a pane. Parts that follow output (run
+/// time, cwd, lines behind a scroll lock) are refreshed from it at most once
+/// per `title_interval`, however fast the pane prints.
+pub struct TitleCache {
+    key: TitleKey,
+    text: String,
+    at: Instant,
+}
+
+/// Snapshot of the cells last drawn into a pane's inner area.
+///
+/// ratatui hands us a blank buffer every frame, so an unchanged pane still has
+/// to be written — but copying cached cells avoids taking the parser lock and
+/// re-converting every cell.
+pub struct RenderCache {
+    generation: u64,
+    area: Rect,
+    collapsed: bool,
+    selection: Option<SelectionState>,
+    cells: Vec<Cell>,
+}
+
+impl RenderCache {
+    fn capture(
+        buf: &Buffer,
+        area: Rect,
+        generation: u64,
+        collapsed: bool,
+        selection: Option<SelectionState>,
+    ) -> Self {
+        let mut cells = Vec::with_capacity(area.width as usize * area.height as usize);
+        for y in area.y..area.y + area.height {
+            for x in area.x..area.x + area.width {
+                cells.push(buf[(x, y)].clone());
+            }
+        }
+        Self {
+            generation,
+            area,
+            collapsed,
+            selection,
+            cells,
+        }
+    }
+
+    fn is_valid(
+        &self,
+        generation: u64,
+        area: Rect,
+        collapsed: bool,
+        selection: Option<&SelectionState>,
+    ) -> bool {
+        self.generation == generation
+            && self.area == area
+            && self.collapsed == collapsed
+            && self.selection.as_ref() == selection
+    }
+
+    /// Whether the cells can stand in for a frozen pane drawn in `area`,
+    /// which may have moved but not changed size.
+    fn fits(&self, area: Rect, collapsed: bool) -> bool {
+        self.area.width == area.width && self.area.height == area.height && self.collapsed == collapsed
+    }
+
+    /// Copy the cells into `area`, which has the captured size.
+    fn blit(&self, buf: &mut Buffer, area: Rect) {
+        let width = self.area.width as usize;
+        if width == 0 {
+            return;
+        }
+        for (i, cell) in self.cells.iter().enumerate() {
+            let x = area.x + (i % width) as u16;
+            let y = area.y + (i / width) as u16;
+            if let Some(target) = buf.cell_mut((x, y)) {
+                *target = cell.clone();
+            }
+        }
+    }
+}
 
 pub fn render(frame: &mut Frame, app: &mut AppState) {
+    app.image_placements.clear();
+    app.gutter_area = None;
+    app.peek_area = None;
     let full_area = frame.area();
     if full_area.height == 0 || full_area.width == 0 {
         return;
     }
 
+    let gutter = app.viewport_indicator == ViewportIndicator::Gutter;
     let pane_area = Rect::new(
         full_area.x,
         full_area.y,
-        full_area.width,
+        full_area.width.saturating_sub(u16::from(gutter)),
         full_area.height.saturating_sub(FOOTER_HEIGHT),
     );
     let footer_area = Rect::new(
@@ -33,44 +352,808 @@ pub fn render(frame: &mut Frame, app: &mut AppState) {
         FOOTER_HEIGHT,
     );
 
-    ensure_focused_visible(app, pane_area.height);
+    if full_area.width < MIN_WINDOW_WIDTH || full_area.height < app.chrome.min_window_height() {
+        app.set_layout(Vec::new());
+        app.hover = None;
+        app.peek = None;
+        app.stack_area = None;
+        render_too_small(frame.buffer_mut(), full_area, app.chrome);
+        return;
+    }
+
+    let lines = app.viewport_indicator == ViewportIndicator::Lines;
+    let indicator = if lines { layout::INDICATOR_HEIGHT } else { 0 };
+    app.stack_area = Some((pane_area, indicator));
+    app.viewport_start = if app.follow_focus {
+        layout::viewport_start_for(
+            &app.panes,
+            app.chrome,
+            indicator,
+            app.viewport_policy,
+            app.focused,
+            app.viewport_start,
+            pane_area.height,
+        )
+    } else {
+        layout::pack_viewport_start(&app.panes, app.chrome, indicator, app.viewport_start, pane_area.height)
+    };
 
     let above_count = app.viewport_start;
-    let (layout, visible_end) = compute_visible_layout(app, pane_area);
+    let (layout, visible_end) =
+        layout::compute_visible_layout(&app.panes, app.chrome, indicator, app.viewport_start, pane_area);
     let below_count = app.panes.len().saturating_sub(visible_end);
 
-    app.last_pane_areas = layout.clone();
+    app.set_layout(layout.clone());
+    let areas = app.last_pane_areas.clone();
+
+    // Size every pane before drawing any, so programs get SIGWINCH for the
+    // size the frame shows and no pane is drawn from a grid being resized.
+    let squeezed = app.squeezed();
+    for &(pane_idx, pa) in &areas {
+        fit_pane(&mut app.panes[pane_idx], pa.inner, squeezed);
+    }
 
     let focused = app.focused;
     let selection = app.selection.clone();
-    for &(pane_idx, pa) in &layout {
-        let is_focused = pane_idx == focused;
+    let quiet = app.quiet_redraws;
+    let glyphs = Glyphs::of(app.accessible);
+    for &(pane_idx, pa) in &areas {
         let pane = &mut app.panes[pane_idx];
-        let pane_sel = selection.as_ref().filter(|s| s.pane_id == pane.id);
-        render_pane(frame, pane, pa, is_focused, pane_sel);
+        let view = PaneView {
+            index: pane_idx,
+            is_focused: pane_idx == focused,
+            marked: app.marked.contains(&pane.id),
+            selection: selection.as_ref().filter(|s| s.pane_id == pane.id),
+            color_mode: app.color_mode,
+            bold_is_bright: app.bold_is_bright,
+            chrome: app.chrome,
+            title_format: &app.title_format,
+            hovered: app.hover.filter(|h| h.pane_id == pane.id && !quiet).map(|h| h.button),
+            exit_bar: app.show_exit_bar,
+            skip_prompt: app.collapsed_skip_prompt,
+            title_interval: if quiet { Duration::MAX } else { app.title_interval },
+            crosshair: (app.crosshair && pane_idx == focused && !quiet).then_some(app.pointer),
+            glyphs,
+            error_pattern: app.error_pattern.as_ref(),
+            mouse: app.mouse,
+            throughput: app.show_throughput,
+        };
+        render_pane(frame, pane, pa, &view);
+        pane.activity = false;
+        if view.is_focused {
+            pane.unseen_output = 0;
+        }
     }
 
     let buf = frame.buffer_mut();
-    if above_count > 0 {
-        let msg = format!(" ▲ {} more above ", above_count);
-        let style = Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD);
-        buf.set_string(pane_area.x, pane_area.y, &msg, style);
+    if let Some(preview) = app.active_weight_preview().filter(|_| !quiet) {
+        render_weight_preview(buf, app, preview, &layout, indicator, pane_area);
+    }
+    if app.jump_mode {
+        for &(pane_idx, pa) in &layout {
+            render_jump_badge(buf, pa, pane_idx);
+        }
+    }
+    if app.resize_mode {
+        render_resize_edge(buf, app, &layout);
+    }
+
+    if gutter {
+        let area = Rect::new(pane_area.right(), pane_area.y, 1, pane_area.height);
+        render_gutter(buf, area, app, app.viewport_start..visible_end);
+        app.gutter_area = Some(area);
+    }
+
+    if lines && above_count > 0 {
+        let hidden = &app.panes[..app.viewport_start];
+        let msg = glyphs.hidden(above_count, false);
+        if app.jump_mode {
+            render_jump_indicator(buf, pane_area, pane_area.y, &msg, hidden, 0);
+        } else {
+            let nearest = &hidden[hidden.len() - 1];
+            render_indicator(buf, pane_area, pane_area.y, &msg, hidden, nearest, glyphs);
+        }
+    }
+
+    if lines && below_count > 0 {
+        let hidden = &app.panes[visible_end..];
+        let msg = glyphs.hidden(below_count, true);
+        let y = pane_area.y + pane_area.height - 1;
+        if app.jump_mode {
+            render_jump_indicator(buf, pane_area, y, &msg, hidden, visible_end);
+        } else {
+            render_indicator(buf, pane_area, y, &msg, hidden, &hidden[0], glyphs);
+        }
+    }
+
+    render_footer(buf, footer_area, app, glyphs);
+
+    if !app.jump_mode && !app.stats.visible && app.startup_warnings.is_empty() && !app.welcome {
+        let mut shown = pane_area;
+        if lines && above_count > 0 {
+            shown.y += 1;
+            shown.height = shown.height.saturating_sub(1);
+        }
+        if lines && below_count > 0 {
+            shown.height = shown.height.saturating_sub(1);
+        }
+        app.image_placements = image_placements(app, &areas, shown);
+    }
+
+    if app.stats.visible {
+        render_debug_overlay(frame, pane_area, app);
+    }
+
+    if !app.startup_warnings.is_empty() {
+        render_startup_warnings(frame, pane_area, &app.startup_warnings);
+    } else if app.welcome {
+        render_welcome(frame, pane_area);
+    }
+
+    if app.output_pause.is_paused() {
+        render_pause_banner(frame.buffer_mut(), pane_area, app);
+    }
+
+    let peek = app.peek.filter(|_| lines && !app.jump_mode).map(|peek| {
+        let hidden = if peek.below { visible_end..app.panes.len() } else { 0..app.viewport_start };
+        (peek, hidden)
+    });
+    match peek {
+        Some((peek, hidden)) if !hidden.is_empty() => render_peek(frame, pane_area, app, glyphs, peek, hidden),
+        _ => app.peek = None,
+    }
+}
+
+/// What the UI draws its symbols with: Unicode, or for `accessible` mode
+/// ASCII and words. Picked once per frame by `Glyphs::of`; a feature that
+/// shows something new adds both forms here rather than checking the mode.
+struct Glyphs {
+    /// Each pane's state is spelled out in a title line that replaces its
+    /// border, instead of shown by color, badges, prefixes and border ticks.
+    words: bool,
+    /// Collapse toggle while expanded and while collapsed.
+    toggle: [&'static str; 2],
+    close: &'static str,
+    solo: &'static str,
+    restart: &'static str,
+    /// Borders of overlays such as the peek list.
+    border: border::Set<'static>,
+    /// A hidden pane's state in the peek list: failed, exited, new output,
+    /// running.
+    peek: [&'static str; 4],
+    /// Between a title or message and a note added to it.
+    dash: &'static str,
+    /// Between the two ends of a selection.
+    to: &'static str,
+    /// Title prefixes for a marked pane and a pane in a group.
+    marked: &'static str,
+    group: &'static str,
+    /// Content cut off on both sides, on the left, on the right.
+    clipped: [&'static str; 3],
+}
+
+impl Glyphs {
+    const SYMBOLS: Glyphs = Glyphs {
+        words: false,
+        toggle: ["[▾]", "[▸]"],
+        close: "[x]",
+        solo: "[◱]",
+        restart: "[↻]",
+        border: border::PLAIN,
+        peek: ["✗", "✓", "●", "○"],
+        dash: "—",
+        to: "→",
+        marked: "✓ ",
+        group: "• ",
+        clipped: [" ⇠⇢", " ⇠", " ⇢"],
+    };
+
+    const WORDS: Glyphs = Glyphs {
+        words: true,
+        toggle: ["[-]", "[+]"],
+        close: "[x]",
+        solo: "[=]",
+        restart: "[r]",
+        border: border::Set {
+            top_left: "+",
+            top_right: "+",
+            bottom_left: "+",
+            bottom_right: "+",
+            vertical_left: "|",
+            vertical_right: "|",
+            horizontal_top: "-",
+            horizontal_bottom: "-",
+        },
+        peek: ["failed", "exited", "new output", "running"],
+        dash: "-",
+        to: "to",
+        // Said in the title's list of states instead.
+        marked: "",
+        group: "",
+        clipped: [" [cut off on both sides]", " [cut off on the left]", " [cut off on the right]"],
+    };
+
+    fn of(accessible: bool) -> &'static Glyphs {
+        if accessible {
+            &Glyphs::WORDS
+        } else {
+            &Glyphs::SYMBOLS
+        }
+    }
+
+    fn button(&self, button: TitleButton, collapsed: bool) -> &'static str {
+        match button {
+            TitleButton::Toggle => self.toggle[collapsed as usize],
+            TitleButton::Close => self.close,
+            TitleButton::Solo => self.solo,
+            TitleButton::Restart => self.restart,
+        }
+    }
+
+    /// "▲ 3 more above", or a sentence.
+    fn hidden(&self, count: usize, below: bool) -> String {
+        let place = if below { "below" } else { "above" };
+        if self.words {
+            hidden_sentence(count, place)
+        } else {
+            format!("{} {} more {}", if below { "▼" } else { "▲" }, count, place)
+        }
+    }
+
+    fn scroll_locked(&self, pending_lines: usize) -> String {
+        if self.words {
+            format!(" [scroll locked, {} new lines]", pending_lines)
+        } else {
+            format!(" ⏸ +{} lines", pending_lines)
+        }
+    }
+
+    /// `(left, right)` from `Pane::clipped_sides`.
+    fn clipped(&self, sides: (bool, bool)) -> &'static str {
+        match sides {
+            (true, true) => self.clipped[0],
+            (true, false) => self.clipped[1],
+            (false, true) => self.clipped[2],
+            (false, false) => "",
+        }
+    }
+
+    /// Symbol or word and color of a pane's state in the peek list.
+    fn peek_state(&self, pane: &Pane) -> (&'static str, Color) {
+        if pane.failed() {
+            (self.peek[0], Color::LightRed)
+        } else if pane.closed {
+            (self.peek[1], Color::Gray)
+        } else if pane.activity {
+            (self.peek[2], Color::Yellow)
+        } else {
+            (self.peek[3], Color::Green)
+        }
+    }
+}
+
+/// The panes hidden above or below the viewport, listed over the stack next
+/// to their indicator: one row each with its state, name and last line.
+/// When they don't all fit, the list scrolls from `peek.scroll`.
+fn render_peek(frame: &mut Frame, area: Rect, app: &mut AppState, glyphs: &Glyphs, peek: Peek, hidden: Range<usize>) {
+    // Between the two indicator rows, inset from the sides.
+    let height = (hidden.len() as u16 + 2).min(area.height.saturating_sub(2));
+    let width = area.width.saturating_sub(4);
+    if height < 3 || width < 20 {
+        app.peek = None;
+        return;
+    }
+    let y = if peek.below { area.bottom() - 1 - height } else { area.y + 1 };
+    let overlay = Rect::new(area.x + 2, y, width, height);
+    let rows = (height - 2) as usize;
+    let scroll = peek.scroll.min(hidden.len() - rows.min(hidden.len()));
+    app.peek = Some(Peek { scroll, ..peek });
+    let shown: Vec<usize> = hidden.clone().skip(scroll).take(rows).collect();
+
+    let place = if peek.below { "below" } else { "above" };
+    let title = if glyphs.words {
+        format!(" {} hidden {}: click one to focus it, Esc closes ", hidden.len(), place)
+    } else {
+        format!(" {} · click to focus · Esc closes ", glyphs.hidden(hidden.len(), peek.below))
+    };
+    let mut block = Block::default()
+        .borders(Borders::ALL)
+        .border_set(glyphs.border)
+        .border_style(Style::default().fg(Color::Cyan))
+        .title(title);
+    if shown.len() < hidden.len() {
+        let range = format!(" {}-{} of {}, wheel scrolls ", scroll + 1, scroll + shown.len(), hidden.len());
+        block = block.title_bottom(range);
+    }
+    let inner = block.inner(overlay);
+    frame.render_widget(Clear, overlay);
+    frame.render_widget(block, overlay);
+
+    let state_width = glyphs.peek.iter().map(|state| Line::from(*state).width()).max().unwrap_or(0) as u16;
+    let name_width = shown
+        .iter()
+        .map(|&idx| Line::from(app.panes[idx].name.as_str()).width() as u16)
+        .max()
+        .unwrap_or(0)
+        .min(inner.width / 3);
+    let text_x = inner.x + state_width + 1 + name_width + 1;
+    let buf = frame.buffer_mut();
+    for (y, &idx) in (inner.y..).zip(&shown) {
+        let pane = &app.panes[idx];
+        let (state, color) = glyphs.peek_state(pane);
+        buf.set_stringn(inner.x, y, state, state_width as usize, Style::default().fg(color));
+        let name_style = Style::default().add_modifier(Modifier::BOLD);
+        buf.set_stringn(inner.x + state_width + 1, y, &pane.name, name_width as usize, name_style);
+        if text_x < inner.right() {
+            let palette = Palette::for_pane(pane, app.color_mode, app.bold_is_bright);
+            let prompt_row = if app.collapsed_skip_prompt { pane.prompt_row } else { None };
+            let line = Rect::new(text_x, y, inner.right() - text_x, 1);
+            render_last_terminal_line(buf, &pane.term, line, palette, prompt_row);
+        }
+    }
+    app.peek_area = Some((overlay, shown));
+}
+
+/// Banner across the top of the stack while pane output is frozen.
+fn render_pause_banner(buf: &mut Buffer, area: Rect, app: &AppState) {
+    let pause = &app.output_pause;
+    let dropped = match pause.dropped() {
+        0 => String::new(),
+        n => format!(" · {} dropped", stats::format_bytes(n as f64)),
+    };
+    let msg = format!(" PAUSED{} · {} resumes ", dropped, pause.key);
+    let width = (Line::from(msg.as_str()).width() as u16).min(area.width);
+    let x = area.x + (area.width - width) / 2;
+    let style = Style::default().fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD);
+    buf.set_stringn(x, area.y, &msg, width as usize, style);
+}
+
+/// Keys shown on the welcome screen.
+const WELCOME_KEYS: &[(&str, &str)] = &[
+    ("Alt+j / Alt+k", "focus next / previous pane"),
+    ("Alt+n", "open a shell pane"),
+    ("Alt+r", "run a command in a new pane"),
+    ("Alt+w", "close the focused pane"),
+    ("Alt+c", "collapse / expand"),
+    ("Alt+g", "jump to a pane by its label"),
+    ("Alt+v", "paste"),
+    ("Ctrl+Q", "quit"),
+];
+
+/// First-run cheat sheet with the offer to write an example config.
+fn render_welcome(frame: &mut Frame, area: Rect) {
+    let width = area.width.saturating_sub(4).min(60);
+    let height = (WELCOME_KEYS.len() as u16 + 6).min(area.height);
+    if width < 30 || height < 6 {
+        return;
+    }
+    let overlay = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+
+    frame.render_widget(Clear, overlay);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .title(" welcome to bamboo ")
+        .title_bottom(" y / n ");
+    let inner = block.inner(overlay);
+    frame.render_widget(block, overlay);
+
+    let buf = frame.buffer_mut();
+    let text_width = inner.width.saturating_sub(2) as usize;
+    let key_style = Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD);
+    let rows = inner.height.saturating_sub(4) as usize;
+    for (y, (keys, action)) in (inner.y + 1..).zip(WELCOME_KEYS.iter().take(rows)) {
+        let (x, _) = buf.set_stringn(inner.x + 1, y, format!("{:<14}", keys), text_width, key_style);
+        buf.set_stringn(x, y, action, text_width.saturating_sub((x - inner.x - 1) as usize), Style::default());
+    }
+    buf.set_stringn(
+        inner.x + 1,
+        inner.bottom().saturating_sub(2),
+        "Write an example .bamboo.toml here? [y/n]",
+        text_width,
+        Style::default().add_modifier(Modifier::BOLD),
+    );
+}
+
+/// Where the focused pane's inline images go: only while it shows its live
+/// screen unchanged, and only images wholly inside `shown`.
+fn image_placements(app: &AppState, layout: &[(usize, PaneAreas)], shown: Rect) -> Vec<Placement> {
+    let Some(&(idx, areas)) = layout.iter().find(|&&(idx, _)| idx == app.focused) else {
+        return Vec::new();
+    };
+    let pane = &app.panes[idx];
+    let selecting = app.selection.as_ref().is_some_and(|s| s.pane_id == pane.id);
+    if pane.images.is_empty()
+        || pane.collapsed
+        || pane.scroll_offset > 0
+        || pane.scroll_lock.is_some()
+        || pane.h_scroll > 0
+        || selecting
+    {
+        return Vec::new();
+    }
+
+    let mut inner = areas.inner;
+    if app.show_exit_bar && pane.closed && inner.height > 1 {
+        inner.height -= 1;
+    }
+    let top = terminal::visible_row_offset(&pane.term, inner.height);
+    let visible = inner.intersection(shown);
+    if visible.is_empty() {
+        return Vec::new();
+    }
+    let skipped = (visible.y - inner.y) as usize;
+    pane.images
+        .visible(&pane.term, top + skipped, visible.width, visible.height)
+        .into_iter()
+        .map(|(image_id, x, y)| Placement {
+            pane_id: pane.id,
+            image_id,
+            x: visible.x + x,
+            y: visible.y + y,
+        })
+        .collect()
+}
+
+/// Centered box listing what went wrong while starting, wrapped to fit.
+fn render_startup_warnings(frame: &mut Frame, area: Rect, warnings: &[String]) {
+    let width = area.width.saturating_sub(4).min(80);
+    if width < 12 || area.height < 5 {
+        return;
+    }
+    let text_width = (width - 4) as usize;
+    let mut lines = Vec::new();
+    for warning in warnings {
+        let mut prefix = "• ";
+        for chunk in wrap_words(warning, text_width - 2) {
+            lines.push(format!("{}{}", prefix, chunk));
+            prefix = "  ";
+        }
+    }
+    let height = (lines.len() as u16 + 4).min(area.height);
+    let overlay = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+
+    frame.render_widget(Clear, overlay);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow))
+        .title(" startup warnings ")
+        .title_bottom(" Enter to continue ");
+    let inner = block.inner(overlay);
+    frame.render_widget(block, overlay);
+
+    let buf = frame.buffer_mut();
+    let style = Style::default().fg(Color::Yellow);
+    let rows = inner.height.saturating_sub(2) as usize;
+    for (i, line) in lines.iter().take(rows).enumerate() {
+        buf.set_stringn(inner.x + 1, inner.y + 1 + i as u16, line, text_width, style);
+    }
+}
+
+/// `text` broken at spaces into lines of at most `width` chars; words
+/// longer than that are split.
+fn wrap_words(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split(' ') {
+        let mut word: Vec<char> = word.chars().collect();
+        while word.len() > width {
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            lines.push(word.drain(..width).collect());
+        }
+        let len = line.chars().count();
+        if len > 0 && len + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.extend(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// "3 more panes are above the view." for `accessible` mode.
+fn hidden_sentence(count: usize, place: &str) -> String {
+    if count == 1 {
+        format!("1 more pane is {} the view.", place)
+    } else {
+        format!("{} more panes are {} the view.", count, place)
+    }
+}
+
+/// A "more above/below" line naming the nearest hidden pane. It turns red
+/// when a hidden pane's process failed, or yellow when one has output that
+/// hasn't been seen; in words mode it says so instead.
+fn render_indicator(
+    buf: &mut Buffer,
+    area: Rect,
+    y: u16,
+    msg: &str,
+    hidden: &[Pane],
+    nearest: &Pane,
+    glyphs: &Glyphs,
+) {
+    let failed = hidden.iter().any(Pane::failed);
+    let active = hidden.iter().any(|p| p.activity);
+    let text = if glyphs.words {
+        let alert = if failed {
+            " One of them has failed."
+        } else if active {
+            " One of them has new output."
+        } else {
+            ""
+        };
+        format!(" {} The nearest is {}.{} ", msg, nearest.name, alert)
+    } else {
+        format!(" {} {} next: {} ", msg, glyphs.dash, nearest.name)
+    };
+    let color = if failed {
+        Color::LightRed
+    } else if active {
+        Color::Yellow
+    } else {
+        Color::Cyan
+    };
+    let style = Style::default().fg(color).add_modifier(Modifier::BOLD);
+    buf.set_stringn(area.x, y, &text, area.width as usize, style);
+}
+
+/// First row of `area` given to each of `count` panes in the stack map.
+/// With more panes than rows, several share a row.
+fn gutter_row(area: Rect, count: usize, idx: usize) -> u16 {
+    area.y + (idx * area.height as usize / count.max(1)) as u16
+}
+
+/// The pane a click on `row` of the stack map jumps to: the first one drawn
+/// on that row, or the one whose block covers it.
+pub fn gutter_pane_at(area: Rect, count: usize, row: u16) -> Option<usize> {
+    if count == 0 || !(area.y..area.bottom()).contains(&row) {
+        return None;
+    }
+    let covering = (0..count).rev().find(|&i| gutter_row(area, count, i) <= row)?;
+    let first_row = gutter_row(area, count, covering);
+    (0..=covering).find(|&i| gutter_row(area, count, i) == first_row)
+}
+
+/// A one-column map of the whole stack: a block per pane colored by its
+/// state, full for the panes in view and thin for the rest. Where panes
+/// share a row, the one with the most pressing state shows.
+fn render_gutter(buf: &mut Buffer, area: Rect, app: &AppState, visible: Range<usize>) {
+    let count = app.panes.len();
+    let mut rows: Vec<Option<(u8, Color, bool)>> = vec![None; area.height as usize];
+    for (idx, pane) in app.panes.iter().enumerate() {
+        let (rank, color) = if pane.failed() {
+            (4, Color::LightRed)
+        } else if idx == app.focused {
+            (3, Color::Green)
+        } else if pane.activity {
+            (2, Color::Yellow)
+        } else if pane.collapsed {
+            (0, Color::DarkGray)
+        } else {
+            (1, Color::Gray)
+        };
+        let start = gutter_row(area, count, idx);
+        let end = gutter_row(area, count, idx + 1).max(start + 1).min(area.bottom());
+        for y in start..end {
+            let row = &mut rows[(y - area.y) as usize];
+            if row.is_none_or(|(shown, _, _)| rank > shown) {
+                *row = Some((rank, color, visible.contains(&idx)));
+            }
+        }
+    }
+    for (y, row) in (area.y..).zip(rows) {
+        let (symbol, style) = match row {
+            Some((_, color, true)) => ("█", Style::default().fg(color)),
+            Some((_, color, false)) => ("▐", Style::default().fg(color)),
+            None => (" ", Style::default()),
+        };
+        buf.set_string(area.x, y, symbol, style);
+    }
+}
+
+fn jump_badge_style() -> Style {
+    Style::default().fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD)
+}
+
+/// The jump-mode key for a visible pane: a 3-row block centered in the pane,
+/// or a single cell on the title row when the pane is too short for it.
+fn render_jump_badge(buf: &mut Buffer, area: Rect, pane_idx: usize) {
+    let Some(key) = jump_key(pane_idx) else {
+        return;
+    };
+    let style = jump_badge_style();
+    if area.height < 5 || area.width < 9 {
+        buf.set_stringn(area.x + 1, area.y, format!(" {} ", key), area.width.saturating_sub(1) as usize, style);
+        return;
+    }
+    let x = area.x + (area.width - 5) / 2;
+    let y = area.y + (area.height - 3) / 2;
+    for (row, text) in ["     ".to_string(), format!("  {}  ", key), "     ".to_string()].iter().enumerate() {
+        buf.set_string(x, y + row as u16, text, style);
+    }
+}
+
+/// In resize mode, the edge a row step moves: the focused slot's bottom
+/// border, or the top row of the pane below it when panes have no box.
+fn render_resize_edge(buf: &mut Buffer, app: &AppState, layout: &[(usize, Rect)]) {
+    let slot = layout::slot_start(&app.panes, app.focused);
+    let style = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+    let in_slot = |idx: usize| layout::slot_start(&app.panes, idx) == slot;
+    let rows: Vec<Rect> = if app.chrome == Chrome::Full {
+        layout
+            .iter()
+            .filter(|&&(idx, _)| in_slot(idx))
+            .map(|&(_, pa)| Rect::new(pa.x, pa.bottom().saturating_sub(1), pa.width, 1))
+            .collect()
+    } else {
+        let Some(next) = layout.iter().map(|&(idx, _)| idx).find(|&idx| idx > slot && !in_slot(idx)) else {
+            return;
+        };
+        layout
+            .iter()
+            .filter(|&&(idx, _)| layout::slot_start(&app.panes, idx) == next)
+            .map(|&(_, pa)| Rect::new(pa.x, pa.y, pa.width, pa.height.min(1)))
+            .collect()
+    };
+    for row in rows {
+        buf.set_style(row, style);
+    }
+}
+
+/// Label the pane whose weight is changing with its share of the expanded
+/// rows before and after, e.g. "34% → 38%", and draw a faint line where its
+/// bottom edge would land after one more step the same way.
+fn render_weight_preview(
+    buf: &mut Buffer,
+    app: &AppState,
+    preview: WeightPreview,
+    layout: &[(usize, Rect)],
+    indicator: u16,
+    area: Rect,
+) {
+    let Some(idx) = app.panes.iter().position(|p| p.id == preview.pane_id) else {
+        return;
+    };
+    let Some(&(_, current)) = layout.iter().find(|&&(i, _)| i == idx) else {
+        return;
+    };
+    let dry_run = |weight: u16| {
+        let mut weights: Vec<u16> = app.panes.iter().map(|p| p.weight).collect();
+        weights[idx] = weight;
+        layout::dry_run_layout(&app.panes, &weights, app.chrome, indicator, app.viewport_start, area)
+    };
+    let (Some(before), Some(now)) = (
+        layout::expanded_share(&app.panes, &dry_run(preview.from), idx),
+        layout::expanded_share(&app.panes, layout, idx),
+    ) else {
+        return;
+    };
+
+    let weights = app.weights();
+    let weight = app.panes[idx].weight;
+    let next = if preview.grew {
+        weights.clamp(weight.saturating_add(weights.weight_step))
+    } else {
+        weights.clamp(weight.saturating_sub(weights.weight_step))
+    };
+    if let Some(&(_, ahead)) = dry_run(next).iter().find(|&&(i, _)| i == idx) {
+        if ahead.bottom() != current.bottom() && ahead.bottom() > area.y {
+            let y = ahead.bottom() - 1;
+            let style = Style::default().fg(Color::DarkGray).add_modifier(Modifier::DIM);
+            buf.set_string(area.x, y, "┄".repeat(area.width as usize), style);
+        }
+    }
+
+    let label = format!(" {}% → {}% ", before, now);
+    let style = Style::default().fg(Color::Black).bg(Color::Yellow).add_modifier(Modifier::BOLD);
+    let y = current.bottom().saturating_sub(1);
+    buf.set_stringn(current.x + 2, y, &label, current.width.saturating_sub(4) as usize, style);
+}
+
+/// A "more above/below" line listing the jump key of every hidden pane.
+/// `first_idx` is the stack position of `hidden[0]`.
+fn render_jump_indicator(buf: &mut Buffer, area: Rect, y: u16, msg: &str, hidden: &[Pane], first_idx: usize) {
+    let style = Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD);
+    let right = area.x + area.width;
+    for x in area.x..right {
+        buf.set_string(x, y, " ", style);
+    }
+    let (mut x, _) = buf.set_stringn(area.x, y, format!(" {} —", msg), area.width as usize, style);
+    for (i, pane) in hidden.iter().enumerate() {
+        let Some(key) = jump_key(first_idx + i) else {
+            break;
+        };
+        if x + 1 >= right {
+            break;
+        }
+        (x, _) = buf.set_stringn(x + 1, y, format!(" {} ", key), (right - x - 1) as usize, jump_badge_style());
+        (x, _) = buf.set_stringn(x, y, format!(" {}", pane.name), right.saturating_sub(x) as usize, style);
+    }
+}
+
+/// Centered notice drawn instead of the pane stack when the window is too small.
+fn render_too_small(buf: &mut Buffer, area: Rect, chrome: Chrome) {
+    let need = format!("need {}x{}", MIN_WINDOW_WIDTH, chrome.min_window_height());
+    let long = format!("window too small ({})", need);
+    let msg = if long.chars().count() as u16 <= area.width { long } else { need };
+    let style = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+    let width = (msg.chars().count() as u16).min(area.width);
+    let x = area.x + (area.width - width) / 2;
+    let y = area.y + area.height / 2;
+    buf.set_stringn(x, y, &msg, width as usize, style);
+}
+
+/// F12 overlay with frame timing, event backlog, and per-pane throughput.
+fn render_debug_overlay(frame: &mut Frame, area: Rect, app: &AppState) {
+    let stats = &app.stats;
+    let scrollback: usize = app.panes.iter().map(|p| terminal::scrollback_bytes(&p.term)).sum();
+    let mut lines = vec![
+        format!(
+            "frame {:.2}ms  {:.0} draws/s",
+            stats.last_frame.as_secs_f64() * 1000.0,
+            stats.draws_per_sec
+        ),
+        format!("queued events {}", stats.queued()),
+        format!("scrollback {}", stats::format_bytes(scrollback as f64)),
+    ];
+    if let Some(resident) = stats.resident {
+        lines.push(format!("rss {}", stats::format_bytes(resident as f64)));
+    }
+    for pane in &app.panes {
+        let rate = stats.pane_rates.get(&pane.id).copied().unwrap_or(0.0);
+        let titles = stats.title_rates.get(&pane.id).copied().unwrap_or(0.0);
+        let parse = pane.parse_load.rate().as_millis();
+        lines.push(format!("{} {}/s  {:.0} titles/s  parse {}ms/s", pane.name, stats::format_bytes(rate), titles, parse));
+    }
+
+    let content_width = lines.iter().map(|l| Line::from(l.as_str()).width()).max().unwrap_or(0) as u16;
+    let width = (content_width + 2).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    if width < 3 || height < 3 {
+        return;
     }
+    let overlay = Rect::new(area.x + area.width - width, area.y, width, height);
 
-    if below_count > 0 {
-        let msg = format!(" ▼ {} more below ", below_count);
-        let style = Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD);
-        let y = pane_area.y + pane_area.height - 1;
-        buf.set_string(pane_area.x, y, &msg, style);
-    }
+    frame.render_widget(Clear, overlay);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow))
+        .title(" debug ");
+    let inner = block.inner(overlay);
+    frame.render_widget(block, overlay);
 
-    render_footer(buf, footer_area, app.active_shoot.as_deref(), app.selection.is_some());
+    let buf = frame.buffer_mut();
+    let style = Style::default().fg(Color::Yellow);
+    for (i, line) in lines.iter().take(inner.height as usize).enumerate() {
+        buf.set_stringn(inner.x, inner.y + i as u16, line, inner.width as usize, style);
+    }
 }
 
-fn render_footer(buf: &mut Buffer, area: Rect, active_shoot: Option<&str>, selection_active: bool) {
+fn render_footer(buf: &mut Buffer, area: Rect, app: &AppState, glyphs: &Glyphs) {
     if area.height == 0 || area.width == 0 {
         return;
     }
+    let active_shoot = app.active_shoot.as_deref();
+    let selection_active = app.selection.is_some();
+    let literal_armed = app.literal_armed;
 
     let bg_style = Style::default().fg(Color::White);
     for x in area.x..area.x + area.width {
@@ -97,35 +1180,126 @@ fn render_footer(buf: &mut Buffer, area: Rect, active_shoot: Option<&str>, selec
         0
     };
 
-    let key_style = if selection_active {
+    // Focus-follows-activity badge, left of the shoot badge.
+    let follow_label = match (app.focus_follow.mode, app.auto_focus_paused_for()) {
+        (FocusFollows::Off, _) => None,
+        (_, Some(left)) => Some(format!(" follow: paused {}s ", left.as_secs() + 1)),
+        (FocusFollows::Any, None) => Some(" follow: any ".to_string()),
+        (FocusFollows::Match, None) => Some(" follow: match ".to_string()),
+    };
+    let follow_badge_width = match follow_label {
+        Some(badge) => {
+            let width = Line::from(badge.as_str()).width() as u16;
+            let right = area.width.saturating_sub(shoot_badge_width + 1);
+            if right >= width {
+                let style = Style::default().fg(Color::Black).bg(Color::Cyan);
+                buf.set_string(area.x + right - width, area.y, &badge, style);
+                width + 1
+            } else {
+                0
+            }
+        }
+        None => 0,
+    };
+
+    let right_margin = shoot_badge_width + follow_badge_width + 1;
+    let usable_right = area.x + area.width.saturating_sub(right_margin);
+
+    if let Some(prompt) = &app.prompt {
+        let width = usable_right.saturating_sub(area.x);
+        prompt.render(buf, Rect::new(area.x, area.y, width, 1));
+        return;
+    }
+
+    if let Some(confirm) = &app.confirm {
+        let style = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+        let width = usable_right.saturating_sub(area.x + 1);
+        buf.set_stringn(area.x + 1, area.y, &confirm.message, width as usize, style);
+        return;
+    }
+
+    if let Some(pane) = app.heavy_output.and_then(|id| app.panes.iter().find(|p| p.id == id)) {
+        let message = format!(
+            "{}: heavy output, {}ms/s parsing {} Alt+Shift+P pause / Alt+Shift+K signal / Alt+Shift+I ignore",
+            pane.name,
+            pane.parse_load.rate().as_millis(),
+            glyphs.dash
+        );
+        let style = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+        let width = usable_right.saturating_sub(area.x + 1);
+        buf.set_stringn(area.x + 1, area.y, &message, width as usize, style);
+        return;
+    }
+
+    // A status message replaces the hints until it expires.
+    if let Some(message) = app.status_message() {
+        let style = Style::default().fg(Color::LightRed).add_modifier(Modifier::BOLD);
+        let width = usable_right.saturating_sub(area.x + 1);
+        buf.set_stringn(area.x + 1, area.y, message, width as usize, style);
+        return;
+    }
+
+    let key_style = if selection_active || literal_armed || app.jump_mode || app.resize_mode || !app.marked.is_empty()
+    {
         Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
     } else {
         Style::default().fg(Color::Gray).add_modifier(Modifier::BOLD)
     };
     let desc_style = Style::default().fg(Color::Gray);
 
-    let hints: &[(&str, &str)] = if selection_active {
+    let weights = app.weights();
+    let resize = format!("Resize (w 1-{} ±{})", weights.weight_max, weights.weight_step);
+    let literal_key = app.literal_key.to_string();
+    let sort = match app.sort_mode {
+        SortMode::Off => "Sort".to_string(),
+        mode => format!("Sort: {}", mode.label()),
+    };
+    let marked = format!("MARKED {}", app.marked.len());
+    let hints: &[(&str, &str)] = if app.jump_mode {
+        &[("JUMP", ""), ("1-9 a-z", "focus pane"), ("Esc", "cancel")]
+    } else if app.resize_mode {
+        &[
+            ("RESIZE", ""),
+            ("j/k ↓/↑", "row"),
+            ("h/l", "pane"),
+            ("=", "equalize"),
+            ("Enter/Esc", "done"),
+        ]
+    } else if literal_armed {
+        &[("LITERAL", ""), ("next key", "goes to the pane"), ("Esc", "cancel")]
+    } else if selection_active {
         &[
             ("SELECT", ""),
             ("↑↓←→", "move"),
             ("Enter/y", "copy"),
             ("Esc", "cancel"),
         ]
+    } else if !app.marked.is_empty() {
+        &[
+            (&marked, ""),
+            ("Alt+m", "mark"),
+            ("Alt+c", "collapse"),
+            ("Alt+w", "close"),
+            ("Alt+b", "broadcast"),
+            ("Ctrl+↑/↓", "weight"),
+            ("Esc", "clear"),
+        ]
     } else {
         &[
             ("Ctrl+Q", "Quit"),
             ("Alt+j/k", "Focus"),
+            ("Alt+g", "Jump"),
             ("Alt+n", "New"),
             ("Alt+w", "Close"),
             ("Alt+c", "Collapse"),
             ("Alt+v", "Paste"),
             ("Alt+s", "Select"),
-            ("Ctrl+↑/↓", "Resize"),
+            ("Alt+o", &sort),
+            ("Ctrl+↑/↓", &resize),
+            (&literal_key, "Literal"),
         ]
     };
 
-    let right_margin = shoot_badge_width + 1;
-    let usable_right = area.x + area.width.saturating_sub(right_margin);
     let mut x = area.x + 1;
     for (key, desc) in hints {
         let key_width = Line::from(*key).width() as u16;
@@ -145,146 +1319,194 @@ fn render_footer(buf: &mut Buffer, area: Rect, active_shoot: Option<&str>, selec
     }
 }
 
-fn compute_visible_layout(app: &AppState, area: Rect) -> (Vec<(usize, Rect)>, usize) {
-    let total_height = area.height;
-    let start = app.viewport_start;
 
-    let above_count = start;
-    let has_above = above_count > 0;
 
-    let usable_top = if has_above { area.y + INDICATOR_HEIGHT } else { area.y };
-
-    // First pass: determine which panes fit with minimum heights,
-    // reserving space for a bottom indicator if needed.
-    let mut remaining = total_height.saturating_sub(if has_above { INDICATOR_HEIGHT } else { 0 });
-    let mut expanded_indices: Vec<usize> = Vec::new();
-    let mut visible: Vec<usize> = Vec::new();
-    let mut total_weight: u32 = 0;
-
-    for i in start..app.panes.len() {
-        let min_h = if app.panes[i].collapsed {
-            COLLAPSED_HEIGHT
-        } else {
-            MIN_EXPANDED_HEIGHT
-        };
-
-        let below_after = app.panes.len().saturating_sub(i + 1);
-        let need_below_indicator = below_after > 0;
-        let reserved = if need_below_indicator { INDICATOR_HEIGHT } else { 0 };
-
-        if remaining < min_h + reserved && !visible.is_empty() {
-            break;
-        }
+/// Per-frame settings that shape how a single pane is drawn.
+struct PaneView<'a> {
+    /// Position of the pane in `app.panes`.
+    index: usize,
+    is_focused: bool,
+    /// Marked for a group action.
+    marked: bool,
+    selection: Option<&'a SelectionState>,
+    color_mode: ColorMode,
+    /// See `Config::bold_is_bright`.
+    bold_is_bright: bool,
+    chrome: Chrome,
+    /// Global title template, used when the pane has none of its own.
+    title_format: &'a str,
+    /// Title-bar button under the mouse pointer.
+    hovered: Option<TitleButton>,
+    /// Give exited panes a bar listing what can be done next.
+    exit_bar: bool,
+    /// Show a collapsed pane's last line from above its shell prompt.
+    skip_prompt: bool,
+    /// Least time between refreshes of the output-driven parts of the title.
+    title_interval: Duration,
+    /// Tint the row and column under the copy-mode cursor, or else under
+    /// this pointer position; `None` when the crosshair is off.
+    crosshair: Option<Option<(u16, u16)>>,
+    /// Symbols, or ASCII and words; see `Config::accessible`.
+    glyphs: &'static Glyphs,
+    /// Lines to mark in the left border; see `Config::error_pattern`.
+    error_pattern: Option<&'a Regex>,
+    /// Mouse capture is on, so title buttons can be clicked.
+    mouse: bool,
+    /// Append the pane's output rate meter.
+    throughput: bool,
+}
 
-        remaining = remaining.saturating_sub(min_h);
-        if !app.panes[i].collapsed {
-            expanded_indices.push(visible.len());
-            total_weight += app.panes[i].weight as u32;
-        }
-        visible.push(i);
+/// Expand the pane's title template from its current state.
+fn pane_title(pane: &Pane, view: &PaneView) -> String {
+    let template = pane.title_format.as_deref().unwrap_or(view.title_format);
+    let name = pane.display_name();
+    let ctx = TitleContext {
+        index: view.index + 1,
+        id: pane.stable_id,
+        name: &name,
+        command: &pane.command,
+        status: match pane.run_state() {
+            RunState::Running => "running",
+            RunState::Draining => "exiting",
+            RunState::Finished => "exited",
+        },
+        duration: pane.run_time().unwrap_or_else(|| pane.started_at.elapsed()),
+        cwd: &pane.cwd,
+        scroll: pane.scroll_offset,
+        weight: pane.weight,
+        collapsed: pane.collapsed,
+    };
+    let mut title = title::expand(template, &ctx);
+    if pane.watch_paused {
+        title.push_str(" [watch paused]");
     }
-
-    let visible_end = visible.last().map(|&i| i + 1).unwrap_or(start);
-    let has_below = visible_end < app.panes.len();
-    let remaining = remaining.saturating_sub(if has_below { INDICATOR_HEIGHT } else { 0 });
-
-    // Second pass: compute heights with weighted distribution for expanded panes
-    let mut heights: Vec<u16> = visible
-        .iter()
-        .map(|&i| {
-            if app.panes[i].collapsed {
-                COLLAPSED_HEIGHT
-            } else {
-                MIN_EXPANDED_HEIGHT
-            }
-        })
-        .collect();
-
-    if !expanded_indices.is_empty() && remaining > 0 && total_weight > 0 {
-        let mut distributed = 0u16;
-        for (j, &idx) in expanded_indices.iter().enumerate() {
-            let pane_idx = visible[idx];
-            let w = app.panes[pane_idx].weight as u32;
-            let bonus = if j == expanded_indices.len() - 1 {
-                remaining - distributed
-            } else {
-                ((remaining as u32 * w) / total_weight) as u16
-            };
-            heights[idx] += bonus;
-            distributed += bonus;
-        }
+    if pane.stale {
+        title.push_str(&format!(" idle {}", idle_label(pane.idle_for(Instant::now()))));
     }
-
-    let mut y = usable_top;
-    let mut result = Vec::with_capacity(visible.len());
-    for (j, &pane_idx) in visible.iter().enumerate() {
-        let h = heights[j];
-        result.push((pane_idx, Rect::new(area.x, y, area.width, h)));
-        y += h;
+    if let Some(lock) = &pane.scroll_lock {
+        title.push_str(&view.glyphs.scroll_locked(lock.pending_lines));
     }
-
-    (result, visible_end)
+    if let Some(meter) = pane.throughput.meter(view.glyphs.words).filter(|_| view.throughput) {
+        title.push(' ');
+        title.push_str(&meter);
+    }
+    if let Some(message) = pane.flash_message() {
+        title.push_str(&format!(" {} {}", view.glyphs.dash, message));
+    }
+    title.push_str(view.glyphs.clipped(pane.clipped_sides()));
+    title
 }
 
-fn ensure_focused_visible(app: &mut AppState, total_height: u16) {
-    if app.panes.is_empty() {
-        return;
+/// "45s", "12m" or "3h": how long a stale pane has been quiet, to the
+/// largest whole unit.
+fn idle_label(idle: Duration) -> String {
+    let secs = idle.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{}h", secs / 3600)
     }
+}
 
-    if app.focused < app.viewport_start {
-        app.viewport_start = app.focused;
-        return;
+/// What the title's colors and badges say about a pane, in words.
+fn pane_states(pane: &Pane, focused: bool, marked: bool) -> Vec<String> {
+    let mut states = Vec::new();
+    if focused {
+        states.push("focused".to_string());
     }
-
-    loop {
-        let end = compute_visible_end(&app.panes, app.viewport_start, total_height);
-        if app.focused < end {
-            break;
+    if marked {
+        states.push("marked".to_string());
+    }
+    if pane.collapsed {
+        states.push("collapsed".to_string());
+    }
+    if let Some(group) = &pane.config.group {
+        states.push(format!("group {}", group));
+    }
+    if pane.reads_held() {
+        states.push("reading paused".to_string());
+    } else if pane.parse_load.is_heavy() {
+        states.push("heavy output".to_string());
+    }
+    let took = || title::format_duration(pane.run_time().unwrap_or_default());
+    let code = pane.exit_status.as_ref().map(|s| s.exit_code());
+    states.push(if pane.pending_start {
+        "not started".to_string()
+    } else if pane.spawn_error.is_some() {
+        "failed to start".to_string()
+    } else if let Some(code) = pane.early_failure() {
+        format!("failed to start, exit {}", code)
+    } else if let Some(code) = code.filter(|_| pane.is_task()) {
+        if code == 0 {
+            format!("succeeded in {}", took())
+        } else {
+            format!("failed in {}, exit {}", took(), code)
         }
-        app.viewport_start += 1;
-        if app.viewport_start >= app.panes.len() {
-            app.viewport_start = app.panes.len().saturating_sub(1);
-            break;
+    } else {
+        match (pane.run_state(), code) {
+            (RunState::Running, _) => "running".to_string(),
+            (RunState::Draining, _) => "exiting".to_string(),
+            (RunState::Finished, Some(code)) if code != 0 => format!("exited, exit {}", code),
+            (RunState::Finished, _) => "exited".to_string(),
         }
-    }
+    });
+    states
 }
 
-fn compute_visible_end(panes: &[Pane], start: usize, total_height: u16) -> usize {
-    let has_above = start > 0;
-    let mut remaining = total_height.saturating_sub(if has_above { INDICATOR_HEIGHT } else { 0 });
-    let mut end = start;
-
-    for i in start..panes.len() {
-        let min_h = if panes[i].collapsed {
-            COLLAPSED_HEIGHT
-        } else {
-            MIN_EXPANDED_HEIGHT
-        };
-        let below_after = panes.len().saturating_sub(i + 1);
-        let reserved = if below_after > 0 { INDICATOR_HEIGHT } else { 0 };
-
-        if remaining < min_h + reserved && end > start {
-            break;
+/// `pane_title`, reused while only output-driven parts could have changed
+/// and the last expansion is younger than the view's `title_interval`.
+fn cached_title(pane: &mut Pane, view: &PaneView) -> String {
+    let key = TitleKey::of(pane, view.index);
+    if let Some(cache) = &pane.title_cache {
+        if cache.key == key && cache.at.elapsed() < view.title_interval {
+            return cache.text.clone();
         }
-        remaining = remaining.saturating_sub(min_h);
-        end = i + 1;
     }
+    let text = pane_title(pane, view);
+    if pane.title_cache.as_ref().is_none_or(|cache| cache.text != text) {
+        pane.title_repaints += 1;
+    }
+    pane.title_cache = Some(TitleCache { key, text: text.clone(), at: Instant::now() });
+    text
+}
 
-    end
+/// "✓ 12s" or "✗ 3s (exit 2)" for a task that has finished.
+fn task_badge(pane: &Pane) -> Option<(String, Color)> {
+    let status = pane.exit_status.as_ref().filter(|_| pane.is_task())?;
+    let took = title::format_duration(pane.run_time().unwrap_or_default());
+    Some(if status.success() {
+        (format!("✓ {}", took), Color::LightGreen)
+    } else {
+        (format!("✗ {} (exit {})", took, status.exit_code()), Color::LightRed)
+    })
 }
 
-fn render_pane(frame: &mut Frame, pane: &mut Pane, area: Rect, is_focused: bool, selection: Option<&SelectionState>) {
-    let border_color = if is_focused {
+fn render_pane(frame: &mut Frame, pane: &mut Pane, areas: PaneAreas, view: &PaneView) {
+    let PaneView {
+        is_focused,
+        marked,
+        selection,
+        color_mode,
+        chrome,
+        hovered,
+        exit_bar,
+        ..
+    } = *view;
+    let border_color = if pane.spawn_error.is_some() || pane.early_failure().is_some() {
+        Color::Red
+    } else if marked {
+        Color::Magenta
+    } else if is_focused {
         Color::Green
     } else {
         Color::Black
     };
 
+    let area = areas.outer;
     let ty = area.y;
 
-    let toggle_style = Style::default()
-        .fg(Color::White)
-        .add_modifier(Modifier::BOLD);
     let name_style = if is_focused {
         Style::default()
             .fg(Color::White)
@@ -293,168 +1515,760 @@ fn render_pane(frame: &mut Frame, pane: &mut Pane, area: Rect, is_focused: bool,
     } else {
         Style::default().fg(Color::Gray)
     };
-    let close_style = Style::default()
-        .fg(Color::LightRed)
+    let button_style = |button: TitleButton| {
+        let style = match button {
+            TitleButton::Close => Style::default().fg(Color::LightRed),
+            _ => Style::default().fg(Color::White),
+        }
         .add_modifier(Modifier::BOLD);
+        if hovered == Some(button) {
+            style.bg(Color::DarkGray)
+        } else {
+            style
+        }
+    };
 
-    let block = Block::default()
-        .borders(Borders::ALL)
-        .border_style(Style::default().fg(border_color));
-
-    let inner = block.inner(area);
-    frame.render_widget(block, area);
+    let inner = areas.inner;
+    let glyphs = view.glyphs;
+    if glyphs.words {
+        // A plain rule; the title spells out what color would show.
+        let rule = "=".repeat(area.width as usize);
+        frame.buffer_mut().set_string(area.x, ty, &rule, Style::default());
+    } else {
+        match chrome {
+            Chrome::Full => {
+                let block = Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(border_color));
+                frame.render_widget(block, area);
+            }
+            Chrome::Compact => {
+                let block = Block::default()
+                    .borders(Borders::TOP)
+                    .border_style(Style::default().fg(border_color));
+                frame.render_widget(block, Rect::new(area.x, area.y, area.width, 1));
+            }
+            Chrome::None => {
+                // Focus marker in the left gutter.
+                let marker = if is_focused || marked { "▌" } else { " " };
+                let marker_style = Style::default().fg(if marked { Color::Magenta } else { Color::Green });
+                let buf = frame.buffer_mut();
+                for y in area.y..area.y + area.height {
+                    buf.set_string(area.x, y, marker, marker_style);
+                }
+            }
+        }
+    }
 
     let buf = frame.buffer_mut();
+    if let Some(pattern) = view.error_pattern.filter(|_| !glyphs.words && chrome != Chrome::Compact) {
+        render_error_ticks(buf, pane, pattern, inner);
+    }
 
-    // Collapse toggle button
-    let toggle = if pane.collapsed { "[▸]" } else { "[▾]" };
-    buf.set_string(area.x + 1, ty, toggle, toggle_style);
-
-    // Pane name + status
-    let status = if pane.collapsed {
-        pane.name.clone()
-    } else if let Some(sel) = selection.filter(|s| s.pane_id == pane.id) {
-        format!("{} [SEL {},{} → {},{}]", pane.name, sel.anchor.0, sel.anchor.1, sel.cursor.0, sel.cursor.1)
-    } else if pane.scroll_offset > 0 {
-        format!("{} [scroll: -{}]", pane.name, pane.scroll_offset)
-    } else {
-        format!("{} (w:{})", pane.name, pane.weight)
-    };
-    let max_name_len = area.width.saturating_sub(10) as usize;
-    let display_name = if status.len() > max_name_len {
-        &status[..max_name_len]
-    } else {
-        status.as_str()
-    };
-    buf.set_string(area.x + 5, ty, display_name, name_style);
+    if chrome.has_title() {
+        let bar = if view.mouse { TitleBar::new(area, pane.closed) } else { TitleBar::plain(area) };
+
+        for (button, span) in &bar.buttons {
+            buf.set_string(span.start, ty, glyphs.button(*button, pane.collapsed), button_style(*button));
+        }
 
-    // Close button
-    if area.width >= 8 {
-        let close_x = area.x + area.width.saturating_sub(4);
-        buf.set_string(close_x, ty, "[x]", close_style);
+        // Pane title, with the selection cursor while selecting
+        if let Some(span) = bar.title {
+            let mut title = cached_title(pane, view);
+            // In words mode every state is in the list after the title;
+            // otherwise colors, a badge and these notes show it.
+            let badge = task_badge(pane).filter(|_| !glyphs.words);
+            if glyphs.words {
+                let states = pane_states(pane, is_focused, marked).join(", ");
+                title = format!(" [{}] {} ({}) ", view.index + 1, title, states);
+            } else {
+                if pane.spawn_error.is_some() {
+                    title.push_str(" [failed to start]");
+                } else if let Some(code) = pane.early_failure() {
+                    title.push_str(&format!(" [failed to start (exit {})]", code));
+                } else if pane.pending_start {
+                    title.push_str(" [not started]");
+                } else if badge.is_none() && !pane.accepts_input() {
+                    title.push_str(" [exited — input disabled]");
+                }
+                if pane.reads_held() {
+                    title.push_str(" [reading paused]");
+                } else if pane.parse_load.is_heavy() {
+                    title.push_str(" [heavy output]");
+                }
+            }
+            let group = pane.config.group.as_deref().filter(|_| !glyphs.group.is_empty());
+            if group.is_some() {
+                title.insert_str(0, glyphs.group);
+            }
+            let marker = if marked { glyphs.marked } else { "" };
+            title.insert_str(0, marker);
+            if let Some(sel) = selection.filter(|s| s.pane_id == pane.id) {
+                title = format!(
+                    "{} [SEL {},{} {} {},{}]",
+                    title,
+                    sel.anchor.0,
+                    sel.anchor.1,
+                    glyphs.to,
+                    sel.cursor.0,
+                    sel.cursor.1
+                );
+            }
+            // Truncate by display width so wide and multi-byte characters are safe.
+            let (end, _) = buf.set_stringn(span.start, ty, &title, span.len(), name_style);
+            if let Some(group) = group {
+                let x = span.start + Line::from(marker).width() as u16;
+                if x < end {
+                    buf.set_style(Rect::new(x, ty, 1, 1), Style::default().fg(group_color(group)));
+                }
+            }
+            if let Some((text, color)) = badge {
+                let room = span.end.saturating_sub(end + 1) as usize;
+                buf.set_stringn(end + 1, ty, text, room, Style::default().fg(color).add_modifier(Modifier::BOLD));
+            }
+        }
     }
 
     if inner.width == 0 || inner.height == 0 {
         return;
     }
 
+    let generation = pane.generation();
+    if let Some(cache) = &pane.render_cache {
+        // A scroll-locked pane keeps showing what was on screen when it was
+        // locked, until its size changes.
+        let frozen = pane.scroll_lock.is_some() && cache.fits(inner, pane.collapsed);
+        if frozen || cache.is_valid(generation, inner, pane.collapsed, selection) {
+            cache.blit(buf, inner);
+            dim_stale(buf, pane, inner, view);
+            render_crosshair(buf, pane, inner, view);
+            return;
+        }
+    }
+
+    // Paint the default background first so cells the program never wrote,
+    // and any area beyond the grid, match its theme.
+    let palette = Palette::for_pane(pane, color_mode, view.bold_is_bright);
+    buf.set_style(inner, palette.fill());
+
+    // Until a full-screen program has repainted for its new size, its screen
+    // is the old one reflowed; show nothing rather than that.
+    if pane.settling() && !pane.collapsed {
+        pane.render_cache = None;
+        return;
+    }
+
     if pane.collapsed {
         // Show last terminal line so status is visible when collapsed
-        render_last_terminal_line(buf, pane, inner);
+        let prompt_row = if view.skip_prompt { pane.prompt_row } else { None };
+        render_last_terminal_line(buf, &pane.term, inner, palette, prompt_row);
+    } else if exit_bar && pane.closed && inner.height > 1 {
+        let content = Rect { height: inner.height - 1, ..inner };
+        render_terminal_cells(buf, &pane.term, content, pane.h_scroll, selection, palette);
+        render_exit_bar(buf, pane, Rect { y: content.bottom(), height: 1, ..inner });
+    } else {
+        render_terminal_cells(buf, &pane.term, inner, pane.h_scroll, selection, palette);
+    }
+
+    pane.render_cache = Some(RenderCache::capture(
+        buf,
+        inner,
+        generation,
+        pane.collapsed,
+        selection.cloned(),
+    ));
+    dim_stale(buf, pane, inner, view);
+    render_crosshair(buf, pane, inner, view);
+}
+
+/// Color of a group's title tick, the same for a name every run.
+fn group_color(name: &str) -> Color {
+    const COLORS: [Color; 6] =
+        [Color::Cyan, Color::LightBlue, Color::Yellow, Color::LightGreen, Color::LightRed, Color::LightMagenta];
+    let hash = name.bytes().fold(0usize, |h, b| h.wrapping_mul(31).wrapping_add(b as usize));
+    COLORS[hash % COLORS.len()]
+}
+
+/// Mark where lines matching `pattern` sit in the pane's whole history, in
+/// the column left of its content: the top row stands for the oldest line
+/// and the bottom row for the last one on screen.
+fn render_error_ticks(buf: &mut Buffer, pane: &mut Pane, pattern: &Regex, inner: Rect) {
+    if pane.collapsed || inner.x == 0 || inner.height == 0 {
+        return;
+    }
+    let history = terminal::history_lines(&pane.term) as i64;
+    let total = history + terminal::screen_rows(&pane.term) as i64;
+    let style = Style::default().fg(Color::LightRed).add_modifier(Modifier::BOLD);
+    for &line in pane.error_lines(pattern, ERROR_SCAN_INTERVAL) {
+        let row = (line as i64 + history) * inner.height as i64 / total.max(1);
+        buf.set_string(inner.x - 1, inner.y + row as u16, "▸", style);
+    }
+}
+
+/// Dim a stale pane's content unless it is focused. Drawn over the cached
+/// cells, so focus coming and going never invalidates the cache.
+fn dim_stale(buf: &mut Buffer, pane: &Pane, inner: Rect, view: &PaneView) {
+    if pane.stale && !view.is_focused {
+        buf.set_style(inner, Style::default().add_modifier(Modifier::DIM));
+    }
+}
+
+/// Tint the content row and column through the copy-mode cursor, or the
+/// mouse pointer when not selecting. Drawn over the cached cells, so
+/// moving it never invalidates the cache.
+fn render_crosshair(buf: &mut Buffer, pane: &Pane, inner: Rect, view: &PaneView) {
+    let Some(pointer) = view.crosshair else {
+        return;
+    };
+    if pane.collapsed {
         return;
     }
+    let offset = terminal::visible_row_offset(&pane.term, inner.height) as u16;
+    let point = match view.selection {
+        Some(sel) => {
+            let (row, col) = sel.cursor;
+            row.checked_sub(offset)
+                .zip(col.checked_sub(pane.h_scroll))
+                .map(|(row, col)| (inner.x.saturating_add(col), inner.y.saturating_add(row)))
+        }
+        None => pointer,
+    };
+    let Some((x, y)) = point.filter(|&(x, y)| inner.contains(Position::new(x, y))) else {
+        return;
+    };
+    let tint = match view.color_mode {
+        ColorMode::Truecolor | ColorMode::Auto => Style::default().bg(Color::Rgb(0x30, 0x32, 0x3a)),
+        ColorMode::Ansi256 => Style::default().bg(Color::Indexed(236)),
+        ColorMode::Ansi16 => Style::default().bg(Color::DarkGray),
+        ColorMode::None => Style::default().add_modifier(Modifier::REVERSED),
+    };
+    // Leave the selection's own highlight alone.
+    let selected = |cx: u16, cy: u16| {
+        view.selection
+            .is_some_and(|sel| sel.contains(cy - inner.y + offset, cx - inner.x + pane.h_scroll))
+    };
+    let row = (inner.x..inner.right()).map(|cx| (cx, y));
+    let column = (inner.y..inner.bottom()).filter(|&cy| cy != y).map(|cy| (x, cy));
+    for (cx, cy) in row.chain(column) {
+        if !selected(cx, cy) {
+            buf[(cx, cy)].set_style(tint);
+        }
+    }
+}
 
-    if inner.width != pane.cols || inner.height != pane.rows {
-        pane.resize(inner.width, inner.height);
+/// Resize the pane's PTY and grid to its content area, unless collapsed.
+fn fit_pane(pane: &mut Pane, inner: Rect, squeezed: bool) {
+    if pane.collapsed || inner.width == 0 || inner.height == 0 {
+        return;
+    }
+    let (mut cols, mut rows) = (inner.width.max(MIN_PTY_COLS), inner.height.max(MIN_PTY_ROWS));
+    // While the window has just shrunk, the pane is drawn clipped rather
+    // than reflowed, in case the old size comes straight back.
+    if squeezed {
+        cols = cols.max(pane.cols);
+        rows = rows.max(pane.rows);
+    }
+    if cols != pane.cols || rows != pane.rows {
+        pane.resize(cols, rows);
     }
+}
 
-    render_terminal_cells(buf, pane, inner, selection);
+/// "process exited (code 1) — [r]estart  [x]close  [e]xport" on the bottom
+/// row of an exited pane.
+fn render_exit_bar(buf: &mut Buffer, pane: &Pane, area: Rect) {
+    let what = match &pane.exit_status {
+        _ if pane.spawn_error.is_some() => "failed to start".to_string(),
+        Some(status) => format!("process exited (code {})", status.exit_code()),
+        None => "process exited".to_string(),
+    };
+    let text = format!(" {} — [r]estart  [x]close  [e]xport", what);
+    let bg = if pane.failed() { Color::LightRed } else { Color::Gray };
+    let style = Style::default().fg(Color::Black).bg(bg);
+    buf.set_style(area, style);
+    buf.set_stringn(area.x, area.y, &text, area.width as usize, style);
 }
 
-fn render_terminal_cells(buf: &mut Buffer, pane: &Pane, area: Rect, selection: Option<&SelectionState>) {
-    let term = pane.term.lock();
-    let screen_rows = terminal::screen_rows(&term);
-    let screen_cols = terminal::screen_cols(&term);
+/// Colors a pane's cells are drawn with: the output color mode plus the
+/// pane's default foreground/background, if any.
+#[derive(Clone, Copy)]
+struct Palette {
+    mode: ColorMode,
+    fg: Option<AnsiColor>,
+    bg: Option<AnsiColor>,
+    overrides: PaneColors,
+    bold_is_bright: bool,
+}
 
-    for row in 0..area.height {
-        for col in 0..area.width {
-            let r = row as usize;
-            let c = col as usize;
-            if r >= screen_rows || c >= screen_cols {
-                continue;
-            }
-            let info = terminal::cell_info(&term, r, c);
-            let fg = terminal::convert_color(info.fg);
-            let bg = terminal::convert_color(info.bg);
-            let mut style = Style::default().fg(fg).bg(bg);
-            if info.bold {
-                style = style.add_modifier(Modifier::BOLD);
-            }
-            if info.dim {
-                style = style.add_modifier(Modifier::DIM);
-            }
-            if info.italic {
-                style = style.add_modifier(Modifier::ITALIC);
-            }
-            if info.underline {
-                style = style.add_modifier(Modifier::UNDERLINED);
-            }
-            if info.inverse {
-                style = style.add_modifier(Modifier::REVERSED);
-            }
+impl Palette {
+    fn for_pane(pane: &Pane, mode: ColorMode, bold_is_bright: bool) -> Self {
+        Self {
+            mode,
+            overrides: pane.colors,
+            bold_is_bright,
+            fg: terminal::dynamic_color(&pane.term, NamedColor::Foreground).or(pane.default_fg),
+            bg: terminal::dynamic_color(&pane.term, NamedColor::Background).or(pane.default_bg),
+        }
+    }
+
+    fn fg(&self, color: AnsiColor) -> Color {
+        let color = match (color, self.fg) {
+            (
+                AnsiColor::Named(
+                    NamedColor::Foreground | NamedColor::BrightForeground | NamedColor::DimForeground,
+                ),
+                Some(default),
+            ) => default,
+            _ => color,
+        };
+        terminal::convert_color(self.overrides.apply(color), self.mode)
+    }
+
+    fn bg(&self, color: AnsiColor) -> Color {
+        let color = match (color, self.bg) {
+            (AnsiColor::Named(NamedColor::Background), Some(default)) => default,
+            _ => color,
+        };
+        terminal::convert_color(self.overrides.apply(color), self.mode)
+    }
+
+    /// Style for the pane's unwritten area.
+    fn fill(&self) -> Style {
+        Style::default()
+            .fg(self.fg(AnsiColor::Named(NamedColor::Foreground)))
+            .bg(self.bg(AnsiColor::Named(NamedColor::Background)))
+    }
+}
+
+/// Build the ratatui style for a cell's colors and attributes.
+fn cell_style(info: &CellInfo, palette: &Palette) -> Style {
+    // Brightened before the pane's palette applies, so a `palette` entry for
+    // the bright color is what bold text gets.
+    let fg = if info.bold && palette.bold_is_bright { terminal::bright_variant(info.fg) } else { info.fg };
+    let mut style = Style::default().fg(palette.fg(fg)).bg(palette.bg(info.bg));
+    if info.bold {
+        style = style.add_modifier(Modifier::BOLD);
+    }
+    if info.dim {
+        style = style.add_modifier(Modifier::DIM);
+    }
+    if info.italic {
+        style = style.add_modifier(Modifier::ITALIC);
+    }
+    if info.underline {
+        style = style.add_modifier(Modifier::UNDERLINED);
+    }
+    if info.inverse {
+        style = style.add_modifier(Modifier::REVERSED);
+    }
+    style
+}
+
+/// Write a terminal cell into the buffer without allocating for the common
+/// single-codepoint case.
+fn put_cell(buf: &mut Buffer, x: u16, y: u16, info: &CellInfo, style: Style) {
+    let Some(cell) = buf.cell_mut((x, y)) else {
+        return;
+    };
+    match info.zerowidth {
+        Some(zw) if !zw.is_empty() => {
+            let mut symbol = String::with_capacity(4 * (zw.len() + 1));
+            symbol.push(info.c);
+            symbol.extend(zw);
+            cell.set_symbol(&symbol);
+        }
+        _ => {
+            cell.set_char(if info.c == '\0' { ' ' } else { info.c });
+        }
+    }
+    cell.set_style(style);
+}
+
+/// Memoizes `cell_style` across runs of cells with identical attributes,
+/// which is the overwhelmingly common case in terminal output.
+struct StyleCache {
+    palette: Palette,
+    key: Option<(AnsiColor, AnsiColor, [bool; 5])>,
+    style: Style,
+}
+
+impl StyleCache {
+    fn new(palette: Palette) -> Self {
+        Self {
+            palette,
+            key: None,
+            style: Style::default(),
+        }
+    }
+
+    fn get(&mut self, info: &CellInfo) -> Style {
+        let key = (
+            info.fg,
+            info.bg,
+            [info.bold, info.dim, info.italic, info.underline, info.inverse],
+        );
+        if self.key != Some(key) {
+            self.key = Some(key);
+            self.style = cell_style(info, &self.palette);
+        }
+        self.style
+    }
+}
+
+/// Draw the screen into `area`, starting `h_scroll` columns in.
+fn render_terminal_cells(
+    buf: &mut Buffer,
+    term: &Term<PaneListener>,
+    area: Rect,
+    h_scroll: u16,
+    selection: Option<&SelectionState>,
+    palette: Palette,
+) {
+    let screen_rows = terminal::screen_rows(term);
+    let screen_cols = terminal::screen_cols(term);
+    let rows = area.height.min(screen_rows as u16);
+    let cols = area.width.min((screen_cols as u16).saturating_sub(h_scroll));
+    let offset = terminal::visible_row_offset(term, area.height) as u16;
+    let mut styles = StyleCache::new(palette);
+
+    for row in 0..rows {
+        let term_row = row + offset;
+        for col in 0..cols {
+            let term_col = col + h_scroll;
+            let info = terminal::cell_info(term, term_row as usize, term_col as usize);
+            let mut style = styles.get(&info);
 
             // Selection highlighting
             if let Some(sel) = selection {
-                if sel.cursor == (row, col) {
+                if sel.cursor == (term_row, term_col) {
                     style = Style::default()
                         .fg(Color::Black)
                         .bg(Color::Yellow)
                         .add_modifier(Modifier::BOLD);
-                } else if sel.contains(row, col) {
+                } else if sel.contains(term_row, term_col) {
                     style = Style::default().fg(Color::White).bg(Color::Blue);
                 }
             }
 
-            let x = area.x + col;
-            let y = area.y + row;
-            if x < area.x + area.width && y < area.y + area.height {
-                let ch = if info.ch == "\0" { " ".to_string() } else { info.ch };
-                buf.set_string(x, y, &ch, style);
-            }
+            put_cell(buf, area.x + col, area.y + row, &info, style);
+        }
+    }
+}
+
+/// What a row shows, for picking the one a collapsed pane displays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum RowContent {
+    Blank,
+    /// Only spaces, some with a background color that stops short of the
+    /// full width.
+    Colored,
+    Text,
+}
+
+fn row_content(term: &Term<PaneListener>, row: usize, cols: usize) -> RowContent {
+    let mut colored = 0;
+    for col in 0..cols {
+        let info = terminal::cell_info(term, row, col);
+        if !info.is_blank() {
+            return RowContent::Text;
+        }
+        if info.bg != AnsiColor::Named(NamedColor::Background) {
+            colored += 1;
         }
     }
+    if colored > 0 && colored < cols {
+        RowContent::Colored
+    } else {
+        RowContent::Blank
+    }
 }
 
-fn render_last_terminal_line(buf: &mut Buffer, pane: &Pane, area: Rect) {
-    let term = pane.term.lock();
-    let screen_rows = terminal::screen_rows(&term);
-    let screen_cols = terminal::screen_cols(&term);
+/// The row a collapsed pane shows: the last with text, else the last with
+/// a partly colored background, else the cursor's. Rows from `prompt_row`
+/// down are passed over while anything above qualifies.
+fn collapsed_line_row(term: &Term<PaneListener>, prompt_row: Option<usize>) -> usize {
+    let rows = terminal::screen_rows(term);
+    let cols = terminal::screen_cols(term);
+    let contents: Vec<RowContent> = (0..rows).map(|row| row_content(term, row, cols)).collect();
+    let last = |wanted: RowContent, end: usize| contents[..end].iter().rposition(|&c| c == wanted);
+    let best = |end: usize| last(RowContent::Text, end).or_else(|| last(RowContent::Colored, end));
+    prompt_row
+        .and_then(|prompt| best(prompt.min(rows)))
+        .or_else(|| best(rows))
+        .unwrap_or_else(|| terminal::cursor_position(term).0.min(rows.saturating_sub(1)))
+}
+
+fn render_last_terminal_line(
+    buf: &mut Buffer,
+    term: &Term<PaneListener>,
+    area: Rect,
+    palette: Palette,
+    prompt_row: Option<usize>,
+) {
+    let screen_rows = terminal::screen_rows(term);
+    let screen_cols = terminal::screen_cols(term);
     if screen_rows == 0 || screen_cols == 0 {
         return;
     }
-    // Find the last row with any non-empty content; fall back to cursor row
-    let target_row = (0..screen_rows)
-        .rev()
-        .find(|&row| {
-            (0..screen_cols).any(|col| {
-                let ch = terminal::cell_char(&term, row, col);
-                ch != " " && ch != "\0"
-            })
-        })
-        .unwrap_or_else(|| {
-            let (cr, _) = terminal::cursor_position(&term);
-            cr.min(screen_rows - 1)
-        });
+    let target_row = collapsed_line_row(term, prompt_row);
 
+    let mut styles = StyleCache::new(palette);
     for col in 0..area.width.min(screen_cols as u16) {
-        let c = col as usize;
-        let info = terminal::cell_info(&term, target_row, c);
-        let fg = terminal::convert_color(info.fg);
-        let bg = terminal::convert_color(info.bg);
-        let mut style = Style::default().fg(fg).bg(bg);
-        if info.bold {
-            style = style.add_modifier(Modifier::BOLD);
-        }
-        if info.dim {
-            style = style.add_modifier(Modifier::DIM);
-        }
-        if info.italic {
-            style = style.add_modifier(Modifier::ITALIC);
+        let info = terminal::cell_info(term, target_row, col as usize);
+        let style = styles.get(&info);
+        put_cell(buf, area.x + col, area.y, &info, style);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buttons(width: u16, exited: bool) -> Vec<TitleButton> {
+        TitleBar::new(Rect::new(3, 0, width, 5), exited)
+            .buttons
+            .into_iter()
+            .map(|(b, _)| b)
+            .collect()
+    }
+
+    #[test]
+    fn title_buttons_drop_out_as_the_pane_narrows() {
+        use TitleButton::*;
+        assert_eq!(buttons(40, true), [Toggle, Close, Solo, Restart]);
+        assert_eq!(buttons(40, false), [Toggle, Close, Solo]);
+        assert_eq!(buttons(24, true), [Toggle, Close, Solo]);
+        assert_eq!(buttons(12, true), [Toggle, Close]);
+        assert_eq!(buttons(6, true), [Toggle]);
+        assert_eq!(buttons(4, true), []);
+    }
+
+    #[test]
+    fn title_buttons_never_overlap_the_title() {
+        for width in 0..60 {
+            let bar = TitleBar::new(Rect::new(3, 0, width, 5), true);
+            let mut spans: Vec<Range<u16>> = bar.buttons.iter().map(|(_, r)| r.clone()).collect();
+            spans.extend(bar.title.clone());
+            spans.sort_by_key(|r| r.start);
+            for pair in spans.windows(2) {
+                assert!(pair[0].end <= pair[1].start, "width {}: {:?}", width, spans);
+            }
+            assert!(spans.iter().all(|r| r.start >= 3 && r.end <= 3 + width));
+            for (button, span) in &bar.buttons {
+                assert_eq!(bar.button_at(span.start), Some(*button));
+            }
         }
-        if info.underline {
-            style = style.add_modifier(Modifier::UNDERLINED);
+    }
+
+    /// Frame cost of a full 200×50 pane of colored text, which must fit well
+    /// inside a 60 Hz frame.
+    ///
+    /// Run with `cargo test --release -- --ignored render_bench`.
+    #[test]
+    #[ignore]
+    fn render_bench_full_colored_pane() {
+        let (cols, rows) = (200u16, 50u16);
+        let mut term = terminal::new_term(rows, cols, 0, Default::default());
+        {
+            let mut processor = terminal::new_processor();
+            let mut text = String::new();
+            for row in 0..rows {
+                for col in 0..cols {
+                    let color = 31 + (row + col) % 7;
+                    text.push_str(&format!("\x1b[{}m{}", color, (b'a' + (col % 26) as u8) as char));
+                }
+                if row + 1 < rows {
+                    text.push_str("\r\n");
+                }
+            }
+            terminal::process_bytes(&mut term, &mut processor, text.as_bytes());
         }
-        if info.inverse {
-            style = style.add_modifier(Modifier::REVERSED);
+
+        let area = Rect::new(0, 0, cols, rows);
+        let mut buf = Buffer::empty(area);
+        let frames = 500;
+        let start = Instant::now();
+        let palette = Palette {
+            mode: ColorMode::Truecolor,
+            fg: None,
+            bg: None,
+            overrides: PaneColors::default(),
+            bold_is_bright: true,
+        };
+        for _ in 0..frames {
+            buf.reset();
+            render_terminal_cells(&mut buf, &term, area, 0, None, palette);
         }
-        let ch = if info.ch == "\0" || info.ch == " " {
-            " ".to_string()
-        } else {
-            info.ch
+        let per_frame = start.elapsed() / frames;
+        assert!(per_frame < Duration::from_millis(8), "render_terminal_cells 200x50: {:?}/frame", per_frame);
+    }
+
+    fn screen(rows: u16, cols: u16, output: &str) -> Term<PaneListener> {
+        let mut term = terminal::new_term(rows, cols, 0, Default::default());
+        terminal::process_bytes(&mut term, &mut terminal::new_processor(), output.as_bytes());
+        term
+    }
+
+    #[test]
+    fn bold_basic_colors_draw_bright_and_stay_bold() {
+        let term = screen(1, 10, "\x1b[1;31mA\x1b[0;31mB\x1b[1;38;5;2mC\x1b[1;38;5;9mD\x1b[1;38;5;100mE");
+        let draw = |bold_is_bright| {
+            let area = Rect::new(0, 0, 10, 1);
+            let mut buf = Buffer::empty(area);
+            let palette = Palette {
+                mode: ColorMode::Ansi256,
+                fg: None,
+                bg: None,
+                overrides: PaneColors::default(),
+                bold_is_bright,
+            };
+            render_terminal_cells(&mut buf, &term, area, 0, None, palette);
+            (0..5).map(|x| (buf[(x, 0)].fg, buf[(x, 0)].modifier.contains(Modifier::BOLD))).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            draw(true),
+            [
+                (Color::LightRed, true),
+                (Color::Red, false),
+                (Color::LightGreen, true),
+                (Color::LightRed, true),
+                (Color::Indexed(100), true),
+            ]
+        );
+        assert_eq!(draw(false)[..3], [(Color::Red, true), (Color::Red, false), (Color::Green, true)]);
+    }
+
+    #[test]
+    fn collapsed_line_prefers_text_over_colored_filler() {
+        // A right-aligned prompt segment of colored spaces under the output.
+        let term = screen(6, 40, "build ok\r\n\x1b[30C\x1b[48;5;236m          \x1b[0m\r\n");
+        assert_eq!(collapsed_line_row(&term, None), 0);
+        let term = screen(6, 40, "\r\n\x1b[30C\x1b[48;5;236m          \x1b[0m\r\n");
+        assert_eq!(collapsed_line_row(&term, None), 1);
+        // A background across the whole width is no content; use the cursor.
+        let term = screen(6, 40, "\x1b[48;5;236m\x1b[K\x1b[0m\r\n\r\n");
+        assert_eq!(collapsed_line_row(&term, None), 2);
+    }
+
+    #[test]
+    fn collapsed_line_can_skip_the_prompt() {
+        let bash = "make\r\nall done\r\n\x1b]133;A\x07user@host:~/src$ \x1b]133;B\x07";
+        let term = screen(6, 40, bash);
+        assert_eq!(collapsed_line_row(&term, None), 2);
+        assert_eq!(collapsed_line_row(&term, Some(2)), 1);
+
+        // A two-line powerlevel-style prompt with segments on both sides.
+        let powerlevel = "make\r\nall done\r\n\x1b]133;A\x07\x1b[44m ~/src \x1b[0m\x1b[34m\u{e0b0}\x1b[0m\x1b[22C\x1b[42m main \x1b[0m\r\n\x1b[32m❯\x1b[0m \x1b]133;B\x07";
+        let term = screen(6, 40, powerlevel);
+        assert_eq!(collapsed_line_row(&term, None), 3);
+        assert_eq!(collapsed_line_row(&term, Some(2)), 1);
+
+        // Nothing above the prompt: show the prompt after all.
+        let term = screen(6, 40, "\x1b]133;A\x07$ ");
+        assert_eq!(collapsed_line_row(&term, Some(0)), 0);
+    }
+
+    #[test]
+    fn panes_fit_their_content_area_down_to_the_minimum_pty() {
+        let config = crate::config::PaneConfig {
+            name: "api".to_string(),
+            ..Default::default()
+        };
+        let mut pane = Pane::spawn_failed(0, &config, &anyhow::anyhow!("no such file"), 20, 5);
+        let size = |pane: &Pane| (pane.cols, pane.rows);
+        fit_pane(&mut pane, Rect::new(1, 1, 30, 10), false);
+        assert_eq!(size(&pane), (30, 10));
+        fit_pane(&mut pane, Rect::new(1, 1, MIN_PTY_COLS - 1, 1), false);
+        assert_eq!(size(&pane), (MIN_PTY_COLS, MIN_PTY_ROWS));
+        fit_pane(&mut pane, Rect::new(1, 1, MIN_PTY_COLS, MIN_PTY_ROWS + 1), false);
+        assert_eq!(size(&pane), (MIN_PTY_COLS, MIN_PTY_ROWS + 1));
+
+        // Nothing to fit to.
+        fit_pane(&mut pane, Rect::new(1, 1, 0, 8), false);
+        fit_pane(&mut pane, Rect::new(1, 1, 30, 0), false);
+        pane.collapsed = true;
+        fit_pane(&mut pane, Rect::new(1, 1, 30, 8), false);
+        assert_eq!(size(&pane), (MIN_PTY_COLS, MIN_PTY_ROWS + 1));
+        pane.collapsed = false;
+
+        // A squeezed pane only grows.
+        fit_pane(&mut pane, Rect::new(1, 1, 30, 10), false);
+        fit_pane(&mut pane, Rect::new(1, 1, 25, 8), true);
+        assert_eq!(size(&pane), (30, 10));
+        fit_pane(&mut pane, Rect::new(1, 1, 40, 8), true);
+        assert_eq!(size(&pane), (40, 10));
+    }
+
+    #[test]
+    fn accessible_mode_says_in_words_what_colors_show() {
+        let config = crate::config::PaneConfig {
+            name: "api".to_string(),
+            ..Default::default()
         };
-        if let Some(cell) = buf.cell_mut((area.x + col, area.y)) {
-            cell.set_symbol(&ch);
-            cell.set_style(style);
+        let mut pane = Pane::spawn_failed(0, &config, &anyhow::anyhow!("no such file"), 20, 5);
+        assert_eq!(pane_states(&pane, true, false), ["focused", "failed to start"]);
+        pane.collapsed = true;
+        assert_eq!(pane_states(&pane, false, false), ["collapsed", "failed to start"]);
+        assert_eq!(pane_states(&pane, false, true), ["marked", "collapsed", "failed to start"]);
+
+        assert_eq!(hidden_sentence(1, "above"), "1 more pane is above the view.");
+        assert_eq!(hidden_sentence(3, "below"), "3 more panes are below the view.");
+        assert_eq!(Glyphs::WORDS.button(TitleButton::Toggle, true), "[+]");
+    }
+
+    #[test]
+    fn every_border_and_corner_cell_hits_the_right_part() {
+        // A 6x4 pane at (2, 3); the map marks each cell and the ring around
+        // it: T title row, B border, C content, . outside.
+        let cases = [
+            (Chrome::Full, ["........", ".TTTTTT.", ".BCCCCB.", ".BCCCCB.", ".BBBBBB.", "........"]),
+            (Chrome::Compact, ["........", ".TTTTTT.", ".CCCCCC.", ".CCCCCC.", ".CCCCCC.", "........"]),
+            (Chrome::None, ["........", ".BCCCCC.", ".BCCCCC.", ".BCCCCC.", ".BCCCCC.", "........"]),
+        ];
+        for (chrome, map) in cases {
+            let areas = PaneAreas::new(Rect::new(2, 3, 6, 4), chrome);
+            for (dy, line) in map.iter().enumerate() {
+                let row = 2 + dy as u16;
+                for (dx, expected) in line.chars().enumerate() {
+                    let col = 1 + dx as u16;
+                    let got = match (areas.contains(col, row), areas.hit_title(col, row), areas.hit_inner(col, row)) {
+                        (false, false, None) => '.',
+                        (true, true, None) => 'T',
+                        (true, false, None) => 'B',
+                        (true, false, Some(_)) => 'C',
+                        other => panic!("{:?} at ({}, {}): {:?}", chrome, col, row, other),
+                    };
+                    assert_eq!(got, expected, "{:?} at ({}, {})", chrome, col, row);
+                }
+            }
         }
+
+        let areas = PaneAreas::new(Rect::new(2, 3, 6, 4), Chrome::Full);
+        assert_eq!(areas.hit_inner(3, 4), Some((0, 0)), "first content cell");
+        assert_eq!(areas.hit_inner(6, 5), Some((3, 1)), "last content cell");
+        assert_eq!(areas.clamp_inner(0, 0), Some((0, 0)));
+        assert_eq!(areas.clamp_inner(7, 6), Some((3, 1)), "bottom-right corner");
+        assert_eq!(PaneAreas::new(Rect::new(0, 0, 6, 2), Chrome::Full).clamp_inner(1, 1), None);
+    }
+
+    #[test]
+    fn gutter_clicks_land_on_the_pane_drawn_there() {
+        let area = Rect::new(99, 2, 1, 10);
+        // Fewer panes than rows: each covers a run of rows.
+        let hits: Vec<_> = (2..12).map(|row| gutter_pane_at(area, 3, row)).collect();
+        assert_eq!(hits, [0, 0, 0, 1, 1, 1, 2, 2, 2, 2].map(Some));
+        // More panes than rows: a row jumps to the first pane drawn on it.
+        assert_eq!(gutter_pane_at(area, 25, 2), Some(0));
+        assert_eq!(gutter_pane_at(area, 25, 3), Some(3));
+        assert_eq!(gutter_pane_at(area, 25, 11), Some(23));
+        assert_eq!(gutter_pane_at(area, 3, 12), None);
+        assert_eq!(gutter_pane_at(area, 0, 2), None);
+    }
+
+    #[test]
+    fn startup_warnings_wrap_at_spaces_and_split_long_words() {
+        assert_eq!(
+            wrap_words("Pane 'api' failed to start: /nonexistent/dir", 12),
+            vec!["Pane 'api'", "failed to", "start:", "/nonexistent", "/dir"]
+        );
     }
 }
@@ -0,0 +1,156 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self as std_mpsc, RecvTimeoutError};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+use crate::events::AppEvent;
+
+/// Changes closer together than this restart the command once.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Compile a pane's `watch` patterns. They're matched against paths
+/// relative to the pane's working directory; `*` stops at `/`, `**` doesn't.
+pub fn compile(patterns: &[String]) -> Result<GlobSet> {
+    let mut set = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid watch pattern {:?}", pattern))?;
+        set.add(glob);
+    }
+    Ok(set.build()?)
+}
+
+/// Watches files for one pane; dropping it stops watching.
+pub struct PaneWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+/// Watch `root` recursively and send `AppEvent::WatchTriggered` after a burst
+/// of changes to files matching `patterns`.
+pub fn start(
+    pane_id: usize,
+    patterns: &[String],
+    root: &Path,
+    tx: mpsc::UnboundedSender<AppEvent>,
+) -> Result<PaneWatcher> {
+    let globs = compile(patterns)?;
+    let root = root
+        .canonicalize()
+        .with_context(|| format!("Cannot watch {}", root.display()))?;
+
+    let (raw_tx, raw_rx) = std_mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            let _ = raw_tx.send(event);
+        }
+    })?;
+    watcher
+        .watch(&root, RecursiveMode::Recursive)
+        .with_context(|| format!("Cannot watch {}", root.display()))?;
+
+    std::thread::Builder::new()
+        .name(format!("watch-{}", pane_id))
+        .spawn(move || debounce(pane_id, &root, &globs, raw_rx, tx))?;
+    tracing::debug!(pane = pane_id, ?patterns, "watching files");
+    Ok(PaneWatcher { _watcher: watcher })
+}
+
+/// Report the first matching change of each burst. Ends once the watcher is
+/// dropped, which closes `raw_rx`.
+fn debounce(
+    pane_id: usize,
+    root: &Path,
+    globs: &GlobSet,
+    raw_rx: std_mpsc::Receiver<notify::Event>,
+    tx: mpsc::UnboundedSender<AppEvent>,
+) {
+    while let Ok(event) = raw_rx.recv() {
+        let Some(path) = matching_path(root, globs, &event) else {
+            continue;
+        };
+        loop {
+            match raw_rx.recv_timeout(DEBOUNCE) {
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+        if tx.send(AppEvent::WatchTriggered { pane_id, path }).is_err() {
+            return;
+        }
+    }
+}
+
+/// First path in `event` that matches, relative to `root`. Reads and other
+/// access events are ignored.
+fn matching_path(root: &Path, globs: &GlobSet, event: &notify::Event) -> Option<PathBuf> {
+    if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) {
+        return None;
+    }
+    event
+        .paths
+        .iter()
+        .filter_map(|p| p.strip_prefix(root).ok())
+        .find(|p| globs.is_match(p))
+        .map(Path::to_path_buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_match_relative_paths() {
+        let globs = compile(&["src/**/*.rs".to_string(), "*.toml".to_string()]).unwrap();
+        assert!(globs.is_match("src/lib.rs"));
+        assert!(globs.is_match("src/a/b.rs"));
+        assert!(globs.is_match("Cargo.toml"));
+        assert!(!globs.is_match("sub/Cargo.toml"));
+        assert!(!globs.is_match("target/debug/x.rs"));
+        assert!(compile(&["src/[".to_string()]).is_err());
+    }
+
+    #[test]
+    fn a_burst_of_changes_triggers_once_for_its_first_match() {
+        use notify::event::{AccessKind, ModifyKind};
+        use std::time::Instant;
+
+        let root = PathBuf::from("/project");
+        let globs = compile(&["src/*.rs".to_string()]).unwrap();
+        let (raw_tx, raw_rx) = std_mpsc::channel();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let thread = std::thread::spawn(move || debounce(7, &root, &globs, raw_rx, tx));
+        let event = |kind, path: &str| notify::Event::new(kind).add_path(PathBuf::from("/project").join(path));
+        let triggered = |rx: &mut mpsc::UnboundedReceiver<AppEvent>| match rx.blocking_recv() {
+            Some(AppEvent::WatchTriggered { pane_id, path }) => Some((pane_id, path)),
+            _ => None,
+        };
+
+        raw_tx.send(event(EventKind::Access(AccessKind::Any), "src/read.rs")).unwrap();
+        raw_tx.send(event(EventKind::Modify(ModifyKind::Any), "notes.txt")).unwrap();
+        raw_tx.send(event(EventKind::Modify(ModifyKind::Any), "src/a.rs")).unwrap();
+        raw_tx.send(event(EventKind::Modify(ModifyKind::Any), "src/b.rs")).unwrap();
+        assert_eq!(triggered(&mut rx), Some((7, PathBuf::from("src/a.rs"))));
+
+        // Changes that keep coming hold the restart back until they stop.
+        let start = Instant::now();
+        for _ in 0..4 {
+            raw_tx.send(event(EventKind::Create(notify::event::CreateKind::Any), "src/b.rs")).unwrap();
+            std::thread::sleep(DEBOUNCE / 3);
+        }
+        assert_eq!(triggered(&mut rx), Some((7, PathBuf::from("src/b.rs"))));
+        // The last change came a whole debounce after the first.
+        assert!(start.elapsed() >= DEBOUNCE * 2, "{:?}", start.elapsed());
+
+        // Dropping the watcher ends the thread without another trigger.
+        drop(raw_tx);
+        thread.join().unwrap();
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -1,5 +1,5 @@
-use std::collections::HashMap;
 use std::io::{self, IsTerminal, Write};
+use std::path::Path;
 
 use anyhow::Result;
 
@@ -37,8 +37,7 @@ pub fn run_wizard() -> Result<Config> {
     writeln!(out)?;
 
     // --- Shell ---
-    let default_shell =
-        std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+    let default_shell = crate::config::default_shell();
     let shell = prompt(
         &mut out,
         &format!("{BOLD_YELLOW}Shell{RESET} {DIM}[{default_shell}]{RESET}"),
@@ -46,7 +45,7 @@ pub fn run_wizard() -> Result<Config> {
     let shell = if shell.is_empty() {
         default_shell
     } else {
-        shell
+        crate::config::ShellCommand::Line(shell)
     };
 
     // --- Layout ---
@@ -114,7 +113,7 @@ pub fn run_wizard() -> Result<Config> {
             name,
             command,
             cwd,
-            env: HashMap::new(),
+            ..PaneConfig::default()
         });
 
         idx += 1;
@@ -123,9 +122,7 @@ pub fn run_wizard() -> Result<Config> {
     if panes.is_empty() {
         panes.push(PaneConfig {
             name: "Shell".to_string(),
-            command: None,
-            cwd: None,
-            env: HashMap::new(),
+            ..PaneConfig::default()
         });
     }
 
@@ -133,6 +130,7 @@ pub fn run_wizard() -> Result<Config> {
         default_shell: shell,
         layout,
         panes,
+        ..Config::default()
     };
 
     // --- Save prompt ---
@@ -172,3 +170,55 @@ pub fn write_config(config: &Config) -> Result<()> {
     std::fs::write(".bamboo.toml", toml_str)?;
     Ok(())
 }
+
+/// Commented starter config offered by the welcome screen.
+pub const EXAMPLE_CONFIG: &str = r#"# bamboo config. Every setting is optional; see the README for the rest.
+
+# Pane decoration: "full", "compact" or "none".
+# chrome = "full"
+
+# Start every pane except the focused one collapsed.
+# start_collapsed = true
+
+# An interactive shell.
+[[panes]]
+name = "Shell"
+focus = true
+
+# A long-running command, restarted when the files it watches change.
+# [[panes]]
+# name = "tests"
+# command = "cargo test"
+# watch = ["src/**/*.rs"]
+
+# A one-shot task that collapses once it succeeds.
+# [[panes]]
+# name = "build"
+# command = "make"
+# kind = "task"
+
+# Panes that only open on demand, from the Alt+Shift+n picker.
+# [[templates]]
+# name = "logs"
+# command = "tail -f /var/log/syslog"
+"#;
+
+/// Write `EXAMPLE_CONFIG` to `path`, which must not exist yet.
+pub fn write_example_config(path: &Path) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new().write(true).create_new(true).open(path)?;
+    file.write_all(EXAMPLE_CONFIG.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn example_config_parses_to_a_single_shell() {
+        let config: Config = toml::from_str(EXAMPLE_CONFIG).unwrap();
+        assert_eq!(config.panes.len(), 1);
+        assert_eq!(config.focus_index(), Some(0));
+        assert!(config.templates.is_empty());
+    }
+}